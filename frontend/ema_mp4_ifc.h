@@ -45,8 +45,8 @@
  * (1) the API is non-reentrant.
  * (2) Only Windows version has been tested.
  * (3) Only file based input/output stream is supported.
- * (4) HEVC parser does not support open GOP, so timing inforing such as CTS and PTS 
- *     may not be accurate for Open GOP ES.
+ * (4) For open GOP HEVC ES, CRA pictures are sync samples of SAP type 3 ('sidx' and the
+ *     'sap ' sample group), the RASL pictures following them are flagged as leading in 'sdtp'.
  * (5) More clean up of the code is under way.The code is being optimized and more 
  *     funcitonalities will be added for the multiplexer.
  */
@@ -203,6 +203,27 @@ uint32_t ema_mp4_mux_set_cm_time(ema_mp4_ctrl_handle_t handle, uint32_t cmtimeh,
  */
 uint32_t ema_mp4_mux_set_chunk_span_time(ema_mp4_ctrl_handle_t handle, uint32_t chunk_span_time);
 
+/** \brief Sets the position of the 'moov' box
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param pos: string. "start": 'moov' is written in front of 'mdat' so that playback
+ *                             can start before the whole file is available (default).
+ *                     "end":   'moov' is written after 'mdat'.
+ *             Fragmented output always starts with 'moov'.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_moov_position(ema_mp4_ctrl_handle_t handle, const int8_t *pos);
+
+/** \brief Reserves free space at the end of the 'moov' box
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param size: payload size in bytes of the 'free' box written at the end of 'moov'.
+ *              The space can be used to grow 'moov' later without relocating 'mdat'.
+ *              A value of 0 (default) writes no 'free' box.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_moov_free_space(ema_mp4_ctrl_handle_t handle, uint32_t size);
+
 /** \brief Sets the main brand
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
@@ -229,6 +250,16 @@ uint32_t ema_mp4_mux_set_cbrand(ema_mp4_ctrl_handle_t handle, const int8_t  *cbr
  */
 uint32_t ema_mp4_mux_set_withopt(ema_mp4_ctrl_handle_t handle, const int8_t  *opt);
 
+/** \brief Sets the chunk offset box selection
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param mode: string. "auto":   'co64' only if a chunk offset exceeds 32 bit (default).
+ *                      "always": always 'co64'.
+ *                      "never":  always 'stco', muxing fails if a chunk offset exceeds 32 bit.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_co64_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode);
+
 /** \brief Sets multiple sample description support
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
@@ -248,10 +279,11 @@ uint32_t ema_mp4_mux_set_sd(ema_mp4_ctrl_handle_t handle, const int8_t  *sd);
 uint32_t ema_mp4_mux_set_db_level(ema_mp4_ctrl_handle_t handle, int8_t  *lvl);
 
 
-/** \brief  Sets the output format( mp4 or frag-mp4)
+/** \brief  Sets the output format( mp4, frag-mp4 or cmaf)
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
- * \param outfm: string to store "mp4" or "frag-mp4".
+ * \param outfm: string to store "mp4", "frag-mp4" or "cmaf". "cmaf" writes a CMAF track
+ *               file and takes a single input.
  * \return EMA_MP4_MUXED_...
  */
 uint32_t ema_mp4_mux_set_output_format(ema_mp4_ctrl_handle_t handle, const int8_t  *outfm);
@@ -265,6 +297,52 @@ uint32_t ema_mp4_mux_set_output_format(ema_mp4_ctrl_handle_t handle, const int8_
  */
 uint32_t ema_mp4_mux_set_max_duration(ema_mp4_ctrl_handle_t handle, uint32_t max_duration);
 
+/** \brief  Adds an event message, written as 'emsg' box in front of the 'moof' of the fragment
+ *          the event falls into for fragmented output
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param scheme_id_uri: the scheme of the event, e.g. "urn:scte:scte35:2013:bin"
+ * \param value: the value of the event within the scheme, may be NULL
+ * \param timescale: the timescale of presentation_time and event_duration
+ * \param presentation_time: the time of the event on the media timeline
+ * \param event_duration: the duration of the event, 0xFFFFFFFF for unknown
+ * \param id: the id of the event
+ * \param version: 0: time relative to the fragment; 1: time on the media timeline
+ * \param message_data: the message of the event, e.g. a SCTE-35 splice_info_section or an ID3 tag
+ * \param message_size: size of message_data in bytes
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_add_event(ema_mp4_ctrl_handle_t handle, const int8_t *scheme_id_uri, const int8_t *value,
+                               uint32_t timescale, uint64_t presentation_time, uint32_t event_duration, uint32_t id,
+                               uint32_t version, const uint8_t *message_data, uint32_t message_size);
+
+/** \brief  Sets the minimum segment duration for fragmented mp4
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param min_duration: min segment duration in millisecond, 0 for the default.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_min_duration(ema_mp4_ctrl_handle_t handle, uint32_t min_duration);
+
+/** \brief  Starts a new fragment at each sync sample of the tracks with non-sync samples
+ *          once the minimum segment duration is reached
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param on_every_sync: 1: enable; 0: fragments take as many sync samples as the max duration allows
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_frag_on_every_sync(ema_mp4_ctrl_handle_t handle, uint32_t on_every_sync);
+
+/** \brief  Sets the times at which fragments must start, e.g. scene cuts or ad insertion points.
+ *          A fragment starts with the first sample at or after each time, which must be a sync sample.
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param boundaries: times in millisecond in ascending order
+ * \param boundary_num: number of times in boundaries
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_frag_boundaries(ema_mp4_ctrl_handle_t handle, const uint64_t *boundaries, uint32_t boundary_num);
+
 /** \brief  Sets the video framerate value
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
@@ -273,6 +351,66 @@ uint32_t ema_mp4_mux_set_max_duration(ema_mp4_ctrl_handle_t handle, uint32_t max
  */
 uint32_t ema_mp4_mux_set_video_framerate(ema_mp4_ctrl_handle_t handle, uint32_t nome, uint32_t deno);
 
+/** \brief  Sets the video framerate value of respective elementary stream
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param nome/deno: video framerate value, for 23.976, nome = 24000 deno = 1001
+ * \param force: if not 0, the frame rate replaces the timing info of the ES (e.g. VUI)
+ *               and makes it constant; otherwise it is used only for an ES without timing info.
+ *               Muxing fails if the ES turns out not to be video.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_es_framerate(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t nome, uint32_t deno, uint32_t force);
+
+/** \brief  Sets the bit rate of an AC-4 elementary stream written into the 'dac4' box
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param bitrate: bit_rate in bits per second, 0 if unknown
+ * \param precision: bit_rate_precision in bits per second, 0xffffffff if unknown.
+ *                   Requires a bit rate. Muxing fails if the ES turns out not to be AC-4.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_ac4_bitrate(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t bitrate, uint32_t precision);
+
+/** \brief  Sets how the IMS presentations of an AC-4 elementary stream are signaled in 'dac4'
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param mode: 'duplicate': each IMS presentation is followed by a presentation_version 1
+ *                            copy for decoders without IMS support (Default)
+ *              'single':    the IMS presentation only
+ *              Muxing fails if the ES turns out not to be AC-4.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_ac4_ims_mode(ema_mp4_ctrl_handle_t handle, int32_t es_idx, const int8_t *mode);
+
+/** \brief  Sets the mastering display colour volume written into the 'mdcv' box of a video track
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param display_primaries: x and y of the green, blue and red primaries in units of 0.00002
+ * \param white_point: x and y of the white point in units of 0.00002
+ * \param max_luminance: maximum display mastering luminance in units of 0.0001 cd/m2
+ * \param min_luminance: minimum display mastering luminance in units of 0.0001 cd/m2, below the maximum.
+ *                       Overrides the mastering display SEI. Muxing fails if the ES is not video.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_mastering_display(ema_mp4_ctrl_handle_t handle, int32_t es_idx, const uint16_t *display_primaries,
+                                           const uint16_t *white_point, uint32_t max_luminance, uint32_t min_luminance);
+
+/** \brief  Sets the content light level written into the 'clli' box of a video track
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param max_cll: maximum content light level in cd/m2
+ * \param max_fall: maximum frame-average light level in cd/m2.
+ *                  Overrides the content light level SEI. Muxing fails if the ES is not video.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_content_light_level(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint16_t max_cll, uint16_t max_fall);
+
 /** \brief  Sets the DoVi ES mode 
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
@@ -301,6 +439,46 @@ uint32_t ema_mp4_mux_set_dv_profile(ema_mp4_ctrl_handle_t handle, int32_t profil
  */
 uint32_t ema_mp4_mux_set_dv_bl_compatible_id(ema_mp4_ctrl_handle_t handle, int32_t compatible_id);
 
+/** \brief  Sets the DoVi layers kept in the samples of AVC and HEVC streams
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param mode: string. "keep":    BL, EL and RPU as in the ES (default).
+ *                      "strip":   the BL only, muxed as a plain AVC or HEVC track.
+ *                      "drop-el": BL and RPU, a single layer track.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_dv_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode);
+
+/** \brief What the HEVC parser takes from the first access unit of an elementary stream */
+typedef struct ema_mp4_hevc_info_t_
+{
+    uint32_t hevc;                        /**< 1 if the HEVC parser takes the ES, the others are set only then */
+    uint32_t width, height;
+    uint32_t bit_depth_luma;
+    uint32_t colour_description_present;
+    uint32_t transfer_characteristics;    /**< of the VUI */
+    uint32_t preferred_transfer_characteristics; /**< of an alternative transfer characteristics SEI,
+                                                      (uint32_t)-1 without */
+    uint32_t time_scale;                  /**< the timing the parser uses */
+    uint32_t num_units_in_tick;
+    uint32_t dv_rpu_found;                /**< Dolby Vision RPU NALs or RPU SEI in the ES */
+    uint32_t dv_el_found;                 /**< Dolby Vision EL NALs in the ES */
+    uint32_t dv_vdr_rpu_profile;          /**< of the first RPU header, (uint32_t)-1 without */
+    uint32_t dv_vdr_bit_depth;
+    uint32_t dv_rpu_nal_flag;             /**< the Dolby Vision signalling with the settings made so far */
+    uint32_t dv_el_nal_flag;
+    uint32_t dv_level;
+} ema_mp4_hevc_info_t;
+
+/** \brief  Parses the first access unit of an HEVC elementary stream with the settings made so far
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param info: the HEVC info, info->hevc is 0 for other ES types.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_get_hevc_info(ema_mp4_ctrl_handle_t handle, int32_t es_idx, ema_mp4_hevc_info_t *info);
+
 /** \brief  Sets the sample entry box name to 'dvh1' for respetive elementry stream
  *
  * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
@@ -317,6 +495,36 @@ uint32_t ema_mp4_mux_set_sampleentry_dvh1(ema_mp4_ctrl_handle_t handle, int32_t
  */
 uint32_t ema_mp4_mux_set_sampleentry_hvc1(ema_mp4_ctrl_handle_t handle, int32_t es_idx);
 
+/** \brief  Sets the track_ID of the track the respective elementary stream is muxed into
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param track_ID: the track ID, must be unique and non-zero. Streams without a track ID
+ *                  get the next free one.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_track_id(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t track_ID);
+
+/** \brief  Sets the alternate_group in 'tkhd' for respective elementary stream
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param alternate_group: 0 for no group. By default all video tracks form group 1
+ *                         and all audio tracks group 2.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_alternate_group(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint16_t alternate_group);
+
+/** \brief  Sets the track_enabled flag in 'tkhd' for respective elementary stream
+ *
+ * \param handle: the multiplexer handle returned by the ema_mp4_mux_create()
+ * \param es_idx: the index of elementry stream.
+ * \param enabled: 0 disables the track. By default only the first track of
+ *                 an alternate group is enabled.
+ * \return EMA_MP4_MUXED_...
+ */
+uint32_t ema_mp4_mux_set_track_enabled(ema_mp4_ctrl_handle_t handle, int32_t es_idx, int32_t enabled);
+
 #ifdef __cplusplus
 }
 #endif
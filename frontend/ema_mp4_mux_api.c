@@ -95,6 +95,65 @@ mux_muxer_destroy(mp4_ctrl_handle_t muxer)
     }
 }
 
+/**
+ * sets alternate group and tkhd flags of an es: without user settings, tracks of the same
+ * media type form an alternate group of which only the first track is enabled
+ */
+static void
+mux_set_tkhd_cfg(ema_mp4_ctrl_handle_t handle, uint32_t es_idx, uint32_t stream_type)
+{
+    usr_cfg_es_t *usr_cfg_es    = &(handle->usr_cfg_ess[es_idx]);
+    BOOL          group_enabled = FALSE;
+    uint32_t      u;
+
+    usr_cfg_es->force_tkhd_flags = (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_MP4) ? 0xF : 0x7;
+
+    if (usr_cfg_es->usr_alternate_group >= 0)
+    {
+        usr_cfg_es->alternate_group = (uint16_t)usr_cfg_es->usr_alternate_group;
+    }
+    else if (stream_type == STREAM_TYPE_VIDEO)
+    {
+        usr_cfg_es->alternate_group = 1;
+    }
+    else if (stream_type == STREAM_TYPE_AUDIO)
+    {
+        usr_cfg_es->alternate_group = 2;
+    }
+
+    /** only one track of a group is enabled: an explicitly enabled one takes over */
+    for (u = 0; usr_cfg_es->alternate_group && u < es_idx; u++)
+    {
+        usr_cfg_es_t *usr_cfg_prev = &(handle->usr_cfg_ess[u]);
+
+        if (!usr_cfg_prev->track_ID || usr_cfg_prev->alternate_group != usr_cfg_es->alternate_group ||
+            !(usr_cfg_prev->force_tkhd_flags & 0x1))
+        {
+            continue;
+        }
+
+        if (usr_cfg_es->usr_track_enabled == 1 && usr_cfg_prev->usr_track_enabled == -1)
+        {
+            track_handle_t track = mp4_muxer_get_track(handle->mp4_handle, usr_cfg_prev->track_ID);
+
+            usr_cfg_prev->force_tkhd_flags &= 0xE;
+            if (track)
+            {
+                track->flags = usr_cfg_prev->force_tkhd_flags;
+            }
+        }
+        else
+        {
+            group_enabled = TRUE;
+        }
+    }
+
+    if (usr_cfg_es->usr_track_enabled == 0 || (usr_cfg_es->usr_track_enabled == -1 && group_enabled))
+    {
+        usr_cfg_es->force_tkhd_flags &= 0xE;
+    }
+}
+
 /**
  * open the input source (file only for now)
  */
@@ -156,6 +215,7 @@ mux_es_parser_create(ema_mp4_ctrl_handle_t handle, uint32_t es_idx, parser_handl
     usr_cfg_es_t *  usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
     int8_t *          es_type  = NULL;
     parser_handle_t parser     = NULL;
+    ext_timing_info_t ext_timing;
     int32_t             ret    = EMA_MP4_MUXED_OK;
 
     /**** get data source type */
@@ -195,8 +255,67 @@ mux_es_parser_create(ema_mp4_ctrl_handle_t handle, uint32_t es_idx, parser_handl
         parser->dv_el_track_flag = 1;
     }
 
+    /** per stream frame rate on top of the muxer wide timing info */
+    ext_timing = handle->usr_cfg_mux.ext_timing_info;
+    ext_timing.chunk_span_time = handle->usr_cfg_mux.chunk_span_time;
+    ext_timing.chunk_span_size = usr_cfg_es->chunk_span_size;
+    if (handle->usr_cfg_ess[es_idx].framerate_nome)
+    {
+        if (parser->stream_type != STREAM_TYPE_VIDEO)
+        {
+            msglog(NULL, MSGLOG_ERR, "ERROR! Frame rate given for non-video stream %u (%s)\n", es_idx, parser->stream_name);
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+        ext_timing.override_timing   = handle->usr_cfg_ess[es_idx].force_framerate;
+        ext_timing.usr_timing        = 1;
+        ext_timing.time_scale        = handle->usr_cfg_ess[es_idx].framerate_nome;
+        ext_timing.num_units_in_tick = handle->usr_cfg_ess[es_idx].framerate_deno;
+    }
+    if (usr_cfg_es->ac4_cfg)
+    {
+        if (parser->stream_id != STREAM_ID_AC4)
+        {
+            msglog(NULL, MSGLOG_ERR, "ERROR! AC-4 option given for non-AC-4 stream %u (%s)\n", es_idx, parser->stream_name);
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+        ext_timing.ac4_bitrate           = usr_cfg_es->ac4_bitrate;
+        ext_timing.ac4_bitrate_precision = usr_cfg_es->ac4_bitrate_precision;
+        ext_timing.ac4_ims_mode          = usr_cfg_es->ac4_ims_mode;
+    }
+    if ((usr_cfg_es->mdcv_cfg || usr_cfg_es->clli_cfg) && parser->stream_type != STREAM_TYPE_VIDEO)
+    {
+        msglog(NULL, MSGLOG_ERR, "ERROR! HDR metadata given for non-video stream %u (%s)\n", es_idx, parser->stream_name);
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
     msglog(NULL, MSGLOG_INFO, "Init %4s parser for stream %u\n", parser->stream_name, es_idx);
-    ret = parser->init(parser, &(handle->usr_cfg_mux.ext_timing_info), es_idx, handle->data_srcs[es_idx]);
+    ret = parser->init(parser, &ext_timing, es_idx, handle->data_srcs[es_idx]);
+
+    /** the user's HDR metadata, the parsers keep what is set over the one of the ES */
+    if (usr_cfg_es->mdcv_cfg)
+    {
+        parser_video_handle_t parser_video = (parser_video_handle_t)parser;
+        int32_t i;
+
+        for (i = 0; i < 3; i++)
+        {
+            parser_video->display_primaries_x[i] = usr_cfg_es->display_primaries[2*i];
+            parser_video->display_primaries_y[i] = usr_cfg_es->display_primaries[2*i + 1];
+        }
+        parser_video->white_point_x                   = usr_cfg_es->white_point[0];
+        parser_video->white_point_y                   = usr_cfg_es->white_point[1];
+        parser_video->max_display_mastering_luminance = usr_cfg_es->max_display_mastering_luminance;
+        parser_video->min_display_mastering_luminance = usr_cfg_es->min_display_mastering_luminance;
+        parser_video->mdcv_present                    = 1;
+    }
+    if (usr_cfg_es->clli_cfg)
+    {
+        parser_video_handle_t parser_video = (parser_video_handle_t)parser;
+
+        parser_video->max_content_light_level     = usr_cfg_es->max_content_light_level;
+        parser_video->max_pic_average_light_level = usr_cfg_es->max_pic_average_light_level;
+        parser_video->clli_present                = 1;
+    }
 
     if (handle->usr_cfg_mux.dv_bl_non_comp_flag)
     {
@@ -301,46 +420,6 @@ mux_es_parsing(ema_mp4_ctrl_handle_t handle, uint32_t es_idx, uint32_t dv_el_fla
     }
 }
 
-/**
- * callback function for creating multiple fragmented mp4 files
- */
-static int32_t
-onWriteNextFrag(void *handle_in)
-{
-    ema_mp4_ctrl_handle_t handle = (ema_mp4_ctrl_handle_t)handle_in;
-    int8_t segment_name[256];
-    uint8_t *output_name;
-    int8_t *seg_name;
-    uint32_t ret = 0;
-
-    if (!handle->usr_cfg_mux.segment_output_flag)
-    {
-        return EMA_MP4_MUXED_PARAM_ERR;
-    }
-
-    handle->mp4_sink->close(handle->mp4_sink);
-
-    output_name = (uint8_t *)(handle->usr_cfg_mux.output_fn);
-    seg_name = segment_name;
-    while (*output_name != '.')
-    {
-        *seg_name++ = *output_name++;
-    }
-    sprintf(seg_name, "_%d.mp4", handle->usr_cfg_mux.SegmentCounter++);
-
-    ret = handle->mp4_sink->open(handle->mp4_sink, (const int8_t *)segment_name);
-    if (ret != 0)
-    {
-        msglog(NULL, MSGLOG_ERR, "ERROR! Can't open output container");
-        return EMA_MP4_MUXED_PARAM_ERR;
-    }
-
-    ret = mp4_muxer_output_segment_hdrs(handle->mp4_handle);
-
-    return ret;
-}
-
-
 /****** interface code starts from here */
 /**
  * for each track,
@@ -351,8 +430,6 @@ uint32_t
 ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
 {
     int32_t      es_idx;
-    int32_t      has_video = 0;
-    int32_t      has_audio = 0;
     usr_cfg_es_t *usr_cfg_es;
     time_t       ltime_s, ltime_e;
     int32_t      ret = EMA_MP4_MUXED_OK;
@@ -392,14 +469,22 @@ ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
 	}
 
     if ( (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_DASH) ||
-         (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_FRAG_MP4) )
+         (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_FRAG_MP4) ||
+         (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_CMAF) )
     {
+        if (usr_cfg_mux_ptr->moov_at_end)
+        {
+            msglog(NULL, MSGLOG_ERR, "ERROR! Fragmented output requires 'moov' in front of the fragments. \n");
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+
         usr_cfg_mux_ptr->mux_cfg_flags = (
             ISOM_MUXCFG_WRITE_IODS |
             ISOM_MUXCFG_WRITE_CTTS_V1 |
             ISOM_MUXCFG_WRITE_SUBS_V1 |
             ISOM_MUXCFG_WRITE_STSS |
-            ISOM_MUXCFG_ENCRYPTSTYLE_CENC
+            ISOM_MUXCFG_ENCRYPTSTYLE_CENC |
+            (usr_cfg_mux_ptr->mux_cfg_flags & ISOM_MUXCFG_WRITE_FREE)
         );
 
         usr_cfg_mux_ptr->frag_cfg_flags = (
@@ -410,7 +495,8 @@ ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
             ISOM_FRAGCFG_NO_BDO_IN_TFHD |
             ISOM_FRAGCFG_ONE_TFRA_ENTRY_PER_TRAF |
             ISOM_FRAGCFG_DEFAULT_BASE_IS_MOOF |
-            ISOM_FRAGCFG_FORCE_TFHD_SAMPDESCIDX
+            ISOM_FRAGCFG_FORCE_TFHD_SAMPDESCIDX |
+            ISOM_FRAGCFG_WRITE_EMSG
         );
 
         usr_cfg_mux_ptr->output_mode |= EMA_MP4_FRAG;
@@ -419,10 +505,6 @@ ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
             /** DASH sepc suggested fragment duration is 2s. */
             usr_cfg_mux_ptr->frag_range_max = 2000; 
         }
-        if (usr_cfg_mux_ptr->frag_range_min == 0)
-        {
-            usr_cfg_mux_ptr->frag_range_min = 1000;
-        }
 
         FREE_CHK((int8_t *)usr_cfg_mux_ptr->major_brand);
         usr_cfg_mux_ptr->major_brand = STRDUP_CHK("mp42");
@@ -445,16 +527,42 @@ ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
             {
                 FREE_CHK((int8_t *)usr_cfg_mux_ptr->compatible_brands);
                 usr_cfg_mux_ptr->compatible_brands = STRDUP_CHK("mp42dashdby1iso5isom");
-                usr_cfg_mux_ptr->segment_output_flag = 1;
-                /**** set fragment callback */
-                mp4_muxer_set_onwrite_next_frag_callback(handle->mp4_handle, onWriteNextFrag, (void *)(handle));
+                /** the segment files are written by the frontend from the fragmented output */
             }
         }
+        else if (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_CMAF)
+        {
+            /** [CMAF] 7.3: a track file carries a single track */
+            if (handle->usr_cfg_mux.es_num > 1)
+            {
+                msglog(NULL, MSGLOG_ERR, "ERROR! A CMAF track file carries a single track, mux each input on its own. \n");
+                return EMA_MP4_MUXED_PARAM_ERR;
+            }
+
+            /** [CMAF] 7.5, 7.7: defaults in 'trex', a version 1 'tfdt' and a 'sidx', no 'iods' and no 'mfra';
+                edit lists for the audio priming only */
+            usr_cfg_mux_ptr->mux_cfg_flags &= ~ISOM_MUXCFG_WRITE_IODS;
+            usr_cfg_mux_ptr->frag_cfg_flags = (
+                ISOM_FRAGCFG_FRAGSTYLE_DEFAULT |
+                ISOM_FRAGCFG_WRITE_TFDT |
+                ISOM_FRAGCFG_FORCE_TFDT_V1 |
+                ISOM_FRAGCFG_NO_BDO_IN_TFHD |
+                ISOM_FRAGCFG_DEFAULT_BASE_IS_MOOF |
+                ISOM_FRAGCFG_WRITE_SIDX |
+                ISOM_FRAGCFG_AUDIO_EDTS_ONLY |
+                ISOM_FRAGCFG_WRITE_EMSG
+            );
+
+            FREE_CHK((int8_t *)usr_cfg_mux_ptr->major_brand);
+            usr_cfg_mux_ptr->major_brand = STRDUP_CHK("cmf2");
+            usr_cfg_mux_ptr->brand_version = 0;
+            FREE_CHK((int8_t *)usr_cfg_mux_ptr->compatible_brands);
+            usr_cfg_mux_ptr->compatible_brands = STRDUP_CHK("cmf2cmfciso6isom");
+        }
         else /** for frag-mp4, add the 'sidx' box */
         {
             usr_cfg_mux_ptr->frag_cfg_flags |= ISOM_FRAGCFG_WRITE_SIDX;
         }
-        usr_cfg_mux_ptr->SegmentCounter = 1;
     }
 
     /**** get muxer sink */
@@ -515,24 +623,15 @@ ema_mp4_mux_start(ema_mp4_ctrl_handle_t handle)
             {
                 parser->destroy(parser);
             }
+            /** a stream the parser fails on is skipped, but not one the user configured wrongly */
+            if (ret == EMA_MP4_MUXED_PARAM_ERR)
+            {
+                return ret;
+            }
             CHK_ERR_CNT(ret);
 
             /** set tkhd flags and alternative group */
-            handle->usr_cfg_ess[es_idx].force_tkhd_flags = (handle->usr_cfg_mux.output_format == OUTPUT_FORMAT_MP4) ? 0xF : 0x7;
-            if(parser->stream_type == STREAM_TYPE_VIDEO)
-            {
-                handle->usr_cfg_ess[es_idx].alternate_group = 1;
-                if(has_video)
-                    handle->usr_cfg_ess[es_idx].force_tkhd_flags &=  0xE;
-                has_video = 1;
-            }
-            else if(parser->stream_type == STREAM_TYPE_AUDIO)
-            {
-                handle->usr_cfg_ess[es_idx].alternate_group = 2;
-                if(has_audio)
-                    handle->usr_cfg_ess[es_idx].force_tkhd_flags &=  0xE;
-                has_audio = 1;            
-            }
+            mux_set_tkhd_cfg(handle, es_idx, parser->stream_type);
 
             /** add a track to muxer */
             handle->usr_cfg_ess[es_idx].track_ID =
@@ -622,12 +721,19 @@ ema_mp4_mux_create(ema_mp4_ctrl_handle_t *handle)
     /*** register video parser */
     parser_hevc_reg();   /** register hevc parser */
     parser_avc_reg();    /** register avc parser */
+    parser_av1_reg();    /** register av1 parser */
+    parser_vvc_reg();    /** register vvc parser */
 
     /*** register audio parser */
     parser_aac_reg();    /** register aac parser */
     parser_ac3_reg();    /** register ac3 parser */
     parser_ec3_reg();    /** register ec3 parser */
     parser_ac4_reg();    /** register ac4 parser */
+    parser_opus_reg();   /** register opus parser */
+    parser_flac_reg();   /** register flac parser */
+    parser_pcm_reg();    /** register pcm parser */
+    parser_mpegh_reg();  /** register mpeg-h parser */
+    parser_dts_reg();    /** register dts parser */
 
     /** I/O */
     reg_bbio_init();
@@ -655,13 +761,17 @@ ema_mp4_mux_create(ema_mp4_ctrl_handle_t *handle)
     usr_cfg_mux_ptr->timescale              = 600;                    /** default time scale in ms */
     usr_cfg_mux_ptr->mux_cfg_flags          = ISOM_MUXCFG_DEFAULT;    /** default to write iods */
     usr_cfg_mux_ptr->free_box_in_moov_size  = 0;                      /** no free box insertion at end of moov */
+    usr_cfg_mux_ptr->moov_at_end            = 0;                      /** moov in front of mdat */
     usr_cfg_mux_ptr->ext_timing_info.override_timing   = 0;           /** not over ride timing info in sps */
     usr_cfg_mux_ptr->ext_timing_info.time_scale        = 30000;
     usr_cfg_mux_ptr->ext_timing_info.num_units_in_tick = 1000;        /** default time_scale/num_units_in_tick for video frame-rate: 30 fps */
     usr_cfg_mux_ptr->ext_timing_info.ext_dv_profile    = 0xff;
+    usr_cfg_mux_ptr->ext_timing_info.ext_dv_mode       = DV_MODE_KEEP;
 	usr_cfg_mux_ptr->ext_timing_info.ac4_bitrate = 0;
 	usr_cfg_mux_ptr->ext_timing_info.ac4_bitrate_precision = 0xffffffff;
+    usr_cfg_mux_ptr->ext_timing_info.ac4_ims_mode      = AC4_IMS_DUPLICATE;
     usr_cfg_mux_ptr->fix_cm_time            = 0;
+    usr_cfg_mux_ptr->fix_cm_time_set        = 0;
     usr_cfg_mux_ptr->chunk_span_time        = 250;                    /** default 250ms */
     usr_cfg_mux_ptr->frag_cfg_flags         = ISOM_FRAGCFG_DEFAULT;
     usr_cfg_mux_ptr->frag_range_max         = 0;
@@ -740,6 +850,7 @@ ema_mp4_mux_destroy(ema_mp4_ctrl_handle_t handle)
     FREE_CHK((int8_t *)usr_cfg_mux_ptr->output_fn_el);
     FREE_CHK((int8_t *)usr_cfg_mux_ptr->major_brand);
     FREE_CHK((int8_t *)usr_cfg_mux_ptr->compatible_brands);
+    FREE_CHK(usr_cfg_mux_ptr->frag_boundaries);
 
     FREE_CHK(handle->fn_in);
     if ((handle->mp4_src) && (handle->demux_flag))
@@ -815,9 +926,21 @@ ema_mp4_mux_set_input(ema_mp4_ctrl_handle_t handle,
     usr_cfg_es->hdlr_name = (name) ? STRDUP_CHK(name) : 0;
     usr_cfg_es->enc_name        = (enc_name) ? STRDUP_CHK(enc_name) : 0;
     /** chunk_span_size: 0 means no chunk span control by size */
-    usr_cfg_es->chunk_span_size = 0;
+    usr_cfg_es->chunk_span_size = chunk_span_size;
     usr_cfg_es->mp4_tid         = tid;
     usr_cfg_es->warp_media_timescale = time_scale;
+    usr_cfg_es->track_ID             = 0;
+    usr_cfg_es->usr_alternate_group  = -1;
+    usr_cfg_es->usr_track_enabled    = -1;
+    usr_cfg_es->framerate_nome       = 0;
+    usr_cfg_es->framerate_deno       = 0;
+    usr_cfg_es->force_framerate      = 0;
+    usr_cfg_es->ac4_bitrate           = handle->usr_cfg_mux.ext_timing_info.ac4_bitrate;
+    usr_cfg_es->ac4_bitrate_precision = handle->usr_cfg_mux.ext_timing_info.ac4_bitrate_precision;
+    usr_cfg_es->ac4_ims_mode          = handle->usr_cfg_mux.ext_timing_info.ac4_ims_mode;
+    usr_cfg_es->ac4_cfg               = 0;
+    usr_cfg_es->mdcv_cfg              = 0;
+    usr_cfg_es->clli_cfg              = 0;
     /** mark for add */
     usr_cfg_es->action = TRACK_EDIT_ACTION_ADD;  
     handle->usr_cfg_mux.es_num++;
@@ -880,7 +1003,15 @@ ema_mp4_mux_set_moov_timescale(ema_mp4_ctrl_handle_t handle, uint32_t timescale)
 uint32_t
 ema_mp4_mux_set_cm_time(ema_mp4_ctrl_handle_t handle, uint32_t cmtimeh, uint32_t cmtimel)
 {
-    handle->usr_cfg_mux.fix_cm_time = (((uint64_t)cmtimeh) << 32) | cmtimel;
+    handle->usr_cfg_mux.fix_cm_time     = (((uint64_t)cmtimeh) << 32) | cmtimel;
+    handle->usr_cfg_mux.fix_cm_time_set = 1;
+
+    /** the muxer picks up fix_cm_time when it is created, so apply it to the already created one */
+    if (handle->mp4_handle)
+    {
+        handle->mp4_handle->creation_time     = handle->usr_cfg_mux.fix_cm_time;
+        handle->mp4_handle->modification_time = handle->usr_cfg_mux.fix_cm_time;
+    }
 
     return EMA_MP4_MUXED_OK;
 }
@@ -893,6 +1024,46 @@ ema_mp4_mux_set_chunk_span_time(ema_mp4_ctrl_handle_t handle, uint32_t chunk_spa
     return EMA_MP4_MUXED_OK;
 }
 
+uint32_t
+ema_mp4_mux_set_moov_position(ema_mp4_ctrl_handle_t handle, const int8_t *pos)
+{
+    if (!pos)
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    if (!OSAL_STRCASECMP(pos, "start"))
+    {
+        handle->usr_cfg_mux.moov_at_end = 0;
+    }
+    else if (!OSAL_STRCASECMP(pos, "end"))
+    {
+        handle->usr_cfg_mux.moov_at_end = 1;
+    }
+    else
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t
+ema_mp4_mux_set_moov_free_space(ema_mp4_ctrl_handle_t handle, uint32_t size)
+{
+    handle->usr_cfg_mux.free_box_in_moov_size = size;
+    if (size)
+    {
+        handle->usr_cfg_mux.mux_cfg_flags |= ISOM_MUXCFG_WRITE_FREE;
+    }
+    else
+    {
+        handle->usr_cfg_mux.mux_cfg_flags &= ~ISOM_MUXCFG_WRITE_FREE;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
 uint32_t
 ema_mp4_mux_set_mbrand(ema_mp4_ctrl_handle_t handle, const int8_t *mbrand)
 {
@@ -927,12 +1098,41 @@ ema_mp4_mux_set_withopt(ema_mp4_ctrl_handle_t handle, const int8_t *opt)
 {
     if (!OSAL_STRCASECMP(opt, "64")) 
     {
-        handle->usr_cfg_mux.withopt |= 0x1;  /** the only withopt for now */
+        handle->usr_cfg_mux.withopt |= ISOM_MUXCFG_WITHOPT_CO64;
+        handle->mp4_handle->co64_mode = TRUE;
     }
 
     return EMA_MP4_MUXED_OK;
 }
 
+uint32_t
+ema_mp4_mux_set_co64_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode)
+{
+    if (!mode)
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    handle->usr_cfg_mux.withopt &= ~(ISOM_MUXCFG_WITHOPT_CO64 | ISOM_MUXCFG_WITHOPT_NO_CO64);
+
+    if (!OSAL_STRCASECMP(mode, "always"))
+    {
+        handle->usr_cfg_mux.withopt |= ISOM_MUXCFG_WITHOPT_CO64;
+    }
+    else if (!OSAL_STRCASECMP(mode, "never"))
+    {
+        handle->usr_cfg_mux.withopt |= ISOM_MUXCFG_WITHOPT_NO_CO64;
+    }
+    else if (OSAL_STRCASECMP(mode, "auto"))
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    handle->mp4_handle->co64_mode = ((handle->usr_cfg_mux.withopt & ISOM_MUXCFG_WITHOPT_CO64) != 0);
+
+    return EMA_MP4_MUXED_OK;
+}
+
 uint32_t
 ema_mp4_mux_set_sd(ema_mp4_ctrl_handle_t handle, const int8_t *sd)
 {
@@ -1009,7 +1209,7 @@ ema_mp4_mux_set_output_format(ema_mp4_ctrl_handle_t handle, const int8_t *outfm)
         return EMA_MP4_MUXED_PARAM_ERR; 
     }
 
-    if (OSAL_STRCASECMP(outfm, "frag-mp4") && OSAL_STRCASECMP(outfm, "mp4")) 
+    if (OSAL_STRCASECMP(outfm, "frag-mp4") && OSAL_STRCASECMP(outfm, "cmaf") && OSAL_STRCASECMP(outfm, "mp4")) 
     {
         return EMA_MP4_MUXED_PARAM_ERR;
     }
@@ -1018,6 +1218,10 @@ ema_mp4_mux_set_output_format(ema_mp4_ctrl_handle_t handle, const int8_t *outfm)
     {
         handle->usr_cfg_mux.output_format = OUTPUT_FORMAT_FRAG_MP4;
     }
+    else if (!OSAL_STRCASECMP(outfm, "cmaf"))
+    {
+        handle->usr_cfg_mux.output_format = OUTPUT_FORMAT_CMAF;
+    }
     else
     {
         handle->usr_cfg_mux.output_format = OUTPUT_FORMAT_MP4;
@@ -1034,17 +1238,209 @@ ema_mp4_mux_set_max_duration(ema_mp4_ctrl_handle_t handle, uint32_t max_duration
     return EMA_MP4_MUXED_OK;
 }
 
+uint32_t 
+ema_mp4_mux_add_event(ema_mp4_ctrl_handle_t handle, const int8_t *scheme_id_uri, const int8_t *value,
+                      uint32_t timescale, uint64_t presentation_time, uint32_t event_duration, uint32_t id,
+                      uint32_t version, const uint8_t *message_data, uint32_t message_size)
+{
+    emsg_t emsg;
+
+    if (!scheme_id_uri || !timescale || version > 1 || (message_size && !message_data))
+    {
+        msglog(NULL, MSGLOG_ERR, "Error parsing command line: Invalid event.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    memset(&emsg, 0, sizeof(emsg_t));
+    emsg.scheme_id_uri     = (int8_t *)scheme_id_uri;
+    emsg.value             = (int8_t *)value;
+    emsg.timescale         = timescale;
+    emsg.presentation_time = presentation_time;
+    emsg.event_duration    = event_duration;
+    emsg.id                = id;
+    emsg.message_data      = (uint8_t *)message_data;
+    emsg.message_size      = message_size;
+    emsg.version           = (uint8_t)version;
+
+    return (uint32_t)mp4_muxer_add_emsg(handle->mp4_handle, &emsg);
+}
+
+uint32_t 
+ema_mp4_mux_set_min_duration(ema_mp4_ctrl_handle_t handle, uint32_t min_duration)
+{
+    handle->usr_cfg_mux.frag_range_min = min_duration;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t 
+ema_mp4_mux_set_frag_on_every_sync(ema_mp4_ctrl_handle_t handle, uint32_t on_every_sync)
+{
+    handle->usr_cfg_mux.frag_on_every_sync = on_every_sync ? 1 : 0;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t 
+ema_mp4_mux_set_frag_boundaries(ema_mp4_ctrl_handle_t handle, const uint64_t *boundaries, uint32_t boundary_num)
+{
+    usr_cfg_mux_t *usr_cfg_mux_ptr = &(handle->usr_cfg_mux);
+    uint32_t       i;
+
+    for (i = 1; i < boundary_num; i++)
+    {
+        if (boundaries[i] <= boundaries[i - 1])
+        {
+            msglog(NULL, MSGLOG_ERR, "Error parsing command line: fragment boundaries must be in ascending order.\n");
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+    }
+
+    MEM_FREE_AND_NULL(usr_cfg_mux_ptr->frag_boundaries);
+    usr_cfg_mux_ptr->frag_boundary_num = 0;
+    if (boundary_num)
+    {
+        usr_cfg_mux_ptr->frag_boundaries = (uint64_t *)MALLOC_CHK(boundary_num * sizeof(uint64_t));
+        if (!usr_cfg_mux_ptr->frag_boundaries)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        memcpy(usr_cfg_mux_ptr->frag_boundaries, boundaries, boundary_num * sizeof(uint64_t));
+        usr_cfg_mux_ptr->frag_boundary_num = boundary_num;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
 
 uint32_t
 ema_mp4_mux_set_video_framerate(ema_mp4_ctrl_handle_t handle, uint32_t nome, uint32_t deno)
 {
     handle->usr_cfg_mux.ext_timing_info.override_timing   = 1;       
+    handle->usr_cfg_mux.ext_timing_info.usr_timing        = 1;
     handle->usr_cfg_mux.ext_timing_info.time_scale        = nome;
     handle->usr_cfg_mux.ext_timing_info.num_units_in_tick = deno;     
 
     return EMA_MP4_MUXED_OK;
 }
 
+uint32_t
+ema_mp4_mux_set_es_framerate(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t nome, uint32_t deno, uint32_t force)
+{
+    if (es_idx >= 0 && es_idx < handle->usr_cfg_mux.es_num && nome && deno)
+    {
+        usr_cfg_es_t *usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+        usr_cfg_es->framerate_nome  = nome;
+        usr_cfg_es->framerate_deno  = deno;
+        usr_cfg_es->force_framerate = force ? 1 : 0;
+        return EMA_MP4_MUXED_OK;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or value for --framerate.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+}
+
+uint32_t
+ema_mp4_mux_set_ac4_bitrate(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t bitrate, uint32_t precision)
+{
+    if (es_idx >= 0 && es_idx < handle->usr_cfg_mux.es_num && (bitrate || precision == 0xffffffff))
+    {
+        usr_cfg_es_t *usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+        usr_cfg_es->ac4_bitrate           = bitrate;
+        usr_cfg_es->ac4_bitrate_precision = precision;
+        usr_cfg_es->ac4_cfg               = 1;
+        return EMA_MP4_MUXED_OK;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or value for --ac4-bitrate.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+}
+
+uint32_t
+ema_mp4_mux_set_ac4_ims_mode(ema_mp4_ctrl_handle_t handle, int32_t es_idx, const int8_t *mode)
+{
+    usr_cfg_es_t *usr_cfg_es;
+
+    if (es_idx < 0 || es_idx >= handle->usr_cfg_mux.es_num || !mode)
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or value for --ac4-ims.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+    if (!OSAL_STRCASECMP(mode, "duplicate"))
+    {
+        usr_cfg_es->ac4_ims_mode = AC4_IMS_DUPLICATE;
+    }
+    else if (!OSAL_STRCASECMP(mode, "single"))
+    {
+        usr_cfg_es->ac4_ims_mode = AC4_IMS_SINGLE;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR, "Error parsing command line: Unknown --ac4-ims mode %s.\n", mode);
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+    usr_cfg_es->ac4_cfg = 1;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t
+ema_mp4_mux_set_mastering_display(ema_mp4_ctrl_handle_t handle, int32_t es_idx, const uint16_t *display_primaries,
+                                  const uint16_t *white_point, uint32_t max_luminance, uint32_t min_luminance)
+{
+    usr_cfg_es_t *usr_cfg_es;
+    int32_t i;
+
+    if (es_idx < 0 || es_idx >= handle->usr_cfg_mux.es_num || !display_primaries || !white_point ||
+        min_luminance >= max_luminance)
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or value for --master-display.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+    for (i = 0; i < 6; i++)
+    {
+        usr_cfg_es->display_primaries[i] = display_primaries[i];
+    }
+    usr_cfg_es->white_point[0]                  = white_point[0];
+    usr_cfg_es->white_point[1]                  = white_point[1];
+    usr_cfg_es->max_display_mastering_luminance = max_luminance;
+    usr_cfg_es->min_display_mastering_luminance = min_luminance;
+    usr_cfg_es->mdcv_cfg                        = 1;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t
+ema_mp4_mux_set_content_light_level(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint16_t max_cll, uint16_t max_fall)
+{
+    if (es_idx >= 0 && es_idx < handle->usr_cfg_mux.es_num)
+    {
+        usr_cfg_es_t *usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+        usr_cfg_es->max_content_light_level     = max_cll;
+        usr_cfg_es->max_pic_average_light_level = max_fall;
+        usr_cfg_es->clli_cfg                    = 1;
+        return EMA_MP4_MUXED_OK;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or value for --max-cll.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+}
+
 uint32_t
 ema_mp4_mux_set_dv_es_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode)
 {
@@ -1074,7 +1470,7 @@ ema_mp4_mux_set_dv_es_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode)
 uint32_t 
 ema_mp4_mux_set_dv_profile(ema_mp4_ctrl_handle_t handle, int32_t profile)
 {
-    if ((profile == 4) || (profile == 5) || (profile >= 7) && (profile <= 9)) 
+    if ((profile == 4) || (profile == 5) || (profile >= 7) && (profile <= 10)) 
     {
 		handle->usr_cfg_mux.ext_timing_info.ext_dv_profile = (uint8_t)profile;
         return EMA_MP4_MUXED_OK;
@@ -1099,6 +1495,118 @@ ema_mp4_mux_set_dv_bl_compatible_id(ema_mp4_ctrl_handle_t handle, int32_t compat
     }
 }
 
+uint32_t
+ema_mp4_mux_set_dv_mode(ema_mp4_ctrl_handle_t handle, const int8_t *mode)
+{
+    if (!mode)
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    if (!OSAL_STRCASECMP(mode, "keep"))
+    {
+        handle->usr_cfg_mux.ext_timing_info.ext_dv_mode = DV_MODE_KEEP;
+    }
+    else if (!OSAL_STRCASECMP(mode, "strip"))
+    {
+        handle->usr_cfg_mux.ext_timing_info.ext_dv_mode = DV_MODE_STRIP;
+    }
+    else if (!OSAL_STRCASECMP(mode, "drop-el"))
+    {
+        handle->usr_cfg_mux.ext_timing_info.ext_dv_mode = DV_MODE_DROP_EL;
+    }
+    else
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t
+ema_mp4_mux_get_hevc_info(ema_mp4_ctrl_handle_t handle, int32_t es_idx, ema_mp4_hevc_info_t *info)
+{
+    usr_cfg_es_t *      usr_cfg_es;
+    const int8_t *      es_type;
+    parser_handle_t     parser = NULL;
+    mp4_sample_handle_t sample = NULL;
+    int32_t             ret;
+
+    if (!info || es_idx < 0 || es_idx >= handle->usr_cfg_mux.es_num)
+    {
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+    usr_cfg_es = &(handle->usr_cfg_ess[es_idx]);
+    memset(info, 0, sizeof(*info));
+
+    /** the parser the muxer picks by the file extension */
+    es_type = usr_cfg_es->input_fn ? strrchr(usr_cfg_es->input_fn, '.') : NULL;
+    parser  = es_type ? reg_parser_get(es_type + 1, DSI_TYPE_MP4FF) : NULL;
+    if (!parser || parser->stream_id != STREAM_ID_HEVC)
+    {
+        if (parser)
+        {
+            parser->destroy(parser);
+        }
+        return EMA_MP4_MUXED_OK;
+    }
+    parser->destroy(parser);
+    parser = NULL;
+    info->hevc = 1;
+
+    /** parses the first AU with the settings made so far, as ema_mp4_mux_start() does */
+    ret = mux_data_src_create(handle, es_idx);
+    if (ret == EMA_MP4_MUXED_OK)
+    {
+        ret = mux_es_parser_create(handle, es_idx, &parser, 0);
+    }
+    if (ret == EMA_MP4_MUXED_OK)
+    {
+        sample = sample_create();
+        ret    = sample ? EMA_MP4_MUXED_NO_CONFIG_ERR : EMA_MP4_MUXED_NO_MEM;
+        while (ret == EMA_MP4_MUXED_NO_CONFIG_ERR)
+        {
+            ret = parser->get_sample(parser, sample);
+        }
+    }
+
+    if (ret == EMA_MP4_MUXED_OK)
+    {
+        info->width                      = ((parser_video_handle_t)parser)->width;
+        info->height                     = ((parser_video_handle_t)parser)->height;
+        info->colour_description_present = ((parser_video_handle_t)parser)->colour_description_present;
+        info->transfer_characteristics   = ((parser_video_handle_t)parser)->transfer_characteristics;
+        info->time_scale                 = parser->get_param(parser, STREAM_PARAM_ID_TIME_SCALE);
+        info->num_units_in_tick          = parser->get_param(parser, STREAM_PARAM_ID_NUM_UNITS_IN_TICK);
+        info->bit_depth_luma             = parser->get_param(parser, STREAM_PARAM_ID_BIT_DEPTH_LUMA);
+        info->preferred_transfer_characteristics = parser->get_param(parser, STREAM_PARAM_ID_PREFERRED_TRANSFER);
+        info->dv_rpu_found               = parser->get_param(parser, STREAM_PARAM_ID_DV_RPU_FOUND);
+        info->dv_el_found                = parser->get_param(parser, STREAM_PARAM_ID_DV_EL_FOUND);
+        info->dv_vdr_rpu_profile         = parser->get_param(parser, STREAM_PARAM_ID_DV_VDR_RPU_PROFILE);
+        info->dv_vdr_bit_depth           = parser->get_param(parser, STREAM_PARAM_ID_DV_VDR_BIT_DEPTH);
+        info->dv_rpu_nal_flag            = parser->dv_rpu_nal_flag;
+        info->dv_el_nal_flag             = parser->dv_el_nal_flag;
+        info->dv_level                   = parser->dv_level;
+    }
+
+    /** ema_mp4_mux_start() opens the ES again */
+    if (sample)
+    {
+        sample->destroy(sample);
+    }
+    if (parser)
+    {
+        parser->destroy(parser);
+    }
+    if (handle->data_srcs[es_idx])
+    {
+        handle->data_srcs[es_idx]->destroy(handle->data_srcs[es_idx]);
+        handle->data_srcs[es_idx] = NULL;
+    }
+
+    return ret;
+}
+
 uint32_t
 ema_mp4_mux_set_sampleentry_dvh1(ema_mp4_ctrl_handle_t handle, int32_t es_idx)
 {
@@ -1116,6 +1624,64 @@ ema_mp4_mux_set_sampleentry_dvh1(ema_mp4_ctrl_handle_t handle, int32_t es_idx)
     }
 }
 
+uint32_t
+ema_mp4_mux_set_track_id(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint32_t track_ID)
+{
+    int32_t u;
+
+    if (es_idx < 0 || es_idx >= handle->usr_cfg_mux.es_num || !track_ID)
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Invalid es index or track ID for --track-id.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+
+    for (u = 0; u < handle->usr_cfg_mux.es_num; u++)
+    {
+        if (u != es_idx && handle->usr_cfg_ess[u].track_ID == track_ID)
+        {
+            msglog(NULL, MSGLOG_ERR,
+                    "Error parsing command line: Track ID %u is used more than once.\n", track_ID);
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+    }
+
+    handle->usr_cfg_ess[es_idx].track_ID = track_ID;
+    return EMA_MP4_MUXED_OK;
+}
+
+uint32_t
+ema_mp4_mux_set_alternate_group(ema_mp4_ctrl_handle_t handle, int32_t es_idx, uint16_t alternate_group)
+{
+    if (es_idx >= 0 && es_idx < handle->usr_cfg_mux.es_num)
+    {
+        handle->usr_cfg_ess[es_idx].usr_alternate_group = alternate_group;
+        return EMA_MP4_MUXED_OK;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Unknown es index for --alternate-group.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+}
+
+uint32_t
+ema_mp4_mux_set_track_enabled(ema_mp4_ctrl_handle_t handle, int32_t es_idx, int32_t enabled)
+{
+    if (es_idx >= 0 && es_idx < handle->usr_cfg_mux.es_num)
+    {
+        handle->usr_cfg_ess[es_idx].usr_track_enabled = enabled ? 1 : 0;
+        return EMA_MP4_MUXED_OK;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR,
+                "Error parsing command line: Unknown es index for --enabled/--disabled.\n");
+        return EMA_MP4_MUXED_PARAM_ERR;
+    }
+}
+
 uint32_t
 ema_mp4_mux_set_sampleentry_hvc1(ema_mp4_ctrl_handle_t handle, int32_t es_idx)
 {
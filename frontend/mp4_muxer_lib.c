@@ -28,6 +28,7 @@
 
 #include "ema_mp4_ifc.h"
 #include "mp4_muxer.h"
+#include "mp4_muxer_lib.h"
 
 unsigned int
 ema_mp4_mux_create_clang(void **handle)
@@ -46,6 +47,38 @@ ema_mp4_mux_set_video_framerate_clang(void *handle, unsigned int nome, unsigned
     return ema_mp4_mux_set_video_framerate((ema_mp4_ctrl_handle_t)handle, (uint32_t)nome, (uint32_t)deno);
 }
 
+unsigned int
+ema_mp4_mux_set_es_framerate_clang(void *handle, int es_idx, unsigned int nome, unsigned int deno, int force)
+{
+    return ema_mp4_mux_set_es_framerate((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (uint32_t)nome, (uint32_t)deno, (uint32_t)force);
+}
+
+unsigned int
+ema_mp4_mux_set_ac4_bitrate_clang(void *handle, int es_idx, unsigned int bitrate, unsigned int precision)
+{
+    return ema_mp4_mux_set_ac4_bitrate((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (uint32_t)bitrate, (uint32_t)precision);
+}
+
+unsigned int
+ema_mp4_mux_set_ac4_ims_mode_clang(void *handle, int es_idx, const char *mode)
+{
+    return ema_mp4_mux_set_ac4_ims_mode((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (const int8_t *)mode);
+}
+
+unsigned int
+ema_mp4_mux_set_mastering_display_clang(void *handle, int es_idx, const unsigned short *display_primaries,
+                                        const unsigned short *white_point, unsigned int max_luminance, unsigned int min_luminance)
+{
+    return ema_mp4_mux_set_mastering_display((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (const uint16_t *)display_primaries,
+                                             (const uint16_t *)white_point, (uint32_t)max_luminance, (uint32_t)min_luminance);
+}
+
+unsigned int
+ema_mp4_mux_set_content_light_level_clang(void *handle, int es_idx, unsigned short max_cll, unsigned short max_fall)
+{
+    return ema_mp4_mux_set_content_light_level((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (uint16_t)max_cll, (uint16_t)max_fall);
+}
+
 unsigned int
 ema_mp4_mux_set_input_clang(void *handle,
                             const char *fn,
@@ -78,6 +111,42 @@ ema_mp4_mux_set_moov_timescale_clang(void *handle, unsigned int timescale)
     return ema_mp4_mux_set_moov_timescale((ema_mp4_ctrl_handle_t)handle, (uint32_t)timescale);
 }
 
+unsigned int
+ema_mp4_mux_set_cm_time_clang(void *handle, unsigned int cmtimeh, unsigned int cmtimel)
+{
+    return ema_mp4_mux_set_cm_time((ema_mp4_ctrl_handle_t)handle, (uint32_t)cmtimeh, (uint32_t)cmtimel);
+}
+
+unsigned int
+ema_mp4_mux_set_chunk_span_time_clang(void *handle, unsigned int chunk_span_time)
+{
+    return ema_mp4_mux_set_chunk_span_time((ema_mp4_ctrl_handle_t)handle, (uint32_t)chunk_span_time);
+}
+
+unsigned int
+ema_mp4_mux_set_moov_position_clang(void *handle, const char *pos)
+{
+    return ema_mp4_mux_set_moov_position((ema_mp4_ctrl_handle_t)handle, (const int8_t *)(!pos || pos[0] != '\0' ? pos : 0));
+}
+
+unsigned int
+ema_mp4_mux_set_moov_free_space_clang(void *handle, unsigned int size)
+{
+    return ema_mp4_mux_set_moov_free_space((ema_mp4_ctrl_handle_t)handle, (uint32_t)size);
+}
+
+unsigned int
+ema_mp4_mux_set_co64_mode_clang(void *handle, const char *mode)
+{
+    return ema_mp4_mux_set_co64_mode((ema_mp4_ctrl_handle_t)handle, (const int8_t *)(!mode || mode[0] != '\0' ? mode : 0));
+}
+
+unsigned int
+ema_mp4_mux_set_sd_clang(void *handle, const char *sd)
+{
+    return ema_mp4_mux_set_sd((ema_mp4_ctrl_handle_t)handle, (const int8_t *)(!sd || sd[0] != '\0' ? sd : 0));
+}
+
 unsigned int
 ema_mp4_mux_set_mbrand_clang(void *handle, const char *mbrand)
 {
@@ -102,6 +171,34 @@ ema_mp4_mux_set_max_duration_clang(void *handle, unsigned int max_duration)
     return ema_mp4_mux_set_max_duration((ema_mp4_ctrl_handle_t)handle, (uint32_t)max_duration);
 }
 
+unsigned int
+ema_mp4_mux_add_event_clang(void *handle, const char *scheme_id_uri, const char *value,
+                            unsigned int timescale, unsigned long long presentation_time, unsigned int event_duration,
+                            unsigned int id, unsigned int version, const unsigned char *message_data, unsigned int message_size)
+{
+    return ema_mp4_mux_add_event((ema_mp4_ctrl_handle_t)handle, (const int8_t *)scheme_id_uri, (const int8_t *)value,
+                                 (uint32_t)timescale, (uint64_t)presentation_time, (uint32_t)event_duration,
+                                 (uint32_t)id, (uint32_t)version, (const uint8_t *)message_data, (uint32_t)message_size);
+}
+
+unsigned int
+ema_mp4_mux_set_min_duration_clang(void *handle, unsigned int min_duration)
+{
+    return ema_mp4_mux_set_min_duration((ema_mp4_ctrl_handle_t)handle, (uint32_t)min_duration);
+}
+
+unsigned int
+ema_mp4_mux_set_frag_on_every_sync_clang(void *handle, unsigned int on_every_sync)
+{
+    return ema_mp4_mux_set_frag_on_every_sync((ema_mp4_ctrl_handle_t)handle, (uint32_t)on_every_sync);
+}
+
+unsigned int
+ema_mp4_mux_set_frag_boundaries_clang(void *handle, const unsigned long long *boundaries, unsigned int boundary_num)
+{
+    return ema_mp4_mux_set_frag_boundaries((ema_mp4_ctrl_handle_t)handle, (const uint64_t *)boundaries, (uint32_t)boundary_num);
+}
+
 unsigned int
 ema_mp4_mux_set_dv_profile_clang(void *handle, unsigned int profile)
 {
@@ -114,6 +211,38 @@ ema_mp4_mux_set_dv_bl_compatible_id_clang(void *handle, int compatible_id)
     return ema_mp4_mux_set_dv_bl_compatible_id((ema_mp4_ctrl_handle_t)handle, (int32_t)compatible_id);
 }
 
+unsigned int
+ema_mp4_mux_set_dv_mode_clang(void *handle, const char *mode)
+{
+    return ema_mp4_mux_set_dv_mode((ema_mp4_ctrl_handle_t)handle, (const int8_t *)mode);
+}
+
+unsigned int
+ema_mp4_mux_get_hevc_info_clang(void *handle, int es_idx, struct hevc_info_clang *info)
+{
+    ema_mp4_hevc_info_t hevc_info;
+    uint32_t ret = ema_mp4_mux_get_hevc_info((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, &hevc_info);
+
+    info->hevc                               = hevc_info.hevc;
+    info->width                              = hevc_info.width;
+    info->height                             = hevc_info.height;
+    info->bit_depth_luma                     = hevc_info.bit_depth_luma;
+    info->colour_description_present         = hevc_info.colour_description_present;
+    info->transfer_characteristics           = hevc_info.transfer_characteristics;
+    info->preferred_transfer_characteristics = hevc_info.preferred_transfer_characteristics;
+    info->time_scale                         = hevc_info.time_scale;
+    info->num_units_in_tick                  = hevc_info.num_units_in_tick;
+    info->dv_rpu_found                       = hevc_info.dv_rpu_found;
+    info->dv_el_found                        = hevc_info.dv_el_found;
+    info->dv_vdr_rpu_profile                 = hevc_info.dv_vdr_rpu_profile;
+    info->dv_vdr_bit_depth                   = hevc_info.dv_vdr_bit_depth;
+    info->dv_rpu_nal_flag                    = hevc_info.dv_rpu_nal_flag;
+    info->dv_el_nal_flag                     = hevc_info.dv_el_nal_flag;
+    info->dv_level                           = hevc_info.dv_level;
+
+    return ret;
+}
+
 unsigned int
 ema_mp4_mux_set_sampleentry_dvh1_clang(void *handle, int es_idx)
 {
@@ -126,6 +255,24 @@ ema_mp4_mux_set_sampleentry_hvc1_clang(void *handle, int es_idx)
     return ema_mp4_mux_set_sampleentry_hvc1((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx);
 }
 
+unsigned int
+ema_mp4_mux_set_track_id_clang(void *handle, int es_idx, unsigned int track_id)
+{
+    return ema_mp4_mux_set_track_id((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (uint32_t)track_id);
+}
+
+unsigned int
+ema_mp4_mux_set_alternate_group_clang(void *handle, int es_idx, unsigned short alternate_group)
+{
+    return ema_mp4_mux_set_alternate_group((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (uint16_t)alternate_group);
+}
+
+unsigned int
+ema_mp4_mux_set_track_enabled_clang(void *handle, int es_idx, int enabled)
+{
+    return ema_mp4_mux_set_track_enabled((ema_mp4_ctrl_handle_t)handle, (int32_t)es_idx, (int32_t)enabled);
+}
+
 void ema_mp4_mux_consistency_check_clang(void *handle)
 {
     ema_mp4_ctrl_handle_t ema_handle = (ema_mp4_ctrl_handle_t)handle;
@@ -34,6 +34,22 @@ void ema_mp4_mux_destroy_clang(void *handle);
 unsigned int
 ema_mp4_mux_set_video_framerate_clang(void *handle, unsigned int nome, unsigned int deno);
 
+unsigned int
+ema_mp4_mux_set_es_framerate_clang(void *handle, int es_idx, unsigned int nome, unsigned int deno, int force);
+
+unsigned int
+ema_mp4_mux_set_ac4_bitrate_clang(void *handle, int es_idx, unsigned int bitrate, unsigned int precision);
+
+unsigned int
+ema_mp4_mux_set_ac4_ims_mode_clang(void *handle, int es_idx, const char *mode);
+
+unsigned int
+ema_mp4_mux_set_mastering_display_clang(void *handle, int es_idx, const unsigned short *display_primaries,
+                                        const unsigned short *white_point, unsigned int max_luminance, unsigned int min_luminance);
+
+unsigned int
+ema_mp4_mux_set_content_light_level_clang(void *handle, int es_idx, unsigned short max_cll, unsigned short max_fall);
+
 unsigned int
 ema_mp4_mux_set_input_clang(void *handle,
                             const char *fn,
@@ -50,6 +66,24 @@ ema_mp4_mux_set_output_clang(void *handle, int buf_out, const char *fn);
 unsigned int
 ema_mp4_mux_set_moov_timescale_clang(void *handle, unsigned int timescale);
 
+unsigned int
+ema_mp4_mux_set_cm_time_clang(void *handle, unsigned int cmtimeh, unsigned int cmtimel);
+
+unsigned int
+ema_mp4_mux_set_chunk_span_time_clang(void *handle, unsigned int chunk_span_time);
+
+unsigned int
+ema_mp4_mux_set_moov_position_clang(void *handle, const char *pos);
+
+unsigned int
+ema_mp4_mux_set_moov_free_space_clang(void *handle, unsigned int size);
+
+unsigned int
+ema_mp4_mux_set_co64_mode_clang(void *handle, const char *mode);
+
+unsigned int
+ema_mp4_mux_set_sd_clang(void *handle, const char *sd);
+
 unsigned int
 ema_mp4_mux_set_mbrand_clang(void *handle, const char *mbrand);
 
@@ -62,18 +96,68 @@ ema_mp4_mux_set_output_format_clang(void *handle, const char *outfm);
 unsigned int
 ema_mp4_mux_set_max_duration_clang(void *handle, unsigned int max_duration);
 
+unsigned int
+ema_mp4_mux_add_event_clang(void *handle, const char *scheme_id_uri, const char *value,
+                            unsigned int timescale, unsigned long long presentation_time, unsigned int event_duration,
+                            unsigned int id, unsigned int version, const unsigned char *message_data, unsigned int message_size);
+
+unsigned int
+ema_mp4_mux_set_min_duration_clang(void *handle, unsigned int min_duration);
+
+unsigned int
+ema_mp4_mux_set_frag_on_every_sync_clang(void *handle, unsigned int on_every_sync);
+
+unsigned int
+ema_mp4_mux_set_frag_boundaries_clang(void *handle, const unsigned long long *boundaries, unsigned int boundary_num);
+
 unsigned int
 ema_mp4_mux_set_dv_profile_clang(void *handle, unsigned int profile);
 
 unsigned int
 ema_mp4_mux_set_dv_bl_compatible_id_clang(void *handle, int compatible_id);
 
+unsigned int
+ema_mp4_mux_set_dv_mode_clang(void *handle, const char *mode);
+
+/* ema_mp4_hevc_info_t */
+struct hevc_info_clang
+{
+    unsigned int hevc;
+    unsigned int width;
+    unsigned int height;
+    unsigned int bit_depth_luma;
+    unsigned int colour_description_present;
+    unsigned int transfer_characteristics;
+    unsigned int preferred_transfer_characteristics;
+    unsigned int time_scale;
+    unsigned int num_units_in_tick;
+    unsigned int dv_rpu_found;
+    unsigned int dv_el_found;
+    unsigned int dv_vdr_rpu_profile;
+    unsigned int dv_vdr_bit_depth;
+    unsigned int dv_rpu_nal_flag;
+    unsigned int dv_el_nal_flag;
+    unsigned int dv_level;
+};
+
+unsigned int
+ema_mp4_mux_get_hevc_info_clang(void *handle, int es_idx, struct hevc_info_clang *info);
+
 unsigned int
 ema_mp4_mux_set_sampleentry_dvh1_clang(void *handle, int es_idx);
 
 unsigned int
 ema_mp4_mux_set_sampleentry_hvc1_clang(void *handle, int es_idx);
 
+unsigned int
+ema_mp4_mux_set_track_id_clang(void *handle, int es_idx, unsigned int track_id);
+
+unsigned int
+ema_mp4_mux_set_alternate_group_clang(void *handle, int es_idx, unsigned short alternate_group);
+
+unsigned int
+ema_mp4_mux_set_track_enabled_clang(void *handle, int es_idx, int enabled);
+
 void ema_mp4_mux_consistency_check_clang(void *handle);
 
 unsigned int
@@ -294,6 +294,145 @@ struct mp4_dsi_ac4_t_
 typedef struct mp4_dsi_ac4_t_  mp4_dsi_ac4_t;
 typedef mp4_dsi_ac4_t  *mp4_dsi_ac4_handle_t;
 
+/**** av1 dsi */
+struct mp4_dsi_av1_t_
+{
+    DSI_BASE;
+
+    /** AV1CodecConfigurationRecord fields, see AV1 Codec ISO Media File Format Binding 2.3.3 */
+    uint8_t  seq_profile;
+    uint8_t  seq_level_idx_0;
+    uint8_t  seq_tier_0;
+    uint8_t  high_bitdepth;
+    uint8_t  twelve_bit;
+    uint8_t  monochrome;
+    uint8_t  chroma_subsampling_x;
+    uint8_t  chroma_subsampling_y;
+    uint8_t  chroma_sample_position;
+
+    uint32_t width, height;         /** max_frame_width/height of the sequence */
+
+    uint8_t *seq_hdr_obu;           /** the sequence header OBU, including its obu_size field */
+    uint32_t seq_hdr_obu_size;
+};
+typedef struct mp4_dsi_av1_t_  mp4_dsi_av1_t;
+typedef mp4_dsi_av1_t  *mp4_dsi_av1_handle_t;
+
+/**** vvc dsi */
+struct mp4_dsi_vvc_t_
+{
+    DSI_BASE;
+
+    list_handle_t vps_lst;
+    list_handle_t sps_lst;
+    list_handle_t pps_lst;
+    list_handle_t aps_lst;          /** prefix APS ahead of the first picture; the samples carry them all */
+
+    /** VvcDecoderConfigurationRecord fields, see ISO/IEC 14496-15 11.2.4.2 */
+    uint8_t  ptl_sps_id;            /** the SPS the fields below are taken from */
+    uint8_t  num_sublayers;
+    uint8_t  chroma_format_idc;
+    uint8_t  bit_depth_minus8;
+    uint16_t max_picture_width;
+    uint16_t max_picture_height;
+    uint8_t  num_bytes_constraint_info;
+    uint8_t *ptl;                   /** profile_tier_level() of the SPS: VvcPTLRecord without its first byte */
+    uint32_t ptl_size;              /** 0: no profile_tier_level() in the SPS */
+
+    uint32_t width, height;         /** the picture size after the conformance window */
+};
+typedef struct mp4_dsi_vvc_t_  mp4_dsi_vvc_t;
+typedef mp4_dsi_vvc_t  *mp4_dsi_vvc_handle_t;
+
+/**** opus dsi */
+struct mp4_dsi_opus_t_
+{
+    DSI_BASE;
+
+    /** OpusSpecificBox fields, see Encapsulation of Opus in ISO Base Media File Format 4.3.2 */
+    uint8_t  output_channel_count;
+    uint16_t pre_skip;
+    uint32_t input_sample_rate;
+    int16_t  output_gain;
+    uint8_t  channel_mapping_family;
+    uint8_t  stream_count;
+    uint8_t  coupled_count;
+    uint8_t  channel_mapping[255];
+};
+typedef struct mp4_dsi_opus_t_  mp4_dsi_opus_t;
+typedef mp4_dsi_opus_t  *mp4_dsi_opus_handle_t;
+
+/**** flac dsi */
+struct mp4_dsi_flac_t_
+{
+    DSI_BASE;
+
+    /** the FLAC metadata blocks of 'dfLa' with their headers, STREAMINFO first */
+    uint8_t *metadata_blocks;
+    uint32_t metadata_blocks_size;
+};
+typedef struct mp4_dsi_flac_t_  mp4_dsi_flac_t;
+typedef mp4_dsi_flac_t  *mp4_dsi_flac_handle_t;
+
+/**** pcm dsi */
+struct mp4_dsi_pcm_t_
+{
+    DSI_BASE;
+
+    /** PCMConfig of ISO/IEC 23003-5 */
+    uint8_t  format_flags;          /** 1: little endian */
+    uint8_t  pcm_sample_size;       /** bits per sample of each channel */
+    uint8_t  valid_bits;            /** msb aligned bits used of pcm_sample_size */
+    BOOL     float_flag;            /** IEEE floating point, else two's complement integer */
+    uint32_t block_align;           /** bytes per frame of all channels */
+
+    /** ChannelLayout: defined_layout 0 gives the speaker_position of each channel */
+    BOOL     layout_present;
+    uint8_t  defined_layout;
+    uint8_t  speaker_position[18];  /** WAVE_FORMAT_EXTENSIBLE knows 18 speakers */
+};
+typedef struct mp4_dsi_pcm_t_  mp4_dsi_pcm_t;
+typedef mp4_dsi_pcm_t  *mp4_dsi_pcm_handle_t;
+
+/**** mpeg-h dsi */
+struct mp4_dsi_mpegh_t_
+{
+    DSI_BASE;
+
+    /** MHADecoderConfigurationRecord of ISO/IEC 23008-3 20.5 */
+    uint8_t  profile_level;         /** mpegh3daProfileLevelIndication */
+    uint8_t  reference_channel_layout;
+    uint8_t *config;                /** mpegh3daConfig() */
+    uint32_t config_size;
+};
+typedef struct mp4_dsi_mpegh_t_  mp4_dsi_mpegh_t;
+typedef mp4_dsi_mpegh_t  *mp4_dsi_mpegh_handle_t;
+
+/**** dts dsi */
+struct mp4_dsi_dts_t_
+{
+    DSI_BASE;
+
+    BOOL     uhd;                   /** DTS-UHD: 'udts', else DTS core: 'ddts' */
+    BOOL     substream;             /** DTS-HD extension substreams follow the core */
+
+    /** DTSSpecificBox of ETSI TS 102 114 Annex E */
+    uint32_t sampling_frequency;
+    uint8_t  pcm_sample_depth;
+    uint8_t  frame_duration_code;
+    uint8_t  stream_construction;
+    BOOL     core_lfe_present;
+    uint8_t  core_layout;
+    uint16_t core_size;             /** FSIZE: the core frame size - 1 */
+    uint16_t channel_layout;
+
+    /** DTSUHDSpecificBox of ETSI TS 103 491 Annex B */
+    uint8_t  base_sampling_frequency_code;
+    uint8_t  sample_rate_mod;
+};
+typedef struct mp4_dsi_dts_t_  mp4_dsi_dts_t;
+typedef mp4_dsi_dts_t  *mp4_dsi_dts_handle_t;
+
 /****** dsi */
 dsi_handle_t dsi_hevc_create(uint32_t dsi_type);
 
@@ -313,6 +452,27 @@ dsi_handle_t dsi_ec3_create(uint32_t dsi_type);
 dsi_handle_t mp4_dsi_ac4_create(void);
 dsi_handle_t dsi_ac4_create(uint32_t dsi_type);
 
+dsi_handle_t mp4_dsi_opus_create(void);
+dsi_handle_t dsi_opus_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_flac_create(void);
+dsi_handle_t dsi_flac_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_pcm_create(void);
+dsi_handle_t dsi_pcm_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_mpegh_create(void);
+dsi_handle_t dsi_mpegh_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_dts_create(void);
+dsi_handle_t dsi_dts_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_av1_create(void);
+dsi_handle_t dsi_av1_create(uint32_t dsi_type);
+
+dsi_handle_t mp4_dsi_vvc_create(void);
+dsi_handle_t dsi_vvc_create(uint32_t dsi_type);
+
 #ifdef __cplusplus
 };
 #endif
@@ -48,6 +48,10 @@
 
 #define ISOM_MUXCFG_DEFAULT         (ISOM_MUXCFG_WRITE_IODS | ISOM_MUXCFG_WRITE_SUBS_V1 | ISOM_MUXCFG_WRITE_STSS)
 
+/* additional options (withopt) */
+#define ISOM_MUXCFG_WITHOPT_CO64    0x1  /**< always write 64 bit chunk offsets ('co64') */
+#define ISOM_MUXCFG_WITHOPT_NO_CO64 0x2  /**< never write 'co64', fail if a chunk offset exceeds 32 bit */
+
 #define ISOM_FRAGCFG_FRAGSTYLE_MASK        (0xff)
 #define ISOM_FRAGCFG_FRAGSTYLE_DEFAULT     1  /**< Fragmentation must follow: each fragment starts with a sync sample. */
 #define ISOM_FRAGCFG_FRAGSTYLE_CCFF        2  /**< Fragmentation must conform to the Common File Format as specified in the Ultraviolet spec */
@@ -68,6 +72,9 @@
 #define ISOM_FRAGCFG_WRITE_MFRA              (0x1<<(ISOM_FRAGCFG_BIT0+12))
 #define ISOM_FRAGCFG_FORCE_TFHD_SAMPDESCIDX  (0x1<<(ISOM_FRAGCFG_BIT0+13))  /**< write sample description index into 'tfhd' */
 #define ISOM_FRAGCFG_FORCE_TRUN_V0           (0x1<<(ISOM_FRAGCFG_BIT0+14))  /**< always write version 0 in TRUN, irrespective of CTTS version */
+#define ISOM_FRAGCFG_FORCE_TFDT_V1           (0x1<<(ISOM_FRAGCFG_BIT0+15))  /**< always write version 1 'tfdt', irrespective of the decode time */
+#define ISOM_FRAGCFG_AUDIO_EDTS_ONLY         (0x1<<(ISOM_FRAGCFG_BIT0+16))  /**< write edit lists for audio priming only, not for composition offsets */
+#define ISOM_FRAGCFG_WRITE_EMSG              (0x1<<(ISOM_FRAGCFG_BIT0+17))  /**< write the added 'emsg' boxes in front of the 'moof' of their fragment */
 
 #define ISOM_FRAGCFG_DEFAULT     (ISOM_FRAGCFG_FRAGSTYLE_DEFAULT)
 
@@ -110,6 +117,7 @@ enum OutputFormat
     OUTPUT_FORMAT_3GP,
     OUTPUT_FORMAT_PIFF,
     OUTPUT_FORMAT_UVU,
+    OUTPUT_FORMAT_CMAF,
 };
 
 
@@ -144,6 +152,21 @@ typedef struct mp4base_version_info_
     const int8_t* text;      /**< version text */
 } mp4base_version_info;
 
+/* event message, written as 'emsg' box */
+typedef struct emsg_t_
+{
+    int8_t  *scheme_id_uri;
+    int8_t  *value;
+    uint32_t timescale;
+    uint64_t presentation_time;     /* in timescale */
+    uint32_t event_duration;        /* in timescale, 0xFFFFFFFF: unknown */
+    uint32_t id;
+    uint8_t *message_data;
+    uint32_t message_size;
+    uint8_t  version;               /* 0: time relative to the fragment, 1: time on the media timeline */
+    BOOL     written;
+} emsg_t;
+
 typedef struct elst_entry_t_
 {
     uint64_t    segment_duration;   /* in movie timescale */
@@ -162,8 +185,13 @@ typedef struct usr_cfg_es_t_
     const int8_t * sample_entry_name;
     uint32_t     chunk_span_size;                      /**< chunk of this stream is controlled by size in byte */
     uint32_t     es_idx;                               /**< its idx in usr_cfg_ess[] */
-    uint32_t     track_ID;                             /**< track_ID this es mapped into: assigned by muxer */
+    uint32_t     track_ID;                             /**< track_ID this es mapped into: requested by user (0: next free) and assigned by muxer */
     uint16_t     alternate_group;                      /**< allow user set-able alternate_group in 'tkhd' */
+    int32_t      usr_alternate_group;                  /**< alternate_group set by user, -1: tracks grouped by media type */
+    int32_t      usr_track_enabled;                    /**< 1: track enabled, 0: track disabled, -1: first track of its alternate group enabled */
+    uint32_t     framerate_nome;                       /**< video frame rate set by user, 0: none */
+    uint32_t     framerate_deno;
+    uint32_t     force_framerate;                      /**< 1: frame rate overrides the timing info of the ES, 0: used only if it has none */
     uint32_t     warp_media_timescale;                 /**< timescale to warp timestamps to */
     uint32_t     force_tkhd_flags;                     /**< override flags in the track header box */
     uint32_t     force_tfhd_flags;                     /**< override flags in the track fragment header */
@@ -174,6 +202,18 @@ typedef struct usr_cfg_es_t_
     int32_t          mp4_tid;                          /**< track ID in mp4 file to extract */
     uint32_t     action;                               /**< track edit action flag */
     uint32_t     sample_entry_name_flag;               /**< flags for specific hevc sample entry name     0: "hvc1"; 1: "hev1"*/
+    uint32_t     ac4_bitrate;                          /**< AC-4 bit_rate written into 'dac4' in bps, 0: unknown */
+    uint32_t     ac4_bitrate_precision;                /**< AC-4 bit_rate_precision in bps, 0xffffffff: unknown */
+    uint32_t     ac4_ims_mode;                         /**< AC4_IMS_DUPLICATE or AC4_IMS_SINGLE */
+    uint32_t     ac4_cfg;                              /**< 1: an AC-4 option is set for this stream */
+    uint32_t     mdcv_cfg;                             /**< 1: mastering display colour volume set by user, overrides the SEI */
+    uint16_t     display_primaries[6];                 /**< G, B, R x and y in units of 0.00002 */
+    uint16_t     white_point[2];                       /**< x and y in units of 0.00002 */
+    uint32_t     max_display_mastering_luminance;      /**< in units of 0.0001 cd/m2 */
+    uint32_t     min_display_mastering_luminance;
+    uint32_t     clli_cfg;                             /**< 1: content light level set by user, overrides the SEI */
+    uint16_t     max_content_light_level;              /**< MaxCLL in cd/m2 */
+    uint16_t     max_pic_average_light_level;          /**< MaxFALL in cd/m2 */
 } usr_cfg_es_t;
 
 /**** config info per mux */
@@ -186,14 +226,19 @@ typedef struct usr_cfg_mux_t_
     uint32_t     timescale;                /**< movie time scale */
     uint32_t     mux_cfg_flags;            /**< flags for enabling specific features ISO media files */
     uint32_t     free_box_in_moov_size;    /**< if set anything but zero than a free box of that size (net) will be inserted at end of moov */
+    uint32_t     moov_at_end;              /**< 1: write moov after mdat; 0: write moov in front of mdat */
 
     ext_timing_info_t ext_timing_info;     /**< external def for h264 timeing in frame rate */
 
-    uint64_t    fix_cm_time;               /**< if creation and modification time is a fixed value */
+    uint64_t    fix_cm_time;               /**< fixed creation and modification time, since 1904 */
+    uint32_t    fix_cm_time_set;           /**< 1: use fix_cm_time instead of the current time */
     uint32_t    chunk_span_time;           /**< chunk span in ms. 0 for non-interleave */
     uint32_t    frag_cfg_flags;            /**< flags for enabling different features of fragmented ISO media files */
     uint32_t    frag_range_max;            /**< max fragment duration in ms */
-    uint32_t    frag_range_min;            /**< min fragment duration in ms */
+    uint32_t    frag_range_min;            /**< min fragment duration in ms, 0: default */
+    uint32_t    frag_on_every_sync;        /**< 1: start a fragment at each sync sample once frag_range_min is reached */
+    uint64_t   *frag_boundaries;           /**< ascending times in ms at which fragments must start */
+    uint32_t    frag_boundary_num;         /**< number of entries in frag_boundaries */
     const int8_t *major_brand;             /**< major brand */
     const int8_t *compatible_brands;       /**< compatible brands */
     uint32_t    brand_version;             /**< the major brand version */
@@ -204,8 +249,6 @@ typedef struct usr_cfg_mux_t_
     int32_t es_num;
     enum OutputFormat output_format;            
     enum DashProfile dash_profile;
-    uint32_t segment_output_flag;
-    uint32_t SegmentCounter;
     uint8_t OD_profile_level;              /**< the OD profile that goes in the initial object description */
     uint8_t scene_profile_level;           /**< the scene profile that goes in the initial object description */
     uint8_t audio_profile_level;           /**< the audio profile that goes in the initial object description */
@@ -297,6 +340,10 @@ typedef struct track_t_
     uint16_t sidx_reference_count;              /**< reference_count used for sidx box creation */
 
     BOOL     write_pre_roll;
+    int16_t  roll_distance;                      /**< of the 'roll' sample group if write_pre_roll */
+    BOOL     open_gop;                           /**< a sample of SAP type 3: 'sap ' sample group written */
+    BOOL     leading_present;                    /**< leading samples: 'sdtp' also written in 'stbl' */
+    uint32_t lpcm_frame_size;                    /**< !0: the sample tables of 'lpcm' count frames of that size */
 
     uint32_t warn_flags;
 
@@ -330,6 +377,7 @@ typedef struct track_t_
     list_handle_t sdtp_lst;                     /**< sample dependency information for 'sdtp' box */
     list_handle_t trik_lst;                     /**< sample dependency information for 'trik' box */
     list_handle_t frame_type_lst;               /**< sample frame type information; level for 'ssix' box*/
+    list_handle_t sap_lst;                      /**< (idx, count, value) SAP type of the video samples, 0: no SAP */
     list_handle_t subs_lst;                     /**< subsample information for 'subs' box */
 
     list_handle_t segment_lst;                  /**< segment index */
@@ -380,6 +428,7 @@ typedef struct track_t_
     tfra_t           tfra;
     uint64_t         frag_dts;                  /**< cut off dts in media_timescale */
     uint32_t         frag_duration;             /**< fragment duration in media_timescale */
+    uint8_t          frag_sap_type;             /**< SAP type of the first sample of the fragment, 0: no SAP */
     /* the number of sample available within an entry still available for next trun */
     BOOL             traf_is_prepared;          /**< indicates a track fragment run is already prepared and the get_trun() 
                                                      only returns the max. size as single trun */
@@ -578,6 +627,8 @@ struct mp4_ctrl_t_
 
     /**** to help build the IOD */
     BOOL has_avc;
+    BOOL has_av1;
+    BOOL has_opus;
     BOOL has_mp4v;
     BOOL has_mp4a;
 
@@ -592,6 +643,7 @@ struct mp4_ctrl_t_
     /* for mfra */
     uint32_t      traf_idx;             /**< for mfra */
     list_handle_t next_track_lst;       /**< list if prepared tracks for fragments (track_handle_t*) */
+    list_handle_t emsg_lst;             /**< event messages written in front of the fragments (emsg_t) */
 
 
     /**** ID32, asset and iTunes atom blobs */
@@ -328,6 +328,18 @@ int32_t   /** @return Error code. */
 mp4_muxer_output_tracks (mp4_muxer_handle_t hmuxer   /** [in] The muxer instance handle. */
                         );
 
+/**
+ *  @brief Checks if the offset of the chunk written last into 'mdat' exceeds 32 bit.
+ *
+ *  The chunk written last starts last_chunk_size bytes before the end of 'mdat'. 'mdat' takes the
+ *  16 byte 'largesize' header if its size doesn't fit into 32 bit.
+ */
+BOOL      /** @return TRUE if the chunk offsets need a 'co64' box. */
+mp4_muxer_chunk_offsets_exceed_32bit (offset_t mdat_pos         /** [in] Position of the 'mdat' box. */
+                                     ,uint64_t mdat_size        /** [in] Size of the media data in 'mdat'. */
+                                     ,uint64_t last_chunk_size  /** [in] Size of the chunk written last. */
+                                     );
+
 /**
  *  @brief Adds child atom to parent udta box.
  *
@@ -341,6 +353,20 @@ mp4_muxer_add_udta_child_atom (mp4_muxer_handle_t  hmuxer     /** [in] The muxer
                               ,      uint32_t      size       /** [in] Size of p_data (data to be added) in bytes. */
                               );
 
+/**
+ *  @brief Adds an event message.
+ *
+ *  The event message gets written as 'emsg' box in front of the 'moof' of the fragment its
+ *  presentation time falls into, if ISOM_FRAGCFG_WRITE_EMSG is set. Events after the last
+ *  fragment go in front of it. The strings and message data are copied. Version 0 boxes carry the
+ *  presentation time as delta to the earliest presentation time of the fragment, writing the
+ *  fragments fails for one before its fragment or 2^32 ticks or more after it.
+ */
+int32_t   /** @return Error code. */
+mp4_muxer_add_emsg (mp4_muxer_handle_t  hmuxer   /** [in] The muxer instance handle. */
+                   ,const emsg_t       *p_emsg   /** [in] The event message. */
+                   );
+
 /**
  *  @brief Adds ainf atom to moov box.
  *
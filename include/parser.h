@@ -336,6 +336,7 @@ typedef enum DescrTag_t_
     uint8_t  pic_type;                                                                  \
     uint8_t  frame_type;                                                                \
     uint8_t  dependency_level;                                                          \
+    uint8_t  sap_type;  /** of a sync sample, 0: not known (type 1 is assumed) */      \
     /** auxiliary data used in demuxer for decryption */                                \
     uint8_t  aux_data[256];                                                             \
     uint32_t aux_data_type;                                                             \
@@ -407,18 +408,32 @@ typedef struct parser_reporter_t_
 } parser_reporter_t;
 
 
+/** IMS (presentation_version 2) presentations in 'dac4' */
+#define AC4_IMS_DUPLICATE 0  /** followed by a presentation_version 1 copy for decoders without IMS support */
+#define AC4_IMS_SINGLE    1  /** the IMS presentation only */
+
+/** Dolby Vision layers kept in the samples */
+#define DV_MODE_KEEP    0  /** base layer, enhancement layer and RPU as in the ES */
+#define DV_MODE_STRIP   1  /** the base layer only, RPU and enhancement layer dropped */
+#define DV_MODE_DROP_EL 2  /** base layer and RPU, the enhancement layer dropped */
+
 /** external timing info: default value and if to overide avc or vc1 embededed timing */
 typedef struct ext_timing_info_t_
 {
     unsigned override_timing;    /** if over ride timing info in sps */
+    unsigned usr_timing;         /** if time_scale/num_units_in_tick are set by user: reported once if they conflict with the ES */
     /** externally time_scale/num_units_in_tick define frame rate in overiding */
     uint32_t time_scale, num_units_in_tick;
     uint8_t         ext_dv_profile;           /* dolby vision profile, overriding set by user */
     uint8_t         ext_dv_bl_compatible_id;  /* dolby vision profile, must provided by user if profile ID is 8 */
+    uint8_t         ext_dv_mode;              /* the dolby vision layers to keep: DV_MODE_... */
     uint8_t         ps_present_flag;          /* the indicator of dsi info (H264: SPS/PPS; H265: VPS/SPS/PPS) in sample entry box */
     uint32_t        ac4_bitrate;
     uint32_t        ac4_bitrate_precision;
+    uint32_t        ac4_ims_mode;             /* how IMS presentations are signaled in 'dac4': AC4_IMS_... */
     uint32_t        hls_flag;
+    uint32_t        chunk_span_time;          /* chunk span in ms, 0: no interleave; for parsers grouping frames into samples */
+    uint32_t        chunk_span_size;          /* chunk size limit in bytes, 0: none */
 } ext_timing_info_t;
 
 #ifdef WANT_GET_SAMPLE_PUSH
@@ -541,7 +556,20 @@ struct parser_t_
     uint32_t framerate;                     \
     uint8_t colour_primaries;               \
     uint8_t transfer_characteristics;       \
-    uint8_t matrix_coefficients           
+    uint8_t matrix_coefficients;            \
+    uint8_t colour_description_present;     \
+    uint8_t full_range_flag;                \
+    /** mastering display colour volume */  \
+    uint8_t  mdcv_present;                  \
+    uint16_t display_primaries_x[3];        /** G, B, R in units of 0.00002 */ \
+    uint16_t display_primaries_y[3];        \
+    uint16_t white_point_x, white_point_y;  \
+    uint32_t max_display_mastering_luminance; /** in units of 0.0001 cd/m2 */ \
+    uint32_t min_display_mastering_luminance; \
+    /** content light level */              \
+    uint8_t  clli_present;                  \
+    uint16_t max_content_light_level;       \
+    uint16_t max_pic_average_light_level
 
 typedef struct parser_video_t_
 {
@@ -558,7 +586,11 @@ typedef parser_video_t *parser_video_handle_t;
     int32_t      samplesize;  /** reserved as 16 */ \
     int32_t      sample_rate;                       \
     uint32_t qtflags;                               \
-    uint32_t wave_format
+    uint32_t wave_format;                           \
+    /** 'elst' in sample_rate units */              \
+    uint32_t priming_samples;                       \
+    uint64_t valid_samples; /** 0: up to the end */ \
+    int16_t  roll_distance   /** !0: 'roll' group of all samples */
 
 typedef struct parser_audio_t_
 {
@@ -677,10 +709,17 @@ void     parser_call_sample_destroy(struct mp4_sample_t_ *);
 /**** for registry function per every parser defined */
 void parser_hevc_reg (void);
 void parser_avc_reg  (void);
+void parser_av1_reg  (void);
+void parser_vvc_reg  (void);
 void parser_aac_reg  (void);
 void parser_ac3_reg  (void);
 void parser_ec3_reg  (void);
 void parser_ac4_reg  (void);
+void parser_opus_reg (void);
+void parser_flac_reg (void);
+void parser_pcm_reg  (void);
+void parser_mpegh_reg(void);
+void parser_dts_reg  (void);
 void parser_video_reg(void);
 void parser_audio_reg(void);
 
@@ -116,6 +116,8 @@ typedef enum sei_msgType_t_
     SEI_T10_PIC_INDEX                           = 34,
     SEI_T1_SWITCHING_POINT                      = 35,
     SEI_FRAME_PACKING                           = 45,
+    SEI_MASTERING_DISPLAY_COLOUR_VOLUME         = 137,
+    SEI_CONTENT_LIGHT_LEVEL_INFO                = 144,
     /** all other valuse are for SEI_RESERVED msg */
 } sei_msgType_t;
 
@@ -358,6 +360,17 @@ typedef struct avc_decode_t_
     /** to support stereoscopic/frame compatible 3D */
     uint32_t frame_packing_type;
 
+    /** HDR static metadata of the SEI, the first one found is used */
+    uint8_t  mdcv_present;
+    uint16_t display_primaries_x[3];    /** G, B, R in units of 0.00002 */
+    uint16_t display_primaries_y[3];
+    uint16_t white_point_x, white_point_y;
+    uint32_t max_display_mastering_luminance; /** in units of 0.0001 cd/m2 */
+    uint32_t min_display_mastering_luminance;
+    uint8_t  clli_present;
+    uint16_t max_content_light_level;
+    uint16_t max_pic_average_light_level;
+
     /** to support push mode: parser work on one nal a time */
     uint8_t  nal_idx_in_au;      /** start from 0 */
     BOOL     last_au;
@@ -149,6 +149,8 @@ typedef enum stream_id_t_
 
     STREAM_ID_VC1,
     STREAM_ID_YUV420P,
+    STREAM_ID_AV1,
+    STREAM_ID_VVC,
     /** audio */
     STREAM_ID_AAC,
     STREAM_ID_AC3,
@@ -158,6 +160,10 @@ typedef enum stream_id_t_
     STREAM_ID_MP3,
     STREAM_ID_MP2,
     STREAM_ID_DTS,
+    STREAM_ID_OPUS,
+    STREAM_ID_FLAC,
+    STREAM_ID_PCM,
+    STREAM_ID_MPEGH,
     /** metadata */
     STREAM_ID_METX,
     STREAM_ID_METT,
@@ -235,6 +241,14 @@ typedef enum stream_param_id_t_
     /** AAC specific, may be generalized and used in audio */
     STREAM_PARAM_ID_CHANNELCOUNT,
 
+    /** HEVC specific, (uint32_t)-1 if not in the ES */
+    STREAM_PARAM_ID_BIT_DEPTH_LUMA,
+    STREAM_PARAM_ID_PREFERRED_TRANSFER,   /** of the alternative transfer characteristics SEI */
+    STREAM_PARAM_ID_DV_RPU_FOUND,         /** Dolby Vision RPU NALs or RPU SEI in the ES */
+    STREAM_PARAM_ID_DV_EL_FOUND,          /** Dolby Vision EL NALs in the ES */
+    STREAM_PARAM_ID_DV_VDR_RPU_PROFILE,   /** of the first RPU header */
+    STREAM_PARAM_ID_DV_VDR_BIT_DEPTH,
+
     STREAM_PARAM_ID_NUM
 } stream_param_id_t;
 
@@ -961,10 +961,31 @@ typedef struct
     uint32_t ui_cum_cu;
     uint32_t ui_tgt_cu_cum;
     bool IDR_pic_flag;
+    bool b_no_rasl_output;     /** NoRaslOutputFlag of the current IRAP picture */
+    bool b_eos;                /** the next picture starts a coded video sequence */
 
-    uint64_t poc_offset;
     uint32_t rpu_flag;
 
+    /** the sequence info of the first Dolby Vision RPU header */
+    uint8_t  rpu_header_present;
+    uint8_t  vdr_rpu_profile;
+    uint8_t  vdr_bit_depth;
+
+    /** preferred_transfer_characteristics of the first alternative transfer characteristics SEI */
+    uint8_t  preferred_transfer_present;
+    uint8_t  preferred_transfer_characteristics;
+
+    /** HDR static metadata of the prefix SEIs, the first one found is used */
+    uint8_t  mdcv_present;
+    uint16_t display_primaries_x[ 3 ];   /** G, B, R in units of 0.00002 */
+    uint16_t display_primaries_y[ 3 ];
+    uint16_t white_point_x, white_point_y;
+    uint32_t max_display_mastering_luminance; /** in units of 0.0001 cd/m2 */
+    uint32_t min_display_mastering_luminance;
+    uint8_t  clli_present;
+    uint16_t max_content_light_level;
+    uint16_t max_pic_average_light_level;
+
     scaling_list_t as_pps_scaling_lists[ NUM_MAX_PIC_PARAM_SETS ];   /** pps scaling lists */
 
 } hevc_decode_t;
@@ -976,6 +997,7 @@ void decode_pps(hevc_decode_t  *context, hevc_nalu_t *p_nalu );
 void decode_vui( hevc_decode_t *context, sequence_parameter_set_t *p_sps, hevc_nalu_t *p_nalu );
 bool gop_decode_slice(hevc_decode_t  *context, hevc_nalu_t *p_nalu );
 void decode_sei_nalu( hevc_decode_t *p_context, hevc_nalu_t *p_nalu );
+void decode_rpu_header( hevc_decode_t *p_context, hevc_nalu_t *p_nalu );
 
 
 void bitstream_init( bitstream_t *bitstream );
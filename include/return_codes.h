@@ -44,6 +44,7 @@
 #define EMA_MP4_MUXED_IO_ERR          0x17  /**< I/O err */
 #define EMA_MP4_MUXED_CLI_ERR         0x18  /**< CLI err */
 #define EMA_MP4_MUXED_EMPTY_ES        0x19  /**< empty es to mux */
+#define EMA_MP4_MUXED_FRAG_ERR        0x1A  /**< fragmentation not possible with the settings */
 
 /** 0x2?: I/O operation */
 #define EMA_MP4_MUXED_WRITE_ERR       0x20  /**< write error */
@@ -1,7 +1,13 @@
-use crate::cli_input_file::InputFiles;
+use crate::cli_events::{parse_timestamp, read_events, Event};
+use crate::cli_input_file::{InputFiles, MasteringDisplay};
+use crate::dv_check::{check_dv_signalling, resolve_dv_options, DvOptions, HevcInfo, HevcInput};
 use crate::mp4_helpers::{ema_mp4_ctrl_handle_t, error_by_code};
-use anyhow::{bail, Result};
-use clap::{crate_authors, crate_description, crate_name, crate_version, AppSettings, Parser};
+use crate::mp4_segment::SegmentOptions;
+use anyhow::{anyhow, bail, Result};
+use clap::{
+    crate_authors, crate_description, crate_name, crate_version, AppSettings, Args, Parser,
+    Subcommand,
+};
 use std::ffi::CString;
 use std::fs::OpenOptions;
 use std::os::raw::c_void;
@@ -12,18 +18,62 @@ const EXAMPLES: &str = "EXAMPLES:
         mp4muxer2 -o output.mp4 -i audio.ec3 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1
     To multiplex AC-4 audio and H.264 video:
         mp4muxer2 -o output.mp4 -i audio.ac4 -i video.h264 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1
+    To multiplex AC-4 audio signaling its bit rate and IMS presentations without a stereo copy:
+        mp4muxer2 -o output.mp4 -i audio.ac4 --ac4-bitrate 144000 --ac4-ims single --verbose
 
     To multiplex Dolby vision BL+EL+RPU file into a .mp4 file with EC-3 audio track:
         mp4muxer2 -i ves_bl_el_rpu.265 -i audio.ec3 -o output.mp4 --dv-profile 8 --dv-bl-compatible-id 2 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1 --overwrite
         Note: For the Dolby vision profile 8, dv-bl-compatible-id is necessary.
 
+    To multiplex a Dolby vision file detecting its profile and base layer compatible ID from the RPU:
+        mp4muxer2 -i ves_8.1.265 -o output.mp4 --dv-profile auto --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1 --overwrite
+
+    To multiplex an HDR10 HEVC file lacking the mastering display and content light level SEI:
+        mp4muxer2 -i video.265 --master-display \"G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,1)\" --max-cll 1000,400 -o output.mp4 --overwrite
+
+    To multiplex an AV1 .ivf, .obu (low overhead or Annex B) file with Dolby vision profile 10 metadata:
+        mp4muxer2 -i video_10.ivf -o output.mp4 --dv-profile 10 --overwrite
+
+    To multiplex an H.266/VVC Annex B file, its frame rate is not taken from the stream:
+        mp4muxer2 -i video.266 -f 50 -o output.mp4 --overwrite
+
+    To multiplex an Ogg Opus file and a FLAC file, the Opus pre-skip becomes an edit list:
+        mp4muxer2 -i audio.opus --language eng -i audio.flac --language deu -o output.mp4 --overwrite
+
+    To multiplex a WAV file next to picture, each PCM sample spanning a 100 ms chunk:
+        mp4muxer2 -i video.h264 -i stems.wav --interleave 100 -o output.mp4 --overwrite
+
+    To multiplex Dolby, MPEG-H (.mhas) and DTS (.dts core/DTS-HD, .dtsx DTS-UHD) audio next to each other:
+        mp4muxer2 -i video.265 -i audio.ec3 -i audio.mhas -i audio.dtsx -o output.mp4 --overwrite
+
     To multiplex Dolby vision profile 8.4 file into a .mp4 file with sample entry name as 'hvc1':
         mp4muxer2 -i ves_8.4.265 -o output.mp4 --hvc1flag 0 --dv-profile 8 --dv-bl-compatible-id 4 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1 --overwrite
         Note: For the Dolby vision profile 8, dv-bl-compatible-id is necessary.
 
     To multiplex Dolby vision BL+EL+RPU file into a .mp4 file with EC-3 audio track, set framerate, track language and name:
-        mp4muxer2 -i ves_bl_el_rpu.265 -n \"Cool video\" -f 24000/1001 -i audio.ec3 -l rus -n \"Dub, Studio\" -o output.mp4 --dv-profile 8 --dv-bl-compatible-id 2 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1 --overwrite
+        mp4muxer2 -i ves_bl_el_rpu.265 -n \"Cool video\" -f 24000/1001 --force-framerate -i audio.ec3 -l rus -n \"Dub, Studio\" -o output.mp4 --dv-profile 8 --dv-bl-compatible-id 2 --mpeg4-comp-brand mp42,iso6,isom,msdh,dby1 --overwrite
         Note: For the Dolby vision profile 8, dv-bl-compatible-id is necessary.
+
+    To write a CMAF track file for each of a video and an audio input, track_1.mp4 and track_2.mp4:
+        mp4muxer2 -i video.265 -i audio.ec3 -o track.mp4 --output-format cmaf --overwrite
+
+    To write an init segment and numbered media segments of 2 seconds for a JIT packaging origin:
+        mp4muxer2 -i video.265 -o video.mp4 --output-format frag-mp4 --init-segment init.mp4 --segment-template 'seg_$Number%05d$.m4s' --overwrite
+
+    To write the segments of a live rendition into its own directory, renaming each segment into place once complete:
+        mp4muxer2 -i video.265 -o /data/v1.2/out.mp4 --output-format frag-mp4 --init-segment '$RepresentationID$/init.mp4' --segment-template '$RepresentationID$/$Number$.m4s' --segment-start-number 0 --atomic-segments --overwrite
+
+    To fragment a rendition at every GOP of at least 2 seconds and at the ad insertion points in cues.txt:
+        mp4muxer2 -i video.265 -o video.mp4 --output-format frag-mp4 --frag-on-every-sync --frag-min-duration 2000 --mpeg4-max-frag-duration 10000 --frag-boundaries cues.txt --overwrite
+
+    To insert SCTE-35 ad cues and ID3 timed metadata listed in events.txt as 'emsg' boxes, the cues starting fragments:
+        mp4muxer2 -i video.265 -o video.mp4 --output-format frag-mp4 --events events.txt --frag-boundaries cues.txt --overwrite
+
+    To print the boxes of an .mp4 file down to the 'trak' level as JSON:
+        mp4muxer2 dump output.mp4 --depth 2 --json
+
+    To check an .mp4 file in CI, failing on errors (exit code 1) but not on warnings (exit code 3):
+        mp4muxer2 verify output.mp4 --json > report.json || [ $? -eq 3 ]
 ";
 
 #[derive(Parser)]
@@ -33,7 +83,12 @@ const EXAMPLES: &str = "EXAMPLES:
 #[clap(about = crate_description!(), long_about = None)]
 #[clap(global_setting(AppSettings::DeriveDisplayOrder))]
 #[clap(after_help = EXAMPLES)]
-struct Cli {
+#[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+#[derive(Clone)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub tool: Option<Tool>,
+
     /// Overwrites the existing output .mp4 file if there is one.
     #[clap(long)]
     overwrite: bool,
@@ -42,7 +97,20 @@ struct Cli {
     #[clap(long, value_name = "arg")]
     mpeg4_timescale: Option<u32>,
 
+    /// Sets the creation and modification time written into the 'mvhd', 'tkhd' and 'mdhd'
+    /// boxes, either as RFC 3339 date-time (e.g. 2022-12-10T12:00:00Z) or as seconds since
+    /// the Unix epoch. Without this option the current time is used.
+    #[clap(long, value_name = "time", value_parser = parse_creation_time)]
+    creation_time: Option<i64>,
+
+    /// Produces byte-identical output for identical inputs. The creation and modification
+    /// time is taken from --creation-time, then from SOURCE_DATE_EPOCH environment variable,
+    /// and falls back to the Unix epoch.
+    #[clap(long)]
+    reproducible: bool,
+
     /// Specifies the ISO base media file format brand in the format.
+    /// With the QuickTime brand 'qt  ' PCM inputs get an 'lpcm' sample entry instead of 'ipcm'/'fpcm'.
     #[clap(long, value_name = "arg")]
     mpeg4_brand: Option<String>,
 
@@ -52,35 +120,125 @@ struct Cli {
     mpeg4_comp_brand: Option<String>,
 
     /// Sets the output file format or the specification to which the
-    /// output file must conform. Valid values include 'mp4' and 'frag-mp4'.
-    #[clap(long, value_name = "arg", possible_values = ["mp4", "frag-mp4"], default_value = "mp4")]
+    /// output file must conform. Valid values include 'mp4', 'frag-mp4' and 'cmaf'.
+    /// 'cmaf' writes one CMAF track file per input file, named after the output file
+    /// with the input index appended (e.g. output_1.mp4) if there is more than one input.
+    #[clap(long, value_name = "arg", possible_values = ["mp4", "frag-mp4", "cmaf"], default_value = "mp4")]
     output_format: String,
 
+    /// Writes the init segment ('ftyp' and 'moov') of fragmented output into its own file as
+    /// well. $RepresentationID$ is replaced as in --segment-template.
+    #[clap(long, value_name = "FILE")]
+    init_segment: Option<String>,
+
+    /// Writes each fragment of fragmented output as a media segment ('styp', 'sidx', 'moof' and
+    /// 'mdat') as well, named after the pattern. $Number$ (counting from
+    /// --segment-start-number), $Time$ (the earliest presentation time) and $RepresentationID$
+    /// (the output file name without extension) are replaced, the first two also with a width
+    /// as in $Number%05d$. Relative names, also of --init-segment, are taken from the directory
+    /// of the output file, and missing directories are created.
+    #[clap(long, value_name = "pattern")]
+    segment_template: Option<String>,
+
+    /// Sets the $Number$ of the first media segment. Default is 1.
+    #[clap(long, value_name = "number")]
+    segment_start_number: Option<u64>,
+
+    /// Writes each segment file under a temporary name ('.<name>.tmp') and renames it when
+    /// complete, so that a server of the directory never delivers a partly written segment.
+    /// The segments are cut from the output file, so they appear only after the muxing finished.
+    #[clap(long)]
+    atomic_segments: bool,
+
     /// Sets the maximum fragment duration in milliseconds.
     #[clap(long, value_name = "arg")]
     mpeg4_max_frag_duration: Option<u32>,
 
+    /// Sets the minimum fragment duration in milliseconds of all fragmented output formats, which
+    /// must be less than the maximum fragment duration. Fragments of video tracks end at a sync
+    /// sample; with --frag-on-every-sync at the first one once the fragment is this long.
+    /// A time of --frag-boundaries ends a fragment even if it is shorter.
+    #[clap(long, value_name = "ms", value_parser = clap::value_parser!(u32).range(1..))]
+    frag_min_duration: Option<u32>,
+
+    /// Starts a new fragment at every sync sample of video tracks once the fragment is as long
+    /// as --frag-min-duration, instead of filling fragments up to the maximum duration.
+    #[clap(long)]
+    frag_on_every_sync: bool,
+
+    /// Starts fragments at the times listed in the file, e.g. scene cuts or ad insertion points,
+    /// so that separately muxed renditions share their fragment boundaries. One time per line,
+    /// in seconds (12.5) or as hh:mm:ss.fff, lines starting with '#' are comments. A fragment
+    /// starts with the first sample at or after each time, which must be a sync sample.
+    #[clap(long, value_name = "FILE")]
+    frag_boundaries: Option<PathBuf>,
+
+    /// Writes the events listed in the file as 'emsg' boxes in front of the 'moof' of the
+    /// fragment each event falls into, e.g. SCTE-35 ad cues or ID3 timed metadata. One event per
+    /// line of space separated fields: scheme=<uri> ('scte35' and 'id3' for the SCTE-35 and ID3
+    /// schemes), value=<string>, time=<time> and duration=<time> in seconds or as hh:mm:ss.fff,
+    /// timescale=<ticks> (default 1000), id=<n>, version=0|1 (default 1) and the message as
+    /// data=<base64> or file=<path>. Lines starting with '#' are comments. Version 0 events fail
+    /// the muxing if their time lies before their fragment or 2^32 ticks or more after it.
+    #[clap(long, value_name = "FILE")]
+    events: Option<PathBuf>,
+
+    /// Sets the chunk interleaving span in milliseconds for 'mp4' output format,
+    /// 0 disables interleaving. Default is 250 ms.
+    #[clap(long, value_name = "ms")]
+    interleave: Option<u32>,
+
+    /// Sets the position of the 'moov' box for 'mp4' output format. With 'start' ("fast start")
+    /// players can begin playback before the whole file has arrived.
+    #[clap(long, value_name = "arg", possible_values = ["start", "end"], default_value = "start")]
+    moov_position: String,
+
+    /// Selects 32-bit ('stco') or 64-bit ('co64') chunk offsets for 'mp4' output format.
+    /// 'auto' uses 'co64' only if the file gets too large for 32-bit offsets.
+    #[clap(long, value_name = "arg", possible_values = ["auto", "always", "never"], default_value = "auto")]
+    co64: String,
+
+    /// Reserves free space in bytes at the end of the 'moov' box, so that it can grow later
+    /// without relocating the media data.
+    #[clap(long, value_name = "bytes")]
+    moov_free_space: Option<u32>,
+
+    /// Controls mid-stream configuration changes, e.g. of resolution or channel configuration.
+    /// 'multiple' starts a new 'stsd' entry at each change, 'single' rejects such streams.
+    #[clap(long, value_name = "arg", possible_values = ["single", "multiple"], default_value = "multiple")]
+    sample_descriptions: String,
+
     #[clap(
         long,
         value_name = "arg",
-        help = "Sets the Dolby Vision profile. This option is MANDATORY for \
-        DoVi elementary stream: Valid profile values are:\n\
+        help = "Sets the Dolby Vision profile. 'auto', the default for HEVC \
+        elementary streams with Dolby Vision RPU, detects the profile and the base layer \
+        compatible ID from the RPU and the stream. Valid profile values are:\n\
         4 - dvhe.04, BL codec: HEVC10; EL codec: HEVC10; BL compatibility: SDR/HDR.\n\
         5 - dvhe.05, BL codec: HEVC10; EL codec: N/A;    BL compatibility: None.\n\
         7 - dvhe.07, BL codec: HEVC10; EL codec: HEVC10; BL compatibility: Blu-ray HDR10.\n\
         8 - dvhe.08, BL codec: HEVC10; EL codec: N/A;    BL compatibility: SDR/HDR.\n\
-        9 - dvav.09, BL codec: AVC;    EL codec: N/A;    BL compatibility: SDR/HDR.\n",
-        possible_values = ["4", "5", "7", "8", "9"]
+        9 - dvav.09, BL codec: AVC;    EL codec: N/A;    BL compatibility: SDR/HDR.\n\
+        10 - dav1.10, BL codec: AV1;   EL codec: N/A;    BL compatibility: None, or SDR/HDR \
+        with '--dv-bl-compatible-id'.\n",
+        possible_values = ["auto", "4", "5", "7", "8", "9", "10"]
     )]
-    dv_profile: Option<u8>,
+    dv_profile: Option<String>,
 
     /// Sets the Dolby Vision base layer compatible ID, if the profile index is 8,
-    /// this option must be set by user.
+    /// this option must be set by user, a detected profile 8 comes with it. For profile 10 it makes the track 'av01' instead of 'dav1'.
     #[clap(long, value_name = "arg", possible_values = ["1", "2", "4"], required_if_eq("dv-profile", "8"))]
     dv_bl_compatible_id: Option<u8>,
 
+    /// Selects the Dolby Vision layers kept in the samples of AVC and HEVC streams. 'strip' keeps
+    /// the base layer only and muxes it as plain AVC or HEVC, 'drop-el' drops the enhancement
+    /// layer, turning profile 7 into 8.1 and profile 4 into 8.2.
+    #[clap(long, value_name = "arg", possible_values = ["keep", "strip", "drop-el"], default_value = "keep")]
+    dv_mode: String,
+
     /// Set the elementary stream index (starting 1) to set HEVC track's sample entry name to 'dvh1',
     /// default sample entry box name is 'dvhe' for non-cross compatible stream.
+    /// Only the non-cross compatible profile 5 takes it.
     #[clap(long, value_name = "stream index", conflicts_with = "hvc1flag")]
     dvh1flag: Option<i32>,
 
@@ -95,19 +253,220 @@ struct Cli {
         short,
         value_name = "FILE",
         allow_hyphen_values = true,
+        required = true,
         parse(from_os_str)
     )]
-    output_file: PathBuf,
+    output_file: Option<PathBuf>,
+
+    /// Prints the layout of the output file after muxing.
+    #[clap(long)]
+    verbose: bool,
 
     #[clap(flatten)]
     input_files: InputFiles,
 }
 
-pub fn parse_cli(handle: *mut c_void) -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Clone, Subcommand)]
+pub enum Tool {
+    /// Prints the box tree of an .mp4 file with the fields of common boxes decoded.
+    Dump(DumpArgs),
+
+    /// Checks an .mp4 file: sample tables against each other and the 'mdat' boxes, track runs,
+    /// brands, the Dolby Vision configuration and fragments starting with sync samples.
+    /// Exits with 1 if there are errors and with 3 if there are warnings only.
+    Verify(VerifyArgs),
+}
+
+#[derive(Args, Clone)]
+pub struct DumpArgs {
+    /// Input .mp4 file name.
+    #[clap(value_name = "FILE", parse(from_os_str))]
+    pub file: PathBuf,
+
+    /// Prints the box tree as JSON.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Prints the given number of box levels only, 1 being the top level boxes.
+    #[clap(long, value_name = "levels", value_parser = clap::value_parser!(u64).range(1..))]
+    pub depth: Option<u64>,
+}
+
+#[derive(Args, Clone)]
+pub struct VerifyArgs {
+    /// Input .mp4 file name.
+    #[clap(value_name = "FILE", parse(from_os_str))]
+    pub file: PathBuf,
+
+    /// Prints the report as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+pub struct Options {
+    pub output_file: PathBuf,
+    pub verbose: bool,
+    pub segments: Option<SegmentOptions>,
+}
+
+/// Splits the command line into one per input file for 'cmaf' output format, as a CMAF track
+/// file carries a single track.
+pub fn split_cli(mut cli: Cli) -> Result<Vec<Cli>> {
+    if cli.output_format != "cmaf" || cli.input_files.files.len() < 2 {
+        return Ok(vec![cli]);
+    }
+
+    for (option, pattern) in [
+        ("--init-segment", &cli.init_segment),
+        ("--segment-template", &cli.segment_template),
+    ] {
+        if pattern
+            .as_ref()
+            .is_some_and(|p| !p.contains("$RepresentationID$"))
+        {
+            bail!(
+                "'{}' needs $RepresentationID$ to tell the tracks of the inputs apart",
+                option
+            );
+        }
+    }
+
+    /* clap requires the output file unless a tool subcommand is given */
+    let output_file = cli.output_file.take().unwrap();
+    let files = std::mem::take(&mut cli.input_files.files);
+
+    Ok(files
+        .into_iter()
+        .enumerate()
+        .map(|(idx, file)| {
+            let mut track_cli = cli.clone();
+            track_cli.input_files.files = vec![file];
+            track_cli.output_file = Some(track_file_name(&output_file, idx + 1));
+            track_cli
+        })
+        .collect())
+}
+
+/* output.mp4 becomes output_<index>.mp4 */
+fn track_file_name(output_file: &Path, index: usize) -> PathBuf {
+    let mut name = output_file.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("_{}", index));
+    if let Some(ext) = output_file.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    output_file.with_file_name(name)
+}
+
+pub fn parse_cli(handle: *mut c_void, cli: Cli) -> Result<Options> {
+    /* clap requires the output file unless a tool subcommand is given */
+    let output_file = cli.output_file.unwrap();
+
+    /* interleaving and 'moov' placement only apply to non-fragmented output */
+    if cli.output_format != "mp4" {
+        if cli.interleave.is_some() {
+            bail!("'--interleave' is supported for 'mp4' output format only");
+        }
+        if cli.input_files.files.iter().any(|f| f.chunk_size.is_some()) {
+            bail!("'--chunk-size' is supported for 'mp4' output format only");
+        }
+        if cli.moov_position != "start" {
+            bail!("Fragmented output requires '--moov-position start'");
+        }
+    } else if cli.init_segment.is_some() || cli.segment_template.is_some() {
+        bail!("'--init-segment' and '--segment-template' are supported for fragmented output formats only");
+    } else if cli.frag_min_duration.is_some()
+        || cli.frag_on_every_sync
+        || cli.frag_boundaries.is_some()
+    {
+        bail!("'--frag-min-duration', '--frag-on-every-sync' and '--frag-boundaries' are supported for fragmented output formats only");
+    } else if cli.events.is_some() {
+        bail!("'--events' is supported for fragmented output formats only");
+    } else if cli.interleave == Some(0)
+        && cli.input_files.files.iter().any(|f| f.chunk_size.is_some())
+    {
+        bail!("'--chunk-size' requires interleaving, it can't be used with '--interleave 0'");
+    }
+
+    if let Some(min_duration) = cli.frag_min_duration {
+        /* the muxer defaults to 2 s fragments */
+        let max_duration = cli.mpeg4_max_frag_duration.unwrap_or(2000);
+        if min_duration >= max_duration {
+            bail!(
+                "'--frag-min-duration' ({} ms) must be less than the maximum fragment duration ({} ms)",
+                min_duration,
+                max_duration
+            );
+        }
+    }
+
+    if cli.segment_start_number.is_some() && cli.segment_template.is_none() {
+        bail!("'--segment-start-number' requires '--segment-template'");
+    }
+    if cli.atomic_segments && cli.init_segment.is_none() && cli.segment_template.is_none() {
+        bail!("'--atomic-segments' requires '--init-segment' or '--segment-template'");
+    }
+
+    if let Some(template) = &cli.segment_template {
+        if !template.contains("$Number") && !template.contains("$Time") {
+            bail!(
+                "'--segment-template' needs $Number$ or $Time$ to give each segment its own name"
+            );
+        }
+    }
+
+    let mut track_ids: Vec<u32> = cli
+        .input_files
+        .files
+        .iter()
+        .filter_map(|f| f.track_id)
+        .collect();
+    track_ids.sort_unstable();
+    if let Some(id) = track_ids.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]) {
+        bail!("Track ID {} is given to more than one input file", id);
+    }
+
+    if let Some(f) = cli
+        .input_files
+        .files
+        .iter()
+        .find(|f| f.force_framerate && f.framerate.is_none())
+    {
+        bail!(
+            "'--force-framerate' requires '--framerate' for input file \"{}\"",
+            f.file.to_str().unwrap_or("<unknown file>")
+        );
+    }
+
+    for f in &cli.input_files.files {
+        let file = f.file.to_str().unwrap_or("<unknown file>");
+        if f.ac4_bitrate_precision.is_some() && f.ac4_bitrate.is_none() {
+            bail!(
+                "'--ac4-bitrate-precision' requires '--ac4-bitrate' for input file \"{}\"",
+                file
+            );
+        }
+        let is_ac4 = f
+            .file
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ac4"));
+        if !is_ac4 && (f.ac4_bitrate.is_some() || f.ac4_ims.is_some()) {
+            bail!(
+                "AC-4 options are supported for AC-4 (.ac4) input files only, not for \"{}\"",
+                file
+            );
+        }
+    }
+
+    let input_paths: Vec<PathBuf> = cli
+        .input_files
+        .files
+        .iter()
+        .map(|f| f.file.clone())
+        .collect();
 
     // --input-file
-    for input_file in cli.input_files.files {
+    for (es_idx, input_file) in cli.input_files.files.into_iter().enumerate() {
         {
             if let Err(err) = OpenOptions::new().read(true).open(&input_file.file) {
                 bail!(
@@ -118,10 +477,6 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
             }
         }
 
-        if let Some(framerate) = input_file.framerate {
-            ema_mp4_mux_set_video_framerate(handle, framerate.0, framerate.1)?;
-        }
-
         ema_mp4_mux_set_input(
             handle,
             &input_file.file,
@@ -129,16 +484,75 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
             input_file.name,
             None,
             input_file.timescale.unwrap_or(0),
-            0,
+            input_file.chunk_size.unwrap_or(0),
             0,
         )?;
+
+        let es_idx = es_idx as i32;
+
+        if let Some(framerate) = input_file.framerate {
+            ema_mp4_mux_set_es_framerate(handle, es_idx, framerate, input_file.force_framerate)?;
+        }
+
+        if let Some(track_id) = input_file.track_id {
+            ema_mp4_mux_set_track_id(handle, es_idx, track_id)?;
+        }
+
+        if let Some(alternate_group) = input_file.alternate_group {
+            ema_mp4_mux_set_alternate_group(handle, es_idx, alternate_group)?;
+        }
+
+        if let Some(enabled) = input_file.enabled {
+            ema_mp4_mux_set_track_enabled(handle, es_idx, enabled)?;
+        }
+
+        if let Some(bitrate) = input_file.ac4_bitrate {
+            ema_mp4_mux_set_ac4_bitrate(
+                handle,
+                es_idx,
+                bitrate,
+                input_file.ac4_bitrate_precision.unwrap_or(u32::MAX),
+            )?;
+        }
+
+        if let Some(ims) = input_file.ac4_ims {
+            ema_mp4_mux_set_ac4_ims_mode(handle, es_idx, &ims)?;
+        }
+
+        if let Some(master_display) = input_file.master_display {
+            ema_mp4_mux_set_mastering_display(handle, es_idx, &master_display)?;
+        }
+
+        if let Some((max_cll, max_fall)) = input_file.max_cll {
+            ema_mp4_mux_set_content_light_level(handle, es_idx, max_cll, max_fall)?;
+        }
     }
 
+    // --dv-profile, --dv-bl-compatible-id, --dv-mode
+    let mut dv_options = DvOptions {
+        profile: cli.dv_profile.as_deref().and_then(|p| p.parse().ok()),
+        auto_profile: cli.dv_profile.as_deref() == Some("auto"),
+        bl_compatible_id: cli.dv_bl_compatible_id,
+        dvh1flag: cli.dvh1flag,
+        mode: cli.dv_mode,
+    };
+    resolve_dv_options(&mut dv_options, &hevc_inputs(handle, &input_paths)?)?;
+
+    if let Some(dv_profile) = dv_options.profile {
+        ema_mp4_mux_set_dv_profile(handle, dv_profile)?;
+    }
+    if let Some(dv_bl_compatible_id) = dv_options.bl_compatible_id {
+        ema_mp4_mux_set_dv_bl_compatible_id(handle, dv_bl_compatible_id)?;
+    }
+    ema_mp4_mux_set_dv_mode(handle, &dv_options.mode)?;
+
+    check_dv_signalling(&dv_options, &hevc_inputs(handle, &input_paths)?)?;
+
     /* output file overwrite check */
     /* if no "--overwrite" option, if the output file had been exist, return error and exit.*/
     /* if providing "--overwrite" option, always create output file */
     {
-        if OpenOptions::new().read(true).open(&cli.output_file).is_ok() && !cli.overwrite {
+        if OpenOptions::new().read(true).open(&output_file).is_ok() && !cli.overwrite {
             bail!("Output file had been existed, please using '--overwrite' if you want to overwrite it");
         }
     }
@@ -147,7 +561,7 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
     ema_mp4_mux_set_output(
         handle,
         0,
-        cli.output_file.into_os_string().into_string().unwrap(),
+        output_file.clone().into_os_string().into_string().unwrap(),
     )?;
 
     // --mpeg4-timescale
@@ -155,6 +569,11 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
         ema_mp4_mux_set_moov_timescale(handle, ts)?;
     }
 
+    // --creation-time, --reproducible
+    if let Some(time) = fixed_creation_time(cli.creation_time, cli.reproducible)? {
+        ema_mp4_mux_set_cm_time(handle, time)?;
+    }
+
     // --mpeg4-brand
     if let Some(brand) = cli.mpeg4_brand {
         ema_mp4_mux_set_mbrand(handle, brand)?;
@@ -173,16 +592,47 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
         ema_mp4_mux_set_max_duration(handle, duration)?;
     }
 
-    // --dv-profile
-    if let Some(dv_profile) = cli.dv_profile {
-        ema_mp4_mux_set_dv_profile(handle, dv_profile)?;
+    // --frag-min-duration
+    if let Some(duration) = cli.frag_min_duration {
+        ema_mp4_mux_set_min_duration(handle, duration)?;
     }
 
-    // --dv-bl-compatible-id
-    if let Some(dv_bl_compatible_id) = cli.dv_bl_compatible_id {
-        ema_mp4_mux_set_dv_bl_compatible_id(handle, dv_bl_compatible_id)?;
+    // --frag-on-every-sync
+    if cli.frag_on_every_sync {
+        ema_mp4_mux_set_frag_on_every_sync(handle, true)?;
+    }
+
+    // --frag-boundaries
+    if let Some(file) = &cli.frag_boundaries {
+        ema_mp4_mux_set_frag_boundaries(handle, &read_frag_boundaries(file)?)?;
+    }
+
+    // --events
+    if let Some(file) = &cli.events {
+        for event in read_events(file)? {
+            ema_mp4_mux_add_event(handle, &event)?;
+        }
     }
 
+    // --interleave
+    if let Some(interleave) = cli.interleave {
+        ema_mp4_mux_set_chunk_span_time(handle, interleave)?;
+    }
+
+    // --moov-position
+    ema_mp4_mux_set_moov_position(handle, cli.moov_position)?;
+
+    // --co64
+    ema_mp4_mux_set_co64_mode(handle, cli.co64)?;
+
+    // --moov-free-space
+    if let Some(size) = cli.moov_free_space {
+        ema_mp4_mux_set_moov_free_space(handle, size)?;
+    }
+
+    // --sample-descriptions
+    ema_mp4_mux_set_sd(handle, cli.sample_descriptions)?;
+
     // --dvh1flag
     if let Some(dvh1flag) = cli.dvh1flag {
         ema_mp4_mux_set_sampleentry_dvh1(handle, dvh1flag)?;
@@ -196,18 +646,47 @@ pub fn parse_cli(handle: *mut c_void) -> Result<()> {
     /* consistency check */
     ema_mp4_mux_consistency_check(handle);
 
-    Ok(())
+    let segments = if cli.init_segment.is_some() || cli.segment_template.is_some() {
+        Some(SegmentOptions {
+            init_segment: cli.init_segment,
+            segment_template: cli.segment_template,
+            representation_id: output_file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            directory: output_file.parent().unwrap_or(Path::new("")).to_path_buf(),
+            start_number: cli.segment_start_number.unwrap_or(1),
+            atomic: cli.atomic_segments,
+            overwrite: cli.overwrite,
+        })
+    } else {
+        None
+    };
+
+    Ok(Options {
+        output_file,
+        verbose: cli.verbose,
+        segments,
+    })
 }
 
-fn ema_mp4_mux_set_video_framerate(
+fn ema_mp4_mux_set_es_framerate(
     handle: ema_mp4_ctrl_handle_t,
-    nome: u32,
-    deno: u32,
+    es_idx: i32,
+    framerate: (u32, u32),
+    force: bool,
 ) -> Result<()> {
     let res;
 
     unsafe {
-        res = crate::mp4::ema_mp4_mux_set_video_framerate_clang(handle, nome, deno);
+        res = crate::mp4::ema_mp4_mux_set_es_framerate_clang(
+            handle,
+            es_idx,
+            framerate.0,
+            framerate.1,
+            force as i32,
+        );
     }
 
     if res != 0 {
@@ -261,6 +740,170 @@ fn ema_mp4_mux_set_input(
     Ok(())
 }
 
+fn ema_mp4_mux_set_track_id(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    track_id: u32,
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_track_id_clang(handle, es_idx, track_id);
+    }
+
+    if res != 0 {
+        bail!("Failed to set track ID with error: {}", error_by_code(res));
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_alternate_group(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    alternate_group: u16,
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_alternate_group_clang(handle, es_idx, alternate_group);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set alternate group with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_ac4_bitrate(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    bitrate: u32,
+    precision: u32,
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_ac4_bitrate_clang(handle, es_idx, bitrate, precision);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set AC-4 bit rate with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_ac4_ims_mode(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    mode: &str,
+) -> Result<()> {
+    let res;
+
+    let mode = CString::new(mode).unwrap();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_ac4_ims_mode_clang(handle, es_idx, mode.as_ptr());
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set AC-4 IMS mode with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_mastering_display(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    master_display: &MasteringDisplay,
+) -> Result<()> {
+    let res;
+
+    let primaries: Vec<u16> = master_display
+        .primaries
+        .iter()
+        .flat_map(|&(x, y)| [x, y])
+        .collect();
+    let white_point = [master_display.white_point.0, master_display.white_point.1];
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_mastering_display_clang(
+            handle,
+            es_idx,
+            primaries.as_ptr(),
+            white_point.as_ptr(),
+            master_display.luminance.0,
+            master_display.luminance.1,
+        );
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set mastering display with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_content_light_level(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    max_cll: u16,
+    max_fall: u16,
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_content_light_level_clang(
+            handle, es_idx, max_cll, max_fall,
+        );
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set content light level with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_track_enabled(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    enabled: bool,
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_track_enabled_clang(handle, es_idx, enabled as i32);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set track enabled flag with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
 fn normalize_media_name(s: &str) -> &str {
     let mut s = s.trim();
 
@@ -312,6 +955,176 @@ fn ema_mp4_mux_set_moov_timescale(handle: ema_mp4_ctrl_handle_t, timescale: u32)
     Ok(())
 }
 
+/* seconds between 1904-01-01 (MP4 epoch) and 1970-01-01 (Unix epoch) */
+const MP4_EPOCH_OFFSET: i64 = 0x7C25B080;
+
+fn fixed_creation_time(creation_time: Option<i64>, reproducible: bool) -> Result<Option<i64>> {
+    if creation_time.is_some() || !reproducible {
+        return Ok(creation_time);
+    }
+
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => match epoch
+            .trim()
+            .parse::<i64>()
+            .map_err(anyhow::Error::from)
+            .and_then(check_mp4_time)
+        {
+            Ok(epoch) => Ok(Some(epoch)),
+            Err(err) => bail!("Invalid SOURCE_DATE_EPOCH value \"{}\": {}", epoch, err),
+        },
+        Err(_) => Ok(Some(0)),
+    }
+}
+
+/* reads the fragment boundaries file into times in ms, sorted and without duplicates */
+fn read_frag_boundaries(file: &Path) -> Result<Vec<u64>> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) => bail!(
+            "Failed to read fragment boundaries file \"{}\": {}",
+            file.display(),
+            err
+        ),
+    };
+
+    let mut boundaries = vec![];
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_timestamp(line, 1000) {
+            Some(ms) => boundaries.push(ms),
+            None => bail!(
+                "Invalid time \"{}\" in line {} of \"{}\", expected seconds (12.5) or hh:mm:ss.fff",
+                line,
+                line_idx + 1,
+                file.display()
+            ),
+        }
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    Ok(boundaries)
+}
+
+fn parse_creation_time(value: &str) -> Result<i64> {
+    let value = value.trim();
+
+    let time = if value.bytes().all(|c| c.is_ascii_digit()) {
+        value.parse::<i64>()?
+    } else {
+        parse_rfc3339(value)?
+    };
+
+    check_mp4_time(time)
+}
+
+/* checks that a Unix time is representable as MP4 time, in seconds since 1904 */
+fn check_mp4_time(time: i64) -> Result<i64> {
+    if time < -MP4_EPOCH_OFFSET || time.checked_add(MP4_EPOCH_OFFSET).is_none() {
+        bail!("is out of range of MP4 timestamps (seconds since 1904-01-01T00:00:00Z)");
+    }
+
+    Ok(time)
+}
+
+/* parses 'YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)' into seconds since the Unix epoch */
+fn parse_rfc3339(value: &str) -> Result<i64> {
+    let invalid = || anyhow!("must be RFC 3339 date-time, e.g. 2022-12-10T12:00:00Z, or Unix time");
+
+    let b = value.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || !b"Tt ".contains(&b[10]) {
+        return Err(invalid());
+    }
+    if b[13] != b':' || b[16] != b':' {
+        return Err(invalid());
+    }
+
+    let num = |from: usize, to: usize| -> Result<i64> {
+        let s = value.get(from..to).ok_or_else(invalid)?;
+        if !s.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        s.parse::<i64>().map_err(|_| invalid())
+    };
+
+    let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (hour, minute, second) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+
+    // fractional seconds are not representable in MP4 and are dropped
+    let mut pos = 19;
+    if b[pos] == b'.' {
+        pos += 1;
+        while pos < b.len() && b[pos].is_ascii_digit() {
+            pos += 1;
+        }
+    }
+
+    let offset = match &value[pos..] {
+        "Z" | "z" => 0,
+        tz if tz.len() == 6 && (tz.starts_with('+') || tz.starts_with('-')) => {
+            if b[pos + 3] != b':' {
+                return Err(invalid());
+            }
+            let (tz_hour, tz_minute) = (num(pos + 1, pos + 3)?, num(pos + 4, pos + 6)?);
+            if tz_hour > 23 || tz_minute > 59 {
+                return Err(invalid());
+            }
+            let offset = tz_hour * 3600 + tz_minute * 60;
+            if tz.starts_with('-') {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day < 1 || day > days_in_month || hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+
+    // days since the Unix epoch of the proleptic Gregorian calendar date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Ok(days * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+fn ema_mp4_mux_set_cm_time(handle: ema_mp4_ctrl_handle_t, unix_time: i64) -> Result<()> {
+    let res;
+
+    let time = u64::try_from(check_mp4_time(unix_time)? + MP4_EPOCH_OFFSET)?;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_cm_time_clang(handle, (time >> 32) as u32, time as u32);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set creation and modification time with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
 fn ema_mp4_mux_set_mbrand(handle: ema_mp4_ctrl_handle_t, mbrand: String) -> Result<()> {
     let res;
 
@@ -369,6 +1182,96 @@ fn ema_mp4_mux_set_output_format(handle: ema_mp4_ctrl_handle_t, outfm: String) -
     Ok(())
 }
 
+fn ema_mp4_mux_add_event(handle: ema_mp4_ctrl_handle_t, event: &Event) -> Result<()> {
+    let res;
+
+    let scheme_id_uri = CString::new(event.scheme_id_uri.as_str())?;
+    let value = CString::new(event.value.as_str())?;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_add_event_clang(
+            handle,
+            scheme_id_uri.as_ptr(),
+            value.as_ptr(),
+            event.timescale,
+            event.presentation_time,
+            event.duration,
+            event.id,
+            event.version,
+            event.message_data.as_ptr(),
+            event.message_data.len() as u32,
+        );
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to add event {} with error: {}",
+            event.id,
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_min_duration(handle: ema_mp4_ctrl_handle_t, duration: u32) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_min_duration_clang(handle, duration);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set min fragment duration with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_frag_on_every_sync(handle: ema_mp4_ctrl_handle_t, on: bool) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_frag_on_every_sync_clang(handle, on as u32);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set fragmenting on every sync sample with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_frag_boundaries(
+    handle: ema_mp4_ctrl_handle_t,
+    boundaries: &[u64],
+) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_frag_boundaries_clang(
+            handle,
+            boundaries.as_ptr(),
+            boundaries.len() as u32,
+        );
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set fragment boundaries with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
 fn ema_mp4_mux_set_max_duration(handle: ema_mp4_ctrl_handle_t, duration: u32) -> Result<()> {
     let res;
 
@@ -386,6 +1289,97 @@ fn ema_mp4_mux_set_max_duration(handle: ema_mp4_ctrl_handle_t, duration: u32) ->
     Ok(())
 }
 
+fn ema_mp4_mux_set_chunk_span_time(handle: ema_mp4_ctrl_handle_t, span: u32) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_chunk_span_time_clang(handle, span);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set chunk interleave time with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_moov_position(handle: ema_mp4_ctrl_handle_t, position: String) -> Result<()> {
+    let res;
+
+    let position = CString::new(position).unwrap();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_moov_position_clang(handle, position.as_ptr());
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set moov position with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_co64_mode(handle: ema_mp4_ctrl_handle_t, mode: String) -> Result<()> {
+    let res;
+
+    let mode = CString::new(mode).unwrap();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_co64_mode_clang(handle, mode.as_ptr());
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set chunk offset mode with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_sd(handle: ema_mp4_ctrl_handle_t, sd: String) -> Result<()> {
+    let res;
+
+    let sd = CString::new(sd).unwrap();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_sd_clang(handle, sd.as_ptr());
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set sample description mode with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+fn ema_mp4_mux_set_moov_free_space(handle: ema_mp4_ctrl_handle_t, size: u32) -> Result<()> {
+    let res;
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_moov_free_space_clang(handle, size);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set moov free space with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
 fn ema_mp4_mux_set_dv_profile(handle: ema_mp4_ctrl_handle_t, dv_profile: u8) -> Result<()> {
     let res;
 
@@ -423,6 +1417,62 @@ fn ema_mp4_mux_set_dv_bl_compatible_id(
     Ok(())
 }
 
+fn ema_mp4_mux_set_dv_mode(handle: ema_mp4_ctrl_handle_t, mode: &str) -> Result<()> {
+    let res;
+
+    let mode = CString::new(mode).unwrap();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_set_dv_mode_clang(handle, mode.as_ptr());
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to set Dolby Vision mode with error: {}",
+            error_by_code(res)
+        );
+    }
+
+    Ok(())
+}
+
+/* the HEVC inputs as the parser of the muxer takes them with the options set so far */
+fn hevc_inputs(
+    handle: ema_mp4_ctrl_handle_t,
+    input_paths: &[PathBuf],
+) -> Result<Vec<HevcInput<'_>>> {
+    let mut hevc_inputs = vec![];
+    for (es_idx, path) in input_paths.iter().enumerate() {
+        if let Some(info) = ema_mp4_mux_get_hevc_info(handle, es_idx as i32, path)? {
+            hevc_inputs.push((path.as_path(), info));
+        }
+    }
+    Ok(hevc_inputs)
+}
+
+fn ema_mp4_mux_get_hevc_info(
+    handle: ema_mp4_ctrl_handle_t,
+    es_idx: i32,
+    path: &Path,
+) -> Result<Option<HevcInfo>> {
+    let res;
+    let mut info = crate::mp4::hevc_info_clang::default();
+
+    unsafe {
+        res = crate::mp4::ema_mp4_mux_get_hevc_info_clang(handle, es_idx, &mut info);
+    }
+
+    if res != 0 {
+        bail!(
+            "Failed to parse HEVC input file \"{}\" with error: {}",
+            path.to_str().unwrap_or("<unknown file>"),
+            error_by_code(res)
+        );
+    }
+
+    Ok((info.hevc != 0).then(|| info.into()))
+}
+
 fn ema_mp4_mux_set_sampleentry_dvh1(handle: ema_mp4_ctrl_handle_t, es_idx: i32) -> Result<()> {
     let res;
 
@@ -0,0 +1,185 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+
+const SCHEME_SCTE35: &str = "urn:scte:scte35:2013:bin";
+const SCHEME_ID3: &str = "https://aomedia.org/emsg/ID3";
+
+/// An event message for an 'emsg' box.
+pub struct Event {
+    pub scheme_id_uri: String,
+    pub value: String,
+    pub timescale: u32,
+    /// In timescale, on the media timeline.
+    pub presentation_time: u64,
+    /// In timescale, 0xFFFFFFFF if unknown.
+    pub duration: u32,
+    pub id: u32,
+    /// The 'emsg' version, 0 carries the time relative to the fragment.
+    pub version: u32,
+    pub message_data: Vec<u8>,
+}
+
+/// Reads the events file, one event per line of space separated `key=value` fields:
+///
+/// `scheme=<uri>` (`scte35` and `id3` stand for the SCTE-35 and ID3 schemes), `value=<string>`,
+/// `time=<time>` and `duration=<time>` in seconds or as hh:mm:ss.fff, `timescale=<ticks>`
+/// (default 1000), `id=<n>` (default the index of the event), `version=0|1` (default 1) and
+/// the message as `data=<base64>` or `file=<path>` relative to the events file.
+/// Lines starting with '#' are comments.
+pub fn read_events(file: &Path) -> Result<Vec<Event>> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) => bail!("Failed to read events file \"{}\": {}", file.display(), err),
+    };
+
+    let mut events = vec![];
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_event(line, events.len() as u32, file) {
+            Ok(event) => events.push(event),
+            Err(err) => bail!(
+                "Invalid event in line {} of \"{}\": {}",
+                line_idx + 1,
+                file.display(),
+                err
+            ),
+        }
+    }
+
+    Ok(events)
+}
+
+fn parse_event(line: &str, index: u32, file: &Path) -> Result<Event> {
+    let mut scheme = None;
+    let mut value = String::new();
+    let mut timescale = 1000;
+    let mut time = None;
+    let mut duration = None;
+    let mut id = index;
+    let mut version = 1;
+    let mut message_data = vec![];
+
+    for field in line.split_whitespace() {
+        let (key, arg) = field
+            .split_once('=')
+            .ok_or_else(|| anyhow!("\"{}\" is no key=value field", field))?;
+        let invalid = || anyhow!("invalid {} \"{}\"", key, arg);
+        match key {
+            "scheme" => {
+                scheme = Some(match arg {
+                    "scte35" => SCHEME_SCTE35.to_string(),
+                    "id3" => SCHEME_ID3.to_string(),
+                    _ => arg.to_string(),
+                })
+            }
+            "value" => value = arg.to_string(),
+            "timescale" => timescale = arg.parse().ok().filter(|&t| t > 0).ok_or_else(invalid)?,
+            "time" => time = Some(arg),
+            "duration" => duration = Some(arg),
+            "id" => id = arg.parse().map_err(|_| invalid())?,
+            "version" => version = arg.parse().ok().filter(|&v| v <= 1).ok_or_else(invalid)?,
+            "data" => message_data = decode_base64(arg).ok_or_else(invalid)?,
+            "file" => {
+                let path = file.parent().unwrap_or(Path::new("")).join(arg);
+                message_data = match std::fs::read(&path) {
+                    Ok(data) => data,
+                    Err(err) => bail!("failed to read \"{}\": {}", path.display(), err),
+                }
+            }
+            _ => bail!("unknown key \"{}\"", key),
+        }
+    }
+
+    let scheme_id_uri = scheme.ok_or_else(|| anyhow!("no scheme"))?;
+    let time = time.ok_or_else(|| anyhow!("no time"))?;
+    let presentation_time =
+        parse_timestamp(time, timescale).ok_or_else(|| anyhow!("invalid time \"{}\"", time))?;
+    let duration = match duration {
+        Some(duration) => parse_timestamp(duration, timescale)
+            .and_then(|d| u32::try_from(d).ok())
+            .filter(|&d| d != u32::MAX)
+            .ok_or_else(|| anyhow!("invalid duration \"{}\"", duration))?,
+        None => u32::MAX,
+    };
+
+    /* the message formats the schemes define */
+    if scheme_id_uri == SCHEME_SCTE35 && message_data.first() != Some(&0xFC) {
+        bail!("SCTE-35 message is no splice_info_section (table_id 0xFC)");
+    }
+    if scheme_id_uri == SCHEME_ID3 && !message_data.starts_with(b"ID3") {
+        bail!("ID3 message is no ID3v2 tag");
+    }
+
+    Ok(Event {
+        scheme_id_uri,
+        value,
+        timescale,
+        presentation_time,
+        duration,
+        id,
+        version,
+        message_data,
+    })
+}
+
+/// Parses '[[hh:]mm:]ss[.fraction]' into ticks of timescale, dropping parts of a tick.
+pub fn parse_timestamp(value: &str, timescale: u32) -> Option<u64> {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if !fraction.is_empty() && !digits(fraction) {
+        return None;
+    }
+    let parts: Vec<&str> = whole.split(':').collect();
+    if parts.len() > 3 || !parts.iter().all(|p| digits(p)) {
+        return None;
+    }
+    /* minutes and seconds after the hours or minutes take two digits below 60 */
+    if parts[1..].iter().any(|p| p.len() != 2 || *p > "59") {
+        return None;
+    }
+
+    let mut seconds: u64 = 0;
+    for part in parts {
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    let fraction = &fraction[..fraction.len().min(18)];
+    let ticks = fraction.parse::<u128>().unwrap_or(0) * timescale as u128
+        / 10u128.pow(fraction.len() as u32);
+
+    seconds
+        .checked_mul(timescale as u64)?
+        .checked_add(ticks as u64)
+}
+
+/* decodes standard base64 with optional padding */
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim_end_matches('=');
+    let mut data = Vec::with_capacity(value.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in value.bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | sextet as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+        }
+    }
+    /* a single character left can't form a byte */
+    if bits >= 6 {
+        return None;
+    }
+    Some(data)
+}
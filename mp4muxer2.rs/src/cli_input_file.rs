@@ -4,18 +4,37 @@ use clap::{Arg, ArgMatches, Args, Command, CommandFactory, FromArgMatches, Parse
 use std::collections::VecDeque;
 use std::path::PathBuf;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct InputFiles {
     pub files: Vec<InputFile>,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct InputFile {
     pub file: PathBuf,
     pub name: Option<String>,
     pub language: Option<String>,
     pub timescale: Option<u32>,
     pub framerate: Option<(u32, u32)>,
+    pub force_framerate: bool,
+    pub chunk_size: Option<u32>,
+    pub alternate_group: Option<u16>,
+    pub enabled: Option<bool>,
+    pub track_id: Option<u32>,
+    pub ac4_bitrate: Option<u32>,
+    pub ac4_bitrate_precision: Option<u32>,
+    pub ac4_ims: Option<String>,
+    pub master_display: Option<MasteringDisplay>,
+    pub max_cll: Option<(u16, u16)>,
+}
+
+/// Mastering display colour volume: the G, B, R primaries and the white point in units of
+/// 0.00002 and the maximum and minimum luminance in units of 0.0001 cd/m2.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    pub primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+    pub luminance: (u32, u32),
 }
 
 #[derive(Parser)]
@@ -66,7 +85,10 @@ struct InputFileArgs {
     )]
     timescale: Vec<u32>,
 
-    /// Set framerate only for video such as 23.97 or 24000/1001. [Optional]
+    /// Video frame rate such as 25, 24000/1001, 23.976, 29.97fps, 50p or 50i (fields), used if
+    /// the stream carries no timing info. Unlike earlier versions it doesn't override the timing
+    /// info of the stream (e.g. VUI) unless --force-framerate is given as well. Rounded NTSC
+    /// rates are taken as their exact 1001 based value. Not allowed for audio. [Optional]
     #[clap(
         long = "framerate",
         short = 'f',
@@ -76,6 +98,119 @@ struct InputFileArgs {
         value_parser=parse_framerate
     )]
     framerate: Vec<(u32, u32)>,
+
+    /// Ignores the timing info of the stream (e.g. VUI) and forces the constant frame rate given
+    /// by --framerate. [Optional]
+    #[clap(
+        long = "force-framerate",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)")
+    )]
+    force_framerate: bool,
+
+    /// Maximum chunk size in bytes, splits chunks of the track in addition to --interleave. [Optional]
+    #[clap(
+        long = "chunk-size",
+        value_name = "bytes",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=clap::value_parser!(u32).range(1..)
+    )]
+    chunk_size: Vec<u32>,
+
+    /// Alternate group in 'tkhd', 0 for none. By default all video tracks form group 1
+    /// and all audio tracks group 2. [Optional]
+    #[clap(
+        long = "alternate-group",
+        value_name = "group",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=clap::value_parser!(u16)
+    )]
+    alternate_group: Vec<u16>,
+
+    /// Marks the track enabled. By default only the first track of an alternate group is. [Optional]
+    #[clap(
+        long = "enabled",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)")
+    )]
+    enabled: bool,
+
+    /// Marks the track disabled. [Optional]
+    #[clap(
+        long = "disabled",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)")
+    )]
+    disabled: bool,
+
+    /// Track ID, e.g. to keep IDs stable across versions of a title. By default a track gets the
+    /// ID following the one of the previous track, skipping the IDs given to other tracks. [Optional]
+    #[clap(
+        long = "track-id",
+        value_name = "id",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=clap::value_parser!(u32).range(1..)
+    )]
+    track_id: Vec<u32>,
+
+    /// AC-4 bit rate in bits per second signaled in 'dac4'. Unknown by default. [Optional]
+    #[clap(
+        long = "ac4-bitrate",
+        value_name = "bps",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=clap::value_parser!(u32).range(1..)
+    )]
+    ac4_bitrate: Vec<u32>,
+
+    /// Precision of --ac4-bitrate in bits per second. Unknown by default. [Optional]
+    #[clap(
+        long = "ac4-bitrate-precision",
+        value_name = "bps",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=clap::value_parser!(u32).range(..0xFFFF_FFFF)
+    )]
+    ac4_bitrate_precision: Vec<u32>,
+
+    /// How IMS presentations are signaled in 'dac4': 'duplicate' follows each with a
+    /// presentation_version 1 copy for decoders without IMS support, 'single' writes the IMS
+    /// presentation only. [Optional, default: duplicate]
+    #[clap(
+        long = "ac4-ims",
+        value_name = "arg",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        possible_values = ["duplicate", "single"]
+    )]
+    ac4_ims: Vec<String>,
+
+    /// Mastering display colour volume in x265 notation, e.g.
+    /// 'G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,1)'. Primaries and white
+    /// point in units of 0.00002, luminance in units of 0.0001 cd/m2. Overrides the SEI of the
+    /// stream. Not allowed for audio. [Optional]
+    #[clap(
+        long = "master-display",
+        value_name = "arg",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=parse_master_display
+    )]
+    master_display: Vec<MasteringDisplay>,
+
+    /// Content light level as 'MaxCLL,MaxFALL' in cd/m2, e.g. '1000,400'. Overrides the SEI of
+    /// the stream. Not allowed for audio. [Optional]
+    #[clap(
+        long = "max-cll",
+        value_name = "arg",
+        multiple_occurrences = true,
+        help_heading = Some("INPUT FILE(S)"),
+        value_parser=parse_max_cll
+    )]
+    max_cll: Vec<(u16, u16)>,
 }
 
 impl Args for InputFiles {
@@ -108,6 +243,40 @@ impl InputFiles {
             matches.remove_many::<T>(arg).unwrap_or_default().collect(),
         )
     }
+
+    // maps the occurrences of flags to their value, ordered by position
+    fn get_flag_sequence(
+        matches: &ArgMatches,
+        flags: &[(&str, bool)],
+    ) -> (VecDeque<usize>, VecDeque<bool>) {
+        let mut occurrences: Vec<(usize, bool)> = flags
+            .iter()
+            .flat_map(|&(arg, value)| {
+                matches
+                    .indices_of(arg)
+                    .unwrap_or_default()
+                    .map(move |idx| (idx, value))
+            })
+            .collect();
+        occurrences.sort_unstable();
+
+        occurrences.into_iter().unzip()
+    }
+
+    // takes the values given before the next input file, the last one wins
+    fn take_argument<T>(sequence: &mut (VecDeque<usize>, VecDeque<T>), next: usize) -> Option<T> {
+        let mut value = None;
+
+        while let Some(&idx) = sequence.0.front() {
+            if idx >= next {
+                break;
+            }
+            value = sequence.1.pop_front();
+            let _ = sequence.0.pop_front();
+        }
+
+        value
+    }
 }
 
 impl FromArgMatches for InputFiles {
@@ -122,13 +291,27 @@ impl FromArgMatches for InputFiles {
         let mut languages = InputFiles::get_argument_sequence::<String>(matches, "language");
         let mut timescales = InputFiles::get_argument_sequence::<u32>(matches, "timescale");
         let mut framerates = InputFiles::get_argument_sequence::<(u32, u32)>(matches, "framerate");
+        let mut force_framerates =
+            InputFiles::get_flag_sequence(matches, &[("force-framerate", true)]);
+        let mut chunk_sizes = InputFiles::get_argument_sequence::<u32>(matches, "chunk-size");
+        let mut alternate_groups =
+            InputFiles::get_argument_sequence::<u16>(matches, "alternate-group");
+        let mut enabled =
+            InputFiles::get_flag_sequence(matches, &[("enabled", true), ("disabled", false)]);
+        let mut track_ids = InputFiles::get_argument_sequence::<u32>(matches, "track-id");
+        let mut ac4_bitrates = InputFiles::get_argument_sequence::<u32>(matches, "ac4-bitrate");
+        let mut ac4_bitrate_precisions =
+            InputFiles::get_argument_sequence::<u32>(matches, "ac4-bitrate-precision");
+        let mut ac4_ims = InputFiles::get_argument_sequence::<String>(matches, "ac4-ims");
+        let mut master_displays =
+            InputFiles::get_argument_sequence::<MasteringDisplay>(matches, "master-display");
+        let mut max_clls = InputFiles::get_argument_sequence::<(u16, u16)>(matches, "max-cll");
 
         let mut input_files = InputFiles {
             ..Default::default()
         };
 
-        let mut i = 1;
-        for file in files.1 {
+        for (i, file) in (1..).zip(files.1) {
             let mut file = InputFile {
                 file,
                 ..Default::default()
@@ -139,55 +322,23 @@ impl FromArgMatches for InputFiles {
             if i < files.0.len() {
                 next = files.0[i];
             }
-            i += 1;
-
-            // set name if provided
-            while !names.0.is_empty() {
-                let idx = names.0[0];
-
-                if idx < next {
-                    file.name = names.1.pop_front();
-                    let _ = names.0.pop_front();
-                } else {
-                    break;
-                }
-            }
-
-            // set language if provided
-            while !languages.0.is_empty() {
-                let idx = languages.0[0];
-
-                if idx < next {
-                    file.language = languages.1.pop_front();
-                    let _ = languages.0.pop_front();
-                } else {
-                    break;
-                }
-            }
-
-            // set timescale if provided
-            while !timescales.0.is_empty() {
-                let idx = timescales.0[0];
-
-                if idx < next {
-                    file.timescale = timescales.1.pop_front();
-                    let _ = timescales.0.pop_front();
-                } else {
-                    break;
-                }
-            }
 
-            // set framerate if provided
-            while !framerates.0.is_empty() {
-                let idx = framerates.0[0];
-
-                if idx < next {
-                    file.framerate = framerates.1.pop_front();
-                    let _ = framerates.0.pop_front();
-                } else {
-                    break;
-                }
-            }
+            file.name = InputFiles::take_argument(&mut names, next);
+            file.language = InputFiles::take_argument(&mut languages, next);
+            file.timescale = InputFiles::take_argument(&mut timescales, next);
+            file.framerate = InputFiles::take_argument(&mut framerates, next);
+            file.force_framerate =
+                InputFiles::take_argument(&mut force_framerates, next).unwrap_or_default();
+            file.chunk_size = InputFiles::take_argument(&mut chunk_sizes, next);
+            file.alternate_group = InputFiles::take_argument(&mut alternate_groups, next);
+            file.enabled = InputFiles::take_argument(&mut enabled, next);
+            file.track_id = InputFiles::take_argument(&mut track_ids, next);
+            file.ac4_bitrate = InputFiles::take_argument(&mut ac4_bitrates, next);
+            file.ac4_bitrate_precision =
+                InputFiles::take_argument(&mut ac4_bitrate_precisions, next);
+            file.ac4_ims = InputFiles::take_argument(&mut ac4_ims, next);
+            file.master_display = InputFiles::take_argument(&mut master_displays, next);
+            file.max_cll = InputFiles::take_argument(&mut max_clls, next);
 
             input_files.files.push(file);
         }
@@ -213,12 +364,157 @@ fn media_lang_validator(v: &str) -> Result<()> {
     }
 }
 
+// the rates of the NTSC derived systems, given in decimals they are rounded
+const NTSC_FRAMERATES: [u32; 6] = [24, 30, 48, 60, 120, 240];
+const MAX_FRAMERATE: u32 = 300;
+
+/// Parses a frame rate like 25, 24000/1001, 23.976, 29.97fps, 50p or 50i, where interlaced
+/// rates count fields, e.g. 50i is 25 frames per second.
 fn parse_framerate(
     value: &str,
 ) -> Result<(u32, u32), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    Ok(if let Some((nome, deno)) = value.split_once('/') {
-        (nome.parse::<u32>()?, deno.parse::<u32>()?)
+    let mut rate = value.trim().to_ascii_lowercase();
+    if let Some(stripped) = rate.strip_suffix("fps") {
+        rate = stripped.trim_end().to_string();
+    }
+    let fields = rate.ends_with('i');
+    if fields || rate.ends_with('p') {
+        rate.pop();
+    }
+
+    let rational = rate.split_once('/');
+    let (mut nome, mut deno) = if let Some((nome, deno)) = rational {
+        (nome.trim().parse::<u64>()?, deno.trim().parse::<u64>()?)
     } else {
-        ((value.parse::<f64>()? * 1000.0) as u32, 1000)
+        parse_decimal(&rate)?
+    };
+    if deno == 0 {
+        return Err("denominator must not be 0".into());
+    }
+    if fields {
+        deno = deno.checked_mul(2).ok_or("denominator too large")?;
+    }
+
+    // snap rounded NTSC rates like 23.976 or 59.94 to their exact value
+    if rational.is_none() {
+        let ntsc = NTSC_FRAMERATES
+            .iter()
+            .find(|&&fps| (nome as f64 / deno as f64 - fps as f64 * 1000.0 / 1001.0).abs() < 0.005);
+        if let Some(&fps) = ntsc {
+            nome = fps as u64 * 1000;
+            deno = 1001;
+        }
+    }
+
+    let out_of_range = || {
+        format!(
+            "must be above 0 and at most {} frames per second",
+            MAX_FRAMERATE
+        )
+    };
+    if nome == 0 || nome as u128 > MAX_FRAMERATE as u128 * deno as u128 {
+        return Err(out_of_range().into());
+    }
+    let divisor = gcd(nome, deno);
+    let (nome, deno) = (nome / divisor, deno / divisor);
+
+    // a rate like 1/99999999999999999 is far below any frame rate
+    match (u32::try_from(nome), u32::try_from(deno)) {
+        (Ok(nome), Ok(deno)) => Ok((nome, deno)),
+        _ => Err(out_of_range().into()),
+    }
+}
+
+// a decimal number as exact fraction, e.g. 29.97 is 2997/100
+fn parse_decimal(
+    value: &str,
+) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if int.is_empty() && frac.is_empty()
+        || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("invalid frame rate '{}'", value).into());
+    }
+    // more precision than that is no frame rate anyone means
+    let frac = frac.trim_end_matches('0');
+    if frac.len() > 6 {
+        return Err("too many decimal places".into());
+    }
+
+    let deno = 10u64.pow(frac.len() as u32);
+    let nome = format!("{}{}", int, frac).parse::<u64>()?;
+    Ok((nome, deno))
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// the chromaticity coordinates of 'mdcv' range from 0 to 1 in units of 0.00002
+const MAX_CHROMATICITY: u16 = 50000;
+
+/// Parses a mastering display in x265 notation, G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min).
+fn parse_master_display(
+    value: &str,
+) -> Result<MasteringDisplay, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut rest = value.as_str();
+    let mut pairs = Vec::new();
+
+    for key in ["G", "B", "R", "WP", "L"] {
+        let (pair, tail) = rest
+            .strip_prefix(key)
+            .and_then(|r| r.strip_prefix('('))
+            .and_then(|r| r.split_once(')'))
+            .ok_or_else(|| {
+                format!(
+                    "expected {}(..) in the form G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)",
+                    key
+                )
+            })?;
+        let (a, b) = pair
+            .split_once(',')
+            .ok_or_else(|| format!("expected two values in {}({})", key, pair))?;
+        pairs.push((a.parse::<u32>()?, b.parse::<u32>()?));
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(format!("unexpected '{}'", rest).into());
+    }
+
+    let mut chromaticities = [(0u16, 0u16); 4];
+    for (c, &(x, y)) in chromaticities.iter_mut().zip(&pairs) {
+        if x > MAX_CHROMATICITY as u32 || y > MAX_CHROMATICITY as u32 {
+            return Err(format!(
+                "primaries and white point must be at most {}",
+                MAX_CHROMATICITY
+            )
+            .into());
+        }
+        *c = (x as u16, y as u16);
+    }
+    let luminance = pairs[4];
+    if luminance.1 >= luminance.0 {
+        return Err("the minimum luminance must be below the maximum".into());
+    }
+
+    Ok(MasteringDisplay {
+        primaries: [chromaticities[0], chromaticities[1], chromaticities[2]],
+        white_point: chromaticities[3],
+        luminance,
     })
 }
+
+/// Parses a content light level as MaxCLL,MaxFALL.
+fn parse_max_cll(
+    value: &str,
+) -> Result<(u16, u16), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let (max_cll, max_fall) = value
+        .split_once(',')
+        .ok_or("expected MaxCLL,MaxFALL, e.g. '1000,400'")?;
+    Ok((max_cll.trim().parse()?, max_fall.trim().parse()?))
+}
@@ -0,0 +1,449 @@
+use crate::mp4::hevc_info_clang;
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/* transfer_characteristics of H.273 */
+const TRANSFER_BT709: u8 = 1;
+const TRANSFER_BT601: u8 = 6;
+const TRANSFER_BT2020_10: u8 = 14;
+const TRANSFER_BT2020_12: u8 = 15;
+const TRANSFER_PQ: u8 = 16;
+const TRANSFER_HLG: u8 = 18;
+
+const SDR_TRANSFERS: [u8; 4] = [
+    TRANSFER_BT709,
+    TRANSFER_BT601,
+    TRANSFER_BT2020_10,
+    TRANSFER_BT2020_12,
+];
+/* HLG streams for SDR receivers signal BT.2020 in the VUI and HLG in an alternative transfer SEI */
+const HLG_TRANSFERS: [u8; 2] = [TRANSFER_HLG, TRANSFER_BT2020_10];
+
+/* the widest picture Dolby Vision levels 1 to 13 allow */
+const DV_LEVEL_MAX_WIDTHS: [u32; 13] = [
+    1280, 1280, 1920, 2560, 3840, 3840, 3840, 3840, 3840, 3840, 7680, 7680, 7680,
+];
+
+/// What the HEVC parser of the muxer takes from the first access unit of an input.
+pub struct HevcInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth_luma: u32,
+    /// transfer_characteristics of the VUI, if it has a colour description
+    pub transfer_characteristics: Option<u8>,
+    /// preferred_transfer_characteristics of an alternative transfer characteristics SEI
+    pub preferred_transfer: Option<u8>,
+    /// the timing the parser uses
+    pub time_scale: u32,
+    pub num_units_in_tick: u32,
+    /// Dolby Vision RPU NAL units or the RPU user data SEI
+    pub rpu_found: bool,
+    /// Dolby Vision enhancement layer NAL units
+    pub el_found: bool,
+    /// the header of the first Dolby Vision RPU NAL unit
+    pub rpu_header: Option<RpuHeader>,
+    /// the Dolby Vision signalling of the muxer with the options set
+    pub rpu_nal_flag: bool,
+    pub el_nal_flag: bool,
+    pub dv_level: u8,
+}
+
+/// The sequence information of a Dolby Vision RPU header.
+pub struct RpuHeader {
+    pub vdr_rpu_profile: u8,
+    pub vdr_bit_depth: u32,
+}
+
+impl From<hevc_info_clang> for HevcInfo {
+    fn from(info: hevc_info_clang) -> Self {
+        HevcInfo {
+            width: info.width,
+            height: info.height,
+            bit_depth_luma: info.bit_depth_luma,
+            transfer_characteristics: (info.colour_description_present != 0)
+                .then_some(info.transfer_characteristics as u8),
+            preferred_transfer: u8::try_from(info.preferred_transfer_characteristics).ok(),
+            time_scale: info.time_scale,
+            num_units_in_tick: info.num_units_in_tick,
+            rpu_found: info.dv_rpu_found != 0,
+            el_found: info.dv_el_found != 0,
+            rpu_header: u8::try_from(info.dv_vdr_rpu_profile)
+                .ok()
+                .map(|vdr_rpu_profile| RpuHeader {
+                    vdr_rpu_profile,
+                    vdr_bit_depth: info.dv_vdr_bit_depth,
+                }),
+            rpu_nal_flag: info.dv_rpu_nal_flag != 0,
+            el_nal_flag: info.dv_el_nal_flag != 0,
+            dv_level: info.dv_level as u8,
+        }
+    }
+}
+
+/// An HEVC input and what the parser takes from it.
+pub type HevcInput<'a> = (&'a Path, HevcInfo);
+
+/// The Dolby Vision options of the command line.
+pub struct DvOptions {
+    /// the profile given, none for 'auto' and without '--dv-profile'
+    pub profile: Option<u8>,
+    /// '--dv-profile auto' is given
+    pub auto_profile: bool,
+    pub bl_compatible_id: Option<u8>,
+    pub dvh1flag: Option<i32>,
+    /// the layers kept: 'keep', 'strip' or 'drop-el'
+    pub mode: String,
+}
+
+/// Detects the Dolby Vision profile and base layer compatible ID from the first HEVC input
+/// with RPU unless given, then cross-checks the options against each other and against the
+/// HEVC inputs, so that combinations devices reject fail before muxing. Dropping layers
+/// with '--dv-mode' updates the profile the muxer signals.
+pub fn resolve_dv_options(options: &mut DvOptions, hevc_files: &[HevcInput]) -> Result<()> {
+    if options.mode == "strip" {
+        return strip_dv(options, hevc_files);
+    }
+
+    if options.profile.is_none() {
+        match hevc_files.iter().find(|(_, info)| info.rpu_found) {
+            Some((file, info)) => detect_profile(options, file, info)?,
+            None if options.auto_profile => bail!(
+                "'--dv-profile auto' detects the profile from the Dolby Vision RPU of HEVC \
+                input files, but none has RPU (NAL unit type 62 or RPU user data SEI)"
+            ),
+            None => {}
+        }
+    }
+
+    let Some(profile) = options.profile else {
+        if options.bl_compatible_id.is_some() {
+            bail!("'--dv-bl-compatible-id' requires '--dv-profile'");
+        }
+        if options.dvh1flag.is_some() {
+            bail!("'--dvh1flag' requires '--dv-profile 5'");
+        }
+        return Ok(());
+    };
+
+    match (profile, options.bl_compatible_id) {
+        (5, Some(id)) => bail!(
+            "Dolby Vision profile 5 has no base layer compatibility, '--dv-bl-compatible-id {}' \
+            can't be signalled with it",
+            id
+        ),
+        (7, Some(id)) => bail!(
+            "The base layer of Dolby Vision profile 7 is Blu-ray HDR10 compatible by definition, \
+            '--dv-bl-compatible-id {}' can't be signalled with it",
+            id
+        ),
+        (4, Some(id)) if id != 2 => bail!(
+            "The base layer of Dolby Vision profile 4 is SDR, '--dv-bl-compatible-id {}' can't be \
+            signalled with it, only 2",
+            id
+        ),
+        (9, Some(4)) => bail!(
+            "The AVC base layer of Dolby Vision profile 9 is SDR or HDR10, \
+            '--dv-bl-compatible-id 4' (HLG) can't be signalled with it"
+        ),
+        (8, None) => bail!("Dolby Vision profile 8 requires '--dv-bl-compatible-id'"),
+        _ => {}
+    }
+
+    if profile != 5 && options.dvh1flag.is_some() {
+        bail!(
+            "'--dvh1flag' is for the non backward compatible profile 5 only, a profile {} track \
+            has to keep a sample entry its base layer can be decoded with",
+            profile
+        );
+    }
+
+    let mut rpu_found = false;
+    for (file, info) in hevc_files.iter().filter(|(_, info)| info.rpu_found) {
+        rpu_found = true;
+        check_hevc_stream(profile, options.bl_compatible_id, file, info)?;
+    }
+
+    if !rpu_found && !hevc_files.is_empty() && matches!(profile, 4 | 5 | 7 | 8) {
+        bail!(
+            "'--dv-profile {}' is given, but no Dolby Vision RPU (NAL unit type 62 or RPU user \
+            data SEI) found in \"{}\"",
+            profile,
+            file_name(hevc_files[0].0)
+        );
+    }
+
+    // the base layer and the RPU of a dual layer stream make a single layer one
+    let converted = match profile {
+        7 => Some(1),
+        4 => Some(2),
+        _ => None,
+    };
+    if let Some(id) = converted.filter(|_| options.mode == "drop-el") {
+        options.profile = Some(8);
+        options.bl_compatible_id = Some(id);
+        println!(
+            "Dolby Vision profile {} becomes profile 8.{} without its enhancement layer",
+            profile, id
+        );
+    }
+
+    Ok(())
+}
+
+/* with RPU and enhancement layer dropped, the base layer is muxed without Dolby Vision signalling */
+fn strip_dv(options: &mut DvOptions, hevc_files: &[HevcInput]) -> Result<()> {
+    // the RPU of the non backward compatible profile 5 has vdr_rpu_profile 0
+    let profile_5 = hevc_files.iter().any(|(_, info)| {
+        !info.el_found
+            && info
+                .rpu_header
+                .as_ref()
+                .is_some_and(|header| header.vdr_rpu_profile == 0)
+    });
+    if options.profile == Some(5) || options.profile.is_none() && profile_5 {
+        bail!(
+            "The base layer of Dolby Vision profile 5 has no compatibility, it can't be shown \
+            without its RPU; '--dv-mode strip' can't be used with it"
+        );
+    }
+    if options.dvh1flag.is_some() {
+        bail!(
+            "'--dvh1flag' can't be used with '--dv-mode strip', no Dolby Vision is left to signal"
+        );
+    }
+
+    if let Some((file, _)) = hevc_files.iter().find(|(_, info)| info.rpu_found) {
+        println!(
+            "Dolby Vision RPU and enhancement layer are stripped, \"{}\" is muxed as its base layer",
+            file_name(file)
+        );
+    }
+    options.profile = None;
+    options.bl_compatible_id = None;
+
+    Ok(())
+}
+
+/* picks the profile by the layers of the stream and its RPU header, the base layer compatible ID
+of profile 8 by the transfer characteristics */
+fn detect_profile(options: &mut DvOptions, file: &Path, info: &HevcInfo) -> Result<()> {
+    let file = file_name(file);
+    let transfer = match info.preferred_transfer {
+        Some(TRANSFER_HLG) => Some(TRANSFER_HLG),
+        _ => info.transfer_characteristics,
+    };
+    let compatible_id = transfer.and_then(transfer_compatible_id);
+
+    let profile = match (info.el_found, &info.rpu_header) {
+        // the VDR of profile 7 is 12 bit, the one of profile 4 10 bit
+        (true, Some(header)) if header.vdr_bit_depth == 12 => 7,
+        (true, Some(_)) => 4,
+        (true, None) if transfer == Some(TRANSFER_PQ) => 7,
+        (true, None) => 4,
+        // the RPU of the non backward compatible profile 5 has vdr_rpu_profile 0
+        (false, Some(header)) if header.vdr_rpu_profile == 0 => 5,
+        (false, Some(_)) => 8,
+        (false, None) if compatible_id.is_some() => 8,
+        (false, None) => 5,
+    };
+
+    if profile == 8 && options.bl_compatible_id.is_none() {
+        let Some(id) = compatible_id else {
+            bail!(
+                "Dolby Vision profile 8 detected for \"{}\", but its VUI signals no transfer \
+                characteristics the base layer compatibility can be told by; \
+                set '--dv-bl-compatible-id'",
+                file
+            );
+        };
+        options.bl_compatible_id = Some(id);
+    }
+    options.profile = Some(profile);
+
+    println!(
+        "Dolby Vision profile {}{} detected from the {} of \"{}\"",
+        profile,
+        match (profile, options.bl_compatible_id) {
+            (8, Some(id)) => format!(".{}", id),
+            _ => String::new(),
+        },
+        if info.rpu_header.is_some() {
+            "RPU header"
+        } else {
+            "layers and VUI"
+        },
+        file
+    );
+
+    Ok(())
+}
+
+fn check_hevc_stream(
+    profile: u8,
+    bl_compatible_id: Option<u8>,
+    file: &Path,
+    info: &HevcInfo,
+) -> Result<()> {
+    let file = file_name(file);
+
+    let codec = match profile {
+        9 => Some("AVC"),
+        10 => Some("AV1"),
+        _ => None,
+    };
+    if let Some(codec) = codec {
+        bail!(
+            "Dolby Vision profile {} has an {} base layer, but \"{}\" is HEVC with Dolby Vision \
+            RPU; HEVC takes profile 4, 5, 7 or 8",
+            profile,
+            codec,
+            file
+        );
+    }
+
+    // the dual layer profiles carry the enhancement layer in the same stream
+    match (profile, info.el_found) {
+        (4 | 7, false) => bail!(
+            "Dolby Vision profile {} is dual layer, but no enhancement layer (NAL unit type 63) \
+            found in \"{}\"; a stream with base layer and RPU only is profile 5 or 8",
+            profile,
+            file
+        ),
+        (5 | 8, true) => bail!(
+            "Dolby Vision profile {} is single layer, but \"{}\" has an enhancement layer \
+            (NAL unit type 63); a stream with base layer, enhancement layer and RPU is profile 4 or 7",
+            profile,
+            file
+        ),
+        _ => {}
+    }
+
+    if info.bit_depth_luma != 10 {
+        bail!(
+            "The base layer of Dolby Vision profile {} is 10 bit HEVC, but \"{}\" is {} bit",
+            profile,
+            file,
+            info.bit_depth_luma
+        );
+    }
+
+    // without a colour description there is nothing to tell the base layer compatibility by
+    if let Some(transfer) = info.transfer_characteristics {
+        let expected: Option<(&[u8], &str)> = match (profile, bl_compatible_id) {
+            (8, Some(1)) | (7, _) => Some((&[TRANSFER_PQ], "HDR10 (PQ)")),
+            (8, Some(2)) | (4, _) => Some((&SDR_TRANSFERS, "SDR")),
+            (8, Some(4)) => Some((&HLG_TRANSFERS, "HLG")),
+            _ => None,
+        };
+        if let Some((_, compatibility)) = expected.filter(|(t, _)| !t.contains(&transfer)) {
+            bail!(
+                "The VUI of \"{}\" signals {}, but {} expects an {} base layer{}",
+                file,
+                transfer_name(transfer),
+                match bl_compatible_id {
+                    Some(id) => format!("profile {} with '--dv-bl-compatible-id {}'", profile, id),
+                    None => format!("profile {}", profile),
+                },
+                compatibility,
+                match (profile, transfer_compatible_id(transfer)) {
+                    (8, Some(id)) => format!("; use '--dv-bl-compatible-id {}'", id),
+                    _ => String::new(),
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Cross-checks the Dolby Vision signalling the HEVC parser derives with the options set
+/// against the resolved options: the layers it signals and the level it takes from the
+/// pixel rate.
+pub fn check_dv_signalling(options: &DvOptions, hevc_files: &[HevcInput]) -> Result<()> {
+    let Some(profile) = options.profile else {
+        return Ok(());
+    };
+
+    for (file, info) in hevc_files.iter().filter(|(_, info)| info.rpu_found) {
+        let file = file_name(file);
+
+        if !info.rpu_nal_flag {
+            bail!(
+                "Dolby Vision profile {} is set, but the muxer signals no Dolby Vision for the RPU \
+                of \"{}\"",
+                profile,
+                file
+            );
+        }
+        // the parser takes the enhancement layer from the first access unit only
+        match (profile, info.el_nal_flag) {
+            (4 | 7, false) => bail!(
+                "Dolby Vision profile {} is dual layer, but the enhancement layer of \"{}\" \
+                doesn't start with the first access unit",
+                profile,
+                file
+            ),
+            (5 | 8, true) => bail!(
+                "Dolby Vision profile {} is single layer, but the muxer signals the enhancement \
+                layer of \"{}\"",
+                profile,
+                file
+            ),
+            _ => {}
+        }
+
+        check_level(file, info)?;
+    }
+
+    Ok(())
+}
+
+/* the Dolby Vision level the HEVC parser derives must allow the picture size */
+fn check_level(file: &str, info: &HevcInfo) -> Result<()> {
+    let level = info.dv_level;
+    let Some(&max_width) = DV_LEVEL_MAX_WIDTHS.get((level as usize).wrapping_sub(1)) else {
+        return Ok(());
+    };
+
+    if info.width > max_width {
+        bail!(
+            "{}x{} at {}/{} frames per second in \"{}\" is Dolby Vision level {} by its pixel \
+            rate, but level {} allows pictures up to {} pixels wide; check the frame rate",
+            info.width,
+            info.height,
+            info.time_scale,
+            info.num_units_in_tick,
+            file,
+            level,
+            level,
+            max_width
+        );
+    }
+
+    Ok(())
+}
+
+fn file_name(file: &Path) -> &str {
+    file.to_str().unwrap_or("<unknown file>")
+}
+
+fn transfer_name(transfer: u8) -> String {
+    match transfer {
+        TRANSFER_BT709 => "BT.709 transfer".to_string(),
+        TRANSFER_BT601 => "BT.601 transfer".to_string(),
+        TRANSFER_BT2020_10 | TRANSFER_BT2020_12 => "BT.2020 transfer".to_string(),
+        TRANSFER_PQ => "PQ transfer".to_string(),
+        TRANSFER_HLG => "HLG transfer".to_string(),
+        _ => format!("transfer characteristics {}", transfer),
+    }
+}
+
+/* the profile 8 compatibility ID matching the transfer of a base layer */
+fn transfer_compatible_id(transfer: u8) -> Option<u8> {
+    match transfer {
+        TRANSFER_PQ => Some(1),
+        TRANSFER_HLG => Some(4),
+        t if SDR_TRANSFERS.contains(&t) => Some(2),
+        _ => None,
+    }
+}
@@ -1,29 +1,70 @@
 mod cli;
+mod cli_events;
 mod cli_input_file;
+mod dv_check;
 mod mp4;
+mod mp4_boxes;
+mod mp4_dump;
 mod mp4_helpers;
+mod mp4_layout;
+mod mp4_segment;
+mod mp4_verify;
 
 use crate::mp4_helpers::{ema_mp4_ctrl_handle_t, error_by_code};
 use anyhow::{bail, Result};
-use cli::parse_cli;
+use clap::Parser;
+use cli::{parse_cli, split_cli, Cli, Tool};
+use mp4_dump::print_boxes;
+use mp4_layout::print_layout;
+use mp4_segment::write_segments;
+use mp4_verify::verify_file;
 use std::ptr::null_mut;
 
 fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+
+    /**** tools working on existing files do not need the muxer */
+    if let Some(tool) = cli.tool.take() {
+        return match tool {
+            Tool::Dump(args) => print_boxes(&args.file, args.json, args.depth),
+            Tool::Verify(args) => std::process::exit(verify_file(&args.file, args.json)),
+        };
+    }
+
+    /**** a CMAF track file per input */
+    for cli in split_cli(cli)? {
+        mux(cli)?;
+    }
+
+    Ok(())
+}
+
+fn mux(cli: Cli) -> Result<()> {
     let mut ema_handle: ema_mp4_ctrl_handle_t = null_mut();
 
     /**** create muxer handle */
     ema_mp4_mux_create(&mut ema_handle)?;
 
-    let mut err = parse_cli(ema_handle);
-
-    if err.is_ok() {
-        err = ema_mp4_mux_start(ema_handle);
-    }
+    let res = parse_cli(ema_handle, cli).and_then(|options| {
+        ema_mp4_mux_start(ema_handle)?;
+        Ok(options)
+    });
 
     /**** clean up. parser and mux already done and their resource released */
     ema_mp4_mux_destroy(ema_handle);
 
-    err
+    let options = res?;
+
+    /**** the output file is complete and closed now */
+    if options.verbose {
+        print_layout(&options.output_file)?;
+    }
+
+    if let Some(segments) = &options.segments {
+        write_segments(&options.output_file, segments)?;
+    }
+
+    Ok(())
 }
 
 fn ema_mp4_mux_create(handle: &mut ema_mp4_ctrl_handle_t) -> Result<()> {
@@ -9,10 +9,45 @@ extern "C" {
     pub fn ema_mp4_mux_destroy_clang(handle: *mut ::std::os::raw::c_void);
 }
 extern "C" {
-    pub fn ema_mp4_mux_set_video_framerate_clang(
+    pub fn ema_mp4_mux_set_es_framerate_clang(
         handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
         nome: ::std::os::raw::c_uint,
         deno: ::std::os::raw::c_uint,
+        force: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_ac4_bitrate_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        bitrate: ::std::os::raw::c_uint,
+        precision: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_ac4_ims_mode_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        mode: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_mastering_display_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        display_primaries: *const ::std::os::raw::c_ushort,
+        white_point: *const ::std::os::raw::c_ushort,
+        max_luminance: ::std::os::raw::c_uint,
+        min_luminance: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_content_light_level_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        max_cll: ::std::os::raw::c_ushort,
+        max_fall: ::std::os::raw::c_ushort,
     ) -> ::std::os::raw::c_uint;
 }
 extern "C" {
@@ -40,6 +75,43 @@ extern "C" {
         timescale: ::std::os::raw::c_uint,
     ) -> ::std::os::raw::c_uint;
 }
+extern "C" {
+    pub fn ema_mp4_mux_set_cm_time_clang(
+        handle: *mut ::std::os::raw::c_void,
+        cmtimeh: ::std::os::raw::c_uint,
+        cmtimel: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_chunk_span_time_clang(
+        handle: *mut ::std::os::raw::c_void,
+        chunk_span_time: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_moov_position_clang(
+        handle: *mut ::std::os::raw::c_void,
+        pos: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_moov_free_space_clang(
+        handle: *mut ::std::os::raw::c_void,
+        size: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_co64_mode_clang(
+        handle: *mut ::std::os::raw::c_void,
+        mode: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_sd_clang(
+        handle: *mut ::std::os::raw::c_void,
+        sd: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_uint;
+}
 extern "C" {
     pub fn ema_mp4_mux_set_mbrand_clang(
         handle: *mut ::std::os::raw::c_void,
@@ -64,6 +136,39 @@ extern "C" {
         max_duration: ::std::os::raw::c_uint,
     ) -> ::std::os::raw::c_uint;
 }
+extern "C" {
+    pub fn ema_mp4_mux_add_event_clang(
+        handle: *mut ::std::os::raw::c_void,
+        scheme_id_uri: *const ::std::os::raw::c_char,
+        value: *const ::std::os::raw::c_char,
+        timescale: ::std::os::raw::c_uint,
+        presentation_time: ::std::os::raw::c_ulonglong,
+        event_duration: ::std::os::raw::c_uint,
+        id: ::std::os::raw::c_uint,
+        version: ::std::os::raw::c_uint,
+        message_data: *const ::std::os::raw::c_uchar,
+        message_size: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_min_duration_clang(
+        handle: *mut ::std::os::raw::c_void,
+        min_duration: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_frag_on_every_sync_clang(
+        handle: *mut ::std::os::raw::c_void,
+        on_every_sync: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_frag_boundaries_clang(
+        handle: *mut ::std::os::raw::c_void,
+        boundaries: *const ::std::os::raw::c_ulonglong,
+        boundary_num: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
 extern "C" {
     pub fn ema_mp4_mux_set_dv_profile_clang(
         handle: *mut ::std::os::raw::c_void,
@@ -76,6 +181,40 @@ extern "C" {
         compatible_id: ::std::os::raw::c_int,
     ) -> ::std::os::raw::c_uint;
 }
+extern "C" {
+    pub fn ema_mp4_mux_set_dv_mode_clang(
+        handle: *mut ::std::os::raw::c_void,
+        mode: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_uint;
+}
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct hevc_info_clang {
+    pub hevc: ::std::os::raw::c_uint,
+    pub width: ::std::os::raw::c_uint,
+    pub height: ::std::os::raw::c_uint,
+    pub bit_depth_luma: ::std::os::raw::c_uint,
+    pub colour_description_present: ::std::os::raw::c_uint,
+    pub transfer_characteristics: ::std::os::raw::c_uint,
+    pub preferred_transfer_characteristics: ::std::os::raw::c_uint,
+    pub time_scale: ::std::os::raw::c_uint,
+    pub num_units_in_tick: ::std::os::raw::c_uint,
+    pub dv_rpu_found: ::std::os::raw::c_uint,
+    pub dv_el_found: ::std::os::raw::c_uint,
+    pub dv_vdr_rpu_profile: ::std::os::raw::c_uint,
+    pub dv_vdr_bit_depth: ::std::os::raw::c_uint,
+    pub dv_rpu_nal_flag: ::std::os::raw::c_uint,
+    pub dv_el_nal_flag: ::std::os::raw::c_uint,
+    pub dv_level: ::std::os::raw::c_uint,
+}
+extern "C" {
+    pub fn ema_mp4_mux_get_hevc_info_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        info: *mut hevc_info_clang,
+    ) -> ::std::os::raw::c_uint;
+}
 extern "C" {
     pub fn ema_mp4_mux_set_sampleentry_dvh1_clang(
         handle: *mut ::std::os::raw::c_void,
@@ -88,6 +227,27 @@ extern "C" {
         es_idx: ::std::os::raw::c_int,
     ) -> ::std::os::raw::c_uint;
 }
+extern "C" {
+    pub fn ema_mp4_mux_set_track_id_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        track_id: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_alternate_group_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        alternate_group: ::std::os::raw::c_ushort,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn ema_mp4_mux_set_track_enabled_clang(
+        handle: *mut ::std::os::raw::c_void,
+        es_idx: ::std::os::raw::c_int,
+        enabled: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_uint;
+}
 extern "C" {
     pub fn ema_mp4_mux_consistency_check_clang(handle: *mut ::std::os::raw::c_void);
 }
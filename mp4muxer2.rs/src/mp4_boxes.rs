@@ -0,0 +1,1231 @@
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A decoded field of a box.
+pub enum Value {
+    UInt(u64),
+    Int(i64),
+    Real(f64),
+    Text(String),
+    List(Vec<Value>),
+    Record(Vec<(&'static str, Value)>),
+}
+
+/// A box of an ISO base media file with its decoded fields and child boxes.
+pub struct Mp4Box {
+    pub box_type: [u8; 4],
+    pub offset: u64,
+    pub size: u64,
+    pub header_size: u64,
+    /// version and flags of a full box
+    pub version_flags: Option<(u8, u32)>,
+    pub fields: Vec<(&'static str, Value)>,
+    pub children: Vec<Mp4Box>,
+    /// why the payload could not be decoded completely
+    pub error: Option<String>,
+}
+
+impl Mp4Box {
+    pub fn type_name(&self) -> String {
+        String::from_utf8_lossy(&self.box_type).into_owned()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// The value of an unsigned integer field, e.g. `track_ID` of 'tkhd'.
+    pub fn uint(&self, name: &str) -> Option<u64> {
+        self.field(name).and_then(Value::as_u64)
+    }
+
+    pub fn child(&self, box_type: &[u8; 4]) -> Option<&Mp4Box> {
+        self.children.iter().find(|b| &b.box_type == box_type)
+    }
+
+    pub fn children_of<'a>(&'a self, box_type: &'a [u8; 4]) -> impl Iterator<Item = &'a Mp4Box> {
+        self.children
+            .iter()
+            .filter(move |b| &b.box_type == box_type)
+    }
+
+    /// The first box reached by a path of child box types, e.g. `["mdia", "minf", "stbl"]`.
+    pub fn descendant(&self, path: &[&[u8; 4]]) -> Option<&Mp4Box> {
+        path.iter().try_fold(self, |b, box_type| b.child(box_type))
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version_flags.map_or(0, |(version, _)| version)
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.version_flags.map_or(0, |(_, flags)| flags)
+    }
+
+    fn push(&mut self, name: &'static str, value: impl Into<Value>) -> Option<()> {
+        self.fields.push((name, value.into()));
+        Some(())
+    }
+}
+
+impl Value {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::UInt(v) => Some(*v),
+            Value::Int(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> &[Value] {
+        match self {
+            Value::List(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    /// The value of a field of a record.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields.iter().find(|(n, _)| *n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The value of an unsigned integer field of a record.
+    pub fn uint(&self, name: &str) -> Option<u64> {
+        self.get(name).and_then(Value::as_u64)
+    }
+}
+
+macro_rules! value_from {
+    ($variant:ident, $as:ty, $($t:ty),*) => {
+        $(impl From<$t> for Value {
+            fn from(v: $t) -> Self {
+                Value::$variant(v as $as)
+            }
+        })*
+    };
+}
+
+value_from!(UInt, u64, u8, u16, u32, u64);
+value_from!(Int, i64, i16, i32, i64);
+value_from!(Real, f64, f64);
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::UInt(v as u64)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::List(v)
+    }
+}
+
+impl From<Vec<(&'static str, Value)>> for Value {
+    fn from(v: Vec<(&'static str, Value)>) -> Self {
+        Value::Record(v)
+    }
+}
+
+/* boxes that only hold other boxes */
+const CONTAINERS: &[&[u8; 4]] = &[
+    b"moov", b"trak", b"edts", b"mdia", b"minf", b"dinf", b"stbl", b"mvex", b"moof", b"traf",
+    b"mfra", b"udta", b"tref", b"sinf", b"schi", b"rinf", b"meco", b"strk", b"strd", b"ipro",
+];
+
+const FULL_BOXES: &[&[u8; 4]] = &[
+    b"mvhd", b"tkhd", b"mdhd", b"hdlr", b"vmhd", b"smhd", b"nmhd", b"sthd", b"hmhd", b"dref",
+    b"url ", b"urn ", b"stsd", b"stts", b"ctts", b"cslg", b"stss", b"stsz", b"stz2", b"stsc",
+    b"stco", b"co64", b"sdtp", b"sbgp", b"sgpd", b"subs", b"saiz", b"saio", b"elst", b"mehd",
+    b"trex", b"leva", b"trep", b"mfhd", b"tfhd", b"tfdt", b"trun", b"tfra", b"mfro", b"sidx",
+    b"ssix", b"prft", b"emsg", b"meta", b"iods", b"esds", b"elng", b"kind", b"cprt", b"pssh",
+    b"tenc", b"schm", b"pcmC", b"chnl", b"dfLa", b"vvcC", b"padb", b"stdp",
+];
+
+/* sample entries with the fields of a VisualSampleEntry before their boxes */
+const VISUAL_ENTRIES: &[&[u8; 4]] = &[
+    b"avc1", b"avc2", b"avc3", b"avc4", b"hvc1", b"hev1", b"dvh1", b"dvhe", b"dva1", b"dvav",
+    b"av01", b"dav1", b"vvc1", b"vvi1", b"mp4v", b"encv",
+];
+
+/* sample entries with the fields of an AudioSampleEntry before their boxes */
+const AUDIO_ENTRIES: &[&[u8; 4]] = &[
+    b"mp4a", b"ac-3", b"ec-3", b"ac-4", b"mlpa", b"Opus", b"fLaC", b"ipcm", b"fpcm", b"lpcm",
+    b"mha1", b"mha2", b"mhm1", b"mhm2", b"dtsc", b"dtsh", b"dtsl", b"dtse", b"dtsx", b"dtsy",
+    b"enca",
+];
+
+/* top level boxes whose payload is not loaded */
+const MEDIA_DATA: &[&[u8; 4]] = &[b"mdat", b"free", b"skip", b"wide"];
+
+/// Reads the box tree of an ISO base media file. Media data is not loaded.
+pub fn read_boxes(path: &Path) -> Result<Vec<Mp4Box>> {
+    read_boxes_from(&mut File::open(path)?)
+}
+
+/// Reads the box tree of ISO base media file data, e.g. of a `Cursor` over a file in memory.
+pub fn read_boxes_from<R: Read + Seek>(file: &mut R) -> Result<Vec<Mp4Box>> {
+    let file_size = file.seek(SeekFrom::End(0))?;
+
+    let mut boxes = vec![];
+    let mut offset = 0;
+    while offset < file_size {
+        let mut header = [0u8; 16];
+        let header_len = (file_size - offset).min(16) as usize;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header[..header_len])?;
+
+        let (box_type, size, header_size) =
+            match parse_header(&header[..header_len], file_size - offset) {
+                Some(header) => header,
+                None => bail!("Invalid box header at offset {}", offset),
+            };
+
+        let mut mp4_box = if MEDIA_DATA.contains(&&box_type) {
+            new_box(box_type, offset, size, header_size)
+        } else {
+            let mut data = vec![0; size as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut data)?;
+            parse_box(&data, offset, box_type, size, header_size)
+        };
+        if &box_type == b"mdat" {
+            mp4_box.push("data_size", size - header_size);
+        }
+
+        boxes.push(mp4_box);
+        offset += size;
+    }
+
+    Ok(boxes)
+}
+
+/* returns the type, size and header size of the box starting the data */
+fn parse_header(data: &[u8], available: u64) -> Option<([u8; 4], u64, u64)> {
+    let box_type: [u8; 4] = data.get(4..8)?.try_into().unwrap();
+    let (size, header_size) = match u32::from_be_bytes(data[0..4].try_into().unwrap()) {
+        0 => (available, 8),
+        1 => (u64::from_be_bytes(data.get(8..16)?.try_into().unwrap()), 16),
+        size => (size as u64, 8),
+    };
+
+    // a 'uuid' box carries its extended type in the header
+    let header_size = if &box_type == b"uuid" {
+        header_size + 16
+    } else {
+        header_size
+    };
+    if size < header_size || size > available {
+        return None;
+    }
+
+    Some((box_type, size, header_size))
+}
+
+fn new_box(box_type: [u8; 4], offset: u64, size: u64, header_size: u64) -> Mp4Box {
+    Mp4Box {
+        box_type,
+        offset,
+        size,
+        header_size,
+        version_flags: None,
+        fields: vec![],
+        children: vec![],
+        error: None,
+    }
+}
+
+/* parses the boxes following each other in the data, which starts at the file offset */
+fn parse_boxes(data: &[u8], offset: u64) -> (Vec<Mp4Box>, Option<String>) {
+    let mut boxes = vec![];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let rest = &data[pos..];
+        let (box_type, size, header_size) = match parse_header(rest, rest.len() as u64) {
+            Some(header) => header,
+            None => {
+                return (
+                    boxes,
+                    Some(format!(
+                        "invalid box header at offset {}",
+                        offset + pos as u64
+                    )),
+                )
+            }
+        };
+
+        boxes.push(parse_box(
+            &rest[..size as usize],
+            offset + pos as u64,
+            box_type,
+            size,
+            header_size,
+        ));
+        pos += size as usize;
+    }
+
+    (boxes, None)
+}
+
+/* decodes a box, the data holds the whole box including its header */
+fn parse_box(data: &[u8], offset: u64, box_type: [u8; 4], size: u64, header_size: u64) -> Mp4Box {
+    let mut mp4_box = new_box(box_type, offset, size, header_size);
+    let mut r = Reader {
+        data,
+        pos: header_size as usize,
+    };
+
+    if FULL_BOXES.contains(&&box_type) {
+        match r.u32() {
+            Some(version_flags) => {
+                mp4_box.version_flags =
+                    Some(((version_flags >> 24) as u8, version_flags & 0xFFFFFF))
+            }
+            None => {
+                mp4_box.error = Some("truncated payload".to_string());
+                return mp4_box;
+            }
+        }
+    }
+
+    if CONTAINERS.contains(&&box_type) {
+        parse_children(&mut mp4_box, data, r.pos);
+    } else if let Some(decode) = decoder(&box_type) {
+        if decode(&mut r, &mut mp4_box).is_none() {
+            mp4_box.error = Some("truncated payload".to_string());
+        } else if is_parent(&box_type) {
+            parse_children(&mut mp4_box, data, r.pos);
+        }
+    }
+
+    mp4_box
+}
+
+fn parse_children(mp4_box: &mut Mp4Box, data: &[u8], pos: usize) {
+    let (children, error) = parse_boxes(&data[pos.min(data.len())..], mp4_box.offset + pos as u64);
+    mp4_box.children = children;
+    if mp4_box.error.is_none() {
+        mp4_box.error = error;
+    }
+}
+
+/* boxes with child boxes following their fields */
+fn is_parent(box_type: &[u8; 4]) -> bool {
+    matches!(box_type, b"stsd" | b"dref" | b"meta")
+        || VISUAL_ENTRIES.contains(&box_type)
+        || AUDIO_ENTRIES.contains(&box_type)
+}
+
+type Decoder = fn(&mut Reader, &mut Mp4Box) -> Option<()>;
+
+fn decoder(box_type: &[u8; 4]) -> Option<Decoder> {
+    let decode: Decoder = match box_type {
+        b"stsd" | b"dref" => |r, b| b.push("entry_count", r.u32()?),
+        b"meta" => |_, _| Some(()),
+        b"ftyp" | b"styp" => decode_ftyp,
+        b"mvhd" => decode_mvhd,
+        b"tkhd" => decode_tkhd,
+        b"mdhd" => decode_mdhd,
+        b"hdlr" => decode_hdlr,
+        b"elst" => decode_elst,
+        b"stts" => |r, b| decode_time_to_sample(r, b, "sample_delta"),
+        b"ctts" => |r, b| decode_time_to_sample(r, b, "sample_offset"),
+        b"stss" => |r, b| decode_list(r, b, "sample_numbers", |r| r.u32()),
+        b"stco" => |r, b| decode_list(r, b, "chunk_offsets", |r| r.u32()),
+        b"co64" => |r, b| decode_list(r, b, "chunk_offsets", |r| r.u64()),
+        b"stsc" => decode_stsc,
+        b"stsz" => decode_stsz,
+        b"sbgp" => decode_sbgp,
+        b"sgpd" => decode_sgpd,
+        b"mehd" => |r, b| b.push("fragment_duration", r.uint(b.version())?),
+        b"trex" => decode_trex,
+        b"mfhd" => |r, b| b.push("sequence_number", r.u32()?),
+        b"tfhd" => decode_tfhd,
+        b"tfdt" => |r, b| b.push("base_media_decode_time", r.uint(b.version())?),
+        b"trun" => decode_trun,
+        b"sidx" => decode_sidx,
+        b"emsg" => decode_emsg,
+        b"avcC" => decode_avcc,
+        b"hvcC" => decode_hvcc,
+        b"dvcC" | b"dvvC" | b"dvwC" => decode_dvcc,
+        b"dac3" => decode_dac3,
+        b"dec3" => decode_dec3,
+        b"dac4" => decode_dac4,
+        b"colr" => decode_colr,
+        b"clli" => |r, b| {
+            b.push("max_content_light_level", r.u16()?);
+            b.push("max_pic_average_light_level", r.u16()?)
+        },
+        b"btrt" => |r, b| {
+            b.push("buffer_size_db", r.u32()?);
+            b.push("max_bitrate", r.u32()?);
+            b.push("avg_bitrate", r.u32()?)
+        },
+        _ if VISUAL_ENTRIES.contains(&box_type) => decode_visual_entry,
+        _ if AUDIO_ENTRIES.contains(&box_type) => decode_audio_entry,
+        _ => return None,
+    };
+
+    Some(decode)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, num: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(num)?)?;
+        self.pos += num;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, num: usize) -> Option<()> {
+        self.bytes(num).map(|_| ())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        self.pos = self.data.len();
+        rest
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|b| u16::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|b| u64::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    /* a 32-bit field in version 0 boxes, a 64-bit one in version 1 */
+    fn uint(&mut self, version: u8) -> Option<u64> {
+        match version {
+            1 => self.u64(),
+            _ => self.u32().map(|v| v as u64),
+        }
+    }
+
+    fn fourcc(&mut self) -> Option<String> {
+        self.bytes(4)
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+    }
+
+    /* 16.16 fixed-point */
+    fn fixed32(&mut self) -> Option<f64> {
+        self.u32().map(|v| v as i32 as f64 / 65536.0)
+    }
+
+    /* 8.8 fixed-point */
+    fn fixed16(&mut self) -> Option<f64> {
+        self.u16().map(|v| v as i16 as f64 / 256.0)
+    }
+
+    /* a null-terminated string, which may also end with the box */
+    fn string(&mut self) -> String {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        self.pos += (len + 1).min(rest.len());
+        String::from_utf8_lossy(&rest[..len]).into_owned()
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/* the sample flags of 'trex', 'tfhd' and 'trun' */
+fn sample_flags(flags: u32) -> Value {
+    Value::Record(vec![
+        ("is_leading", (flags >> 26 & 3).into()),
+        ("depends_on", (flags >> 24 & 3).into()),
+        ("is_depended_on", (flags >> 22 & 3).into()),
+        ("has_redundancy", (flags >> 20 & 3).into()),
+        ("padding", (flags >> 17 & 7).into()),
+        ("non_sync", (flags >> 16 & 1).into()),
+        ("degradation_priority", (flags & 0xFFFF).into()),
+    ])
+}
+
+fn decode_ftyp(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("major_brand", r.fourcc()?);
+    b.push("minor_version", r.u32()?);
+    let mut brands: Vec<Value> = vec![];
+    while let Some(brand) = r.fourcc() {
+        brands.push(brand.into());
+    }
+    b.push("compatible_brands", brands)
+}
+
+fn decode_mvhd(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    b.push("creation_time", r.uint(version)?);
+    b.push("modification_time", r.uint(version)?);
+    b.push("timescale", r.u32()?);
+    b.push("duration", r.uint(version)?);
+    b.push("rate", r.fixed32()?);
+    b.push("volume", r.fixed16()?);
+    // reserved, matrix and pre_defined
+    r.skip(10 + 36 + 24)?;
+    b.push("next_track_ID", r.u32()?)
+}
+
+fn decode_tkhd(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    b.push("creation_time", r.uint(version)?);
+    b.push("modification_time", r.uint(version)?);
+    b.push("track_ID", r.u32()?);
+    r.skip(4)?;
+    b.push("duration", r.uint(version)?);
+    r.skip(8)?;
+    b.push("layer", r.u16()? as i16);
+    b.push("alternate_group", r.u16()?);
+    b.push("volume", r.fixed16()?);
+    // reserved and matrix
+    r.skip(2 + 36)?;
+    b.push("width", r.fixed32()?);
+    b.push("height", r.fixed32()?)
+}
+
+fn decode_mdhd(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    b.push("creation_time", r.uint(version)?);
+    b.push("modification_time", r.uint(version)?);
+    b.push("timescale", r.u32()?);
+    b.push("duration", r.uint(version)?);
+    // ISO 639-2/T code packed as three 5-bit letters
+    let language = r.u16()?;
+    let language: String = [10, 5, 0]
+        .iter()
+        .map(|shift| ((language >> shift & 0x1F) as u8 + 0x60) as char)
+        .collect();
+    b.push("language", language)
+}
+
+fn decode_hdlr(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    r.skip(4)?;
+    b.push("handler_type", r.fourcc()?);
+    r.skip(12)?;
+    b.push("name", r.string())
+}
+
+fn decode_elst(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        let segment_duration = r.uint(version)?;
+        let media_time = match version {
+            1 => r.u64()? as i64,
+            _ => r.u32()? as i32 as i64,
+        };
+        entries.push(Value::Record(vec![
+            ("segment_duration", segment_duration.into()),
+            ("media_time", media_time.into()),
+            ("media_rate", r.fixed32()?.into()),
+        ]));
+    }
+    b.push("entries", entries)
+}
+
+/* 'stts' and 'ctts', whose sample offsets are signed in version 1 */
+fn decode_time_to_sample(r: &mut Reader, b: &mut Mp4Box, name: &'static str) -> Option<()> {
+    let signed = &b.box_type == b"ctts" && b.version() == 1;
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        let sample_count = r.u32()?;
+        let value = r.u32()?;
+        entries.push(Value::Record(vec![
+            ("sample_count", sample_count.into()),
+            (
+                name,
+                if signed {
+                    (value as i32).into()
+                } else {
+                    value.into()
+                },
+            ),
+        ]));
+    }
+    b.push("entries", entries)
+}
+
+fn decode_list<T: Into<Value>>(
+    r: &mut Reader,
+    b: &mut Mp4Box,
+    name: &'static str,
+    read: fn(&mut Reader) -> Option<T>,
+) -> Option<()> {
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        entries.push(read(r)?.into());
+    }
+    b.push(name, entries)
+}
+
+fn decode_stsc(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        entries.push(Value::Record(vec![
+            ("first_chunk", r.u32()?.into()),
+            ("samples_per_chunk", r.u32()?.into()),
+            ("sample_description_index", r.u32()?.into()),
+        ]));
+    }
+    b.push("entries", entries)
+}
+
+fn decode_stsz(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let sample_size = r.u32()?;
+    let sample_count = r.u32()?;
+    b.push("sample_size", sample_size);
+    b.push("sample_count", sample_count);
+
+    if sample_size == 0 {
+        let mut sizes: Vec<Value> = vec![];
+        for _ in 0..sample_count {
+            sizes.push(r.u32()?.into());
+        }
+        b.push("entry_sizes", sizes);
+    }
+    Some(())
+}
+
+fn decode_sbgp(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("grouping_type", r.fourcc()?);
+    if b.version() == 1 {
+        b.push("grouping_type_parameter", r.u32()?);
+    }
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        entries.push(Value::Record(vec![
+            ("sample_count", r.u32()?.into()),
+            ("group_description_index", r.u32()?.into()),
+        ]));
+    }
+    b.push("entries", entries)
+}
+
+fn decode_sgpd(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    let grouping_type = r.fourcc()?;
+    b.push("grouping_type", grouping_type.as_str());
+    let default_length = match version {
+        1 => r.u32()?,
+        _ => 0,
+    };
+    if version == 1 {
+        b.push("default_length", default_length);
+    }
+    if version >= 2 {
+        b.push("default_sample_description_index", r.u32()?);
+    }
+    let entry_count = r.u32()?;
+    b.push("entry_count", entry_count);
+
+    // the entries of version 0 have no length to find the next one by
+    if version == 0 {
+        return Some(());
+    }
+
+    let mut entries = vec![];
+    for _ in 0..entry_count {
+        let length = match default_length {
+            0 => r.u32()?,
+            length => length,
+        };
+        let entry = r.bytes(length as usize)?;
+        entries.push(match (grouping_type.as_str(), entry) {
+            ("sap ", [sap]) => Value::Record(vec![
+                ("dependent_flag", (sap >> 7).into()),
+                ("SAP_type", (sap & 0xF).into()),
+            ]),
+            _ => hex(entry).into(),
+        });
+    }
+    b.push("entries", entries)
+}
+
+fn decode_trex(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("track_ID", r.u32()?);
+    b.push("default_sample_description_index", r.u32()?);
+    b.push("default_sample_duration", r.u32()?);
+    b.push("default_sample_size", r.u32()?);
+    b.push("default_sample_flags", sample_flags(r.u32()?))
+}
+
+fn decode_tfhd(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let flags = b.flags();
+    b.push("track_ID", r.u32()?);
+    if flags & 0x1 != 0 {
+        b.push("base_data_offset", r.u64()?);
+    }
+    if flags & 0x2 != 0 {
+        b.push("sample_description_index", r.u32()?);
+    }
+    if flags & 0x8 != 0 {
+        b.push("default_sample_duration", r.u32()?);
+    }
+    if flags & 0x10 != 0 {
+        b.push("default_sample_size", r.u32()?);
+    }
+    if flags & 0x20 != 0 {
+        b.push("default_sample_flags", sample_flags(r.u32()?));
+    }
+    b.push("duration_is_empty", flags & 0x10000 != 0);
+    b.push("default_base_is_moof", flags & 0x20000 != 0)
+}
+
+fn decode_trun(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let (version, flags) = (b.version(), b.flags());
+    let sample_count = r.u32()?;
+    b.push("sample_count", sample_count);
+    if flags & 0x1 != 0 {
+        b.push("data_offset", r.u32()? as i32);
+    }
+    if flags & 0x4 != 0 {
+        b.push("first_sample_flags", sample_flags(r.u32()?));
+    }
+
+    // the samples take all their fields from the defaults
+    if flags & 0xF00 == 0 {
+        return Some(());
+    }
+
+    let mut samples = vec![];
+    for _ in 0..sample_count {
+        let mut sample = vec![];
+        if flags & 0x100 != 0 {
+            sample.push(("sample_duration", r.u32()?.into()));
+        }
+        if flags & 0x200 != 0 {
+            sample.push(("sample_size", r.u32()?.into()));
+        }
+        if flags & 0x400 != 0 {
+            sample.push(("sample_flags", sample_flags(r.u32()?)));
+        }
+        if flags & 0x800 != 0 {
+            let offset = r.u32()?;
+            sample.push((
+                "sample_composition_time_offset",
+                if version == 1 {
+                    (offset as i32).into()
+                } else {
+                    offset.into()
+                },
+            ));
+        }
+        samples.push(Value::Record(sample));
+    }
+    b.push("samples", samples)
+}
+
+fn decode_sidx(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let version = b.version();
+    b.push("reference_ID", r.u32()?);
+    b.push("timescale", r.u32()?);
+    b.push("earliest_presentation_time", r.uint(version)?);
+    b.push("first_offset", r.uint(version)?);
+    r.skip(2)?;
+    let reference_count = r.u16()?;
+    b.push("reference_count", reference_count);
+
+    let mut references = vec![];
+    for _ in 0..reference_count {
+        let reference = r.u32()?;
+        let subsegment_duration = r.u32()?;
+        let sap = r.u32()?;
+        references.push(Value::Record(vec![
+            ("reference_type", (reference >> 31).into()),
+            ("referenced_size", (reference & 0x7FFFFFFF).into()),
+            ("subsegment_duration", subsegment_duration.into()),
+            ("starts_with_SAP", (sap >> 31).into()),
+            ("SAP_type", (sap >> 28 & 7).into()),
+            ("SAP_delta_time", (sap & 0x0FFFFFFF).into()),
+        ]));
+    }
+    b.push("references", references)
+}
+
+fn decode_emsg(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    if b.version() == 0 {
+        b.push("scheme_id_uri", r.string());
+        b.push("value", r.string());
+        b.push("timescale", r.u32()?);
+        b.push("presentation_time_delta", r.u32()?);
+    } else {
+        b.push("timescale", r.u32()?);
+        b.push("presentation_time", r.u64()?);
+    }
+    b.push("event_duration", r.u32()?);
+    b.push("id", r.u32()?);
+    if b.version() != 0 {
+        b.push("scheme_id_uri", r.string());
+        b.push("value", r.string());
+    }
+    b.push("message_data", hex(r.rest()))
+}
+
+fn decode_avcc(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("configuration_version", r.u8()?);
+    b.push("profile_indication", r.u8()?);
+    b.push("profile_compatibility", r.u8()?);
+    b.push("level_indication", r.u8()?);
+    b.push("length_size", (r.u8()? & 3) + 1);
+
+    let num_sps = r.u8()? & 0x1F;
+    for _ in 0..num_sps {
+        let len = r.u16()?;
+        r.skip(len as usize)?;
+    }
+    b.push("num_sps", num_sps);
+    b.push("num_pps", r.u8()?)
+}
+
+fn decode_hvcc(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("configuration_version", r.u8()?);
+    let profile = r.u8()?;
+    b.push("general_profile_space", profile >> 6);
+    b.push("general_tier_flag", profile >> 5 & 1);
+    b.push("general_profile_idc", profile & 0x1F);
+    b.push("general_profile_compatibility_flags", r.u32()?);
+    b.push("general_constraint_indicator_flags", hex(r.bytes(6)?));
+    b.push("general_level_idc", r.u8()?);
+    b.push("min_spatial_segmentation_idc", r.u16()? & 0xFFF);
+    b.push("parallelism_type", r.u8()? & 3);
+    b.push("chroma_format_idc", r.u8()? & 3);
+    b.push("bit_depth_luma", (r.u8()? & 7) + 8);
+    b.push("bit_depth_chroma", (r.u8()? & 7) + 8);
+    b.push("avg_frame_rate", r.u16()?);
+    let byte = r.u8()?;
+    b.push("constant_frame_rate", byte >> 6);
+    b.push("num_temporal_layers", byte >> 3 & 7);
+    b.push("temporal_id_nested", byte >> 2 & 1);
+    b.push("length_size", (byte & 3) + 1);
+
+    let num_arrays = r.u8()?;
+    let mut arrays = vec![];
+    for _ in 0..num_arrays {
+        let byte = r.u8()?;
+        let num_nalus = r.u16()?;
+        for _ in 0..num_nalus {
+            let len = r.u16()?;
+            r.skip(len as usize)?;
+        }
+        arrays.push(Value::Record(vec![
+            ("array_completeness", (byte >> 7).into()),
+            ("nal_unit_type", (byte & 0x3F).into()),
+            ("num_nalus", num_nalus.into()),
+        ]));
+    }
+    b.push("arrays", arrays)
+}
+
+/* DOVIDecoderConfigurationRecord of 'dvcC', 'dvvC' and 'dvwC' */
+fn decode_dvcc(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    b.push("dv_version_major", r.u8()?);
+    b.push("dv_version_minor", r.u8()?);
+    let bits = r.u16()?;
+    b.push("dv_profile", bits >> 9);
+    b.push("dv_level", bits >> 3 & 0x3F);
+    b.push("rpu_present_flag", bits >> 2 & 1);
+    b.push("el_present_flag", bits >> 1 & 1);
+    b.push("bl_present_flag", bits & 1);
+    b.push("dv_bl_signal_compatibility_id", r.u8()? >> 4)
+}
+
+fn decode_dac3(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let bits = u32::from_be_bytes([0, r.u8()?, r.u8()?, r.u8()?]);
+    b.push("fscod", bits >> 22);
+    b.push("bsid", bits >> 17 & 0x1F);
+    b.push("bsmod", bits >> 14 & 7);
+    b.push("acmod", bits >> 11 & 7);
+    b.push("lfeon", bits >> 10 & 1);
+    b.push("bit_rate_code", bits >> 5 & 0x1F)
+}
+
+fn decode_dec3(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let bits = r.u16()?;
+    b.push("data_rate", bits >> 3);
+    let num_ind_sub = (bits & 7) + 1;
+
+    let mut substreams = vec![];
+    for _ in 0..num_ind_sub {
+        let bits = r.u16()?;
+        let dep = r.u16()?;
+        let num_dep_sub = dep >> 9 & 0xF;
+        let mut substream = vec![
+            ("fscod", (bits >> 14).into()),
+            ("bsid", (bits >> 9 & 0x1F).into()),
+            ("asvc", (bits >> 7 & 1).into()),
+            ("bsmod", (bits >> 4 & 7).into()),
+            ("acmod", (bits >> 1 & 7).into()),
+            ("lfeon", (bits & 1).into()),
+            ("num_dep_sub", num_dep_sub.into()),
+        ];
+        if num_dep_sub > 0 {
+            substream.push(("chan_loc", (dep & 0x1FF).into()));
+        }
+        substreams.push(Value::Record(substream));
+    }
+    b.push("independent_substreams", substreams);
+
+    // the Dolby Atmos extension of ETSI TS 102 366 Annex F
+    if let Some(flags) = r.u8() {
+        if flags & 1 != 0 {
+            b.push("complexity_index_type_a", r.u8()?);
+        }
+    }
+    Some(())
+}
+
+fn decode_dac4(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let Ac4Dsi {
+        bitstream_version,
+        bit_rate,
+        bit_rate_precision,
+        presentations,
+    } = parse_dac4(r.rest())?;
+    b.push("bitstream_version", bitstream_version);
+    b.push("bit_rate", bit_rate);
+    b.push("bit_rate_precision", bit_rate_precision);
+
+    let presentations = presentations
+        .into_iter()
+        .map(|pres| {
+            let mut fields = vec![("presentation_version", pres.version.into())];
+            if let Some(id) = pres.id {
+                fields.push(("presentation_id", id.into()));
+            }
+            if let Some(ch_mode) = pres.ch_mode {
+                fields.push(("channel_mode", ch_mode.into()));
+            }
+            if let Some(atmos) = pres.atmos {
+                fields.push(("dolby_atmos_indicator", atmos.into()));
+            }
+            if let Some(language) = pres.language {
+                fields.push(("language", language.into()));
+            }
+            Value::Record(fields)
+        })
+        .collect::<Vec<_>>();
+    b.push("presentations", presentations)
+}
+
+/* the parts of an AC-4 presentation in 'dac4' telling the presentations of a stream apart */
+#[derive(Default)]
+struct Ac4Presentation {
+    version: u8,
+    id: Option<u32>,
+    ch_mode: Option<u32>,
+    atmos: Option<bool>,
+    language: Option<String>,
+}
+
+struct Ac4Dsi {
+    bitstream_version: u32,
+    bit_rate: u32,
+    bit_rate_precision: u32,
+    presentations: Vec<Ac4Presentation>,
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, num: usize) -> Option<u32> {
+        let mut value = 0u64;
+        for _ in 0..num {
+            let byte = *self.data.get(self.pos / 8)?;
+            value = value << 1 | ((byte >> (7 - self.pos % 8)) & 1) as u64;
+            self.pos += 1;
+        }
+        Some(value as u32)
+    }
+
+    fn skip(&mut self, num: usize) -> Option<()> {
+        self.pos += num;
+        (self.pos <= self.data.len() * 8).then_some(())
+    }
+
+    fn byte_align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8);
+    }
+}
+
+/* ac4_dsi_v1() of ETSI TS 103 190-2 Annex E */
+fn parse_dac4(dac4: &[u8]) -> Option<Ac4Dsi> {
+    let mut r = BitReader { data: dac4, pos: 0 };
+    if r.bits(3)? != 1 {
+        return None;
+    }
+    let bitstream_version = r.bits(7)?;
+    r.skip(5)?;
+    let n_presentations = r.bits(9)?;
+    if bitstream_version > 1 && r.bits(1)? == 1 {
+        r.skip(16)?;
+        if r.bits(1)? == 1 {
+            r.skip(128)?;
+        }
+    }
+    r.skip(2)?;
+    let bit_rate = r.bits(32)?;
+    let bit_rate_precision = r.bits(32)?;
+    r.byte_align();
+
+    let mut presentations = vec![];
+    for _ in 0..n_presentations {
+        let version = r.bits(8)? as u8;
+        let mut pres_bytes = r.bits(8)? as usize;
+        if pres_bytes == 255 {
+            pres_bytes += r.bits(16)? as usize;
+        }
+        let start = r.pos / 8;
+        let data = dac4.get(start..start + pres_bytes)?;
+        r.skip(pres_bytes * 8)?;
+
+        let mut pres = Ac4Presentation {
+            version,
+            ..Default::default()
+        };
+        // a presentation cut short still shows what was read
+        if version == 1 || version == 2 {
+            let _ = parse_ac4_presentation_v1(&mut BitReader { data, pos: 0 }, &mut pres);
+        }
+        presentations.push(pres);
+    }
+
+    Some(Ac4Dsi {
+        bitstream_version,
+        bit_rate,
+        bit_rate_precision,
+        presentations,
+    })
+}
+
+fn parse_ac4_presentation_v1(r: &mut BitReader, pres: &mut Ac4Presentation) -> Option<()> {
+    let config = r.bits(5)?;
+    let mut b_add_emdf_substreams = true;
+    if config != 6 {
+        r.skip(3)?;
+        if r.bits(1)? == 1 {
+            pres.id = Some(r.bits(5)?);
+        }
+        r.skip(2 + 2 + 5 + 10)?;
+        if r.bits(1)? == 1 {
+            let ch_mode = r.bits(5)?;
+            pres.ch_mode = Some(ch_mode);
+            if (11..=14).contains(&ch_mode) {
+                r.skip(3)?;
+            }
+            r.skip(24)?;
+        } else {
+            pres.ch_mode = Some(0x1F);
+        }
+        if r.bits(1)? == 1 && r.bits(1)? == 1 {
+            r.skip(2)?;
+        }
+        if r.bits(1)? == 1 {
+            r.skip(1)?;
+            let n_filter_bytes = r.bits(8)? as usize;
+            r.skip(n_filter_bytes * 8)?;
+        }
+
+        let n_substream_groups = match config {
+            0x1F => 1,
+            _ => {
+                r.skip(1)?;
+                match config {
+                    0..=2 => 2,
+                    3 | 4 => 3,
+                    5 => r.bits(3)? + 2,
+                    _ => {
+                        let n_skip_bytes = r.bits(7)? as usize;
+                        r.skip(n_skip_bytes * 8)?;
+                        0
+                    }
+                }
+            }
+        };
+        for _ in 0..n_substream_groups {
+            let language = parse_ac4_substream_group(r)?;
+            if pres.language.is_none() {
+                pres.language = language;
+            }
+        }
+
+        r.skip(1)?;
+        b_add_emdf_substreams = r.bits(1)? == 1;
+    }
+    if b_add_emdf_substreams {
+        let n_add_emdf_substreams = r.bits(7)? as usize;
+        r.skip(n_add_emdf_substreams * 15)?;
+    }
+    if r.bits(1)? == 1 {
+        r.skip(66)?;
+    }
+    if r.bits(1)? == 1 {
+        r.byte_align();
+        let name_len = r.bits(16)? as usize;
+        r.skip(name_len * 8)?;
+        let n_targets = r.bits(5)? as usize;
+        r.skip(n_targets * 11)?;
+    }
+    r.byte_align();
+
+    // de_indicator, dolby_atmos_indicator
+    r.skip(1)?;
+    pres.atmos = Some(r.bits(1)? == 1);
+
+    Some(())
+}
+
+/* ac4_substream_group_dsi(), returns the language tag if there is one */
+fn parse_ac4_substream_group(r: &mut BitReader) -> Option<Option<String>> {
+    r.skip(2)?;
+    let b_channel_coded = r.bits(1)? == 1;
+    let n_substreams = r.bits(8)?;
+    for _ in 0..n_substreams {
+        r.skip(2)?;
+        if r.bits(1)? == 1 {
+            r.skip(5)?;
+        }
+        if b_channel_coded {
+            r.skip(24)?;
+        } else {
+            if r.bits(1)? == 1 {
+                if r.bits(1)? == 0 {
+                    r.skip(4)?;
+                }
+                r.skip(6)?;
+            }
+            r.skip(4)?;
+        }
+    }
+
+    let mut language = None;
+    if r.bits(1)? == 1 {
+        r.skip(3)?;
+        if r.bits(1)? == 1 {
+            let n_language_tag_bytes = r.bits(6)?;
+            let tag = (0..n_language_tag_bytes)
+                .map(|_| r.bits(8).map(|b| b as u8))
+                .collect::<Option<Vec<u8>>>()?;
+            language = Some(String::from_utf8_lossy(&tag).into_owned());
+        }
+    }
+
+    Some(language)
+}
+
+fn decode_colr(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    let colour_type = r.fourcc()?;
+    b.push("colour_type", colour_type.as_str());
+    if colour_type == "nclx" {
+        b.push("colour_primaries", r.u16()?);
+        b.push("transfer_characteristics", r.u16()?);
+        b.push("matrix_coefficients", r.u16()?);
+        b.push("full_range_flag", r.u8()? >> 7);
+    }
+    Some(())
+}
+
+fn decode_visual_entry(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    r.skip(6)?;
+    b.push("data_reference_index", r.u16()?);
+    r.skip(16)?;
+    b.push("width", r.u16()?);
+    b.push("height", r.u16()?);
+    b.push("horizresolution", r.fixed32()?);
+    b.push("vertresolution", r.fixed32()?);
+    r.skip(4)?;
+    b.push("frame_count", r.u16()?);
+    let name = r.bytes(32)?;
+    let len = (name[0] as usize).min(31);
+    b.push(
+        "compressorname",
+        String::from_utf8_lossy(&name[1..1 + len]).into_owned(),
+    );
+    b.push("depth", r.u16()?);
+    r.skip(2)
+}
+
+fn decode_audio_entry(r: &mut Reader, b: &mut Mp4Box) -> Option<()> {
+    r.skip(6)?;
+    b.push("data_reference_index", r.u16()?);
+    let version = r.u16()?;
+    b.push("entry_version", version);
+    r.skip(6)?;
+    b.push("channelcount", r.u16()?);
+    b.push("samplesize", r.u16()?);
+    r.skip(4)?;
+    b.push("samplerate", r.u32()? >> 16);
+
+    // the QuickTime sound description versions 1 and 2 extend the fields
+    match (version, &b.box_type) {
+        (1, b"lpcm") => r.skip(16),
+        (2, _) => {
+            r.skip(4)?;
+            b.push("audio_sample_rate", f64::from_bits(r.u64()?));
+            b.push("num_audio_channels", r.u32()?);
+            r.skip(4)?;
+            b.push("const_bits_per_channel", r.u32()?);
+            b.push("format_specific_flags", r.u32()?);
+            r.skip(8)
+        }
+        _ => Some(()),
+    }
+}
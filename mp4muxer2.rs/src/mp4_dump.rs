@@ -0,0 +1,209 @@
+use crate::mp4_boxes::{read_boxes, Mp4Box, Value};
+use anyhow::Result;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+
+/* the entries of a table shown in text output, JSON output has all of them */
+const TEXT_ENTRIES: usize = 10;
+
+/// Prints the box tree of an ISO base media file as text or JSON, down to the given depth.
+/// A closed stdout, e.g. of `dump | head`, ends the output without an error.
+pub fn print_boxes(path: &Path, json: bool, depth: Option<u64>) -> Result<()> {
+    let boxes = read_boxes(path)?;
+    let depth = depth.unwrap_or(u64::MAX);
+
+    let res = write_boxes(&mut io::stdout().lock(), path, &boxes, json, depth);
+    match res {
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => Ok(res?),
+    }
+}
+
+fn write_boxes(
+    out: &mut impl Write,
+    path: &Path,
+    boxes: &[Mp4Box],
+    json: bool,
+    depth: u64,
+) -> io::Result<()> {
+    if json {
+        let mut text = format!(
+            "{{\"file\":{},\"boxes\":[",
+            json_string(&path.display().to_string())
+        );
+        for (i, mp4_box) in boxes.iter().enumerate() {
+            if i > 0 {
+                text.push(',');
+            }
+            json_box(&mut text, mp4_box, depth - 1);
+        }
+        text.push_str("]}");
+        writeln!(out, "{}", text)?;
+    } else {
+        for mp4_box in boxes {
+            print_box(out, mp4_box, 0, depth - 1)?;
+        }
+    }
+
+    out.flush()
+}
+
+fn print_box(out: &mut impl Write, mp4_box: &Mp4Box, level: usize, depth: u64) -> io::Result<()> {
+    let indent = "  ".repeat(level);
+    let version_flags = match mp4_box.version_flags {
+        Some((version, flags)) => format!(", version {}, flags 0x{:06x}", version, flags),
+        None => String::new(),
+    };
+    writeln!(
+        out,
+        "{}{} @ {}, {} bytes{}",
+        indent,
+        mp4_box.type_name(),
+        mp4_box.offset,
+        mp4_box.size,
+        version_flags
+    )?;
+
+    if let Some(error) = &mp4_box.error {
+        writeln!(out, "{}  ! {}", indent, error)?;
+    }
+
+    for (name, value) in &mp4_box.fields {
+        match value {
+            Value::List(entries) if matches!(entries.first(), Some(Value::Record(_))) => {
+                writeln!(out, "{}  {}: {} entries", indent, name, entries.len())?;
+                for (i, entry) in entries.iter().take(TEXT_ENTRIES).enumerate() {
+                    writeln!(out, "{}    [{}] {}", indent, i, text_value(entry))?;
+                }
+                if entries.len() > TEXT_ENTRIES {
+                    writeln!(
+                        out,
+                        "{}    ... {} more",
+                        indent,
+                        entries.len() - TEXT_ENTRIES
+                    )?;
+                }
+            }
+            value => writeln!(out, "{}  {}: {}", indent, name, text_value(value))?,
+        }
+    }
+
+    if depth == 0 {
+        if !mp4_box.children.is_empty() {
+            writeln!(
+                out,
+                "{}  ({} child boxes not shown)",
+                indent,
+                mp4_box.children.len()
+            )?;
+        }
+        return Ok(());
+    }
+    for child in &mp4_box.children {
+        print_box(out, child, level + 1, depth - 1)?;
+    }
+
+    Ok(())
+}
+
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::UInt(v) => v.to_string(),
+        Value::Int(v) => v.to_string(),
+        Value::Real(v) => v.to_string(),
+        Value::Text(v) => format!("\"{}\"", v),
+        Value::List(entries) => {
+            let mut items: Vec<String> =
+                entries.iter().take(TEXT_ENTRIES).map(text_value).collect();
+            if entries.len() > TEXT_ENTRIES {
+                items.push(format!("... {} more", entries.len() - TEXT_ENTRIES));
+            }
+            format!("[{}]", items.join(", "))
+        }
+        Value::Record(fields) => fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, text_value(value)))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn json_box(out: &mut String, mp4_box: &Mp4Box, depth: u64) {
+    out.push_str(&format!(
+        "{{\"type\":{},\"offset\":{},\"size\":{},\"header_size\":{}",
+        json_string(&mp4_box.type_name()),
+        mp4_box.offset,
+        mp4_box.size,
+        mp4_box.header_size
+    ));
+    if let Some((version, flags)) = mp4_box.version_flags {
+        out.push_str(&format!(",\"version\":{},\"flags\":{}", version, flags));
+    }
+    if let Some(error) = &mp4_box.error {
+        out.push_str(&format!(",\"error\":{}", json_string(error)));
+    }
+    if !mp4_box.fields.is_empty() {
+        out.push_str(",\"fields\":");
+        json_record(out, &mp4_box.fields);
+    }
+    if depth > 0 && !mp4_box.children.is_empty() {
+        out.push_str(",\"children\":[");
+        for (i, child) in mp4_box.children.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json_box(out, child, depth - 1);
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+fn json_record(out: &mut String, fields: &[(&str, Value)]) {
+    out.push('{');
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&json_string(name));
+        out.push(':');
+        json_value(out, value);
+    }
+    out.push('}');
+}
+
+fn json_value(out: &mut String, value: &Value) {
+    match value {
+        Value::UInt(v) => out.push_str(&v.to_string()),
+        Value::Int(v) => out.push_str(&v.to_string()),
+        Value::Real(v) if v.is_finite() => out.push_str(&v.to_string()),
+        Value::Real(_) => out.push_str("null"),
+        Value::Text(v) => out.push_str(&json_string(v)),
+        Value::List(entries) => {
+            out.push('[');
+            for (i, entry) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                json_value(out, entry);
+            }
+            out.push(']');
+        }
+        Value::Record(fields) => json_record(out, fields),
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -16,6 +16,7 @@ const EMA_MP4_MUXED_EOES: u32 = 0x16; /* end of es */
 const EMA_MP4_MUXED_IO_ERR: u32 = 0x17; /* I/O err */
 const EMA_MP4_MUXED_CLI_ERR: u32 = 0x18; /* CLI err */
 const EMA_MP4_MUXED_EMPTY_ES: u32 = 0x19; /* empty es to mux */
+const EMA_MP4_MUXED_FRAG_ERR: u32 = 0x1A; /* fragmentation not possible with the settings */
 
 /** 0x2?: I/O operation */
 const EMA_MP4_MUXED_WRITE_ERR: u32 = 0x20; /* write error */
@@ -52,6 +53,10 @@ pub fn error_by_code(code: u32) -> String {
         EMA_MP4_MUXED_IO_ERR => "I/O err (EMA_MP4_MUXED_IO_ERR)".to_string(),
         EMA_MP4_MUXED_CLI_ERR => "CLI err (EMA_MP4_MUXED_CLI_ERR)".to_string(),
         EMA_MP4_MUXED_EMPTY_ES => "empty es to mux (EMA_MP4_MUXED_EMPTY_ES)".to_string(),
+        EMA_MP4_MUXED_FRAG_ERR => {
+            "fragmentation not possible with the settings, e.g. no sync sample at a fragment boundary or sync samples farther apart than the max fragment duration (EMA_MP4_MUXED_FRAG_ERR)"
+                .to_string()
+        }
         EMA_MP4_MUXED_WRITE_ERR => "write error (EMA_MP4_MUXED_WRITE_ERR)".to_string(),
         EMA_MP4_MUXED_READ_ERR => "read error (EMA_MP4_MUXED_READ_ERR)".to_string(),
         EMA_MP4_MUXED_SYNC_ERR => "parsing ES error (sync) (EMA_MP4_MUXED_SYNC_ERR)".to_string(),
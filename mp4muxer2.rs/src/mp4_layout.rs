@@ -0,0 +1,242 @@
+use crate::mp4_boxes::{read_boxes, Mp4Box, Value};
+use anyhow::Result;
+use std::path::Path;
+
+/* a sample description other than the first one and the (1-based) sample it starts at */
+struct SampleDescriptionStart {
+    track_id: u64,
+    index: u64,
+    sample: u64,
+}
+
+/// Prints the top level box layout of the produced file and the chunking of its tracks.
+pub fn print_layout(path: &Path) -> Result<()> {
+    let boxes = read_boxes(path)?;
+
+    println!("Layout of \"{}\":", path.display());
+    for mp4_box in &boxes {
+        println!(
+            "  {} @ {:>12}, {:>12} bytes",
+            mp4_box.type_name(),
+            mp4_box.offset,
+            mp4_box.size
+        );
+    }
+
+    let position = |box_type: &[u8; 4]| boxes.iter().position(|b| &b.box_type == box_type);
+    match (position(b"moov"), position(b"mdat")) {
+        (Some(moov), Some(mdat)) if moov < mdat => {
+            println!("  'moov' precedes 'mdat': playback can start before the whole file arrives")
+        }
+        (Some(_), Some(_)) => {
+            println!("  'moov' follows 'mdat': the whole file is needed before playback starts")
+        }
+        _ => {}
+    }
+
+    if let Some(moov) = boxes.iter().find(|b| &b.box_type == b"moov") {
+        for trak in moov.children_of(b"trak") {
+            let stbl = match trak.descendant(&[b"mdia", b"minf", b"stbl"]) {
+                Some(stbl) => stbl,
+                None => continue,
+            };
+            for chunk_offsets in stbl
+                .children
+                .iter()
+                .filter(|b| &b.box_type == b"stco" || &b.box_type == b"co64")
+            {
+                let co64 = &chunk_offsets.box_type == b"co64";
+                println!(
+                    "  track {}: {} chunks, {}-bit chunk offsets ('{}')",
+                    track_id(trak),
+                    chunk_offsets.uint("entry_count").unwrap_or(0),
+                    if co64 { 64 } else { 32 },
+                    chunk_offsets.type_name()
+                );
+            }
+        }
+
+        let mut starts = moov_sample_description_starts(moov);
+        starts.extend(fragment_sample_description_starts(moov, &boxes));
+
+        for start in starts {
+            println!(
+                "  track {}: sample description {} starts at sample {}",
+                start.track_id, start.index, start.sample
+            );
+        }
+
+        for trak in moov.children_of(b"trak") {
+            let stsd = match trak.descendant(&[b"mdia", b"minf", b"stbl", b"stsd"]) {
+                Some(stsd) => stsd,
+                None => continue,
+            };
+            for dac4 in stsd
+                .children_of(b"ac-4")
+                .filter_map(|entry| entry.child(b"dac4"))
+            {
+                match dac4.error {
+                    None => print_ac4_presentations(track_id(trak), dac4),
+                    Some(_) => println!("  track {}: invalid 'dac4'", track_id(trak)),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn track_id(trak: &Mp4Box) -> u64 {
+    trak.child(b"tkhd")
+        .and_then(|tkhd| tkhd.uint("track_ID"))
+        .unwrap_or(0)
+}
+
+/* walks the 'stsc' runs of each track, a run with another sample description index starts a new one */
+fn moov_sample_description_starts(moov: &Mp4Box) -> Vec<SampleDescriptionStart> {
+    let mut starts = vec![];
+
+    for trak in moov.children_of(b"trak") {
+        let entries = match trak
+            .descendant(&[b"mdia", b"minf", b"stbl", b"stsc"])
+            .and_then(|stsc| stsc.field("entries"))
+        {
+            Some(entries) => entries.as_list(),
+            None => continue,
+        };
+
+        let mut sample = 1u64;
+        let mut index = 1;
+        for (i, entry) in entries.iter().enumerate() {
+            let sample_description_index = entry.uint("sample_description_index").unwrap_or(0);
+            if sample_description_index != index {
+                index = sample_description_index;
+                starts.push(SampleDescriptionStart {
+                    track_id: track_id(trak),
+                    index,
+                    sample,
+                });
+            }
+            if let Some(next) = entries.get(i + 1) {
+                let chunks = next
+                    .uint("first_chunk")
+                    .unwrap_or(0)
+                    .saturating_sub(entry.uint("first_chunk").unwrap_or(0));
+                sample += chunks * entry.uint("samples_per_chunk").unwrap_or(0);
+            }
+        }
+    }
+
+    starts
+}
+
+/* follows the sample description index of the 'tfhd' boxes, which defaults to the one in 'trex' */
+fn fragment_sample_description_starts(
+    moov: &Mp4Box,
+    boxes: &[Mp4Box],
+) -> Vec<SampleDescriptionStart> {
+    let mut starts = vec![];
+
+    struct FragmentedTrack {
+        track_id: u64,
+        default_index: u64,
+        index: u64,
+        sample_count: u64,
+    }
+
+    let mut tracks: Vec<FragmentedTrack> = moov
+        .child(b"mvex")
+        .map(|mvex| {
+            mvex.children_of(b"trex")
+                .filter_map(|trex| {
+                    Some((
+                        trex.uint("track_ID")?,
+                        trex.uint("default_sample_description_index")?,
+                    ))
+                })
+                .map(|(track_id, index)| FragmentedTrack {
+                    track_id,
+                    default_index: index,
+                    index,
+                    sample_count: 0,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for traf in boxes
+        .iter()
+        .filter(|b| &b.box_type == b"moof")
+        .flat_map(|moof| moof.children_of(b"traf"))
+    {
+        let tfhd = match traf.child(b"tfhd") {
+            Some(tfhd) => tfhd,
+            None => continue,
+        };
+        let track_id = tfhd.uint("track_ID").unwrap_or(0);
+        let track = match tracks.iter_mut().find(|t| t.track_id == track_id) {
+            Some(track) => track,
+            None => continue,
+        };
+
+        let index = tfhd
+            .uint("sample_description_index")
+            .unwrap_or(track.default_index);
+        if index != track.index {
+            track.index = index;
+            starts.push(SampleDescriptionStart {
+                track_id,
+                index,
+                sample: track.sample_count + 1,
+            });
+        }
+
+        for trun in traf.children_of(b"trun") {
+            track.sample_count += trun.uint("sample_count").unwrap_or(0);
+        }
+    }
+
+    starts
+}
+
+fn print_ac4_presentations(track_id: u64, dac4: &Mp4Box) {
+    let bit_rate = match (
+        dac4.uint("bit_rate").unwrap_or(0),
+        dac4.uint("bit_rate_precision").unwrap_or(0),
+    ) {
+        (0, _) => "unknown".to_string(),
+        (bit_rate, 0xFFFFFFFF) => format!("{} bps", bit_rate),
+        (bit_rate, precision) => format!("{} bps (precision {} bps)", bit_rate, precision),
+    };
+    let presentations = dac4.field("presentations").map_or(&[][..], Value::as_list);
+    println!(
+        "  track {}: AC-4 bitstream version {}, bit rate {}, {} presentations",
+        track_id,
+        dac4.uint("bitstream_version").unwrap_or(0),
+        bit_rate,
+        presentations.len()
+    );
+
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    for (i, pres) in presentations.iter().enumerate() {
+        println!(
+            "    presentation {}: version {}, ID {}, Atmos {}, language {}, channel mode {}",
+            i,
+            pres.uint("presentation_version").unwrap_or(0),
+            or_dash(pres.uint("presentation_id").map(|id| id.to_string())),
+            or_dash(
+                pres.uint("dolby_atmos_indicator")
+                    .map(|a| if a == 1 { "yes" } else { "no" }.to_string())
+            ),
+            or_dash(
+                pres.get("language")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            ),
+            or_dash(pres.uint("channel_mode").map(|m| match m {
+                0x1F => "objects".to_string(),
+                m => m.to_string(),
+            }))
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use crate::mp4_boxes::{read_boxes, Mp4Box, Value};
+use anyhow::{bail, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{copy, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the segments of a fragmented file go.
+pub struct SegmentOptions {
+    /// The file taking 'ftyp' and 'moov'.
+    pub init_segment: Option<String>,
+    /// The file name pattern of the media segments.
+    pub segment_template: Option<String>,
+    /// Replaces `$RepresentationID$`.
+    pub representation_id: String,
+    /// The directory relative segment file names are taken from, the one of the output file.
+    pub directory: PathBuf,
+    /// The `$Number$` of the first media segment.
+    pub start_number: u64,
+    /// Writes each segment file under a temporary name first and renames it when complete.
+    pub atomic: bool,
+    pub overwrite: bool,
+}
+
+/* a media segment: the 'moof' with the boxes up to and including its 'mdat' */
+struct Segment {
+    start: u64,
+    size: u64,
+    track_id: u64,
+    earliest_presentation_time: u64,
+    duration: u64,
+    /* starts_with_SAP and SAP_type */
+    sap: u32,
+}
+
+/// Writes the init segment and the media segments of a fragmented .mp4 file, each fragment
+/// becoming a segment of 'styp', 'sidx', 'moof' and 'mdat' (preceded by its 'emsg' boxes).
+pub fn write_segments(path: &Path, options: &SegmentOptions) -> Result<()> {
+    let boxes = read_boxes(path)?;
+    let (ftyp, moov) = match (
+        boxes.iter().find(|b| &b.box_type == b"ftyp"),
+        boxes.iter().find(|b| &b.box_type == b"moov"),
+    ) {
+        (Some(ftyp), Some(moov)) => (ftyp, moov),
+        _ => bail!("\"{}\" has no 'ftyp' and 'moov' box", path.display()),
+    };
+    if moov.children_of(b"trak").count() != 1 {
+        bail!("Segmented output takes a single track, use '--output-format cmaf' to get a track file per input");
+    }
+
+    let mut file = File::open(path)?;
+
+    if let Some(init_segment) = &options.init_segment {
+        let name = expand_template(init_segment, &options.representation_id, 0, 0);
+        write_file(&name, options, |out| {
+            copy_range(&mut file, ftyp.offset, ftyp.size, out)?;
+            copy_range(&mut file, moov.offset, moov.size, out)
+        })?;
+    }
+
+    let template = match &options.segment_template {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+
+    let brands = ftyp
+        .field("compatible_brands")
+        .map(Value::as_list)
+        .unwrap_or(&[]);
+    let cmaf = brands.iter().any(|b| b.as_str() == Some("cmfc"));
+    let styp = styp_box(cmaf);
+
+    for (number, segment) in (options.start_number..).zip(segments(&boxes, moov)?) {
+        let name = expand_template(
+            template,
+            &options.representation_id,
+            number,
+            segment.earliest_presentation_time,
+        );
+        let sidx = sidx_box(&segment, timescale(moov))?;
+        write_file(&name, options, |out| {
+            out.write_all(&styp)?;
+            out.write_all(&sidx)?;
+            copy_range(&mut file, segment.start, segment.size, out)
+        })?;
+    }
+
+    Ok(())
+}
+
+/* replaces $RepresentationID$, $Number$ and $Time$, the latter two with an optional width
+ * format tag as in $Number%05d$, and $$ */
+fn expand_template(template: &str, representation_id: &str, number: u64, time: u64) -> PathBuf {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        name.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = match after.find('$') {
+            Some(end) => end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        let (identifier, format) = match after[..end].split_once('%') {
+            Some((identifier, format)) => (identifier, Some(format)),
+            None => (&after[..end], None),
+        };
+        let width = format
+            .and_then(|f| f.strip_prefix('0'))
+            .and_then(|f| f.strip_suffix('d'))
+            .and_then(|w| w.parse().ok())
+            .unwrap_or(0);
+        match identifier {
+            "" => name.push('$'),
+            "RepresentationID" => name.push_str(representation_id),
+            "Number" => name.push_str(&format!("{:0width$}", number, width = width)),
+            "Time" => name.push_str(&format!("{:0width$}", time, width = width)),
+            _ => name.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &after[end + 1..];
+    }
+    name.push_str(rest);
+    PathBuf::from(name)
+}
+
+/* writes a segment file into the output file directory, creating missing directories; with
+ * atomic writes a reader of the directory never sees a partly written file */
+fn write_file<F>(name: &Path, options: &SegmentOptions, write: F) -> Result<()>
+where
+    F: FnOnce(&mut File) -> Result<()>,
+{
+    let path = options.directory.join(name);
+    if path.exists() && !options.overwrite {
+        bail!(
+            "Segment file \"{}\" had been existed, please using '--overwrite' if you want to overwrite it",
+            path.display()
+        );
+    }
+    if let Some(parent) = path.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            bail!(
+                "Failed to create directory \"{}\": {}",
+                parent.display(),
+                err
+            );
+        }
+    }
+
+    let temp_path = if options.atomic {
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(path.file_name().unwrap_or_default());
+        temp_name.push(".tmp");
+        path.with_file_name(temp_name)
+    } else {
+        path.clone()
+    };
+    let mut out = match OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp_path)
+    {
+        Ok(file) => file,
+        Err(err) => bail!(
+            "Failed to create segment file \"{}\": {}",
+            temp_path.display(),
+            err
+        ),
+    };
+
+    let res = write(&mut out);
+    if !options.atomic {
+        return res;
+    }
+    // the data reaches the disk before the rename publishes the segment
+    let res = res.and_then(|_| Ok(out.sync_all()?));
+    drop(out);
+    if let Err(err) = res.and_then(|_| Ok(fs::rename(&temp_path, &path)?)) {
+        let _ = fs::remove_file(&temp_path);
+        bail!(
+            "Failed to write segment file \"{}\": {}",
+            path.display(),
+            err
+        );
+    }
+
+    Ok(())
+}
+
+fn copy_range(file: &mut File, offset: u64, size: u64, out: &mut File) -> Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    copy(&mut Read::by_ref(file).take(size), out)?;
+    Ok(())
+}
+
+fn timescale(moov: &Mp4Box) -> u32 {
+    moov.child(b"trak")
+        .and_then(|trak| trak.descendant(&[b"mdia", b"mdhd"]))
+        .and_then(|mdhd| mdhd.uint("timescale"))
+        .unwrap_or(1000) as u32
+}
+
+/* the fragments of the file, each starting with the boxes between the previous 'mdat' and
+ * its 'moof', e.g. 'emsg' */
+fn segments(boxes: &[Mp4Box], moov: &Mp4Box) -> Result<Vec<Segment>> {
+    let trex = moov.descendant(&[b"mvex", b"trex"]);
+    // the SAP types the muxer put into the 'sidx' of the whole file, one reference per fragment
+    let fragment_count = boxes.iter().filter(|b| &b.box_type == b"moof").count();
+    let saps: Vec<u32> = boxes
+        .iter()
+        .find(|b| &b.box_type == b"sidx")
+        .and_then(|sidx| sidx.field("references"))
+        .map(Value::as_list)
+        .unwrap_or(&[])
+        .iter()
+        .map(|r| {
+            (r.uint("starts_with_SAP").unwrap_or(0) as u32) << 31
+                | (r.uint("SAP_type").unwrap_or(0) as u32) << 28
+        })
+        .collect();
+    let saps = if saps.len() == fragment_count {
+        saps
+    } else {
+        vec![]
+    };
+
+    let mut segments: Vec<Segment> = vec![];
+    let mut start = None;
+    let mut segment: Option<Segment> = None;
+    for b in boxes {
+        match &b.box_type {
+            b"ftyp" | b"moov" | b"sidx" | b"mfra" => continue,
+            b"moof" => {
+                let traf = match b.children_of(b"traf").collect::<Vec<_>>()[..] {
+                    [traf] => traf,
+                    _ => bail!("Fragment at offset {} has not exactly one 'traf'", b.offset),
+                };
+                let mut s = fragment(b, traf, trex)?;
+                s.start = start.unwrap_or(b.offset);
+                s.sap = saps.get(segments.len()).copied().unwrap_or(s.sap);
+                segment = Some(s);
+            }
+            b"mdat" => {
+                if let Some(mut s) = segment.take() {
+                    s.size = b.offset + b.size - s.start;
+                    segments.push(s);
+                    start = None;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if segment.is_none() && start.is_none() {
+            start = Some(b.offset);
+        }
+    }
+
+    Ok(segments)
+}
+
+/* the times and the SAP of a fragment */
+fn fragment(moof: &Mp4Box, traf: &Mp4Box, trex: Option<&Mp4Box>) -> Result<Segment> {
+    let tfhd = match traf.child(b"tfhd") {
+        Some(tfhd) => tfhd,
+        None => bail!("Fragment at offset {} has no 'tfhd'", moof.offset),
+    };
+    if tfhd.uint("base_data_offset").is_some() {
+        bail!(
+            "Fragment at offset {} uses an absolute base data offset, it can't be moved into a segment",
+            moof.offset
+        );
+    }
+    let default = |name: &str| {
+        tfhd.uint(name)
+            .or_else(|| trex.and_then(|t| t.uint(name)))
+            .unwrap_or(0)
+    };
+    let default_duration = default("default_sample_duration");
+    let default_flags = tfhd
+        .field("default_sample_flags")
+        .or_else(|| trex.and_then(|t| t.field("default_sample_flags")));
+
+    let decode_time = traf
+        .child(b"tfdt")
+        .and_then(|tfdt| tfdt.uint("base_media_decode_time"))
+        .unwrap_or(0);
+    let mut dts = decode_time;
+    let mut earliest = None;
+    let mut first_flags = None;
+    for trun in traf.children_of(b"trun") {
+        let samples = trun.field("samples").map(Value::as_list).unwrap_or(&[]);
+        if first_flags.is_none() {
+            first_flags = trun
+                .field("first_sample_flags")
+                .or_else(|| samples.first().and_then(|s| s.get("sample_flags")))
+                .or(default_flags);
+        }
+        for s in 0..trun.uint("sample_count").unwrap_or(0) as usize {
+            let sample = samples.get(s);
+            let offset = match sample.and_then(|s| s.get("sample_composition_time_offset")) {
+                Some(Value::Int(offset)) => *offset,
+                Some(offset) => offset.as_u64().unwrap_or(0) as i64,
+                None => 0,
+            };
+            let pts = dts.saturating_add_signed(offset);
+            earliest = Some(earliest.map_or(pts, |e: u64| e.min(pts)));
+            dts += sample
+                .and_then(|s| s.uint("sample_duration"))
+                .unwrap_or(default_duration);
+        }
+    }
+
+    let sync = first_flags.and_then(|f| f.uint("non_sync")) != Some(1);
+    Ok(Segment {
+        start: moof.offset,
+        size: 0,
+        track_id: tfhd.uint("track_ID").unwrap_or(0),
+        earliest_presentation_time: earliest.unwrap_or(decode_time),
+        duration: dts - decode_time,
+        sap: if sync { 0x9000_0000 } else { 0 },
+    })
+}
+
+fn styp_box(cmaf: bool) -> Vec<u8> {
+    let mut brands: Vec<&[u8; 4]> = vec![b"msdh", b"msix"];
+    if cmaf {
+        brands.push(b"cmfs");
+    }
+    let mut data = ((16 + 4 * brands.len()) as u32).to_be_bytes().to_vec();
+    data.extend(b"styp");
+    data.extend(b"msdh");
+    data.extend(0u32.to_be_bytes());
+    for brand in brands {
+        data.extend(brand);
+    }
+    data
+}
+
+/* a 'sidx' referencing the rest of the segment, of version 1 if the earliest presentation time
+ * needs 64 bits */
+fn sidx_box(segment: &Segment, timescale: u32) -> Result<Vec<u8>> {
+    if segment.size > 0x7FFF_FFFF {
+        bail!(
+            "Segment at offset {} of {} bytes exceeds the 31-bit referenced_size of 'sidx'",
+            segment.start,
+            segment.size
+        );
+    }
+    let duration = match u32::try_from(segment.duration) {
+        Ok(duration) => duration,
+        Err(_) => bail!(
+            "Segment at offset {} lasts {} ticks of timescale {}, more than the 32-bit subsegment_duration of 'sidx'",
+            segment.start,
+            segment.duration,
+            timescale
+        ),
+    };
+    let earliest_presentation_time = u32::try_from(segment.earliest_presentation_time).ok();
+
+    let (version, size) = match earliest_presentation_time {
+        Some(_) => (0u32, 44u32),
+        None => (1, 52),
+    };
+    let mut data = size.to_be_bytes().to_vec();
+    data.extend(b"sidx");
+    data.extend((version << 24).to_be_bytes());
+    data.extend((segment.track_id as u32).to_be_bytes());
+    data.extend(timescale.to_be_bytes());
+    match earliest_presentation_time {
+        Some(time) => {
+            data.extend(time.to_be_bytes());
+            data.extend(0u32.to_be_bytes()); // first_offset
+        }
+        None => {
+            data.extend(segment.earliest_presentation_time.to_be_bytes());
+            data.extend(0u64.to_be_bytes()); // first_offset
+        }
+    }
+    data.extend(0u16.to_be_bytes());
+    data.extend(1u16.to_be_bytes()); // reference_count
+    data.extend((segment.size as u32).to_be_bytes());
+    data.extend(duration.to_be_bytes());
+    data.extend(segment.sap.to_be_bytes());
+    Ok(data)
+}
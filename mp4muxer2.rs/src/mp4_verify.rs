@@ -0,0 +1,649 @@
+use crate::mp4_boxes::{read_boxes, Mp4Box, Value};
+use crate::mp4_dump::json_string;
+use std::path::Path;
+
+/// Exit code of `verify` for a file with errors.
+pub const EXIT_ERRORS: i32 = 1;
+/// Exit code of `verify` for a file with warnings only.
+pub const EXIT_WARNINGS: i32 = 3;
+
+/* sample entries of Dolby audio and Dolby Vision, which should come with the 'dby1' brand */
+const DOLBY_ENTRIES: &[&[u8; 4]] = &[
+    b"ac-3", b"ec-3", b"ac-4", b"mlpa", b"dvh1", b"dvhe", b"dvav", b"dva1", b"dav1",
+];
+
+const DV_CONFIGS: &[&[u8; 4]] = &[b"dvcC", b"dvvC", b"dvwC"];
+
+/* brands allowing version 1 of 'tfdt' */
+const TFDT_V1_BRANDS: &[&str] = &["iso6", "iso7", "iso8", "iso9", "isoa", "isob", "isoc"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+struct Finding {
+    severity: Severity,
+    check: &'static str,
+    message: String,
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    fn error(&mut self, check: &'static str, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Error,
+            check,
+            message,
+        });
+    }
+
+    fn warning(&mut self, check: &'static str, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Warning,
+            check,
+            message,
+        });
+    }
+
+    fn of(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity == severity)
+    }
+}
+
+/// Checks the structure, the brands, the Dolby Vision configuration and the fragments of an
+/// .mp4 file, prints the findings as text or JSON and returns the exit code.
+pub fn verify_file(path: &Path, json: bool) -> i32 {
+    let mut report = Report::default();
+
+    match read_boxes(path) {
+        Ok(boxes) => verify_boxes(&boxes, &mut report),
+        Err(err) => report.error("structure", err.to_string()),
+    }
+
+    let errors = report.of(Severity::Error).count();
+    let warnings = report.of(Severity::Warning).count();
+    let (status, code) = match (errors, warnings) {
+        (0, 0) => ("passed", 0),
+        (0, _) => ("warnings", EXIT_WARNINGS),
+        _ => ("errors", EXIT_ERRORS),
+    };
+
+    if json {
+        let list = |severity| {
+            report
+                .of(severity)
+                .map(|f| {
+                    format!(
+                        "{{\"check\":{},\"message\":{}}}",
+                        json_string(f.check),
+                        json_string(&f.message)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        println!(
+            "{{\"file\":{},\"status\":\"{}\",\"errors\":[{}],\"warnings\":[{}]}}",
+            json_string(&path.display().to_string()),
+            status,
+            list(Severity::Error),
+            list(Severity::Warning)
+        );
+    } else {
+        for finding in &report.findings {
+            let severity = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            println!("{} [{}]: {}", severity, finding.check, finding.message);
+        }
+        println!(
+            "\"{}\": {} errors, {} warnings",
+            path.display(),
+            errors,
+            warnings
+        );
+    }
+
+    code
+}
+
+fn verify_boxes(boxes: &[Mp4Box], report: &mut Report) {
+    check_box_errors(boxes, report);
+
+    // the payload of each 'mdat', where chunks and track runs have to be
+    let mdats: Vec<(u64, u64)> = boxes
+        .iter()
+        .filter(|b| &b.box_type == b"mdat")
+        .map(|b| (b.offset + b.header_size, b.offset + b.size))
+        .collect();
+
+    let moov = match boxes.iter().find(|b| &b.box_type == b"moov") {
+        Some(moov) => moov,
+        None => {
+            report.error("structure", "no 'moov' box".to_string());
+            return;
+        }
+    };
+
+    for trak in moov.children_of(b"trak") {
+        let track_id = trak
+            .child(b"tkhd")
+            .and_then(|tkhd| tkhd.uint("track_ID"))
+            .unwrap_or(0);
+
+        let stbl = match trak.descendant(&[b"mdia", b"minf", b"stbl"]) {
+            Some(stbl) => stbl,
+            None => {
+                report.error("structure", format!("track {}: no 'stbl' box", track_id));
+                continue;
+            }
+        };
+
+        check_sample_tables(track_id, stbl, &mdats, report);
+
+        if let Some(stsd) = stbl.child(b"stsd") {
+            for entry in &stsd.children {
+                check_dolby_vision(track_id, entry, report);
+            }
+        }
+    }
+
+    check_fragments(boxes, moov, &mdats, report);
+    check_brands(boxes, moov, report);
+}
+
+fn check_box_errors(boxes: &[Mp4Box], report: &mut Report) {
+    for mp4_box in boxes {
+        if let Some(error) = &mp4_box.error {
+            report.error(
+                "structure",
+                format!(
+                    "'{}' at offset {}: {}",
+                    mp4_box.type_name(),
+                    mp4_box.offset,
+                    error
+                ),
+            );
+        }
+        check_box_errors(&mp4_box.children, report);
+    }
+}
+
+fn inside_mdat(mdats: &[(u64, u64)], start: u64, size: u64) -> bool {
+    mdats
+        .iter()
+        .any(|&(begin, end)| start >= begin && start.saturating_add(size) <= end)
+}
+
+fn uints(mp4_box: &Mp4Box, name: &str) -> Vec<u64> {
+    mp4_box
+        .field(name)
+        .map(|v| v.as_list().iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default()
+}
+
+/* the number of samples a 'stts' or 'ctts' box covers */
+fn covered_samples(mp4_box: &Mp4Box) -> u64 {
+    mp4_box
+        .field("entries")
+        .map(|v| {
+            v.as_list()
+                .iter()
+                .filter_map(|e| e.uint("sample_count"))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn check_sample_tables(track_id: u64, stbl: &Mp4Box, mdats: &[(u64, u64)], report: &mut Report) {
+    let stsz = match stbl.child(b"stsz") {
+        Some(stsz) => stsz,
+        None => {
+            if stbl.child(b"stz2").is_none() {
+                report.error("structure", format!("track {}: no 'stsz' box", track_id));
+            }
+            return;
+        }
+    };
+    let sample_count = stsz.uint("sample_count").unwrap_or(0);
+    let sample_size = stsz.uint("sample_size").unwrap_or(0);
+    let sizes = uints(stsz, "entry_sizes");
+
+    for box_type in [b"stts", b"ctts"] {
+        match stbl.child(box_type) {
+            Some(table) if covered_samples(table) != sample_count => report.error(
+                "structure",
+                format!(
+                    "track {}: '{}' covers {} samples, 'stsz' has {}",
+                    track_id,
+                    table.type_name(),
+                    covered_samples(table),
+                    sample_count
+                ),
+            ),
+            None if box_type == b"stts" => {
+                report.error("structure", format!("track {}: no 'stts' box", track_id))
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(stss) = stbl.child(b"stss") {
+        if let Some(number) = uints(stss, "sample_numbers")
+            .into_iter()
+            .find(|&n| n == 0 || n > sample_count)
+        {
+            report.error(
+                "structure",
+                format!(
+                    "track {}: 'stss' lists sample {} of {}",
+                    track_id, number, sample_count
+                ),
+            );
+        }
+    }
+
+    let chunk_offsets = match stbl.child(b"stco").or_else(|| stbl.child(b"co64")) {
+        Some(offsets) => uints(offsets, "chunk_offsets"),
+        None => {
+            report.error(
+                "structure",
+                format!("track {}: no 'stco' or 'co64' box", track_id),
+            );
+            return;
+        }
+    };
+    let entries = match stbl.child(b"stsc").and_then(|stsc| stsc.field("entries")) {
+        Some(entries) => entries.as_list(),
+        None => {
+            report.error("structure", format!("track {}: no 'stsc' box", track_id));
+            return;
+        }
+    };
+    let description_count = stbl
+        .child(b"stsd")
+        .and_then(|stsd| stsd.uint("entry_count"))
+        .unwrap_or(0);
+
+    // the runs of chunks as (first chunk, end chunk, samples per chunk)
+    let chunk_count = chunk_offsets.len() as u64;
+    let mut runs = vec![];
+    for (i, entry) in entries.iter().enumerate() {
+        let first_chunk = entry.uint("first_chunk").unwrap_or(0);
+        let end_chunk = entries
+            .get(i + 1)
+            .and_then(|next| next.uint("first_chunk"))
+            .unwrap_or(chunk_count + 1);
+        let index = entry.uint("sample_description_index").unwrap_or(0);
+
+        if (i == 0 && first_chunk != 1) || first_chunk > chunk_count || end_chunk <= first_chunk {
+            report.error(
+                "structure",
+                format!(
+                    "track {}: 'stsc' entry {} starts at chunk {} of {}",
+                    track_id,
+                    i + 1,
+                    first_chunk,
+                    chunk_count
+                ),
+            );
+            return;
+        }
+        if index == 0 || index > description_count {
+            report.error(
+                "structure",
+                format!(
+                    "track {}: 'stsc' entry {} refers to sample description {} of {}",
+                    track_id,
+                    i + 1,
+                    index,
+                    description_count
+                ),
+            );
+        }
+        runs.push((
+            first_chunk,
+            end_chunk,
+            entry.uint("samples_per_chunk").unwrap_or(0),
+        ));
+    }
+
+    // sample sizes are only known with 'stsz' complete
+    let sizes_known = sample_size != 0 || sizes.len() as u64 == sample_count;
+    let size_of = |sample: u64| match sample_size {
+        0 => sizes.get(sample as usize).copied().unwrap_or(0),
+        size => size,
+    };
+
+    let mut sample = 0;
+    let mut outside = vec![];
+    for (first_chunk, end_chunk, samples_per_chunk) in runs {
+        for chunk in first_chunk..end_chunk {
+            let bytes: u64 = (sample..sample + samples_per_chunk).map(size_of).sum();
+            let offset = chunk_offsets[chunk as usize - 1];
+            if sizes_known && !inside_mdat(mdats, offset, bytes) {
+                outside.push((chunk, offset, bytes));
+            }
+            sample += samples_per_chunk;
+        }
+    }
+
+    if sample != sample_count {
+        report.error(
+            "structure",
+            format!(
+                "track {}: 'stsc' and the chunk offsets hold {} samples, 'stsz' has {}",
+                track_id, sample, sample_count
+            ),
+        );
+    }
+    if let Some((chunk, offset, bytes)) = outside.first() {
+        report.error(
+            "structure",
+            format!(
+                "track {}: {} chunks lie outside 'mdat', the first is chunk {} at offset {} ({} bytes)",
+                track_id,
+                outside.len(),
+                chunk,
+                offset,
+                bytes
+            ),
+        );
+    }
+}
+
+/* the Dolby Vision profiles with the codec specific sample entries of the base layer,
+ * the Dolby Vision sample entries and the defined base layer compatibility IDs */
+fn dv_profile(
+    profile: u64,
+) -> Option<(
+    &'static [&'static str],
+    &'static [&'static str],
+    &'static [u64],
+)> {
+    const HEVC: &[&str] = &["hvc1", "hev1"];
+    const DV_HEVC: &[&str] = &["dvh1", "dvhe"];
+
+    match profile {
+        4 => Some((HEVC, DV_HEVC, &[2])),
+        5 => Some((HEVC, DV_HEVC, &[0])),
+        7 => Some((HEVC, DV_HEVC, &[6])),
+        8 => Some((HEVC, DV_HEVC, &[1, 2, 4])),
+        9 => Some((&["avc1", "avc3"], &["dvav", "dva1"], &[2])),
+        10 => Some((&["av01"], &["dav1"], &[0, 1, 2, 4])),
+        _ => None,
+    }
+}
+
+fn check_dolby_vision(track_id: u64, entry: &Mp4Box, report: &mut Report) {
+    let name = entry.type_name();
+    let check = "dolby-vision";
+
+    let config = match entry
+        .children
+        .iter()
+        .find(|b| DV_CONFIGS.contains(&&b.box_type))
+    {
+        Some(config) => config,
+        None => {
+            if ["dvh1", "dvhe", "dvav", "dva1", "dav1"].contains(&name.as_str()) {
+                report.error(
+                    check,
+                    format!(
+                        "track {}: '{}' sample entry without Dolby Vision configuration",
+                        track_id, name
+                    ),
+                );
+            }
+            return;
+        }
+    };
+
+    let profile = config.uint("dv_profile").unwrap_or(0);
+    let level = config.uint("dv_level").unwrap_or(0);
+    let compatibility_id = config.uint("dv_bl_signal_compatibility_id").unwrap_or(0);
+
+    let (bl_entries, dv_entries, compatibility_ids) = match dv_profile(profile) {
+        Some(profile) => profile,
+        None => {
+            report.error(
+                check,
+                format!(
+                    "track {}: Dolby Vision profile {} is not supported in a single track",
+                    track_id, profile
+                ),
+            );
+            return;
+        }
+    };
+
+    // a base layer playable without Dolby Vision has the sample entry of its codec
+    if bl_entries.contains(&name.as_str()) {
+        if compatibility_id == 0 {
+            report.error(
+                check,
+                format!(
+                    "track {}: '{}' sample entry with Dolby Vision profile {}, which has no compatible base layer, calls for '{}'",
+                    track_id,
+                    name,
+                    profile,
+                    dv_entries.join("' or '")
+                ),
+            );
+        }
+    } else if !dv_entries.contains(&name.as_str()) {
+        report.error(
+            check,
+            format!(
+                "track {}: '{}' sample entry does not match Dolby Vision profile {}",
+                track_id, name, profile
+            ),
+        );
+    }
+
+    let config_type: &[u8; 4] = match profile {
+        0..=7 => b"dvcC",
+        8..=10 => b"dvvC",
+        _ => b"dvwC",
+    };
+    if &config.box_type != config_type {
+        report.error(
+            check,
+            format!(
+                "track {}: Dolby Vision profile {} is signalled in '{}' instead of '{}'",
+                track_id,
+                profile,
+                config.type_name(),
+                String::from_utf8_lossy(config_type)
+            ),
+        );
+    }
+
+    if !(1..=13).contains(&level) {
+        report.error(
+            check,
+            format!("track {}: invalid Dolby Vision level {}", track_id, level),
+        );
+    }
+
+    if !compatibility_ids.contains(&compatibility_id) {
+        report.warning(
+            check,
+            format!(
+                "track {}: base layer compatibility ID {} is not defined for Dolby Vision profile {}",
+                track_id, compatibility_id, profile
+            ),
+        );
+    }
+}
+
+fn check_fragments(boxes: &[Mp4Box], moov: &Mp4Box, mdats: &[(u64, u64)], report: &mut Report) {
+    let moofs: Vec<&Mp4Box> = boxes.iter().filter(|b| &b.box_type == b"moof").collect();
+    if moofs.is_empty() {
+        return;
+    }
+
+    let trexs: Vec<&Mp4Box> = match moov.child(b"mvex") {
+        Some(mvex) => mvex.children_of(b"trex").collect(),
+        None => {
+            report.error(
+                "structure",
+                "'moof' boxes without 'mvex' in 'moov'".to_string(),
+            );
+            return;
+        }
+    };
+
+    for (i, moof) in moofs.iter().enumerate() {
+        let sequence_number = moof
+            .child(b"mfhd")
+            .and_then(|mfhd| mfhd.uint("sequence_number"))
+            .unwrap_or(i as u64 + 1);
+        // without a base data offset the data of a track fragment follows that of the previous one
+        let mut data_end = moof.offset;
+
+        for (j, traf) in moof.children_of(b"traf").enumerate() {
+            let tfhd = match traf.child(b"tfhd") {
+                Some(tfhd) => tfhd,
+                None => {
+                    report.error(
+                        "structure",
+                        format!("fragment {}: 'traf' without 'tfhd'", sequence_number),
+                    );
+                    continue;
+                }
+            };
+            let track_id = tfhd.uint("track_ID").unwrap_or(0);
+            let trex = match trexs.iter().find(|t| t.uint("track_ID") == Some(track_id)) {
+                Some(trex) => trex,
+                None => {
+                    report.error(
+                        "structure",
+                        format!(
+                            "fragment {}: track {} has no 'trex' box",
+                            sequence_number, track_id
+                        ),
+                    );
+                    continue;
+                }
+            };
+
+            let default_size = tfhd
+                .uint("default_sample_size")
+                .or_else(|| trex.uint("default_sample_size"))
+                .unwrap_or(0);
+            let default_flags = tfhd
+                .field("default_sample_flags")
+                .or_else(|| trex.field("default_sample_flags"));
+            let base = match tfhd.uint("base_data_offset") {
+                Some(offset) => offset,
+                None if tfhd.flags() & 0x20000 != 0 || j == 0 => moof.offset,
+                None => data_end,
+            };
+
+            let mut pos = base;
+            for (k, trun) in traf.children_of(b"trun").enumerate() {
+                let sample_count = trun.uint("sample_count").unwrap_or(0);
+                let samples = trun.field("samples").map(Value::as_list).unwrap_or(&[]);
+                let bytes: u64 = (0..sample_count as usize)
+                    .map(|s| {
+                        samples
+                            .get(s)
+                            .and_then(|sample| sample.uint("sample_size"))
+                            .unwrap_or(default_size)
+                    })
+                    .sum();
+                let start = match trun.field("data_offset") {
+                    Some(Value::Int(offset)) => base.saturating_add_signed(*offset),
+                    _ => pos,
+                };
+                if !inside_mdat(mdats, start, bytes) {
+                    report.error(
+                        "structure",
+                        format!(
+                            "fragment {}: 'trun' data of track {} at offset {} ({} bytes) lies outside 'mdat'",
+                            sequence_number, track_id, start, bytes
+                        ),
+                    );
+                }
+                pos = start + bytes;
+
+                if k == 0 && sample_count > 0 {
+                    let flags = trun
+                        .field("first_sample_flags")
+                        .or_else(|| samples.first().and_then(|s| s.get("sample_flags")))
+                        .or(default_flags);
+                    if flags.and_then(|f| f.uint("non_sync")) == Some(1) {
+                        report.error(
+                            "fragments",
+                            format!(
+                                "fragment {}: track {} does not start with a sync sample",
+                                sequence_number, track_id
+                            ),
+                        );
+                    }
+                }
+            }
+            data_end = pos;
+        }
+    }
+}
+
+fn check_brands(boxes: &[Mp4Box], moov: &Mp4Box, report: &mut Report) {
+    let ftyp = match boxes.iter().find(|b| &b.box_type == b"ftyp") {
+        Some(ftyp) => ftyp,
+        None => {
+            report.warning("brands", "no 'ftyp' box".to_string());
+            return;
+        }
+    };
+    let mut brands: Vec<&str> = ftyp
+        .field("compatible_brands")
+        .map(|v| v.as_list().iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    brands.extend(ftyp.field("major_brand").and_then(Value::as_str));
+
+    let mut dolby_entries: Vec<String> = moov
+        .children_of(b"trak")
+        .filter_map(|trak| trak.descendant(&[b"mdia", b"minf", b"stbl", b"stsd"]))
+        .flat_map(|stsd| stsd.children.iter())
+        .filter(|entry| {
+            DOLBY_ENTRIES.contains(&&entry.box_type)
+                || entry
+                    .children
+                    .iter()
+                    .any(|b| DV_CONFIGS.contains(&&b.box_type))
+        })
+        .map(Mp4Box::type_name)
+        .collect();
+    dolby_entries.dedup();
+    if !dolby_entries.is_empty() && !brands.contains(&"dby1") {
+        report.warning(
+            "brands",
+            format!(
+                "Dolby tracks ('{}') without the 'dby1' brand",
+                dolby_entries.join("', '")
+            ),
+        );
+    }
+
+    let tfdt_v1 = boxes
+        .iter()
+        .filter(|b| &b.box_type == b"moof")
+        .flat_map(|moof| moof.children_of(b"traf"))
+        .filter_map(|traf| traf.child(b"tfdt"))
+        .any(|tfdt| tfdt.version() == 1);
+    if tfdt_v1 && !brands.iter().any(|brand| TFDT_V1_BRANDS.contains(brand)) {
+        report.error(
+            "brands",
+            "'tfdt' version 1 without the 'iso6' brand".to_string(),
+        );
+    }
+}
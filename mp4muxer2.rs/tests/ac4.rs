@@ -0,0 +1,171 @@
+use std::process::Output;
+
+mod common;
+use common::{find_boxes, mux_data, BitWriter};
+
+/* a presentation_v1_info() of a single substream group */
+fn presentation_info(w: &mut BitWriter, version: u64, id: u64, group: u64) {
+    w.put(1, 1);
+    // presentation_version(), unary
+    w.put(version as usize + 1, (1 << (version + 1)) - 2);
+    w.put(3, 0);
+    // presentation_id as variable_bits(2) below 4
+    w.put(1, 1);
+    w.put(3, id << 1);
+    // b_multiplier, emdf_info() with no protection, b_presentation_filter
+    w.put(1, 0);
+    w.put(10, 0);
+    w.put(1, 0);
+    w.put(3, group);
+    w.put(2, 0);
+    // ac4_presentation_substream_info()
+    w.put(4, 0);
+}
+
+/* a channel coded ac4_substream_group_info() of one substream and a language */
+fn substream_group_info(w: &mut BitWriter, ch_mode_bits: (usize, u64), language: &[u8]) {
+    w.put(3, 0b101);
+    w.put(1, 1);
+    w.put(ch_mode_bits.0, ch_mode_bits.1);
+    // b_sf_multiplier, b_bitrate_info, b_audio_ndot, substream_index
+    w.put(5, 0);
+    w.put(1, 1);
+    w.put(3, 0);
+    w.put(2, 0b10);
+    w.put(6, language.len() as u64);
+    w.bytes(language);
+}
+
+/* 25 fps I-frames: a stereo presentation with ID 1 and an IMS presentation with ID 2 */
+fn ac4_frame() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(2, 2);
+    w.put(10, 0);
+    w.put(1, 0);
+    w.put(1, 1);
+    w.put(4, 2);
+    w.put(1, 1);
+    // two presentations
+    w.put(2, 0b01);
+    w.put(3, 0);
+    w.put(1, 0);
+    w.put(1, 0);
+    presentation_info(&mut w, 1, 1, 0);
+    presentation_info(&mut w, 2, 2, 1);
+    substream_group_info(&mut w, (2, 0b10), b"en");
+    // 7.0.4, the Atmos stream of the IMS presentation
+    substream_group_info(&mut w, (7, 0b111_1001), b"de");
+
+    let mut frame = vec![0xAC, 0x40, 0, 100];
+    frame.extend(w.data);
+    frame.resize(104, 0x5A);
+    frame
+}
+
+fn mux_ac4(extra: &[&str]) -> (Output, Vec<u8>) {
+    mux_data("audio.ac4", &ac4_frame().repeat(5), extra)
+}
+
+/* bit_rate and bit_rate_precision, following b_program_id and bit_rate_mode */
+fn ac4_bitrate_dsi(dac4: &[u8]) -> (u32, u32) {
+    let bits = dac4[3..12]
+        .iter()
+        .fold(0u128, |bits, &b| bits << 8 | b as u128);
+    ((bits >> 37) as u32, (bits >> 5) as u32)
+}
+
+/* the presentation_version and pres_bytes of the presentations in 'dac4' */
+fn dac4_presentations(dac4: &[u8], n_presentations: usize) -> Vec<(u8, usize)> {
+    // the fixed ac4_dsi_v1() fields and ac4_bitrate_dsi()
+    let mut pos = 12;
+    (0..n_presentations)
+        .map(|_| {
+            let entry = (dac4[pos], dac4[pos + 1] as usize);
+            pos += 2 + entry.1;
+            entry
+        })
+        .collect()
+}
+
+#[test]
+fn ac4_ims_duplicated_by_default() {
+    let (res, mp4) = mux_ac4(&["--verbose"]);
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(res.status.success(), "{}", stdout);
+
+    let dac4 = find_boxes(&mp4, b"dac4")[0];
+    // version 1, bitstream version 2, 48 kHz, 25 fps, 3 presentations
+    assert_eq!(&dac4[..3], [0x20, 0xA4, 0x03]);
+    // unknown bit rate and precision
+    assert_eq!(ac4_bitrate_dsi(dac4), (0, 0xFFFF_FFFF));
+    let presentations = dac4_presentations(dac4, 3);
+    assert_eq!(
+        presentations.iter().map(|p| p.0).collect::<Vec<_>>(),
+        [1, 2, 1]
+    );
+    assert_eq!(presentations[1].1, presentations[2].1);
+
+    assert!(stdout.contains("AC-4 bitstream version 2, bit rate unknown, 3 presentations"));
+    assert!(
+        stdout.contains("presentation 0: version 1, ID 1, Atmos no, language en, channel mode 1")
+    );
+    assert!(
+        stdout.contains("presentation 1: version 2, ID 2, Atmos yes, language de, channel mode 1")
+    );
+    assert!(
+        stdout.contains("presentation 2: version 1, ID 2, Atmos yes, language de, channel mode 1")
+    );
+}
+
+#[test]
+fn ac4_bitrate_and_single_ims() {
+    let (res, mp4) = mux_ac4(&[
+        "--ac4-bitrate",
+        "144000",
+        "--ac4-bitrate-precision",
+        "2000",
+        "--ac4-ims",
+        "single",
+        "--verbose",
+    ]);
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(res.status.success(), "{}", stdout);
+
+    let dac4 = find_boxes(&mp4, b"dac4")[0];
+    assert_eq!(&dac4[..3], [0x20, 0xA4, 0x02]);
+    assert_eq!(ac4_bitrate_dsi(dac4), (144000, 2000));
+    assert_eq!(
+        dac4_presentations(dac4, 2)
+            .iter()
+            .map(|p| p.0)
+            .collect::<Vec<_>>(),
+        [1, 2]
+    );
+
+    assert!(stdout.contains(
+        "AC-4 bitstream version 2, bit rate 144000 bps (precision 2000 bps), 2 presentations"
+    ));
+}
+
+#[test]
+fn ac4_options_validated() {
+    let (res, _) = mux_ac4(&["--ac4-bitrate-precision", "1000"]);
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("requires '--ac4-bitrate'"));
+
+    let (res, _) = mux_data("audio.ec3", &[0x0B, 0x77], &["--ac4-ims", "single"]);
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("AC-4 (.ac4) input files only"));
+
+    let (res, _) = mux_ac4(&["--ac4-ims", "twice"]);
+    assert!(!res.status.success());
+}
+
+#[test]
+fn ac4_without_presentation_id_fails() {
+    let mut frame = ac4_frame();
+    // b_presentation_id of the stereo presentation, required with two presentations
+    frame[8] &= !0x80;
+    let (res, _) = mux_data("no_id.ac4", &frame, &[]);
+    assert!(!res.status.success());
+}
@@ -0,0 +1,296 @@
+use std::fs;
+use std::process::Output;
+
+mod common;
+use common::{find_boxes, mux, temp_file, u32s, BitWriter};
+
+const OBU_SEQUENCE_HEADER: u8 = 1;
+const OBU_TEMPORAL_DELIMITER: u8 = 2;
+const OBU_METADATA: u8 = 5;
+const OBU_FRAME: u8 = 6;
+
+/* 1920x1080 10 bit 4:2:0, level 4.0, BT.2020 PQ, 24000/1001 frames per second */
+fn sequence_header() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(3, 0); // seq_profile
+    w.put(1, 0); // still_picture
+    w.put(1, 0); // reduced_still_picture_header
+    w.put(1, 1); // timing_info_present_flag
+    w.put(32, 1001); // num_units_in_display_tick
+    w.put(32, 24000); // time_scale
+    w.put(1, 1); // equal_picture_interval
+    w.put(1, 1); // num_ticks_per_picture_minus_1 = 0
+    w.put(1, 0); // decoder_model_info_present_flag
+    w.put(1, 0); // initial_display_delay_present_flag
+    w.put(5, 0); // operating_points_cnt_minus_1
+    w.put(12, 0); // operating_point_idc[0]
+    w.put(5, 8); // seq_level_idx[0]
+    w.put(1, 0); // seq_tier[0]
+    w.put(4, 10); // frame_width_bits_minus_1
+    w.put(4, 10); // frame_height_bits_minus_1
+    w.put(11, 1919);
+    w.put(11, 1079);
+    w.put(1, 0); // frame_id_numbers_present_flag
+    w.put(3, 0b011); // use_128x128_superblock, enable_filter_intra, enable_intra_edge_filter
+    w.put(4, 0b1111); // interintra, masked compound, warped motion, dual filter
+    w.put(1, 1); // enable_order_hint
+    w.put(2, 0b11); // enable_jnt_comp, enable_ref_frame_mvs
+    w.put(1, 1); // seq_choose_screen_content_tools
+    w.put(1, 1); // seq_choose_integer_mv
+    w.put(3, 6); // order_hint_bits_minus_1
+    w.put(3, 0b011); // enable_superres, enable_cdef, enable_restoration
+    w.put(1, 1); // high_bitdepth
+    w.put(1, 0); // mono_chrome
+    w.put(1, 1); // color_description_present_flag
+    w.put(8, 9); // BT.2020
+    w.put(8, 16); // PQ
+    w.put(8, 9); // BT.2020 non-constant luminance
+    w.put(1, 0); // color_range
+    w.put(2, 0); // chroma_sample_position
+    w.put(1, 0); // separate_uv_delta_q
+    w.put(1, 0); // film_grain_params_present
+    w.finish()
+}
+
+fn fixed(value: f64, frac_bits: u32) -> Vec<u8> {
+    let v = (value * (1u64 << frac_bits) as f64).round() as u32;
+    if frac_bits == 16 {
+        (v as u16).to_be_bytes().to_vec()
+    } else {
+        v.to_be_bytes().to_vec()
+    }
+}
+
+fn metadata_mdcv() -> Vec<u8> {
+    let mut payload = vec![2];
+    // R, G, B and white point
+    for (x, y) in [
+        (0.708, 0.292),
+        (0.170, 0.797),
+        (0.131, 0.046),
+        (0.3127, 0.3290),
+    ] {
+        payload.extend(fixed(x, 16));
+        payload.extend(fixed(y, 16));
+    }
+    payload.extend(fixed(1000.0, 8));
+    payload.extend(fixed(0.005, 14));
+    payload
+}
+
+fn metadata_cll() -> Vec<u8> {
+    vec![1, 0x03, 0xE8, 0x01, 0x90] // MaxCLL 1000, MaxFALL 400
+}
+
+fn metadata_dv_rpu() -> Vec<u8> {
+    vec![
+        4, 0xB5, 0x00, 0x3B, 0x00, 0x00, 0x08, 0x00, 0x19, 0x08, 0x09,
+    ]
+}
+
+fn frame(key: bool, index: u8) -> Vec<u8> {
+    // show_existing_frame = 0, frame_type (KEY_FRAME or INTER_FRAME), show_frame = 1
+    let first = if key { 0x10 } else { 0x30 };
+    let mut payload = vec![first];
+    payload.extend(std::iter::repeat_n(index, 20 + index as usize));
+    payload
+}
+
+/* the OBUs of each temporal unit, temporal delimiters included */
+fn temporal_units(dv_rpu: bool) -> Vec<Vec<(u8, Vec<u8>)>> {
+    let td = (OBU_TEMPORAL_DELIMITER, vec![]);
+    let seq = (OBU_SEQUENCE_HEADER, sequence_header());
+
+    let mut first = vec![
+        td.clone(),
+        seq.clone(),
+        (OBU_METADATA, metadata_cll()),
+        (OBU_METADATA, metadata_mdcv()),
+    ];
+    if dv_rpu {
+        first.push((OBU_METADATA, metadata_dv_rpu()));
+    }
+    first.push((OBU_FRAME, frame(true, 0)));
+
+    vec![
+        first,
+        vec![td.clone(), (OBU_FRAME, frame(false, 1))],
+        vec![td.clone(), (OBU_FRAME, frame(false, 2))],
+        // a key frame with a sequence header is a sync sample
+        vec![td.clone(), seq.clone(), (OBU_FRAME, frame(true, 3))],
+        // an inter frame with a sequence header is not
+        vec![td.clone(), seq, (OBU_FRAME, frame(false, 4))],
+        vec![td, (OBU_FRAME, frame(false, 5))],
+    ]
+}
+
+fn obu(obu_type: u8, payload: &[u8], with_size: bool) -> Vec<u8> {
+    let mut data = vec![obu_type << 3 | if with_size { 0x02 } else { 0 }];
+    if with_size {
+        data.extend(leb128(payload.len()));
+    }
+    data.extend(payload);
+    data
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut data = vec![];
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            return data;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn low_overhead(tu: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    tu.iter().flat_map(|(t, p)| obu(*t, p, true)).collect()
+}
+
+fn annexb(tus: &[Vec<(u8, Vec<u8>)>]) -> Vec<u8> {
+    let mut data = vec![];
+    for tu in tus {
+        let mut frame_unit = vec![];
+        for (t, p) in tu {
+            let o = obu(*t, p, false);
+            frame_unit.extend(leb128(o.len()));
+            frame_unit.extend(o);
+        }
+        let mut temporal_unit = leb128(frame_unit.len());
+        temporal_unit.extend(frame_unit);
+        data.extend(leb128(temporal_unit.len()));
+        data.extend(temporal_unit);
+    }
+    data
+}
+
+fn ivf(tus: &[Vec<(u8, Vec<u8>)>]) -> Vec<u8> {
+    let mut data = b"DKIF".to_vec();
+    data.extend(0u16.to_le_bytes());
+    data.extend(32u16.to_le_bytes());
+    data.extend(b"AV01");
+    data.extend(1920u16.to_le_bytes());
+    data.extend(1080u16.to_le_bytes());
+    data.extend(24000u32.to_le_bytes());
+    data.extend(1001u32.to_le_bytes());
+    data.extend((tus.len() as u32).to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    for (pts, tu) in tus.iter().enumerate() {
+        let frame = low_overhead(tu);
+        data.extend((frame.len() as u32).to_le_bytes());
+        data.extend((pts as u64).to_le_bytes());
+        data.extend(frame);
+    }
+    data
+}
+
+/* the samples as the muxer is expected to store them */
+fn expected_sample_sizes(tus: &[Vec<(u8, Vec<u8>)>]) -> Vec<u32> {
+    tus.iter()
+        .map(|tu| {
+            tu.iter()
+                .filter(|(t, _)| *t != OBU_TEMPORAL_DELIMITER)
+                .map(|(t, p)| obu(*t, p, true).len() as u32)
+                .sum()
+        })
+        .collect()
+}
+
+fn mux_av1(name: &str, data: &[u8], extra: &[&str]) -> (Output, Vec<u8>) {
+    let input = temp_file(name);
+    let output = temp_file(&format!("{}.mp4", name));
+    fs::write(&input, data).unwrap();
+
+    let res = mux(&input, &output, extra);
+    let mp4 = fs::read(&output).unwrap_or_default();
+    let _ = fs::remove_file(&input);
+    let _ = fs::remove_file(&output);
+    (res, mp4)
+}
+
+#[test]
+fn av1_input_formats() {
+    let tus = temporal_units(false);
+    let obu_stream: Vec<u8> = tus.iter().flat_map(|tu| low_overhead(tu)).collect();
+
+    let mut av1c = vec![0x81, 0x08, 0x4C, 0x00];
+    av1c.extend(obu(OBU_SEQUENCE_HEADER, &sequence_header(), true));
+
+    for (name, data) in [
+        ("low_overhead.obu", obu_stream),
+        ("annexb.obu", annexb(&tus)),
+        ("stream.ivf", ivf(&tus)),
+    ] {
+        let (res, mp4) = mux_av1(name, &data, &[]);
+        assert!(res.status.success(), "{}", name);
+
+        assert!(find_boxes(&mp4, b"ftyp")[0].chunks(4).any(|b| b == b"av01"));
+        let stsd = find_boxes(&mp4, b"stsd")[0];
+        assert_eq!(&stsd[12..16], b"av01", "{}", name);
+        // width and height of the VisualSampleEntry
+        assert_eq!(&stsd[16 + 24..16 + 28], &[0x07, 0x80, 0x04, 0x38]);
+        assert_eq!(find_boxes(&mp4, b"av1C"), [&av1c[..]], "{}", name);
+
+        // stsz, stss and stts
+        let stsz = u32s(find_boxes(&mp4, b"stsz")[0]);
+        assert_eq!(&stsz[3..], &expected_sample_sizes(&tus)[..], "{}", name);
+        assert_eq!(u32s(find_boxes(&mp4, b"stss")[0]), [0, 2, 1, 4]);
+        let mdhd = u32s(&find_boxes(&mp4, b"mdhd")[0][..16]);
+        assert_eq!(mdhd[3], 24000);
+        assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 6, 1001]);
+
+        // HDR signalling from the sequence header and the metadata OBUs
+        assert_eq!(
+            find_boxes(&mp4, b"colr"),
+            [&[b'n', b'c', b'l', b'x', 0, 9, 0, 16, 0, 9, 0][..]]
+        );
+        let mdcv: Vec<u8> = [8500u16, 39850, 6550, 2300, 35400, 14600, 15635, 16450]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .chain(10000000u32.to_be_bytes())
+            .chain(50u32.to_be_bytes())
+            .collect();
+        assert_eq!(find_boxes(&mp4, b"mdcv"), [&mdcv[..]]);
+        assert_eq!(find_boxes(&mp4, b"clli"), [&[0x03, 0xE8, 0x01, 0x90][..]]);
+        assert!(find_boxes(&mp4, b"dvvC").is_empty());
+    }
+}
+
+#[test]
+fn av1_dolby_vision_profile_10() {
+    let data = ivf(&temporal_units(true));
+
+    let (res, _) = mux_av1("dv_no_profile.ivf", &data, &[]);
+    assert!(!res.status.success());
+
+    // 1920x1080 at 23 (integer) frames per second is level 3
+    let dvvc = |compatible_id: u8| {
+        let mut dvvc = vec![1, 0, 10 << 1, 3 << 3 | 5, compatible_id << 4];
+        dvvc.resize(24, 0);
+        dvvc
+    };
+
+    let (res, mp4) = mux_av1("dv_10.ivf", &data, &["--dv-profile", "10"]);
+    assert!(res.status.success());
+    assert_eq!(&find_boxes(&mp4, b"stsd")[0][12..16], b"dav1");
+    assert_eq!(find_boxes(&mp4, b"dvvC"), [&dvvc(0)[..]]);
+
+    let (res, mp4) = mux_av1(
+        "dv_10_2.ivf",
+        &data,
+        &["--dv-profile", "10", "--dv-bl-compatible-id", "2"],
+    );
+    assert!(res.status.success());
+    assert_eq!(&find_boxes(&mp4, b"stsd")[0][12..16], b"av01");
+    assert_eq!(find_boxes(&mp4, b"dvvC"), [&dvvc(2)[..]]);
+
+    let (res, _) = mux_av1(
+        "dv_8.ivf",
+        &data,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "2"],
+    );
+    assert!(!res.status.success());
+}
@@ -0,0 +1,112 @@
+use std::fs;
+
+mod common;
+use common::{
+    box_tree, find_boxes, hevc_stream, open_gop_pictures, payload, run, signal, temp_file, u32_at,
+    u64_at, NAL_IDR_W_RADL,
+};
+
+/* checks the CMAF track file constraints the muxer is responsible for */
+fn check_track_file(mp4: &[u8]) {
+    let boxes = box_tree(mp4);
+    let types: Vec<&[u8; 4]> = boxes.iter().map(|b| &b.box_type).collect();
+    assert_eq!(types[..3], [b"ftyp", b"moov", b"sidx"]);
+    assert!(!types.contains(&b"mfra"));
+    assert_eq!(types.iter().filter(|&&t| t == b"sidx").count(), 1);
+
+    let ftyp = payload(mp4, &boxes[0]);
+    assert_eq!(&ftyp[..4], b"cmf2");
+    assert!(ftyp[8..].chunks(4).any(|brand| brand == b"cmfc"));
+
+    let moov = payload(mp4, &boxes[1]);
+    assert_eq!(find_boxes(moov, b"trak").len(), 1);
+    assert!(find_boxes(moov, b"iods").is_empty());
+
+    // defaults of duration, size and flags in 'trex'
+    let trex = find_boxes(moov, b"trex")[0];
+    assert_eq!(u32_at(trex, 4), 1);
+    assert_ne!(u32_at(trex, 12), 0);
+
+    // every fragment of the one track starts with a version 1 'tfdt'
+    let moofs: Vec<_> = boxes
+        .iter()
+        .filter(|b| &b.box_type == b"moof")
+        .map(|b| payload(mp4, b))
+        .collect();
+    assert!(!moofs.is_empty());
+    let mut decode_time = 0;
+    for moof in moofs {
+        let trafs = find_boxes(moof, b"traf");
+        assert_eq!(trafs.len(), 1);
+        let tfdt = find_boxes(trafs[0], b"tfdt")[0];
+        assert_eq!(tfdt[0], 1);
+        let time = u64_at(tfdt, 4);
+        assert!(time >= decode_time);
+        decode_time = time;
+    }
+}
+
+#[test]
+fn track_file_per_input() {
+    let pictures = open_gop_pictures(NAL_IDR_W_RADL, 0, 8);
+    let hevc = temp_file("cmaf.265");
+    fs::write(&hevc, hevc_stream(&pictures)).unwrap();
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file("cmaf.mp4");
+
+    let res = run(&[
+        "-i".as_ref(),
+        hevc.as_os_str(),
+        "-i".as_ref(),
+        ec3.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "--output-format".as_ref(),
+        "cmaf".as_ref(),
+        "--mpeg4-max-frag-duration".as_ref(),
+        "320".as_ref(),
+        "--overwrite".as_ref(),
+    ]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    let video = fs::read(temp_file("cmaf_1.mp4")).unwrap();
+    let audio = fs::read(temp_file("cmaf_2.mp4")).unwrap();
+    let _ = fs::remove_file(&hevc);
+    let _ = fs::remove_file(temp_file("cmaf_1.mp4"));
+    let _ = fs::remove_file(temp_file("cmaf_2.mp4"));
+    assert!(!output.exists());
+
+    check_track_file(&video);
+    check_track_file(&audio);
+    assert_eq!(&find_boxes(&video, b"hdlr")[0][8..12], b"vide");
+    assert_eq!(&find_boxes(&audio, b"hdlr")[0][8..12], b"soun");
+
+    // the composition offsets of the reordered pictures are not compensated by an edit list
+    assert!(find_boxes(&video, b"edts").is_empty());
+    assert!(find_boxes(&video, b"moof").len() > 1);
+}
+
+#[test]
+fn single_input_keeps_output_name() {
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file("cmaf_single.mp4");
+
+    let res = run(&[
+        "-i".as_ref(),
+        ec3.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "--output-format".as_ref(),
+        "cmaf".as_ref(),
+        "--overwrite".as_ref(),
+    ]);
+    assert!(res.status.success());
+
+    let audio = fs::read(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    check_track_file(&audio);
+}
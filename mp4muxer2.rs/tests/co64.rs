@@ -0,0 +1,150 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+mod common;
+use common::{mux, read_boxes, signal, temp_file, Mp4Box};
+
+/* returns the type and the entries of the chunk offset box of the first track */
+fn chunk_offsets(moov: &Mp4Box) -> (String, Vec<u64>) {
+    let stbl = moov
+        .descendant(&[b"trak", b"mdia", b"minf", b"stbl"])
+        .expect("no 'stbl' box");
+    for tag in [b"stco", b"co64"] {
+        if let Some(chunk_offsets) = stbl.child(tag) {
+            let offsets = chunk_offsets
+                .field("chunk_offsets")
+                .unwrap()
+                .as_list()
+                .iter()
+                .map(|offset| offset.as_u64().unwrap())
+                .collect();
+            return (String::from_utf8_lossy(tag).to_string(), offsets);
+        }
+    }
+    panic!("no chunk offset box");
+}
+
+fn moov(boxes: &[Mp4Box]) -> &Mp4Box {
+    boxes.iter().find(|b| &b.box_type == b"moov").unwrap()
+}
+
+fn chunk_offset_box(output: &Path) -> String {
+    chunk_offsets(moov(&read_boxes(output).unwrap())).0
+}
+
+#[test]
+fn co64_mode_is_honoured() {
+    let input = signal("7ch_ddp_25fps_channel_id.ec3");
+
+    for (mode, expected) in [("auto", "stco"), ("always", "co64"), ("never", "stco")] {
+        let output = temp_file(&format!("co64_{}.mp4", mode));
+
+        let res = mux(&input, &output, &["--co64", mode]);
+        assert!(res.status.success());
+
+        let tag = chunk_offset_box(&output);
+        let _ = fs::remove_file(&output);
+        assert_eq!(tag, expected, "--co64 {}", mode);
+    }
+}
+
+extern "C" {
+    fn mp4_muxer_chunk_offsets_exceed_32bit(
+        mdat_pos: i64,
+        mdat_size: u64,
+        last_chunk_size: u64,
+    ) -> i32;
+}
+
+fn chunk_offsets_exceed_32bit(mdat_pos: i64, mdat_size: u64, last_chunk_size: u64) -> bool {
+    unsafe { mp4_muxer_chunk_offsets_exceed_32bit(mdat_pos, mdat_size, last_chunk_size) != 0 }
+}
+
+#[test]
+fn co64_needed_from_the_offset_of_the_last_chunk() {
+    const MAX: u64 = u32::MAX as u64;
+
+    // the last chunk starts at 2^32 - 1 or 2^32 behind an 8 byte 'mdat' header
+    let mdat_pos = 1 << 31;
+    let mdat_size = 1 << 31;
+    assert!(!chunk_offsets_exceed_32bit(mdat_pos, mdat_size, 9));
+    assert!(chunk_offsets_exceed_32bit(mdat_pos, mdat_size, 8));
+
+    // the same behind the 16 byte 'largesize' header, only the chunk offset counts
+    let mdat_pos = 1000;
+    let last_chunk_size = 1 << 20;
+    let mdat_size = MAX - 1000 - 16 + last_chunk_size;
+    assert!(!chunk_offsets_exceed_32bit(
+        mdat_pos,
+        mdat_size,
+        last_chunk_size
+    ));
+    assert!(chunk_offsets_exceed_32bit(
+        mdat_pos,
+        mdat_size + 1,
+        last_chunk_size
+    ));
+
+    // a 'largesize' 'mdat' whose last chunk still starts below 2^32 keeps 'stco'
+    assert!(!chunk_offsets_exceed_32bit(0, 2 * MAX, MAX + 16));
+    assert!(chunk_offsets_exceed_32bit(0, 2 * MAX, MAX + 15));
+}
+
+#[test]
+#[ignore = "synthesizes a 4.3 GB stream and writes an output file of the same size"]
+fn co64_and_largesize_mdat_beyond_4gib() {
+    const FRAME_SIZE: usize = 2560; // AC-3 at 640 kbit/s and 48 kHz
+    const FRAME_COUNT: usize = 1_700_000; // ~4.35 GB, well beyond 4 GiB
+
+    // the bit stream info of a real frame followed by silence-like zero payload,
+    // the parser only looks at the header to delimit frames
+    let mut header = [0u8; 32];
+    File::open(signal("5ch_dd_25fps_channel_id.ac3"))
+        .unwrap()
+        .read_exact(&mut header)
+        .unwrap();
+    header[4] = (header[4] & 0xC0) | 37; // frmsizecod: 640 kbit/s
+
+    let mut frame = vec![0u8; FRAME_SIZE];
+    frame[..header.len()].copy_from_slice(&header);
+
+    let input = temp_file("large.ac3");
+    {
+        let mut writer = BufWriter::with_capacity(1 << 20, File::create(&input).unwrap());
+        for _ in 0..FRAME_COUNT {
+            writer.write_all(&frame).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    let output = temp_file("large.mp4");
+
+    // 32-bit chunk offsets can't address the media data
+    let res = mux(&input, &output, &["--co64", "never"]);
+    assert!(!res.status.success());
+
+    let res = mux(&input, &output, &["--co64", "auto"]);
+    let _ = fs::remove_file(&input);
+    assert!(res.status.success());
+
+    let len = fs::metadata(&output).unwrap().len();
+    let boxes = read_boxes(&output).unwrap();
+    let mdat = boxes.iter().find(|b| &b.box_type == b"mdat").unwrap();
+
+    // 'mdat' uses the 64-bit largesize header and spans to the end of the file
+    assert_eq!(mdat.header_size, 16);
+    assert_eq!(mdat.size, (FRAME_SIZE * FRAME_COUNT) as u64 + 16);
+    assert_eq!(mdat.offset + mdat.size, len);
+
+    let (tag, offsets) = chunk_offsets(moov(&boxes));
+    let _ = fs::remove_file(&output);
+
+    assert_eq!(tag, "co64");
+    assert_eq!(offsets[0], mdat.offset + mdat.header_size);
+    assert!(*offsets.last().unwrap() > u32::MAX as u64);
+    assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+    assert!(offsets
+        .iter()
+        .all(|&o| o >= mdat.offset + mdat.header_size && o < len));
+}
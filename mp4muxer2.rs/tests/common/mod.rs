@@ -0,0 +1,396 @@
+/* helpers shared by the integration tests, not every test uses all of them */
+#![allow(dead_code, unused_imports)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+#[path = "../../src/mp4_boxes.rs"]
+mod mp4_boxes;
+
+use mp4_boxes::read_boxes_from;
+pub use mp4_boxes::{read_boxes, Mp4Box, Value};
+
+pub const NAL_VPS: u8 = 32;
+pub const NAL_SPS: u8 = 33;
+pub const NAL_PPS: u8 = 34;
+pub const NAL_TRAIL_N: u8 = 0;
+pub const NAL_TRAIL_R: u8 = 1;
+pub const NAL_RASL_N: u8 = 8;
+pub const NAL_IDR_W_RADL: u8 = 19;
+pub const NAL_CRA: u8 = 21;
+
+/* a signal of the test directory of the repository */
+pub fn signal(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../test/signals")
+        .join(name)
+}
+
+pub fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mp4muxer2_{}_{}", std::process::id(), name))
+}
+
+pub fn run<S: AsRef<OsStr>>(args: &[S]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mp4muxer2"))
+        .args(args)
+        .output()
+        .expect("failed to run mp4muxer2")
+}
+
+/* runs mp4muxer2 in dir, for relative output paths */
+pub fn run_in<S: AsRef<OsStr>>(dir: &Path, args: &[S]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mp4muxer2"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("failed to run mp4muxer2")
+}
+
+/* muxes a single input, the extra arguments follow it so that per input options apply to it */
+pub fn mux(input: &Path, output: &Path, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mp4muxer2"))
+        .arg("-i")
+        .arg(input)
+        .args(extra)
+        .arg("-o")
+        .arg(output)
+        .arg("--overwrite")
+        .output()
+        .expect("failed to run mp4muxer2")
+}
+
+/* muxes the inputs given with their options as arguments */
+pub fn mux_inputs(output: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mp4muxer2"))
+        .args(args)
+        .arg("-o")
+        .arg(output)
+        .arg("--overwrite")
+        .output()
+        .expect("failed to run mp4muxer2")
+}
+
+/* muxes the elementary stream data, returns the output file data, empty on failure */
+pub fn mux_data(name: &str, data: &[u8], extra: &[&str]) -> (Output, Vec<u8>) {
+    let input = temp_file(name);
+    let output = temp_file(&format!("{}.mp4", name));
+    fs::write(&input, data).unwrap();
+
+    let res = mux(&input, &output, extra);
+    let mp4 = fs::read(&output).unwrap_or_default();
+    let _ = fs::remove_file(&input);
+    let _ = fs::remove_file(&output);
+    (res, mp4)
+}
+
+pub fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+pub fn u64_at(data: &[u8], pos: usize) -> u64 {
+    u64::from_be_bytes(data[pos..pos + 8].try_into().unwrap())
+}
+
+pub fn u32s(data: &[u8]) -> Vec<u32> {
+    data.chunks(4)
+        .map(|c| u32::from_be_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+/* codingname, channelcount, samplesize and samplerate of the first AudioSampleEntry */
+pub fn audio_entry(mp4: &[u8]) -> (&[u8], u16, u16, u32) {
+    let stsd = find_boxes(mp4, b"stsd")[0];
+    (
+        &stsd[12..16],
+        u16::from_be_bytes(stsd[32..34].try_into().unwrap()),
+        u16::from_be_bytes(stsd[34..36].try_into().unwrap()),
+        u32_at(stsd, 40) >> 16,
+    )
+}
+
+/* the box tree of file data */
+pub fn box_tree(data: &[u8]) -> Vec<Mp4Box> {
+    read_boxes_from(&mut Cursor::new(data)).expect("invalid box structure")
+}
+
+/* the payload of a box of the file data */
+pub fn payload<'a>(data: &'a [u8], mp4_box: &Mp4Box) -> &'a [u8] {
+    &data[(mp4_box.offset + mp4_box.header_size) as usize..(mp4_box.offset + mp4_box.size) as usize]
+}
+
+/* the (start, end) of the payloads of the boxes of a type in the box tree of the file data */
+fn find_payload_ranges(data: &[u8], box_type: &[u8; 4]) -> Vec<(usize, usize)> {
+    fn collect(boxes: &[Mp4Box], box_type: &[u8; 4], found: &mut Vec<(usize, usize)>) {
+        for b in boxes {
+            if &b.box_type == box_type {
+                found.push((
+                    (b.offset + b.header_size) as usize,
+                    (b.offset + b.size) as usize,
+                ));
+            }
+            collect(&b.children, box_type, found);
+        }
+    }
+
+    let mut found = vec![];
+    collect(&box_tree(data), box_type, &mut found);
+    found
+}
+
+/* the payloads of the boxes of a type in the box tree of the file data, in file order */
+pub fn find_boxes<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Vec<&'a [u8]> {
+    find_payload_ranges(data, box_type)
+        .into_iter()
+        .map(|(start, end)| &data[start..end])
+        .collect()
+}
+
+/* the offsets of the payloads of the boxes of a type in the file data, in file order */
+pub fn find_payload_offsets(data: &[u8], box_type: &[u8; 4]) -> Vec<usize> {
+    find_payload_ranges(data, box_type)
+        .into_iter()
+        .map(|(start, _)| start)
+        .collect()
+}
+
+#[derive(Default)]
+pub struct BitWriter {
+    pub data: Vec<u8>,
+    pub bits: usize,
+}
+
+impl BitWriter {
+    pub fn put(&mut self, bit_num: usize, value: u64) {
+        for i in (0..bit_num).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.data.last_mut().unwrap() |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+
+    pub fn put_ue(&mut self, value: u32) {
+        let len = 32 - (value + 1).leading_zeros() as usize;
+        self.put(len - 1, 0);
+        self.put(len, value as u64 + 1);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.put(8, b as u64);
+        }
+    }
+
+    /* rbsp_trailing_bits() */
+    pub fn finish(mut self) -> Vec<u8> {
+        self.put(1, 1);
+        while !self.bits.is_multiple_of(8) {
+            self.put(1, 0);
+        }
+        self.data
+    }
+}
+
+/* HEVC NAL unit header with nuh_layer_id 0 and TemporalId 0, emulation prevention applied */
+pub fn nal(nal_type: u8, rbsp: &[u8]) -> Vec<u8> {
+    let mut nal = vec![nal_type << 1, 1];
+    let mut zeros = 0;
+    for &b in rbsp {
+        if zeros == 2 && b <= 3 {
+            nal.push(3);
+            zeros = 0;
+        }
+        nal.push(b);
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+    }
+    nal
+}
+
+/* Main 10 profile, progressive frames, level 4.1 */
+fn profile_tier_level(w: &mut BitWriter) {
+    w.put(8, 2); // general_profile_space, general_tier_flag, general_profile_idc
+    w.put(32, 1 << 29); // general_profile_compatibility_flag[2]
+    w.put(4, 0b1001); // progressive source, frame only constraint
+    w.put(32, 0);
+    w.put(12, 0);
+    w.put(8, 123); // general_level_idc
+}
+
+pub fn vps() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(4, 0); // vps_video_parameter_set_id
+    w.put(2, 3); // vps_base_layer_internal_flag, vps_base_layer_available_flag
+    w.put(6, 0); // vps_max_layers_minus1
+    w.put(3, 0); // vps_max_sub_layers_minus1
+    w.put(1, 1); // vps_temporal_id_nesting_flag
+    w.put(16, 0xFFFF);
+    profile_tier_level(&mut w);
+    w.put(1, 1); // vps_sub_layer_ordering_info_present_flag
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put(6, 0); // vps_max_layer_id
+    w.put_ue(0); // vps_num_layer_sets_minus1
+    w.put(1, 0); // vps_timing_info_present_flag
+    w.put(1, 0); // vps_extension_flag
+    nal(NAL_VPS, &w.finish())
+}
+
+/* the colour description of the VUI */
+pub struct Colour {
+    pub full_range: bool,
+    pub primaries: u8,
+    pub transfer: u8,
+    pub matrix: u8,
+}
+
+/* the properties of the sequence parameter set */
+pub struct Sps {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u32,
+    pub colour: Option<Colour>,
+}
+
+impl Default for Sps {
+    /* 64x64 4:2:0 10 bit without colour description */
+    fn default() -> Self {
+        Sps {
+            width: 64,
+            height: 64,
+            bit_depth: 10,
+            colour: None,
+        }
+    }
+}
+
+/* 4:2:0 at 25 fps */
+pub fn sps(sps: &Sps) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(4, 0); // sps_video_parameter_set_id
+    w.put(3, 0); // sps_max_sub_layers_minus1
+    w.put(1, 1); // sps_temporal_id_nesting_flag
+    profile_tier_level(&mut w);
+    w.put_ue(0); // sps_seq_parameter_set_id
+    w.put_ue(1); // chroma_format_idc
+    w.put_ue(sps.width);
+    w.put_ue(sps.height);
+    w.put(1, 0); // conformance_window_flag
+    w.put_ue(sps.bit_depth - 8); // bit_depth_luma_minus8
+    w.put_ue(sps.bit_depth - 8); // bit_depth_chroma_minus8
+    w.put_ue(4); // log2_max_pic_order_cnt_lsb_minus4
+    w.put(1, 1); // sps_sub_layer_ordering_info_present_flag
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put_ue(0); // log2_min_luma_coding_block_size_minus3
+    w.put_ue(3); // log2_diff_max_min_luma_coding_block_size
+    w.put_ue(0); // log2_min_luma_transform_block_size_minus2
+    w.put_ue(3); // log2_diff_max_min_luma_transform_block_size
+    w.put_ue(0); // max_transform_hierarchy_depth_inter
+    w.put_ue(0); // max_transform_hierarchy_depth_intra
+    w.put(4, 0); // scaling list, amp, sao, pcm
+    w.put_ue(0); // num_short_term_ref_pic_sets
+    w.put(1, 0); // long_term_ref_pics_present_flag
+    w.put(2, 0); // sps_temporal_mvp_enabled_flag, strong_intra_smoothing_enabled_flag
+    w.put(1, 1); // vui_parameters_present_flag
+    w.put(2, 0); // aspect_ratio_info_present_flag, overscan_info_present_flag
+    match &sps.colour {
+        Some(colour) => {
+            w.put(1, 1); // video_signal_type_present_flag
+            w.put(3, 5); // video_format
+            w.put(1, colour.full_range as u64); // video_full_range_flag
+            w.put(1, 1); // colour_description_present_flag
+            w.put(8, colour.primaries as u64); // colour_primaries
+            w.put(8, colour.transfer as u64); // transfer_characteristics
+            w.put(8, colour.matrix as u64); // matrix_coeffs
+        }
+        None => w.put(1, 0), // video_signal_type_present_flag
+    }
+    w.put(5, 0); // chroma loc, neutral chroma, field seq, frame field info, display window
+    w.put(1, 1); // vui_timing_info_present_flag
+    w.put(32, 1);
+    w.put(32, 25);
+    w.put(2, 0); // vui_poc_proportional_to_timing_flag, vui_hrd_parameters_present_flag
+    w.put(1, 0); // bitstream_restriction_flag
+    w.put(1, 0); // sps_extension_present_flag
+    nal(NAL_SPS, &w.finish())
+}
+
+pub fn pps() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put_ue(0); // pps_pic_parameter_set_id
+    w.put_ue(0); // pps_seq_parameter_set_id
+    w.put(7, 0);
+    w.put_ue(0); // num_ref_idx_l0_default_active_minus1
+    w.put_ue(0); // num_ref_idx_l1_default_active_minus1
+    w.put_ue(0); // init_qp_minus26
+    w.put(3, 0);
+    w.put_ue(0); // pps_cb_qp_offset
+    w.put_ue(0); // pps_cr_qp_offset
+    w.put(10, 0); // slice chroma qp offsets up to lists_modification_present_flag
+    w.put_ue(0); // log2_parallel_merge_level_minus2
+    w.put(2, 0); // slice_segment_header_extension_present_flag, pps_extension_present_flag
+    nal(NAL_PPS, &w.finish())
+}
+
+/* I slice with an empty short-term reference picture set, POC lsb of 8 bits */
+pub fn slice(nal_type: u8, poc: u32) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(1, 1); // first_slice_segment_in_pic_flag
+    if (16..=23).contains(&nal_type) {
+        w.put(1, 0); // no_output_of_prior_pics_flag
+    }
+    w.put_ue(0); // slice_pic_parameter_set_id
+    w.put_ue(2); // slice_type I
+    if nal_type != NAL_IDR_W_RADL {
+        w.put(8, poc as u64 & 0xFF); // slice_pic_order_cnt_lsb
+        w.put(1, 0); // short_term_ref_pic_set_sps_flag
+        w.put_ue(0); // num_negative_pics
+        w.put_ue(0); // num_positive_pics
+    }
+    w.put_ue(0); // slice_qp_delta
+    let mut rbsp = w.finish();
+    rbsp.extend([0x5A; 16]);
+    nal(nal_type, &rbsp)
+}
+
+/* an IDR picture of a single I slice */
+pub fn idr_slice() -> Vec<u8> {
+    slice(NAL_IDR_W_RADL, 0)
+}
+
+/* Mini-GOPs of an anchor picture followed by the 3 pictures before it in output order,
+every other anchor is a CRA picture with RASL pictures, the POC starts at first_poc */
+pub fn open_gop_pictures(first: u8, first_poc: u32, anchors: u32) -> Vec<(u8, u32)> {
+    let mut pictures = vec![(first, first_poc)];
+    for k in 1..=anchors {
+        let poc = first_poc + 4 * k;
+        let (anchor, leading) = if k % 2 == 0 {
+            (NAL_CRA, NAL_RASL_N)
+        } else {
+            (NAL_TRAIL_R, NAL_TRAIL_N)
+        };
+        pictures.push((anchor, poc));
+        pictures.extend((poc - 3..poc).map(|poc| (leading, poc)));
+    }
+    pictures
+}
+
+/* an Annex B stream of the parameter sets and a slice per (type, POC) picture */
+pub fn hevc_stream(pictures: &[(u8, u32)]) -> Vec<u8> {
+    let mut data = vec![];
+    for nal in [vps(), sps(&Sps::default()), pps()]
+        .into_iter()
+        .chain(pictures.iter().map(|&(t, poc)| slice(t, poc)))
+    {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal);
+    }
+    data
+}
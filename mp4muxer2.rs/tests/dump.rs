@@ -0,0 +1,127 @@
+use std::fs;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+mod common;
+use common::{run, signal, temp_file};
+
+/* muxes the EC-3 signal and returns what the dump of the output prints */
+fn mux_and_dump(name: &str, mux_args: &[&str], dump_args: &[&str]) -> String {
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file(name);
+    let output_str = output.to_str().unwrap();
+
+    let mut args = vec!["-i", ec3.to_str().unwrap(), "-o", output_str, "--overwrite"];
+    args.extend(mux_args);
+    assert!(run(&args).status.success());
+
+    let mut args = vec!["dump", output_str];
+    args.extend(dump_args);
+    let res = run(&args);
+    let _ = fs::remove_file(&output);
+    assert!(res.status.success());
+
+    String::from_utf8(res.stdout).unwrap()
+}
+
+#[test]
+fn dump_prints_box_tree_with_fields() {
+    let text = mux_and_dump("dump.mp4", &[], &[]);
+
+    assert!(text.starts_with("ftyp @ 0, 28 bytes\n  major_brand: \"mp42\"\n"));
+    assert!(text.contains("\n  mvhd @ 36, 108 bytes, version 0, flags 0x000000\n"));
+    assert!(text.contains("\n        handler_type: \"soun\"\n"));
+    assert!(text.contains("\n              dec3 @ "));
+    assert!(text
+        .contains("[0] fscod=0 bsid=16 asvc=0 bsmod=0 acmod=7 lfeon=1 num_dep_sub=1 chan_loc=2\n"));
+    assert!(text.contains("\n            sample_count: 634\n"));
+
+    // the depth limit stops at the 'trak' level
+    let text = mux_and_dump("dump_depth.mp4", &[], &["--depth", "2"]);
+    assert!(text.contains("\n  trak @ "));
+    assert!(text.contains("(2 child boxes not shown)"));
+    assert!(!text.contains("tkhd"));
+}
+
+#[test]
+fn dump_prints_fragments_as_json() {
+    let json = mux_and_dump(
+        "dump_frag.mp4",
+        &["--output-format", "frag-mp4"],
+        &["--json"],
+    );
+
+    assert!(json.starts_with("{\"file\":"));
+    assert!(json.contains("{\"type\":\"mfhd\",\"offset\":"));
+    assert!(json.contains(",\"fields\":{\"sequence_number\":2}}"));
+    assert!(json.contains("\"type\":\"trun\""));
+    assert!(json.contains("\"fields\":{\"sample_count\":62,\"data_offset\":112,"));
+    assert!(json.contains("\"starts_with_SAP\":1,\"SAP_type\":1"));
+    assert_eq!(
+        json.matches("\"type\":\"moof\"").count(),
+        json.matches("\"type\":\"mdat\"").count()
+    );
+
+    let json = mux_and_dump(
+        "dump_frag_depth.mp4",
+        &["--output-format", "frag-mp4"],
+        &["--json", "--depth", "1"],
+    );
+    assert!(json.contains("\"type\":\"moof\""));
+    assert!(!json.contains("\"type\":\"traf\""));
+}
+
+#[test]
+fn dump_rejects_invalid_files() {
+    let input = temp_file("dump_invalid.mp4");
+    fs::write(&input, [0, 0, 0, 64, b'f', b't', b'y', b'p', 0, 0]).unwrap();
+
+    let res = run(&["dump", input.to_str().unwrap()]);
+    let _ = fs::remove_file(&input);
+
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Invalid box header at offset 0"));
+}
+
+#[test]
+fn dump_stops_quietly_at_a_closed_pipe() {
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file("dump_pipe.mp4");
+    let output_str = output.to_str().unwrap();
+    // short fragments for a dump larger than the pipe buffer
+    assert!(run(&[
+        "-i",
+        ec3.to_str().unwrap(),
+        "-o",
+        output_str,
+        "--output-format",
+        "frag-mp4",
+        "--mpeg4-max-frag-duration",
+        "100",
+        "--overwrite"
+    ])
+    .status
+    .success());
+
+    // like `dump | head -c 4`, the reader goes away after the first bytes
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mp4muxer2"))
+        .args(["dump", output_str])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run mp4muxer2");
+    let mut stdout = child.stdout.take().unwrap();
+    let mut first = [0; 4];
+    stdout.read_exact(&mut first).unwrap();
+    drop(stdout);
+    let res = child.wait_with_output().unwrap();
+    let _ = fs::remove_file(&output);
+
+    assert_eq!(&first, b"ftyp");
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    assert!(res.stderr.is_empty());
+}
@@ -0,0 +1,356 @@
+use std::process::Output;
+
+mod common;
+use common::{find_boxes, idr_slice, mux_data, nal, pps, sps, vps, BitWriter, Colour, Sps};
+
+const NAL_DV_RPU: u8 = 62;
+const NAL_DV_EL: u8 = 63;
+
+const TRANSFER_BT709: u8 = 1;
+const TRANSFER_BT2020_10: u8 = 14;
+const TRANSFER_PQ: u8 = 16;
+const TRANSFER_HLG: u8 = 18;
+
+const RPU_NAL_PREFIX: u8 = 25;
+
+fn stderr(res: &Output) -> String {
+    String::from_utf8_lossy(&res.stderr).into_owned()
+}
+
+/* the properties of a stream the Dolby Vision options are checked against */
+struct Stream {
+    width: u32,
+    height: u32,
+    bit_depth: u32,
+    transfer: u8,
+    rpu: bool,
+    vdr_rpu_profile: u32,
+    vdr_bit_depth: u32,
+    el: bool,
+}
+
+impl Default for Stream {
+    /* 64x64 10 bit PQ with the RPU of a backward compatible profile at 25 fps */
+    fn default() -> Self {
+        Stream {
+            width: 64,
+            height: 64,
+            bit_depth: 10,
+            transfer: TRANSFER_PQ,
+            rpu: true,
+            vdr_rpu_profile: 1,
+            vdr_bit_depth: 12,
+            el: false,
+        }
+    }
+}
+
+/* an RPU NAL unit with rpu_data_header() up to the bit depths */
+fn rpu(stream: &Stream) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(8, RPU_NAL_PREFIX as u64);
+    w.put(6, 2); // rpu_type
+    w.put(11, 18); // rpu_format
+    w.put(4, stream.vdr_rpu_profile as u64);
+    w.put(4, 0); // vdr_rpu_level
+    w.put(1, 1); // vdr_seq_info_present_flag
+    w.put(1, 0); // chroma_resampling_explicit_filter_flag
+    w.put(2, 0); // coefficient_data_type
+    w.put_ue(23); // coefficient_log2_denom
+    w.put(2, 1); // vdr_rpu_normalized_idc
+    w.put(1, (stream.vdr_rpu_profile == 0) as u64); // bl_video_full_range_flag
+    w.put_ue(2); // bl_bit_depth_minus8
+    w.put_ue(2); // el_bit_depth_minus8
+    w.put_ue(stream.vdr_bit_depth - 8);
+    w.put(6, 0); // up to disable_residual_flag
+    nal(NAL_DV_RPU, &w.finish())
+}
+
+/* 5 IDR pictures, each followed by the EL and RPU NAL units the stream has */
+fn hevc_stream(stream: &Stream) -> Vec<u8> {
+    // 4:2:0 BT.2020 at 25 fps
+    let sps = sps(&Sps {
+        width: stream.width,
+        height: stream.height,
+        bit_depth: stream.bit_depth,
+        colour: Some(Colour {
+            full_range: false,
+            primaries: 9,
+            transfer: stream.transfer,
+            matrix: 9,
+        }),
+    });
+    let mut nals = vec![vps(), sps, pps()];
+    for _ in 0..5 {
+        nals.push(idr_slice());
+        if stream.el {
+            nals.push(vec![NAL_DV_EL << 1, 1, 0x28, 0x01, 0xAF, 0x1D]);
+        }
+        if stream.rpu {
+            nals.push(rpu(stream));
+        }
+    }
+
+    let mut data = vec![];
+    for nal in nals {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal);
+    }
+    data
+}
+
+#[test]
+fn compatible_id_checked_against_transfer() {
+    let data = hevc_stream(&Stream::default());
+
+    let (res, _) = mux_data(
+        "dv_hlg_on_pq.265",
+        &data,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "4"],
+    );
+    assert!(!res.status.success());
+    let err = stderr(&res);
+    assert!(err.contains("signals PQ transfer"), "{}", err);
+    assert!(err.contains("use '--dv-bl-compatible-id 1'"), "{}", err);
+
+    let (res, mp4) = mux_data(
+        "dv_hdr10.265",
+        &data,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "1"],
+    );
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert_eq!(find_boxes(&mp4, b"dvvC").len(), 1);
+
+    let sdr = hevc_stream(&Stream {
+        transfer: TRANSFER_BT709,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_hdr10_on_sdr.265",
+        &sdr,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "1"],
+    );
+    let err = stderr(&res);
+    assert!(err.contains("signals BT.709 transfer"), "{}", err);
+    assert!(err.contains("use '--dv-bl-compatible-id 2'"), "{}", err);
+
+    for (profile, id) in [("5", "1"), ("7", "1"), ("4", "1")] {
+        let (res, _) = mux_data(
+            "dv_compatible_id.265",
+            &data,
+            &["--dv-profile", profile, "--dv-bl-compatible-id", id],
+        );
+        let err = stderr(&res);
+        assert!(err.contains("'--dv-bl-compatible-id 1'"), "{}", err);
+    }
+}
+
+#[test]
+fn layers_checked_against_profile() {
+    let single_layer = hevc_stream(&Stream::default());
+    let (res, _) = mux_data("dv_no_el.265", &single_layer, &["--dv-profile", "7"]);
+    let err = stderr(&res);
+    assert!(err.contains("profile 7 is dual layer"), "{}", err);
+
+    let dual_layer = hevc_stream(&Stream {
+        el: true,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_el.265",
+        &dual_layer,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "1"],
+    );
+    let err = stderr(&res);
+    assert!(err.contains("profile 8 is single layer"), "{}", err);
+
+    let no_rpu = hevc_stream(&Stream {
+        rpu: false,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_no_rpu.265",
+        &no_rpu,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "1"],
+    );
+    let err = stderr(&res);
+    assert!(err.contains("no Dolby Vision RPU"), "{}", err);
+
+    let (res, _) = mux_data("dv_profile_10.265", &single_layer, &["--dv-profile", "10"]);
+    let err = stderr(&res);
+    assert!(err.contains("has an AV1 base layer"), "{}", err);
+}
+
+#[test]
+fn sample_entry_and_stream_properties_checked() {
+    let data = hevc_stream(&Stream {
+        el: true,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_dvh1.265",
+        &data,
+        &["--dv-profile", "7", "--dvh1flag", "0"],
+    );
+    let err = stderr(&res);
+    assert!(
+        err.contains("'--dvh1flag' is for the non backward compatible profile 5"),
+        "{}",
+        err
+    );
+
+    let data = hevc_stream(&Stream {
+        bit_depth: 8,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_8bit.265",
+        &data,
+        &["--dv-profile", "8", "--dv-bl-compatible-id", "1"],
+    );
+    let err = stderr(&res);
+    assert!(err.contains("is 8 bit"), "{}", err);
+
+    // the pixel rate of UHD at 5 fps is level 3, which is for pictures up to 1920 wide
+    let data = hevc_stream(&Stream {
+        width: 3840,
+        height: 2160,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_level.265",
+        &data,
+        &[
+            "-f",
+            "5",
+            "--force-framerate",
+            "--dv-profile",
+            "8",
+            "--dv-bl-compatible-id",
+            "1",
+        ],
+    );
+    let err = stderr(&res);
+    assert!(err.contains("is Dolby Vision level 3"), "{}", err);
+}
+
+#[test]
+fn profile_detected_from_rpu() {
+    for (transfer, detected) in [
+        (TRANSFER_PQ, "profile 8.1"),
+        (TRANSFER_BT709, "profile 8.2"),
+        (TRANSFER_HLG, "profile 8.4"),
+    ] {
+        let data = hevc_stream(&Stream {
+            transfer,
+            ..Stream::default()
+        });
+        let (res, mp4) = mux_data("dv_auto.265", &data, &[]);
+        assert!(res.status.success(), "{}", stderr(&res));
+        let stdout = String::from_utf8_lossy(&res.stdout);
+        assert!(stdout.contains(detected), "{}", stdout);
+        assert!(stdout.contains("from the RPU header"), "{}", stdout);
+
+        // dv_version_major, profile 8, base layer compatible ID
+        let dvvc = find_boxes(&mp4, b"dvvC")[0];
+        assert_eq!(dvvc[0], 1);
+        assert_eq!(dvvc[2] >> 1, 8);
+        assert_eq!(dvvc[4] >> 4, detected.as_bytes()[10] - b'0');
+    }
+
+    let data = hevc_stream(&Stream {
+        vdr_rpu_profile: 0,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data("dv_auto_5.265", &data, &["--dv-profile", "auto"]);
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert!(String::from_utf8_lossy(&res.stdout).contains("profile 5 detected"));
+
+    // every extension the muxer takes as HEVC
+    let (res, _) = mux_data("dv_auto_5.hvc", &data, &["--dv-profile", "auto"]);
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert!(String::from_utf8_lossy(&res.stdout).contains("profile 5 detected"));
+
+    // the 12 bit VDR of a dual layer stream is profile 7
+    let data = hevc_stream(&Stream {
+        el: true,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data("dv_auto_7.265", &data, &[]);
+    assert!(String::from_utf8_lossy(&res.stdout).contains("profile 7 detected"));
+
+    // an explicit compatible ID is kept, HLG is signalled as BT.2020 here
+    let data = hevc_stream(&Stream {
+        transfer: TRANSFER_BT2020_10,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data(
+        "dv_auto_hlg.265",
+        &data,
+        &["--dv-profile", "auto", "--dv-bl-compatible-id", "4"],
+    );
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert!(String::from_utf8_lossy(&res.stdout).contains("profile 8.4"));
+
+    let no_rpu = hevc_stream(&Stream {
+        rpu: false,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data("dv_auto_no_rpu.265", &no_rpu, &["--dv-profile", "auto"]);
+    let err = stderr(&res);
+    assert!(err.contains("none has RPU"), "{}", err);
+}
+
+#[test]
+fn layers_dropped_by_dv_mode() {
+    let rpu_header = [NAL_DV_RPU << 1, 1, RPU_NAL_PREFIX];
+    let el_header = [NAL_DV_EL << 1, 1];
+    let contains = |mp4: &[u8], bytes: &[u8]| mp4.windows(bytes.len()).any(|w| w == bytes);
+
+    let data = hevc_stream(&Stream::default());
+    let (res, kept) = mux_data("dv_keep.265", &data, &[]);
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert!(contains(&kept, &rpu_header));
+
+    let (res, stripped) = mux_data("dv_strip.265", &data, &["--dv-mode", "strip"]);
+    assert!(res.status.success(), "{}", stderr(&res));
+    assert!(String::from_utf8_lossy(&res.stdout).contains("muxed as its base layer"));
+    assert!(!contains(&stripped, &rpu_header));
+    assert!(!contains(&stripped, b"dvvC"));
+    assert!(!contains(&stripped, b"dvh1") && !contains(&stripped, b"dvhe"));
+    // 5 RPU NAL units with their length field and the 'dvvC' box
+    assert_eq!(
+        kept.len() - stripped.len(),
+        5 * (4 + rpu(&Stream::default()).len()) + 32
+    );
+
+    // profile 7 becomes 8.1
+    let data = hevc_stream(&Stream {
+        el: true,
+        ..Stream::default()
+    });
+    let (res, mp4) = mux_data("dv_drop_el.265", &data, &["--dv-mode", "drop-el"]);
+    assert!(res.status.success(), "{}", stderr(&res));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(
+        stdout.contains("profile 7 becomes profile 8.1"),
+        "{}",
+        stdout
+    );
+    assert!(contains(&mp4, &rpu_header));
+    assert!(!contains(&mp4, &el_header));
+    assert!(!contains(&mp4, b"hvcE"));
+    let dvvc = find_boxes(&mp4, b"dvvC")[0];
+    assert_eq!(dvvc[2] >> 1, 8);
+    // rpu_present_flag, el_present_flag, bl_present_flag
+    assert_eq!(dvvc[3] & 7, 5);
+    assert_eq!(dvvc[4] >> 4, 1);
+
+    let data = hevc_stream(&Stream {
+        vdr_rpu_profile: 0,
+        ..Stream::default()
+    });
+    let (res, _) = mux_data("dv_strip_5.265", &data, &["--dv-mode", "strip"]);
+    let err = stderr(&res);
+    assert!(err.contains("profile 5 has no compatibility"), "{}", err);
+}
@@ -0,0 +1,250 @@
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+mod common;
+use common::{
+    box_tree, find_boxes, hevc_stream, mux, payload, signal, temp_file, u32_at, u64_at, NAL_CRA,
+    NAL_IDR_W_RADL, NAL_RASL_N, NAL_TRAIL_N, NAL_TRAIL_R,
+};
+
+/* a splice_info_section with a splice_null() command */
+const SCTE35: [u8; 20] = [
+    0xFC, 0x30, 0x11, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xF0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+const SCTE35_BASE64: &str = "/DARAAAAAAAAAP/wAAAAAAAAAAA=";
+/* an empty ID3v2.4 tag */
+const ID3: [u8; 10] = [b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 0];
+
+/* the null terminated string at pos and the position after it */
+fn c_string(data: &[u8], pos: usize) -> (&str, usize) {
+    let end = pos + data[pos..].iter().position(|&b| b == 0).unwrap();
+    (std::str::from_utf8(&data[pos..end]).unwrap(), end + 1)
+}
+
+/* the decode time of a fragment in ms */
+fn fragment_start(moof: &[u8], timescale: u64) -> u64 {
+    let tfdt = find_boxes(moof, b"tfdt")[0];
+    let time = if tfdt[0] == 1 {
+        u64_at(tfdt, 4)
+    } else {
+        u32_at(tfdt, 4) as u64
+    };
+    time * 1000 / timescale
+}
+
+/* muxes EC-3 with the events into <name>.mp4 */
+fn mux_events(name: &str, events: &str, output_format: &str) -> Output {
+    mux_events_of(
+        name,
+        &signal("7ch_ddp_25fps_channel_id.ec3"),
+        events,
+        output_format,
+    )
+}
+
+fn mux_events_of(name: &str, input: &Path, events: &str, output_format: &str) -> Output {
+    let events_file = temp_file(&format!("{}.txt", name));
+    fs::write(&events_file, events).unwrap();
+    let res = mux(
+        input,
+        &temp_file(&format!("{}.mp4", name)),
+        &[
+            "--output-format",
+            output_format,
+            "--mpeg4-max-frag-duration",
+            "1000",
+            "--events",
+            events_file.to_str().unwrap(),
+        ],
+    );
+    let _ = fs::remove_file(&events_file);
+    res
+}
+
+#[test]
+fn events_in_front_of_their_fragments() {
+    let tag = temp_file("tag.id3");
+    fs::write(&tag, ID3).unwrap();
+    let events = format!(
+        "# an ad cue and timed metadata\n\
+         scheme=scte35 time=1.5 duration=30 timescale=90000 id=7 data={}\n\
+         \n\
+         scheme=id3 value=meta time=00:00:03.100 version=0 file={}\n",
+        SCTE35_BASE64,
+        tag.display()
+    );
+    let res = mux_events("events", &events, "frag-mp4");
+    let _ = fs::remove_file(&tag);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    let mp4 = fs::read(temp_file("events.mp4")).unwrap();
+    let _ = fs::remove_file(temp_file("events.mp4"));
+
+    let boxes = box_tree(&mp4);
+    let moov = payload(&mp4, boxes.iter().find(|b| &b.box_type == b"moov").unwrap());
+    let mdhd = find_boxes(moov, b"mdhd")[0];
+    let timescale = u32_at(mdhd, if mdhd[0] == 1 { 20 } else { 12 }) as u64;
+    let starts: Vec<u64> = boxes
+        .iter()
+        .filter(|b| &b.box_type == b"moof")
+        .map(|moof| fragment_start(payload(&mp4, moof), timescale))
+        .collect();
+    assert!(starts.len() > 3);
+
+    // each 'emsg' goes right in front of the 'moof' of the fragment its event falls into
+    let mut emsgs = vec![];
+    for (i, emsg) in boxes.iter().enumerate() {
+        if &emsg.box_type == b"emsg" {
+            assert_eq!(&boxes[i + 1].box_type, b"moof");
+            let start = fragment_start(payload(&mp4, &boxes[i + 1]), timescale);
+            let fragment = starts.iter().position(|&s| s == start).unwrap();
+            emsgs.push((payload(&mp4, emsg), fragment));
+        }
+    }
+    assert_eq!(emsgs.len(), 2);
+    let in_fragment = |time_ms: u64, fragment: usize| {
+        starts[fragment] <= time_ms && starts.get(fragment + 1).is_none_or(|&end| time_ms < end)
+    };
+
+    // version 1: the presentation time on the media timeline
+    let (emsg, fragment) = emsgs[0];
+    assert_eq!(emsg[0], 1);
+    assert_eq!(u32_at(emsg, 4), 90000);
+    assert_eq!(u64_at(emsg, 8), 135000);
+    assert_eq!(u32_at(emsg, 16), 30 * 90000);
+    assert_eq!(u32_at(emsg, 20), 7);
+    let (scheme, pos) = c_string(emsg, 24);
+    let (value, pos) = c_string(emsg, pos);
+    assert_eq!(scheme, "urn:scte:scte35:2013:bin");
+    assert_eq!(value, "");
+    assert_eq!(emsg[pos..], SCTE35);
+    assert!(in_fragment(1500, fragment));
+
+    // version 0: the time relative to the fragment, the strings in front, unknown duration
+    let (emsg, fragment) = emsgs[1];
+    assert_eq!(emsg[0], 0);
+    let (scheme, pos) = c_string(emsg, 4);
+    let (value, pos) = c_string(emsg, pos);
+    assert_eq!(scheme, "https://aomedia.org/emsg/ID3");
+    assert_eq!(value, "meta");
+    assert_eq!(u32_at(emsg, pos), 1000);
+    assert_eq!(u32_at(emsg, pos + 4) as u64, 3100 - starts[fragment]);
+    assert_eq!(u32_at(emsg, pos + 8), 0xFFFF_FFFF);
+    assert_eq!(u32_at(emsg, pos + 12), 1);
+    assert_eq!(emsg[pos + 16..], ID3);
+    assert!(in_fragment(3100, fragment));
+}
+
+/* an open GOP: the RASL picture of the CRA picture is presented before TRAIL_N pictures decoded
+ahead of the CRA picture, so the second fragment is presented from 5 frames on but decoded from 8 on */
+fn open_gop_stream() -> Vec<u8> {
+    let mut pictures = vec![(NAL_IDR_W_RADL, 0), (NAL_TRAIL_R, 8)];
+    pictures.extend([1, 2, 3, 4, 6, 7].map(|poc| (NAL_TRAIL_N, poc)));
+    pictures.push((NAL_CRA, 12));
+    pictures.extend([5, 9, 10, 11].map(|poc| (NAL_RASL_N, poc)));
+    for poc in (16..=28).step_by(4) {
+        pictures.push((NAL_TRAIL_R, poc));
+        pictures.extend((poc - 3..poc).map(|poc| (NAL_TRAIL_N, poc)));
+    }
+    hevc_stream(&pictures)
+}
+
+#[test]
+fn events_timed_by_the_earliest_presentation_time() {
+    let input = temp_file("events_ept.265");
+    fs::write(&input, open_gop_stream()).unwrap();
+    let res = mux_events_of(
+        "events_ept",
+        &input,
+        "scheme=urn:example value=v1 time=0.22\n\
+         scheme=urn:example value=v0 time=0.26 version=0\n",
+        "frag-mp4",
+    );
+    let _ = fs::remove_file(&input);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    let mp4 = fs::read(temp_file("events_ept.mp4")).unwrap();
+    let _ = fs::remove_file(temp_file("events_ept.mp4"));
+
+    let boxes = box_tree(&mp4);
+    let moov = payload(&mp4, boxes.iter().find(|b| &b.box_type == b"moov").unwrap());
+    let mdhd = find_boxes(moov, b"mdhd")[0];
+    let timescale = u32_at(mdhd, if mdhd[0] == 1 { 20 } else { 12 }) as u64;
+    let emsgs: Vec<(&[u8], u64)> = boxes
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| &b.box_type == b"emsg")
+        .map(|(i, emsg)| {
+            let moof = boxes[i..].iter().find(|b| &b.box_type == b"moof").unwrap();
+            (
+                payload(&mp4, emsg),
+                fragment_start(payload(&mp4, moof), timescale),
+            )
+        })
+        .collect();
+    assert_eq!(emsgs.len(), 2);
+
+    // both events fall into the second fragment, presented from 200 ms on
+    assert!(emsgs.iter().all(|&(_, start)| start == 320));
+    let (emsg, _) = emsgs[1];
+    assert_eq!(emsg[0], 0);
+    let (_, pos) = c_string(emsg, 4);
+    let (value, pos) = c_string(emsg, pos);
+    assert_eq!(value, "v0");
+    assert_eq!(u32_at(emsg, pos + 4), 60);
+}
+
+#[test]
+fn events_checked() {
+    let res = mux_events(
+        "events_checked",
+        "scheme=scte35 time=1 data=AAAA\n",
+        "frag-mp4",
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("Invalid event in line 1"), "{}", err);
+    assert!(err.contains("no splice_info_section"), "{}", err);
+
+    let res = mux_events(
+        "events_checked",
+        "# no time\nscheme=urn:example value=1\n",
+        "frag-mp4",
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("Invalid event in line 2"), "{}", err);
+    assert!(err.contains("no time"), "{}", err);
+
+    let res = mux_events(
+        "events_checked",
+        "scheme=urn:example time=1 data=not*base64\n",
+        "frag-mp4",
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("invalid data"), "{}", err);
+
+    // the delta to the last fragment doesn't fit version 0
+    let res = mux_events(
+        "events_checked",
+        "scheme=urn:example time=100 timescale=4000000000 version=0\n",
+        "frag-mp4",
+    );
+    assert!(!res.status.success());
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("EMA_MP4_MUXED_PARAM_ERR"), "{}", err);
+
+    let res = mux_events("events_checked", "scheme=urn:example time=1\n", "mp4");
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(
+        err.contains("'--events' is supported for fragmented output formats only"),
+        "{}",
+        err
+    );
+    let _ = fs::remove_file(temp_file("events_checked.mp4"));
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{
+    box_tree, find_boxes, mux, payload, pps, run, signal, slice, sps, temp_file, u32_at, u64_at,
+    vps, Sps, NAL_IDR_W_RADL, NAL_TRAIL_R,
+};
+
+/* closed GOPs of the given lengths in pictures, an IDR picture followed by trailing pictures */
+fn gops(lengths: &[u32]) -> Vec<u8> {
+    let mut data = vec![];
+    let pictures = lengths.iter().flat_map(|&length| {
+        (0..length).map(|poc| {
+            slice(
+                if poc == 0 {
+                    NAL_IDR_W_RADL
+                } else {
+                    NAL_TRAIL_R
+                },
+                poc,
+            )
+        })
+    });
+    for nal in [vps(), sps(&Sps::default()), pps()]
+        .into_iter()
+        .chain(pictures)
+    {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal);
+    }
+    data
+}
+
+/* the start times of the fragments in ms */
+fn fragment_starts(mp4: &[u8]) -> Vec<u64> {
+    let boxes = box_tree(mp4);
+    let moov = payload(mp4, boxes.iter().find(|b| &b.box_type == b"moov").unwrap());
+    let mdhd = find_boxes(moov, b"mdhd")[0];
+    let timescale = u32_at(mdhd, if mdhd[0] == 1 { 20 } else { 12 }) as u64;
+
+    boxes
+        .iter()
+        .filter(|b| &b.box_type == b"moof")
+        .map(|moof| {
+            let tfdt = find_boxes(payload(mp4, moof), b"tfdt")[0];
+            let time = if tfdt[0] == 1 {
+                u64_at(tfdt, 4)
+            } else {
+                u32_at(tfdt, 4) as u64
+            };
+            time * 1000 / timescale
+        })
+        .collect()
+}
+
+/* muxes the input into fragmented .mp4 with the options, returning the output or the error */
+fn mux_frag(input: &Path, name: &str, options: &[&str]) -> Result<Vec<u8>, String> {
+    let output = temp_file(name);
+    let mut extra = vec!["--output-format", "frag-mp4"];
+    extra.extend(options);
+    let res = mux(input, &output, &extra);
+    let mp4 = fs::read(&output);
+    let _ = fs::remove_file(&output);
+    if res.status.success() {
+        Ok(mp4.unwrap())
+    } else {
+        Err(String::from_utf8_lossy(&res.stderr).into_owned())
+    }
+}
+
+#[test]
+fn fragment_on_every_sync() {
+    // sync samples at 0, 400, 800, 1000, 1600 and 2000 ms of 2400 ms at 25 fps
+    let hevc = temp_file("every_sync.265");
+    fs::write(&hevc, gops(&[10, 10, 5, 15, 10, 10])).unwrap();
+
+    let mp4 = mux_frag(
+        &hevc,
+        "every_sync.mp4",
+        &["--frag-on-every-sync", "--mpeg4-max-frag-duration", "10000"],
+    );
+    assert_eq!(
+        fragment_starts(&mp4.unwrap()),
+        [0, 400, 800, 1000, 1600, 2000]
+    );
+
+    // GOPs shorter than the minimum duration are merged with the next ones
+    let mp4 = mux_frag(
+        &hevc,
+        "every_sync_min.mp4",
+        &[
+            "--frag-on-every-sync",
+            "--frag-min-duration",
+            "500",
+            "--mpeg4-max-frag-duration",
+            "10000",
+        ],
+    );
+    assert_eq!(fragment_starts(&mp4.unwrap()), [0, 800, 1600]);
+
+    // without the option fragments take as many GOPs as the maximum duration allows
+    let mp4 = mux_frag(
+        &hevc,
+        "every_sync_max.mp4",
+        &["--mpeg4-max-frag-duration", "1000"],
+    );
+    assert_eq!(fragment_starts(&mp4.unwrap()), [0, 1000, 2000]);
+
+    let _ = fs::remove_file(&hevc);
+}
+
+#[test]
+fn fragment_boundaries() {
+    let hevc = temp_file("boundaries.265");
+    fs::write(&hevc, gops(&[10, 10, 5, 15, 10, 10])).unwrap();
+    let cues = temp_file("boundaries.txt");
+    fs::write(&cues, "# ad insertion points\n0.8\n\n00:00:01.600\n").unwrap();
+    let cues = cues.to_str().unwrap();
+
+    let mp4 = mux_frag(
+        &hevc,
+        "boundaries.mp4",
+        &[
+            "--mpeg4-max-frag-duration",
+            "1000",
+            "--frag-boundaries",
+            cues,
+        ],
+    );
+    assert_eq!(fragment_starts(&mp4.unwrap()), [0, 800, 1600]);
+
+    // audio fragments start at the same times, 32 ms EC-3 frames fall on both boundaries
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let mp4 = mux_frag(&ec3, "boundaries_ec3.mp4", &["--frag-boundaries", cues]);
+    assert_eq!(fragment_starts(&mp4.unwrap())[..3], [0, 800, 1600]);
+
+    // a boundary without a sync sample can't start a fragment
+    fs::write(cues, "0.5\n").unwrap();
+    let err = mux_frag(
+        &hevc,
+        "boundaries_no_sync.mp4",
+        &["--frag-boundaries", cues],
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("no sync sample at a fragment boundary"),
+        "{}",
+        err
+    );
+
+    fs::write(cues, "0.8\nlater\n").unwrap();
+    let err = mux_frag(
+        &hevc,
+        "boundaries_invalid.mp4",
+        &["--frag-boundaries", cues],
+    )
+    .unwrap_err();
+    assert!(err.contains("Invalid time \"later\" in line 2"), "{}", err);
+
+    let _ = fs::remove_file(cues);
+    let _ = fs::remove_file(&hevc);
+}
+
+#[test]
+fn fragment_options_checked() {
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file("fragment_options.mp4");
+    let res = run(&[
+        "-i".as_ref(),
+        ec3.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "--frag-on-every-sync".as_ref(),
+    ]);
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("fragmented output formats only"), "{}", err);
+
+    let err = mux_frag(
+        &ec3,
+        "fragment_options.mp4",
+        &["--frag-min-duration", "2000"],
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("must be less than the maximum fragment duration (2000 ms)"),
+        "{}",
+        err
+    );
+}
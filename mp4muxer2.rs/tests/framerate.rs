@@ -0,0 +1,134 @@
+use std::fs;
+
+mod common;
+use common::{
+    find_boxes, idr_slice, mux, mux_inputs, pps, signal, sps, temp_file, u32_at, vps, Sps,
+};
+
+#[test]
+fn framerate_is_rejected_for_audio() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+
+    // the frame rate belongs to the second input only
+    let res = mux_inputs(
+        &temp_file("framerate_audio.mp4"),
+        &[
+            "-i",
+            ac3.to_str().unwrap(),
+            "-i",
+            ec3.to_str().unwrap(),
+            "-f",
+            "25",
+        ],
+    );
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("EMA_MP4_MUXED_PARAM_ERR"));
+
+    let output = temp_file("framerate_none.mp4");
+    let res = mux_inputs(
+        &output,
+        &["-i", ac3.to_str().unwrap(), "-i", ec3.to_str().unwrap()],
+    );
+    let _ = fs::remove_file(&output);
+    assert!(res.status.success());
+}
+
+#[test]
+fn force_framerate_requires_framerate() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+
+    let res = mux_inputs(
+        &temp_file("framerate_force.mp4"),
+        &["-i", ac3.to_str().unwrap(), "--force-framerate"],
+    );
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("'--force-framerate' requires"));
+}
+
+#[test]
+fn framerate_notations() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let output = temp_file("framerate_notations.mp4");
+
+    // valid rates get past the command line, up to the audio check of the muxer
+    for rate in [
+        "25",
+        "24000/1001",
+        "23.976",
+        "29.97fps",
+        "59.94 fps",
+        "50i",
+        "25p",
+    ] {
+        let res = mux_inputs(&output, &["-i", ac3.to_str().unwrap(), "-f", rate]);
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(
+            stderr.contains("EMA_MP4_MUXED_PARAM_ERR"),
+            "{}: {}",
+            rate,
+            stderr
+        );
+    }
+
+    for rate in [
+        "0",
+        "24/0",
+        "1000",
+        "abc",
+        "1e3",
+        "0.0000001",
+        "25x",
+        "1/99999999999999999",
+        "99999999999999999/1",
+        "1/18446744073709551615i",
+    ] {
+        let res = mux_inputs(&output, &["-i", ac3.to_str().unwrap(), "-f", rate]);
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(stderr.contains("Invalid value"), "{}: {}", rate, stderr);
+    }
+}
+
+/* the frame duration in seconds of the first track */
+fn frame_duration(mp4: &[u8]) -> f64 {
+    let timescale = u32_at(find_boxes(mp4, b"mdhd")[0], 12);
+    let sample_delta = u32_at(find_boxes(mp4, b"stts")[0], 12);
+    sample_delta as f64 / timescale as f64
+}
+
+#[test]
+fn framerate_overrides_stream_timing_only_if_forced() {
+    // 25 fps in the VUI
+    let mut data = vec![];
+    for nal in [vps(), sps(&Sps::default()), pps()]
+        .into_iter()
+        .chain((0..5).map(|_| idr_slice()))
+    {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal);
+    }
+    let input = temp_file("framerate_vui.265");
+    fs::write(&input, data).unwrap();
+
+    for (extra, duration) in [
+        (&["-f", "50"][..], 1.0 / 25.0),
+        (&["-f", "50", "--force-framerate"][..], 1.0 / 50.0),
+    ] {
+        let output = temp_file("framerate_vui.mp4");
+        let res = mux(&input, &output, extra);
+        assert!(
+            res.status.success(),
+            "{}",
+            String::from_utf8_lossy(&res.stderr)
+        );
+
+        let mp4 = fs::read(&output).unwrap();
+        let _ = fs::remove_file(&output);
+        assert!(
+            (frame_duration(&mp4) - duration).abs() < 1e-9,
+            "{:?}",
+            extra
+        );
+    }
+    let _ = fs::remove_file(&input);
+}
@@ -0,0 +1,171 @@
+use std::fs;
+
+mod common;
+use common::{
+    find_boxes, idr_slice, mux, mux_data, nal, pps, signal, sps, temp_file, vps, Colour, Sps,
+};
+
+const NAL_PREFIX_SEI: u8 = 39;
+
+const SEI_MASTERING_DISPLAY: u32 = 137;
+const SEI_CONTENT_LIGHT_LEVEL: u32 = 144;
+
+/* sei_message()s of the given payload type and bytes */
+fn sei(messages: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut rbsp = vec![];
+    for (payload_type, payload) in messages {
+        rbsp.push(*payload_type as u8);
+        rbsp.push(payload.len() as u8);
+        rbsp.extend(payload);
+    }
+    rbsp.push(0x80);
+    nal(NAL_PREFIX_SEI, &rbsp)
+}
+
+/* P3 D65 primaries as G, B, R and white point, 1000 to 0.0001 cd/m2 */
+const MASTERING_DISPLAY: [u16; 8] = [13250, 34500, 7500, 3000, 34000, 16000, 15635, 16450];
+const MASTERING_LUMINANCE: (u32, u32) = (10_000_000, 1);
+
+fn mastering_display_sei() -> (u32, Vec<u8>) {
+    let mut payload: Vec<u8> = MASTERING_DISPLAY
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect();
+    payload.extend(MASTERING_LUMINANCE.0.to_be_bytes());
+    payload.extend(MASTERING_LUMINANCE.1.to_be_bytes());
+    (SEI_MASTERING_DISPLAY, payload)
+}
+
+fn content_light_level_sei(max_cll: u16, max_fall: u16) -> (u32, Vec<u8>) {
+    let mut payload = max_cll.to_be_bytes().to_vec();
+    payload.extend(max_fall.to_be_bytes());
+    (SEI_CONTENT_LIGHT_LEVEL, payload)
+}
+
+/* 5 IDR pictures, the first one with the parameter sets and the given SEI */
+fn hevc_stream(sei_messages: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    // BT.2020 PQ in full range
+    let sps = sps(&Sps {
+        colour: Some(Colour {
+            full_range: true,
+            primaries: 9,
+            transfer: 16,
+            matrix: 9,
+        }),
+        ..Sps::default()
+    });
+    let mut first = vec![vps(), sps, pps()];
+    if !sei_messages.is_empty() {
+        first.push(sei(sei_messages));
+    }
+    first.push(idr_slice());
+
+    let mut data = vec![];
+    for nal in first
+        .into_iter()
+        .chain(std::iter::repeat_with(idr_slice).take(4))
+    {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal);
+    }
+    data
+}
+
+fn expected_mdcv(primaries: &[u16; 8], luminance: (u32, u32)) -> Vec<u8> {
+    let mut mdcv: Vec<u8> = primaries.iter().flat_map(|v| v.to_be_bytes()).collect();
+    mdcv.extend(luminance.0.to_be_bytes());
+    mdcv.extend(luminance.1.to_be_bytes());
+    mdcv
+}
+
+#[test]
+fn hdr_metadata_from_vui_and_sei() {
+    let data = hevc_stream(&[mastering_display_sei(), content_light_level_sei(1000, 400)]);
+    let (res, mp4) = mux_data("hdr.265", &data, &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    // nclx BT.2020, PQ, BT.2020 non-constant luminance, full range
+    assert_eq!(
+        find_boxes(&mp4, b"colr"),
+        [&b"nclx\x00\x09\x00\x10\x00\x09\x80"[..]]
+    );
+    assert_eq!(
+        find_boxes(&mp4, b"mdcv"),
+        [expected_mdcv(&MASTERING_DISPLAY, MASTERING_LUMINANCE).as_slice()]
+    );
+    assert_eq!(find_boxes(&mp4, b"clli"), [&[0x03, 0xE8, 0x01, 0x90][..]]);
+}
+
+#[test]
+fn hdr_metadata_given_by_user() {
+    let args = [
+        "--master-display",
+        "G(8500,39850)B(6550,2300)R(35400,14600)WP(15635,16450)L(40000000,50)",
+        "--max-cll",
+        "4000,1000",
+    ];
+    let primaries = [8500, 39850, 6550, 2300, 35400, 14600, 15635, 16450];
+
+    // overrides the SEI of the stream
+    let data = hevc_stream(&[mastering_display_sei(), content_light_level_sei(1000, 400)]);
+    let (res, mp4) = mux_data("hdr_override.265", &data, &args);
+    assert!(res.status.success());
+    assert_eq!(
+        find_boxes(&mp4, b"mdcv"),
+        [expected_mdcv(&primaries, (40_000_000, 50)).as_slice()]
+    );
+    assert_eq!(find_boxes(&mp4, b"clli"), [&[0x0F, 0xA0, 0x03, 0xE8][..]]);
+
+    // and fills in for a stream without
+    let data = hevc_stream(&[]);
+    let (res, mp4) = mux_data("hdr_no_sei.265", &data, &[]);
+    assert!(res.status.success());
+    assert_eq!(find_boxes(&mp4, b"colr").len(), 1);
+    assert!(find_boxes(&mp4, b"mdcv").is_empty());
+    assert!(find_boxes(&mp4, b"clli").is_empty());
+
+    let (res, mp4) = mux_data("hdr_no_sei.265", &data, &args);
+    assert!(res.status.success());
+    assert_eq!(
+        find_boxes(&mp4, b"mdcv"),
+        [expected_mdcv(&primaries, (40_000_000, 50)).as_slice()]
+    );
+    assert_eq!(find_boxes(&mp4, b"clli"), [&[0x0F, 0xA0, 0x03, 0xE8][..]]);
+}
+
+#[test]
+fn hdr_options_validated() {
+    let data = hevc_stream(&[]);
+    for master_display in [
+        "G(8500,39850)B(6550,2300)R(35400,14600)WP(15635,16450)",
+        "G(8500,39850)B(6550,2300)R(35400,14600)WP(15635,16450)L(50,40000000)",
+        "G(8500,50001)B(6550,2300)R(35400,14600)WP(15635,16450)L(40000000,50)",
+        "R(35400,14600)G(8500,39850)B(6550,2300)WP(15635,16450)L(40000000,50)",
+    ] {
+        let (res, _) = mux_data(
+            "hdr_invalid.265",
+            &data,
+            &["--master-display", master_display],
+        );
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(stderr.contains("Invalid value"), "{}", stderr);
+    }
+    for max_cll in ["1000", "1000,70000", "a,b"] {
+        let (res, _) = mux_data("hdr_invalid.265", &data, &["--max-cll", max_cll]);
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(stderr.contains("Invalid value"), "{}", stderr);
+    }
+
+    let output = temp_file("hdr_audio.mp4");
+    let res = mux(
+        &signal("5ch_dd_25fps_channel_id.ac3"),
+        &output,
+        &["--max-cll", "1000,400"],
+    );
+    let _ = fs::remove_file(&output);
+    assert!(String::from_utf8_lossy(&res.stderr).contains("EMA_MP4_MUXED_PARAM_ERR"));
+}
@@ -0,0 +1,211 @@
+use std::fs;
+
+mod common;
+use common::{mux_inputs, read_boxes, signal, temp_file, Mp4Box, Value};
+
+/* the values of a list field */
+fn uints(mp4_box: &Mp4Box, name: &str) -> Vec<u64> {
+    mp4_box
+        .field(name)
+        .map(|v| v.as_list().iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default()
+}
+
+/* the chunks of a track as (offset, size in bytes, decode times of the first and last sample in ms) */
+fn chunks(trak: &Mp4Box) -> Vec<(u64, u64, u64, u64)> {
+    let timescale = trak
+        .descendant(&[b"mdia", b"mdhd"])
+        .and_then(|mdhd| mdhd.uint("timescale"))
+        .unwrap();
+    let stbl = trak.descendant(&[b"mdia", b"minf", b"stbl"]).unwrap();
+    let offsets = uints(stbl.child(b"stco").unwrap(), "chunk_offsets");
+    let stsz = stbl.child(b"stsz").unwrap();
+    let sizes = match stsz.uint("sample_size").unwrap() {
+        0 => uints(stsz, "entry_sizes"),
+        size => vec![size; stsz.uint("sample_count").unwrap() as usize],
+    };
+    // audio frames of a constant duration
+    let stts = stbl.child(b"stts").unwrap().field("entries").unwrap();
+    assert_eq!(stts.as_list().len(), 1);
+    let sample_delta = stts.as_list()[0].uint("sample_delta").unwrap();
+
+    let stsc = stbl.child(b"stsc").unwrap().field("entries").unwrap();
+    let runs = stsc.as_list();
+    let mut chunks = vec![];
+    let mut sample = 0;
+    for (i, run) in runs.iter().enumerate() {
+        let first_chunk = run.uint("first_chunk").unwrap() as usize;
+        let last_chunk = runs.get(i + 1).map_or(offsets.len() + 1, |next| {
+            next.uint("first_chunk").unwrap() as usize
+        });
+        let samples_per_chunk = run.uint("samples_per_chunk").unwrap() as usize;
+        for chunk in first_chunk..last_chunk {
+            let size = sizes[sample..sample + samples_per_chunk].iter().sum();
+            let time = |sample: usize| sample as u64 * sample_delta * 1000 / timescale;
+            chunks.push((
+                offsets[chunk - 1],
+                size,
+                time(sample),
+                time(sample + samples_per_chunk - 1),
+            ));
+            sample += samples_per_chunk;
+        }
+    }
+    assert_eq!(sample, sizes.len());
+    chunks
+}
+
+/* muxes AC-3 and EC-3 with the options, returns the boxes and the output on stdout */
+fn mux_layout(name: &str, options: &[&str]) -> (Vec<Mp4Box>, String) {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file(name);
+
+    let mut args = vec!["-i", ac3.to_str().unwrap(), "-i", ec3.to_str().unwrap()];
+    args.extend(options);
+    let res = mux_inputs(&output, &args);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    let boxes = read_boxes(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    (boxes, String::from_utf8_lossy(&res.stdout).into_owned())
+}
+
+fn types(boxes: &[Mp4Box]) -> Vec<[u8; 4]> {
+    boxes.iter().map(|b| b.box_type).collect()
+}
+
+fn moov(boxes: &[Mp4Box]) -> &Mp4Box {
+    boxes.iter().find(|b| &b.box_type == b"moov").unwrap()
+}
+
+/* checks that the chunks of the tracks fill the media data without gaps or overlaps */
+fn check_chunks_in_mdat(boxes: &[Mp4Box]) {
+    let mdat = boxes.iter().find(|b| &b.box_type == b"mdat").unwrap();
+    let mut chunks: Vec<(u64, u64)> = moov(boxes)
+        .children_of(b"trak")
+        .flat_map(|trak| {
+            chunks(trak)
+                .into_iter()
+                .map(|(offset, size, _, _)| (offset, size))
+        })
+        .collect();
+    chunks.sort_unstable();
+
+    let mut pos = mdat.offset + mdat.header_size;
+    for (offset, size) in chunks {
+        assert_eq!(offset, pos);
+        pos += size;
+    }
+    assert_eq!(pos, mdat.offset + mdat.size);
+}
+
+/* checks that each chunk holds the samples decoded within one span of the interleaving */
+fn check_spans(chunks: &[(u64, u64, u64, u64)], span: u64) {
+    for (i, &(_, _, first, last)) in chunks.iter().enumerate() {
+        assert_eq!(first / span, last / span, "chunk {}", i);
+        if i > 0 {
+            assert!(chunks[i - 1].3 / span < first / span, "chunk {}", i);
+        }
+    }
+}
+
+#[test]
+fn moov_at_start_by_default() {
+    let (boxes, _) = mux_layout("layout_default.mp4", &[]);
+
+    assert_eq!(types(&boxes), [*b"ftyp", *b"moov", *b"mdat"]);
+    assert!(moov(&boxes).child(b"free").is_none());
+    check_chunks_in_mdat(&boxes);
+
+    // a chunk per 250 ms by default
+    for trak in moov(&boxes).children_of(b"trak") {
+        check_spans(&chunks(trak), 250);
+    }
+}
+
+#[test]
+fn moov_at_end_with_free_space() {
+    let (boxes, _) = mux_layout(
+        "layout_end.mp4",
+        &["--moov-position", "end", "--moov-free-space", "1000"],
+    );
+
+    assert_eq!(types(&boxes), [*b"ftyp", *b"mdat", *b"moov"]);
+    check_chunks_in_mdat(&boxes);
+
+    // the free space follows the tracks at the end of 'moov'
+    let moov = moov(&boxes);
+    let free = moov.children.last().unwrap();
+    assert_eq!(&free.box_type, b"free");
+    assert_eq!(free.size, 8 + 1000);
+    assert_eq!(free.offset + free.size, moov.offset + moov.size);
+}
+
+#[test]
+fn interleave_and_chunk_size() {
+    // the chunk size belongs to the EC-3 track only
+    let (boxes, _) = mux_layout(
+        "layout_interleave.mp4",
+        &["--chunk-size", "3000", "--interleave", "500"],
+    );
+    check_chunks_in_mdat(&boxes);
+
+    let traks: Vec<&Mp4Box> = moov(&boxes).children_of(b"trak").collect();
+    let ac3 = chunks(traks[0]);
+    let ec3 = chunks(traks[1]);
+
+    // a chunk per 500 ms
+    check_spans(&ac3, 500);
+    assert!(ac3.iter().any(|&(_, size, _, _)| size > 3000));
+
+    // the chunk size splits the chunks of the EC-3 track further, within the spans
+    assert!(ec3.iter().all(|&(_, size, _, _)| size <= 3000));
+    assert!(ec3
+        .iter()
+        .all(|&(_, _, first, last)| first / 500 == last / 500));
+    assert!(ec3.len() > ac3.len());
+
+    // no interleaving puts each track into a single chunk
+    let (boxes, _) = mux_layout("layout_no_interleave.mp4", &["--interleave", "0"]);
+    check_chunks_in_mdat(&boxes);
+    for trak in moov(&boxes).children_of(b"trak") {
+        assert_eq!(chunks(trak).len(), 1);
+    }
+}
+
+#[test]
+fn verbose_prints_layout() {
+    let (boxes, stdout) = mux_layout(
+        "layout_verbose.mp4",
+        &["--moov-position", "end", "--verbose"],
+    );
+
+    assert!(stdout.contains("Layout of"), "{}", stdout);
+    for mp4_box in &boxes {
+        let line = format!(
+            "  {} @ {:>12}, {:>12} bytes",
+            mp4_box.type_name(),
+            mp4_box.offset,
+            mp4_box.size
+        );
+        assert!(stdout.contains(&line), "{}", stdout);
+    }
+    assert!(stdout.contains("'moov' follows 'mdat'"), "{}", stdout);
+
+    for (i, trak) in moov(&boxes).children_of(b"trak").enumerate() {
+        let line = format!(
+            "track {}: {} chunks, 32-bit chunk offsets ('stco')",
+            i + 1,
+            chunks(trak).len()
+        );
+        assert!(stdout.contains(&line), "{}", stdout);
+    }
+
+    let (_, stdout) = mux_layout("layout_quiet.mp4", &[]);
+    assert!(!stdout.contains("Layout of"), "{}", stdout);
+}
@@ -0,0 +1,251 @@
+mod common;
+use common::{audio_entry, find_boxes, mux_data, u32s, BitWriter};
+
+impl BitWriter {
+    /* escapedValue() of ISO/IEC 23008-3 */
+    fn escaped(&mut self, n: [usize; 3], value: u64) {
+        let max1 = (1 << n[0]) - 1;
+        let max2 = (1 << n[1]) - 1;
+        if value < max1 {
+            self.put(n[0], value);
+        } else if value - max1 < max2 {
+            self.put(n[0], max1);
+            self.put(n[1], value - max1);
+        } else {
+            self.put(n[0], max1);
+            self.put(n[1], max2);
+            self.put(n[2], value - max1 - max2);
+        }
+    }
+}
+
+fn mhas_packet(packet_type: u64, payload: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.escaped([3, 8, 8], packet_type);
+    w.escaped([2, 8, 32], 1);
+    w.escaped([11, 24, 24], payload.len() as u64);
+    [w.data, payload.to_vec()].concat()
+}
+
+/* LC profile level 3, 48 kHz, 1024 samples per frame, CICP 5.1 */
+fn mpegh3da_config() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(8, 0x0D);
+    w.put(5, 3);
+    w.put(3, 1);
+    w.put(2, 0);
+    w.put(2, 0);
+    w.put(6, 6);
+    w.put(14, 0x1234);
+    w.data
+}
+
+/* MHAS access units, a configuration in the 1st and 5th */
+fn mhas() -> (Vec<u8>, Vec<usize>) {
+    let sync = mhas_packet(6, &[0xA5]);
+    let cfg = mhas_packet(1, &mpegh3da_config());
+    let mut data = Vec::new();
+    let mut sizes = Vec::new();
+    for i in 0..7usize {
+        let frame = mhas_packet(2, &vec![i as u8; if i == 2 { 3000 } else { 100 + i }]);
+        let mut size = frame.len();
+        if i % 4 == 0 {
+            data.extend(&sync);
+            data.extend(&cfg);
+            size += cfg.len();
+        }
+        data.extend(frame);
+        sizes.push(size);
+    }
+    (data, sizes)
+}
+
+#[test]
+fn mpegh_mhas() {
+    let (data, sizes) = mhas();
+    let (res, mp4) = mux_data("audio.mhas", &data, &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"mhm1"[..], 6, 16, 48000));
+    let config = mpegh3da_config();
+    let mut mhac = vec![1, 0x0D, 6];
+    mhac.extend((config.len() as u16).to_be_bytes());
+    mhac.extend(config);
+    assert_eq!(find_boxes(&mp4, b"mhaC"), [&mhac[..]]);
+
+    // the SYNC packets are dropped
+    let sizes: Vec<u32> = sizes.iter().map(|&s| s as u32).collect();
+    assert_eq!(&u32s(find_boxes(&mp4, b"stsz")[0])[3..], &sizes[..]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 7, 1024]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stss")[0]), [0, 2, 1, 5]);
+}
+
+#[test]
+fn mpegh_without_last_frame_fails() {
+    let (mut data, _) = mhas();
+    data.extend(mhas_packet(0, &[0; 8]));
+    let (res, _) = mux_data("truncated.mhas", &data, &[]);
+    assert!(!res.status.success());
+}
+
+/* a 512 sample 48 kHz core frame of size bytes, 5.1, 24 bit source */
+fn dts_core_frame(size: usize) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(32, 0x7FFE_8001);
+    w.put(1, 1);
+    w.put(5, 31);
+    w.put(1, 0);
+    w.put(7, 15);
+    w.put(14, size as u64 - 1);
+    w.put(6, 9);
+    w.put(4, 13);
+    w.put(5, 15);
+    w.put(10, 0);
+    w.put(2, 1);
+    w.put(1, 0);
+    w.put(1, 0);
+    w.put(4, 7);
+    w.put(2, 0);
+    w.put(3, 5);
+    let mut frame = w.data;
+    frame.resize(size, 0x11);
+    frame
+}
+
+fn dts_substream(size: usize) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(32, 0x6458_2025);
+    w.put(8, 0);
+    w.put(2, 0);
+    w.put(1, 0);
+    w.put(8, 15);
+    w.put(16, size as u64 - 1);
+    let mut substream = w.data;
+    substream.resize(size, 0x22);
+    substream
+}
+
+fn ddts(max_bitrate: u32, avg_bitrate: u32, stream_construction: u64) -> Vec<u8> {
+    let mut ddts = Vec::new();
+    ddts.extend(48000u32.to_be_bytes());
+    ddts.extend(max_bitrate.to_be_bytes());
+    ddts.extend(avg_bitrate.to_be_bytes());
+    ddts.push(24);
+    let mut w = BitWriter::default();
+    w.put(2, 0);
+    w.put(5, stream_construction);
+    w.put(1, 1);
+    w.put(6, 9);
+    w.put(14, 999);
+    w.put(4, 0);
+    // C L R Ls Rs LFE
+    w.put(16, 0x000F);
+    w.put(8, 0);
+    ddts.extend(w.data);
+    ddts
+}
+
+#[test]
+fn dts_core() {
+    let data = dts_core_frame(1000).repeat(4);
+    let (res, mp4) = mux_data("audio.dts", &data, &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"dtsc"[..], 6, 16, 48000));
+    // 1000 bytes per 512 samples
+    assert_eq!(find_boxes(&mp4, b"ddts"), [&ddts(750000, 750000, 1)[..]]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stsz")[0]), [0, 1000, 4]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 4, 512]);
+    assert!(find_boxes(&mp4, b"stss").is_empty());
+}
+
+#[test]
+fn dts_core_with_substream() {
+    let sizes = [1200u32, 1500, 1100];
+    let data: Vec<u8> = sizes
+        .iter()
+        .flat_map(|&s| [dts_core_frame(1000), dts_substream(s as usize - 1000)].concat())
+        .collect();
+    let (res, mp4) = mux_data("hd.dts", &data, &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"dtsh"[..], 6, 16, 48000));
+    assert_eq!(
+        find_boxes(&mp4, b"ddts"),
+        [&ddts(1500 * 8 * 48000 / 512, 3800 * 8 * 48000 / 1536, 0)[..]]
+    );
+    assert_eq!(&u32s(find_boxes(&mp4, b"stsz")[0])[3..], sizes);
+}
+
+#[test]
+fn dts_with_broken_frame_fails() {
+    let mut data = dts_core_frame(1000).repeat(4);
+    data[2000] = 0;
+    let (res, _) = mux_data("broken.dts", &data, &[]);
+    assert!(!res.status.success());
+}
+
+/* 1024 samples at 48 kHz, a payload without sync words */
+fn dts_uhd_frame(sync: bool, size: usize) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(32, if sync { 0x4041_1BF2 } else { 0x71C4_42E8 });
+    w.put(1, 0);
+    w.put(5, 19);
+    if sync {
+        w.put(1, 1);
+        w.put(2, 0);
+        w.put(3, 1);
+        w.put(2, 2);
+        w.put(1, 0);
+        w.put(2, 0);
+    }
+    let mut frame = w.data;
+    frame.resize(size, 0x55);
+    frame
+}
+
+#[test]
+fn dts_uhd() {
+    let frames = [
+        (true, 300),
+        (false, 250),
+        (false, 280),
+        (true, 310),
+        (false, 200),
+    ];
+    let data: Vec<u8> = frames
+        .iter()
+        .flat_map(|&(sync, size)| dts_uhd_frame(sync, size))
+        .collect();
+    let (res, mp4) = mux_data("audio.dtsx", &data, &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"dtsx"[..], 2, 16, 48000));
+    // profile 2, 1024 samples, 2048 bytes at most, 48 kHz base
+    assert_eq!(
+        find_boxes(&mp4, b"udts"),
+        [&[0x01, 0x00, 0, 0, 0, 0, 0x80, 0x00][..]]
+    );
+    assert_eq!(
+        &u32s(find_boxes(&mp4, b"stsz")[0])[3..],
+        [300, 250, 280, 310, 200]
+    );
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 5, 1024]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stss")[0]), [0, 2, 1, 4]);
+}
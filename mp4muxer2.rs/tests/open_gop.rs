@@ -0,0 +1,134 @@
+mod common;
+use common::{
+    find_boxes, hevc_stream, mux_data, open_gop_pictures, u32_at, NAL_CRA, NAL_IDR_W_RADL,
+};
+
+/* (count, value) pairs of a FullBox table with 8 byte entries */
+fn table(payload: &[u8]) -> Vec<(u32, u32)> {
+    let entry_count = u32_at(payload, 4) as usize;
+    (0..entry_count)
+        .map(|i| (u32_at(payload, 8 + 8 * i), u32_at(payload, 12 + 8 * i)))
+        .collect()
+}
+
+fn expand(entries: &[(u32, u32)]) -> Vec<u32> {
+    entries
+        .iter()
+        .flat_map(|&(count, value)| std::iter::repeat_n(value, count as usize))
+        .collect()
+}
+
+/* composition offsets in frames */
+fn ctts_in_frames(mp4: &[u8]) -> Vec<u32> {
+    let delta = table(find_boxes(mp4, b"stts")[0])[0].1;
+    expand(&table(find_boxes(mp4, b"ctts")[0]))
+        .iter()
+        .map(|offset| offset / delta)
+        .collect()
+}
+
+#[test]
+fn open_gop_timing_and_sync_samples() {
+    let pictures = open_gop_pictures(NAL_IDR_W_RADL, 0, 4);
+    let (res, mp4) = mux_data("open_gop.265", &hevc_stream(&pictures), &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    // every picture is presented once, in POC order
+    assert_eq!(
+        ctts_in_frames(&mp4),
+        [1, 4, 0, 0, 0, 4, 0, 0, 0, 4, 0, 0, 0, 4, 0, 0, 0]
+    );
+
+    let stss = find_boxes(&mp4, b"stss")[0];
+    assert_eq!(
+        &stss[4..],
+        [0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 6, 0, 0, 0, 14]
+    );
+
+    // is_leading 1 for RASL pictures, sample_depends_on 2 for the IRAP pictures
+    let sdtp = find_boxes(&mp4, b"sdtp")[0];
+    let mut expected = vec![0x20];
+    for _ in 0..2 {
+        expected.extend([0x10, 0x10, 0x10, 0x10, 0x20, 0x50, 0x50, 0x50]);
+    }
+    assert_eq!(&sdtp[4..], expected);
+
+    // the IDR picture is of SAP type 2 and the CRA pictures of type 3
+    let sgpd = find_boxes(&mp4, b"sgpd")[0];
+    assert_eq!(&sgpd[4..8], b"sap ");
+    assert_eq!(&sgpd[12..], [0, 0, 0, 3, 1, 2, 3]);
+    let sbgp = find_boxes(&mp4, b"sbgp")[0];
+    assert_eq!(&sbgp[4..8], b"sap ");
+    assert_eq!(
+        table(&sbgp[4..]),
+        [(1, 2), (4, 0), (1, 3), (7, 0), (1, 3), (3, 0)]
+    );
+}
+
+#[test]
+fn open_gop_starting_with_cra() {
+    // a cut out of an open GOP encode: the RASL pictures of the first CRA are not decodable
+    let pictures: Vec<_> = open_gop_pictures(NAL_CRA, 8, 6)
+        .into_iter()
+        .skip(5)
+        .collect();
+    let (res, mp4) = mux_data("open_gop_cra.265", &hevc_stream(&pictures), &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    assert_eq!(ctts_in_frames(&mp4), [4, 0, 0, 0].repeat(5));
+}
+
+#[test]
+fn open_gop_fragments() {
+    let pictures = open_gop_pictures(NAL_IDR_W_RADL, 0, 8);
+    let (res, mp4) = mux_data(
+        "open_gop_frag.265",
+        &hevc_stream(&pictures),
+        &[
+            "--output-format",
+            "frag-mp4",
+            "--mpeg4-max-frag-duration",
+            "320",
+        ],
+    );
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    // the first fragment starts with the IDR picture, the others with a CRA picture
+    let sidx = find_boxes(&mp4, b"sidx")[0];
+    let reference_count = u16::from_be_bytes([sidx[22], sidx[23]]) as usize;
+    let sap: Vec<u32> = (0..reference_count)
+        .map(|i| u32_at(sidx, 24 + 12 * i + 8))
+        .collect();
+    assert!(reference_count > 1, "{}", reference_count);
+    assert_eq!(sap[0], 0xA000_0000);
+    assert!(
+        sap[1..].iter().all(|&sap| sap == 0xB000_0000),
+        "{:08x?}",
+        sap
+    );
+
+    assert_eq!(find_boxes(&mp4, b"moof").len(), reference_count);
+    for (i, traf) in find_boxes(&mp4, b"traf").into_iter().enumerate() {
+        let sdtp = find_boxes(traf, b"sdtp")[0];
+        if i > 0 {
+            assert_eq!(sdtp[4..8], [0x20, 0x50, 0x50, 0x50]);
+        }
+        let sgpd = find_boxes(traf, b"sgpd")[0];
+        assert_eq!(&sgpd[4..8], b"sap ");
+        let sbgp = find_boxes(traf, b"sbgp")[0];
+        let entries = table(&sbgp[4..]);
+        assert!(entries[0].1 == 0x10002 || entries[0].1 == 0x10003);
+    }
+}
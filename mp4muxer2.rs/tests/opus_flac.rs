@@ -0,0 +1,215 @@
+mod common;
+use common::{audio_entry, find_boxes, mux_data, u32s};
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |crc, &b| {
+        (0..8).fold(crc ^ (b as u32) << 24, |crc, _| {
+            if crc & 0x8000_0000 != 0 {
+                crc << 1 ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn ogg_page(flags: u8, granule: u64, seq: u32, packets: &[Vec<u8>]) -> Vec<u8> {
+    let mut lacing = Vec::new();
+    for p in packets {
+        lacing.extend(std::iter::repeat_n(255, p.len() / 255));
+        lacing.push((p.len() % 255) as u8);
+    }
+    let mut page = b"OggS".to_vec();
+    page.extend([0, flags]);
+    page.extend(granule.to_le_bytes());
+    page.extend(0x1234_5678u32.to_le_bytes());
+    page.extend(seq.to_le_bytes());
+    page.extend([0; 4]);
+    page.push(lacing.len() as u8);
+    page.extend(lacing);
+    for p in packets {
+        page.extend(p);
+    }
+    let crc = ogg_crc(&page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+    page
+}
+
+const PRE_SKIP: u16 = 312;
+
+/* 10 CELT fullband 20 ms packets, the last page ends 500 samples before the last packet */
+fn ogg_opus() -> (Vec<u8>, Vec<u32>) {
+    let mut head = b"OpusHead".to_vec();
+    head.extend([1, 2]);
+    head.extend(PRE_SKIP.to_le_bytes());
+    head.extend(44100u32.to_le_bytes());
+    head.extend([0, 0, 0]);
+
+    let packets: Vec<Vec<u8>> = (0..10)
+        .map(|i| [vec![31 << 3], vec![i as u8; 40 + i * 30]].concat())
+        .collect();
+
+    let mut ogg = ogg_page(2, 0, 0, &[head]);
+    ogg.extend(ogg_page(0, 0, 1, &[b"OpusTags\0\0\0\0\0\0\0\0".to_vec()]));
+    ogg.extend(ogg_page(0, 5 * 960, 2, &packets[..5]));
+    ogg.extend(ogg_page(4, 10 * 960 - 500, 3, &packets[5..]));
+    (ogg, packets.iter().map(|p| p.len() as u32).collect())
+}
+
+#[test]
+fn opus_in_ogg() {
+    let (ogg, sizes) = ogg_opus();
+    let (res, mp4) = mux_data("audio.opus", &ogg, &["--language", "eng"]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"Opus"[..], 2, 16, 48000));
+    let mut dops = vec![0, 2];
+    dops.extend(PRE_SKIP.to_be_bytes());
+    dops.extend(44100u32.to_be_bytes());
+    dops.extend([0, 0, 0]);
+    assert_eq!(find_boxes(&mp4, b"dOps"), [&dops[..]]);
+    assert!(u32s(&find_boxes(&mp4, b"ftyp")[0][8..]).contains(&u32::from_be_bytes(*b"Opus")));
+
+    assert_eq!(&u32s(find_boxes(&mp4, b"stsz")[0])[3..], &sizes[..]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 10, 960]);
+
+    // pre-skip and the end trimming of the last page
+    let mdhd = find_boxes(&mp4, b"mdhd")[0];
+    assert_eq!(u32s(&mdhd[12..20]), [48000, 9600]);
+    let mvhd = find_boxes(&mp4, b"mvhd")[0];
+    let movie_timescale = u32s(&mvhd[12..16])[0] as u64;
+    let elst = u32s(find_boxes(&mp4, b"elst")[0]);
+    assert_eq!(elst[1], 1);
+    assert_eq!(
+        elst[2] as u64,
+        ((9100 - PRE_SKIP as u64) * movie_timescale + 24000) / 48000
+    );
+    assert_eq!(elst[3], PRE_SKIP as u32);
+
+    // 80 ms pre-roll
+    let mut sgpd = vec![1, 0, 0, 0];
+    sgpd.extend(b"roll");
+    sgpd.extend([0, 0, 0, 2, 0, 0, 0, 1]);
+    sgpd.extend((-4i16).to_be_bytes());
+    assert_eq!(find_boxes(&mp4, b"sgpd"), [&sgpd[..]]);
+    assert_eq!(
+        &u32s(find_boxes(&mp4, b"sbgp")[0])[1..],
+        [u32::from_be_bytes(*b"roll"), 1, 10, 1]
+    );
+}
+
+#[test]
+fn opus_with_invalid_packet_fails() {
+    let (mut ogg, _) = ogg_opus();
+    // code 3 packet of 0 frames
+    let last_page = ogg.windows(4).rposition(|w| w == b"OggS").unwrap();
+    let first_packet = last_page + 27 + 5;
+    ogg[first_packet] = 31 << 3 | 3;
+    ogg[first_packet + 1] = 0;
+    let (res, _) = mux_data("invalid.opus", &ogg, &[]);
+    assert!(!res.status.success());
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| {
+            if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ (b as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/* a stereo 16 bit 44.1 kHz frame of two CONSTANT subframes */
+fn flac_frame(number: u8, block_size: u16) -> Vec<u8> {
+    let mut frame = vec![0xFF, 0xF8];
+    if block_size == 192 {
+        frame.extend([0x19, 0x18, number]);
+    } else {
+        frame.extend([0x69, 0x18, number, (block_size - 1) as u8]);
+    }
+    frame.push(crc8(&frame));
+    for value in [0x1234u16 + number as u16, 0x4321] {
+        frame.push(0);
+        frame.extend(value.to_be_bytes());
+    }
+    frame.extend(crc16(&frame).to_be_bytes());
+    frame
+}
+
+fn metadata_block(last: bool, block_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut block = vec![(last as u8) << 7 | block_type];
+    block.extend(&(data.len() as u32).to_be_bytes()[1..]);
+    block.extend(data);
+    block
+}
+
+fn flac() -> (Vec<u8>, Vec<Vec<u8>>, Vec<u8>) {
+    let mut streaminfo = vec![0, 192, 0, 192, 0, 0, 0, 0, 0, 0];
+    // sample rate, channels - 1, bits per sample - 1, total samples
+    let packed: u64 = 44100 << 44 | 1 << 41 | 15 << 36 | 868;
+    streaminfo.extend(packed.to_be_bytes());
+    streaminfo.extend([0; 16]);
+    let comment = b"\x05\0\0\0flac1\0\0\0\0".to_vec();
+
+    let mut data = b"fLaC".to_vec();
+    data.extend(metadata_block(false, 0, &streaminfo));
+    data.extend(metadata_block(false, 1, &[0; 16]));
+    data.extend(metadata_block(true, 4, &comment));
+    let frames: Vec<Vec<u8>> = (0..5)
+        .map(|i| flac_frame(i, if i < 4 { 192 } else { 100 }))
+        .collect();
+    data.extend(frames.concat());
+
+    // FullBox header, then the metadata blocks but PADDING
+    let mut dfla = vec![0; 4];
+    dfla.extend(metadata_block(false, 0, &streaminfo));
+    dfla.extend(metadata_block(true, 4, &comment));
+    (data, frames, dfla)
+}
+
+#[test]
+fn native_flac() {
+    let (data, frames, dfla) = flac();
+    let (res, mp4) = mux_data("audio.flac", &data, &["--name", "FLAC"]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    assert_eq!(audio_entry(&mp4), (&b"fLaC"[..], 2, 16, 44100));
+    assert_eq!(find_boxes(&mp4, b"dfLa"), [&dfla[..]]);
+
+    let sizes: Vec<u32> = frames.iter().map(|f| f.len() as u32).collect();
+    assert_eq!(&u32s(find_boxes(&mp4, b"stsz")[0])[3..], &sizes[..]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 2, 4, 192, 1, 100]);
+    assert!(find_boxes(&mp4, b"elst").is_empty());
+}
+
+#[test]
+fn flac_with_corrupt_frame_fails() {
+    let (mut data, _, _) = flac();
+    let len = data.len();
+    data[len - 20] ^= 0x40;
+    let (res, _) = mux_data("corrupt.flac", &data, &[]);
+    assert!(!res.status.success());
+}
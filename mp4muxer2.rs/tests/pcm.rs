@@ -0,0 +1,169 @@
+mod common;
+use common::{audio_entry, find_boxes, mux_data, u32s};
+
+fn mux_pcm(name: &str, data: &[u8], extra: &[&str]) -> Vec<u8> {
+    let (res, mp4) = mux_data(name, data, extra);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+    mp4
+}
+
+const KSDATAFORMAT_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/* WAVEFORMATEX, WAVEFORMATEXTENSIBLE if there is a channel mask */
+fn fmt(format: u16, channels: u16, rate: u32, bits: u16, mask: Option<u32>) -> Vec<u8> {
+    let block_align = channels * bits / 8;
+    let mut fmt = Vec::new();
+    fmt.extend(if mask.is_some() { 0xFFFE } else { format }.to_le_bytes());
+    fmt.extend(channels.to_le_bytes());
+    fmt.extend(rate.to_le_bytes());
+    fmt.extend((rate * block_align as u32).to_le_bytes());
+    fmt.extend(block_align.to_le_bytes());
+    fmt.extend(bits.to_le_bytes());
+    if let Some(mask) = mask {
+        fmt.extend(22u16.to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        fmt.extend(mask.to_le_bytes());
+        fmt.extend(format.to_le_bytes());
+        fmt.extend(KSDATAFORMAT_TAIL);
+    }
+    fmt
+}
+
+fn riff_chunk(id: &[u8; 4], size: u32, data: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend(size.to_le_bytes());
+    chunk.extend(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn pcm_data(frames: usize, block_align: usize) -> Vec<u8> {
+    (0..frames * block_align)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect()
+}
+
+#[test]
+fn wav_extensible_5_1() {
+    // 24 bit 5.1 with side surrounds, 0.6 s
+    let data = pcm_data(28800, 18);
+    let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+    wav.extend(riff_chunk(b"fmt ", 40, &fmt(1, 6, 48000, 24, Some(0x60F))));
+    wav.extend(riff_chunk(b"LIST", 3, b"abc"));
+    wav.extend(riff_chunk(b"data", data.len() as u32, &data));
+
+    let mp4 = mux_pcm("stem.wav", &wav, &[]);
+    assert_eq!(audio_entry(&mp4), (&b"ipcm"[..], 6, 24, 48000));
+    // little endian, 24 bit
+    assert_eq!(find_boxes(&mp4, b"pcmC"), [&[0, 0, 0, 0, 1, 24][..]]);
+    // channel structured, L R C LFE Ls Rs
+    assert_eq!(
+        find_boxes(&mp4, b"chnl"),
+        [&[0, 0, 0, 0, 1, 0, 0, 1, 2, 3, 4, 5][..]]
+    );
+
+    // a sample per 250 ms chunk span
+    assert_eq!(
+        &u32s(find_boxes(&mp4, b"stsz")[0])[3..],
+        [12000 * 18, 12000 * 18, 4800 * 18]
+    );
+    assert_eq!(
+        u32s(find_boxes(&mp4, b"stts")[0]),
+        [0, 2, 2, 12000, 1, 4800]
+    );
+    assert_eq!(
+        find_boxes(&mp4, b"mdat")[0],
+        &data[..],
+        "the samples are the 'data' chunk"
+    );
+}
+
+#[test]
+fn rf64_float_with_chunk_size() {
+    let data = pcm_data(5000, 8);
+    let mut ds64 = Vec::new();
+    ds64.extend(0u64.to_le_bytes());
+    ds64.extend((data.len() as u64).to_le_bytes());
+    ds64.extend(5000u64.to_le_bytes());
+    ds64.extend(0u32.to_le_bytes());
+
+    let mut wav = b"RF64\xff\xff\xff\xffWAVE".to_vec();
+    wav.extend(riff_chunk(b"ds64", 28, &ds64));
+    wav.extend(riff_chunk(b"fmt ", 16, &fmt(3, 2, 44100, 32, None)));
+    wav.extend(riff_chunk(b"data", 0xFFFF_FFFF, &data));
+    // not audio
+    wav.extend(riff_chunk(b"JUNK", 4, b"junk"));
+
+    let mp4 = mux_pcm(
+        "float.rf64",
+        &wav,
+        &["--chunk-size", "16000", "--interleave", "100"],
+    );
+    assert_eq!(audio_entry(&mp4), (&b"fpcm"[..], 2, 32, 44100));
+    assert_eq!(find_boxes(&mp4, b"pcmC"), [&[0, 0, 0, 0, 1, 32][..]]);
+    // stereo ChannelConfiguration, nothing omitted
+    assert_eq!(
+        find_boxes(&mp4, b"chnl"),
+        [&[0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0][..]]
+    );
+
+    // 100 ms are 4410 frames, 16000 bytes only 2000
+    assert_eq!(
+        &u32s(find_boxes(&mp4, b"stsz")[0])[3..],
+        [16000, 16000, 8000]
+    );
+    assert_eq!(find_boxes(&mp4, b"mdat")[0], &data[..]);
+}
+
+fn w64_chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    const TAIL: [u8; 12] = [
+        0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A,
+    ];
+    let mut chunk = id.to_vec();
+    chunk.extend(TAIL);
+    chunk.extend((24 + data.len() as u64).to_le_bytes());
+    chunk.extend(data);
+    chunk.resize(chunk.len().next_multiple_of(8), 0);
+    chunk
+}
+
+#[test]
+fn w64_as_quicktime_lpcm() {
+    let data = pcm_data(5000, 2);
+    let mut w64 = vec![
+        b'r', b'i', b'f', b'f', 0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00,
+        0x00,
+    ];
+    w64.extend(0u64.to_le_bytes());
+    w64.extend(&w64_chunk(b"wave", &[])[..16]);
+    w64.extend(w64_chunk(b"fmt ", &fmt(1, 1, 8000, 16, None)));
+    w64.extend(w64_chunk(b"data", &data));
+
+    let mp4 = mux_pcm("mono.w64", &w64, &["--mpeg4-brand", "qt  "]);
+    let stsd = find_boxes(&mp4, b"stsd")[0];
+    assert_eq!(&stsd[12..16], b"lpcm");
+    let mut lpcm = vec![0, 2, 0, 0, 0, 0, 0, 0];
+    lpcm.extend([0, 3, 0, 16, 0xFF, 0xFE, 0, 0, 0, 1, 0, 0, 0, 0, 0, 72]);
+    lpcm.extend(8000f64.to_be_bytes());
+    for v in [1, 0x7F00_0000, 16, 0x4 | 0x8, 2, 1u32] {
+        lpcm.extend(v.to_be_bytes());
+    }
+    assert_eq!(&stsd[24..], &lpcm[..]);
+    assert!(find_boxes(&mp4, b"pcmC").is_empty());
+
+    // the sample tables count frames
+    assert_eq!(u32s(find_boxes(&mp4, b"stsz")[0]), [0, 2, 5000]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stts")[0]), [0, 1, 5000, 1]);
+    assert_eq!(
+        u32s(find_boxes(&mp4, b"stsc")[0]),
+        [0, 2, 1, 2000, 1, 3, 1000, 1]
+    );
+}
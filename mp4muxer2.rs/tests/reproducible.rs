@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+mod common;
+use common::{find_boxes, signal, temp_file, u32_at};
+
+/* muxes an EC-3 and an AC-3 track with the SOURCE_DATE_EPOCH of the environment, if any */
+fn mux_two_tracks(output: &Path, extra: &[&str], source_date_epoch: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mp4muxer2"));
+    command
+        .arg("-i")
+        .arg(signal("7ch_ddp_25fps_channel_id.ec3"))
+        .arg("-i")
+        .arg(signal("5ch_dd_25fps_channel_id.ac3"))
+        .arg("-o")
+        .arg(output)
+        .arg("--overwrite")
+        .args(extra);
+    match source_date_epoch {
+        Some(epoch) => command.env("SOURCE_DATE_EPOCH", epoch),
+        None => command.env_remove("SOURCE_DATE_EPOCH"),
+    };
+    command.output().expect("failed to run mp4muxer2")
+}
+
+/* muxes and returns the output file data */
+fn mux_data(name: &str, extra: &[&str], source_date_epoch: Option<&str>) -> Vec<u8> {
+    let output = temp_file(name);
+    let res = mux_two_tracks(&output, extra, source_date_epoch);
+    let data = fs::read(&output).unwrap_or_default();
+    let _ = fs::remove_file(&output);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    data
+}
+
+/* the creation and modification time of a version 0 'mvhd', after its version and flags */
+fn mvhd_times(data: &[u8]) -> (u32, u32) {
+    let mvhd = find_boxes(data, b"mvhd")[0];
+    (u32_at(mvhd, 4), u32_at(mvhd, 8))
+}
+
+#[test]
+fn reproducible_output_is_byte_identical() {
+    let a = mux_data("repro_1.mp4", &["--reproducible"], None);
+    // make sure the wall clock moved on between the two runs
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let b = mux_data("repro_2.mp4", &["--reproducible"], None);
+
+    assert!(!a.is_empty());
+    assert!(a == b, "reproducible outputs differ");
+}
+
+#[test]
+fn creation_time_is_written_to_mvhd() {
+    let data = mux_data(
+        "ctime.mp4",
+        &["--creation-time", "2022-12-10T12:00:00+01:00"],
+        None,
+    );
+
+    // 2022-12-10T11:00:00Z = 1670670000 seconds since 1970 + 2082844800 since 1904
+    assert_eq!(
+        mvhd_times(&data),
+        (1670670000 + 2082844800, 1670670000 + 2082844800)
+    );
+
+    // the MP4 epoch itself is a time like any other, not the current time
+    let data = mux_data(
+        "ctime_1904.mp4",
+        &["--creation-time", "1904-01-01T00:00:00Z"],
+        None,
+    );
+    assert_eq!(mvhd_times(&data), (0, 0));
+
+    let data = mux_data("ctime_epoch.mp4", &["--reproducible"], Some("-2082844800"));
+    assert_eq!(mvhd_times(&data), (0, 0));
+}
+
+#[test]
+fn source_date_epoch_out_of_range_is_rejected() {
+    let output = temp_file("ctime_range.mp4");
+
+    for epoch in ["-2082844801", "9223372036854775807", "abc"] {
+        let res = mux_two_tracks(&output, &["--reproducible"], Some(epoch));
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(!res.status.success(), "{}", epoch);
+        assert!(
+            stderr.contains("Invalid SOURCE_DATE_EPOCH value"),
+            "{}: {}",
+            epoch,
+            stderr
+        );
+    }
+    let _ = fs::remove_file(&output);
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::PathBuf;
+
+mod common;
+use common::{find_boxes, mux, signal, temp_file, u32_at};
+
+const FRAME_SIZE: usize = 1536; // AC-3 at 384 kbit/s and 48 kHz
+const CHANGE_AT_FRAME: usize = 219;
+
+/* an AC-3 stream whose bit stream mode changes mid-stream, which calls for a new 'dac3' */
+fn stream_with_config_change(name: &str) -> PathBuf {
+    let mut data = fs::read(signal("5ch_dd_25fps_channel_id.ac3")).unwrap();
+    for frame in data.chunks_mut(FRAME_SIZE).skip(CHANGE_AT_FRAME) {
+        assert_eq!(&frame[..2], &[0x0B, 0x77]);
+        frame[5] = (frame[5] & 0xF8) | 1; // bsmod: music and effects
+    }
+
+    let input = temp_file(name);
+    fs::write(&input, data).unwrap();
+    input
+}
+
+#[test]
+fn config_change_starts_new_sample_description() {
+    let input = stream_with_config_change("sd_change.ac3");
+    let output = temp_file("sd_change.mp4");
+
+    let res = mux(&input, &output, &["--verbose"]);
+    let data = fs::read(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    let _ = fs::remove_file(&input);
+    assert!(res.status.success());
+
+    // two 'ac-3' entries with a 'dac3' each, differing in bsmod
+    let stsd = find_boxes(&data, b"stsd");
+    assert_eq!(u32_at(stsd[0], 4), 2);
+    let dac3 = find_boxes(&data, b"dac3");
+    assert_eq!(dac3.len(), 2);
+    let bsmod = |dac3: &[u8]| ((dac3[0] & 0x01) << 2) | (dac3[1] >> 6);
+    assert_eq!(bsmod(dac3[0]), 0);
+    assert_eq!(bsmod(dac3[1]), 1);
+
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    let report = format!(
+        "sample description 2 starts at sample {}",
+        CHANGE_AT_FRAME + 1
+    );
+    assert!(stdout.contains(&report), "{}", stdout);
+}
+
+#[test]
+fn config_change_is_rejected_with_single_sample_description() {
+    let input = stream_with_config_change("sd_single.ac3");
+    let output = temp_file("sd_single.mp4");
+
+    let res = mux(&input, &output, &["--sample-descriptions", "single"]);
+    let _ = fs::remove_file(&output);
+    let _ = fs::remove_file(&input);
+
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("EMA_MP4_MUXED_MULTI_SD_ERR"));
+}
+
+#[test]
+fn fragments_signal_sample_description_index() {
+    let input = stream_with_config_change("sd_frag.ac3");
+    let output = temp_file("sd_frag.mp4");
+
+    let res = mux(&input, &output, &["--output-format", "frag-mp4"]);
+    let data = fs::read(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    let _ = fs::remove_file(&input);
+    assert!(res.status.success());
+
+    // each 'traf' carries the index of the entry its samples use, and no fragment
+    // spans the configuration change
+    let mut sample = 0;
+    for (tfhd, trun) in find_boxes(&data, b"tfhd")
+        .into_iter()
+        .zip(find_boxes(&data, b"trun"))
+    {
+        let flags = u32_at(tfhd, 0) & 0xFFFFFF;
+        assert!(flags & 0x2 != 0);
+        let index = u32_at(tfhd, if flags & 0x1 != 0 { 16 } else { 8 });
+
+        let sample_count = u32_at(trun, 4) as usize;
+        let expected = if sample < CHANGE_AT_FRAME { 1 } else { 2 };
+        assert_eq!(index, expected, "fragment starting at sample {}", sample);
+        assert!(sample >= CHANGE_AT_FRAME || sample + sample_count <= CHANGE_AT_FRAME);
+        sample += sample_count;
+    }
+    assert_eq!(
+        sample * FRAME_SIZE,
+        fs::metadata(signal("5ch_dd_25fps_channel_id.ac3"))
+            .unwrap()
+            .len() as usize
+    );
+}
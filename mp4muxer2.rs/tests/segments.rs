@@ -0,0 +1,289 @@
+use std::fs;
+use std::path::PathBuf;
+
+mod common;
+use common::{box_tree, read_boxes, run_in, signal, temp_file, u32_at};
+
+/* a directory of its own for the segments of a test */
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = temp_file(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn types(data: &[u8]) -> Vec<[u8; 4]> {
+    box_tree(data).iter().map(|b| b.box_type).collect()
+}
+
+#[test]
+fn init_and_media_segments() {
+    let dir = temp_dir("segments");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3.to_str().unwrap(),
+            "-o",
+            "audio.mp4",
+            "--output-format",
+            "frag-mp4",
+            "--init-segment",
+            "$RepresentationID$_init.mp4",
+            "--segment-template",
+            "$RepresentationID$_$Number%03d$_$Time$.m4s",
+        ],
+    );
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    let whole = fs::read(dir.join("audio.mp4")).unwrap();
+    let init = fs::read(dir.join("audio_init.mp4")).unwrap();
+    assert_eq!(types(&init), [*b"ftyp", *b"moov"]);
+    assert_eq!(init, whole[..init.len()]);
+
+    let moof_count = types(&whole).iter().filter(|t| t == &b"moof").count();
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|n| n.ends_with(".m4s"))
+        .collect();
+    names.sort();
+    assert!(moof_count > 1);
+    assert_eq!(names.len(), moof_count);
+
+    let mut joined = init.clone();
+    let mut next_time = 0;
+    for (i, name) in names.iter().enumerate() {
+        let segment = fs::read(dir.join(name)).unwrap();
+        let boxes = box_tree(&segment);
+        assert_eq!(types(&segment), [*b"styp", *b"sidx", *b"moof", *b"mdat"]);
+
+        // a version 0 'sidx' referencing the rest of the segment from the end of the 'sidx'
+        let sidx = &boxes[1];
+        assert_eq!(sidx.version(), 0);
+        let earliest_presentation_time = sidx.uint("earliest_presentation_time").unwrap();
+        let reference = &sidx.field("references").unwrap().as_list()[0];
+        assert_eq!(
+            reference.uint("referenced_size").unwrap(),
+            segment.len() as u64 - sidx.offset - sidx.size
+        );
+        assert_eq!(earliest_presentation_time, next_time);
+        next_time += reference.uint("subsegment_duration").unwrap();
+
+        // the fragment sequence numbers continue from segment to segment and name the segment
+        let moof = boxes[2].offset as usize;
+        assert_eq!(u32_at(&segment, moof + 20), i as u32 + 1);
+        assert_eq!(
+            *name,
+            format!("audio_{:03}_{}.m4s", i + 1, earliest_presentation_time)
+        );
+
+        joined.extend(&segment);
+    }
+
+    // the segments played after the init segment form a valid file
+    fs::write(dir.join("joined.mp4"), &joined).unwrap();
+    let res = run_in(&dir, &["verify", "joined.mp4"]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn segment_names_next_to_output_file() {
+    let dir = temp_dir("segment_names");
+    fs::create_dir_all(dir.join("v1.2")).unwrap();
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3.to_str().unwrap(),
+            "-o",
+            "v1.2/out.mp4",
+            "--output-format",
+            "frag-mp4",
+            "--init-segment",
+            "$RepresentationID$/init.mp4",
+            "--segment-template",
+            "$RepresentationID$/$Number$.m4s",
+            "--segment-start-number",
+            "0",
+            "--atomic-segments",
+        ],
+    );
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+
+    // the names are relative to the directory of the output file, whose name has a dot in it
+    let whole = fs::read(dir.join("v1.2/out.mp4")).unwrap();
+    let segment_dir = dir.join("v1.2/out");
+    let init = fs::read(segment_dir.join("init.mp4")).unwrap();
+    assert_eq!(init, whole[..init.len()]);
+
+    // numbered from the start number, no temporary file left behind
+    let moof_count = types(&whole).iter().filter(|t| t == &b"moof").count();
+    let mut names: Vec<String> = fs::read_dir(&segment_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    let mut expected: Vec<String> = (0..moof_count).map(|n| format!("{}.m4s", n)).collect();
+    expected.push("init.mp4".to_string());
+    expected.sort();
+    assert_eq!(names, expected);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sidx_version_follows_earliest_presentation_time() {
+    let dir = temp_dir("segments_v1");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let mux_segments = |max_frag_duration: &str| {
+        // 32 ms frames of 32000000 ticks pass the 32 bits of time after 4.3 seconds
+        run_in(
+            &dir,
+            &[
+                "-i",
+                ec3.to_str().unwrap(),
+                "--timescale",
+                "1000000000",
+                "-o",
+                "a.mp4",
+                "--output-format",
+                "frag-mp4",
+                "--mpeg4-max-frag-duration",
+                max_frag_duration,
+                "--segment-template",
+                "a_$Number%02d$.m4s",
+                "--overwrite",
+            ],
+        )
+    };
+
+    let res = mux_segments("2000");
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|n| n.ends_with(".m4s"))
+        .collect();
+    names.sort();
+    assert!(names.len() > 3);
+    for name in &names {
+        let boxes = read_boxes(&dir.join(name)).unwrap();
+        let sidx = &boxes[1];
+        let earliest_presentation_time = sidx.uint("earliest_presentation_time").unwrap();
+        let version = if earliest_presentation_time > u32::MAX as u64 {
+            1
+        } else {
+            0
+        };
+        assert_eq!(sidx.version(), version, "{}", name);
+        assert_eq!(sidx.size, if version == 1 { 52 } else { 44 });
+    }
+    let boxes = read_boxes(&dir.join(names.last().unwrap())).unwrap();
+    assert_eq!(boxes[1].version(), 1);
+
+    // a segment of 5 seconds doesn't fit the 32-bit subsegment_duration
+    let res = mux_segments("5000");
+    assert!(!res.status.success());
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("32-bit subsegment_duration"), "{}", err);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn segment_options_checked() {
+    let dir = temp_dir("segment_options");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let ec3 = ec3.to_str().unwrap();
+
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3,
+            "-o",
+            "a.mp4",
+            "--segment-template",
+            "a_$Number$.m4s",
+        ],
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("fragmented output formats only"), "{}", err);
+
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3,
+            "-o",
+            "a.mp4",
+            "--output-format",
+            "frag-mp4",
+            "--segment-template",
+            "a.m4s",
+        ],
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("needs $Number$ or $Time$"), "{}", err);
+
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3,
+            "-i",
+            ec3,
+            "-o",
+            "a.mp4",
+            "--output-format",
+            "cmaf",
+            "--init-segment",
+            "init.mp4",
+        ],
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(err.contains("needs $RepresentationID$"), "{}", err);
+
+    let res = run_in(
+        &dir,
+        &[
+            "-i",
+            ec3,
+            "-o",
+            "a.mp4",
+            "--output-format",
+            "frag-mp4",
+            "--segment-start-number",
+            "0",
+        ],
+    );
+    let err = String::from_utf8_lossy(&res.stderr);
+    assert!(
+        err.contains("'--segment-start-number' requires '--segment-template'"),
+        "{}",
+        err
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,115 @@
+use std::fs;
+
+mod common;
+use common::{find_boxes, mux_inputs, signal, temp_file, u32_at};
+
+/* returns (track_ID, enabled, alternate_group) of each 'tkhd' */
+fn track_headers(data: &[u8]) -> Vec<(u32, bool, u16)> {
+    find_boxes(data, b"tkhd")
+        .iter()
+        .map(|tkhd| {
+            let flags = u32_at(tkhd, 0) & 0xFFFFFF;
+            let times = if tkhd[0] == 1 { 16 } else { 8 };
+            let track_id = u32_at(tkhd, 4 + times);
+            // reserved, duration, reserved[2] and layer precede alternate_group
+            let group_pos = 4 + times + 8 + times / 2 + 8 + 2;
+            let group = u16::from_be_bytes(tkhd[group_pos..group_pos + 2].try_into().unwrap());
+            (track_id, flags & 0x1 != 0, group)
+        })
+        .collect()
+}
+
+fn mux_track_headers(name: &str, args: &[&str]) -> Vec<(u32, bool, u16)> {
+    let output = temp_file(name);
+
+    let res = mux_inputs(&output, args);
+    let data = fs::read(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    assert!(res.status.success());
+
+    let mut headers = track_headers(&data);
+    headers.sort_unstable();
+    headers
+}
+
+#[test]
+fn audio_tracks_are_grouped_by_default() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let args = ["-i", ac3.to_str().unwrap(), "-i", ec3.to_str().unwrap()];
+
+    assert_eq!(
+        mux_track_headers("tkhd_default.mp4", &args),
+        [(1, true, 2), (2, false, 2)]
+    );
+}
+
+#[test]
+fn track_header_options_are_honoured() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let aac = signal("Blue_Devils_30s.aac");
+
+    // the explicitly enabled track takes over from the first one in the group,
+    // and automatic track IDs skip those given explicitly
+    let args = [
+        "-i",
+        ac3.to_str().unwrap(),
+        "-i",
+        ec3.to_str().unwrap(),
+        "--enabled",
+        "--track-id",
+        "1",
+        "-i",
+        aac.to_str().unwrap(),
+        "--alternate-group",
+        "0",
+        "--disabled",
+    ];
+    assert_eq!(
+        mux_track_headers("tkhd_options.mp4", &args),
+        [(1, true, 2), (2, false, 2), (3, false, 0)]
+    );
+
+    let args = [
+        "-i",
+        ac3.to_str().unwrap(),
+        "--track-id",
+        "10",
+        "--alternate-group",
+        "7",
+        "-i",
+        ec3.to_str().unwrap(),
+        "--alternate-group",
+        "7",
+    ];
+    assert_eq!(
+        mux_track_headers("tkhd_ids.mp4", &args),
+        [(10, true, 7), (11, false, 7)]
+    );
+}
+
+#[test]
+fn duplicate_track_ids_are_rejected() {
+    let ac3 = signal("5ch_dd_25fps_channel_id.ac3");
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file("tkhd_duplicate.mp4");
+
+    let res = mux_inputs(
+        &output,
+        &[
+            "-i",
+            ac3.to_str().unwrap(),
+            "--track-id",
+            "2",
+            "-i",
+            ec3.to_str().unwrap(),
+            "--track-id",
+            "2",
+        ],
+    );
+    let _ = fs::remove_file(&output);
+
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Track ID 2"));
+}
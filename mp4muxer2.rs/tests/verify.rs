@@ -0,0 +1,188 @@
+use std::fs;
+
+mod common;
+use common::{find_payload_offsets, run, signal, temp_file};
+
+fn mux_ec3(name: &str, extra: &[&str]) -> Vec<u8> {
+    let ec3 = signal("7ch_ddp_25fps_channel_id.ec3");
+    let output = temp_file(name);
+
+    let mut args = vec![
+        "-i",
+        ec3.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--overwrite",
+    ];
+    args.extend(extra);
+    assert!(run(&args).status.success());
+
+    let data = fs::read(&output).unwrap();
+    let _ = fs::remove_file(&output);
+    data
+}
+
+/* returns the exit code and the JSON report of verifying the data */
+fn verify(name: &str, data: &[u8]) -> (i32, String) {
+    let input = temp_file(name);
+    fs::write(&input, data).unwrap();
+
+    let res = run(&["verify", input.to_str().unwrap(), "--json"]);
+    let _ = fs::remove_file(&input);
+
+    (
+        res.status.code().unwrap(),
+        String::from_utf8(res.stdout).unwrap(),
+    )
+}
+
+fn bx(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = (8 + payload.len() as u32).to_be_bytes().to_vec();
+    data.extend(box_type);
+    data.extend(payload);
+    data
+}
+
+fn full_box(box_type: &[u8; 4], version_flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = version_flags.to_be_bytes().to_vec();
+    data.extend(payload);
+    bx(box_type, &data)
+}
+
+fn u32s(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_be_bytes()).collect()
+}
+
+/* a file with one video track of three 100 byte samples in one chunk, the sample entry
+ * carrying the Dolby Vision configuration box */
+fn dv_file(
+    entry: &[u8; 4],
+    config: &[u8; 4],
+    profile: u16,
+    level: u16,
+    compatibility_id: u8,
+) -> Vec<u8> {
+    let mut visual = vec![0; 6];
+    visual.extend([0, 1]);
+    visual.extend([0; 16]);
+    visual.extend([0x07, 0x80, 0x04, 0x38]);
+    visual.extend(u32s(&[0x480000, 0x480000, 0]));
+    visual.extend([0, 1]);
+    visual.extend([0; 32]);
+    visual.extend([0, 0x18, 0xFF, 0xFF]);
+    let mut hvcc = vec![
+        1, 2, 0x20, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 120, 0xF0, 0, 0xFC, 0xFD, 0xFA, 0xFA, 0, 0, 0x0F,
+    ];
+    hvcc.push(0);
+    visual.extend(bx(b"hvcC", &hvcc));
+    let bits = profile << 9 | level << 3 | 0x5;
+    let mut dv = vec![1, 0];
+    dv.extend(bits.to_be_bytes());
+    dv.push(compatibility_id << 4);
+    dv.extend([0; 19]);
+    visual.extend(bx(config, &dv));
+
+    let stbl = [
+        full_box(b"stsd", 0, &[u32s(&[1]), bx(entry, &visual)].concat()),
+        full_box(b"stts", 0, &u32s(&[1, 3, 1000])),
+        full_box(b"stsc", 0, &u32s(&[1, 1, 3, 1])),
+        full_box(b"stsz", 0, &u32s(&[100, 3])),
+    ]
+    .concat();
+
+    let ftyp = bx(b"ftyp", b"mp42\0\0\0\x01mp42dby1isom");
+    let mut tkhd = u32s(&[0, 0, 1, 0, 3000, 0, 0, 0, 0]);
+    tkhd.extend([0; 36]);
+    tkhd.extend(u32s(&[1920 << 16, 1080 << 16]));
+
+    // the chunk offset follows from the size of the boxes before 'mdat'
+    let moov = |chunk_offset: u32| {
+        let stbl = [
+            stbl.clone(),
+            full_box(b"stco", 0, &u32s(&[1, chunk_offset])),
+        ]
+        .concat();
+        let minf = bx(b"minf", &bx(b"stbl", &stbl));
+        let trak = [full_box(b"tkhd", 3, &tkhd), bx(b"mdia", &minf)].concat();
+        bx(b"moov", &bx(b"trak", &trak))
+    };
+    let chunk_offset = (ftyp.len() + moov(0).len() + 8) as u32;
+
+    [ftyp, moov(chunk_offset), bx(b"mdat", &[0; 300])].concat()
+}
+
+#[test]
+fn muxed_files_pass() {
+    let (code, report) = verify("verify.mp4", &mux_ec3("verify_src.mp4", &[]));
+    assert_eq!(code, 0, "{}", report);
+    assert!(report.contains("\"status\":\"passed\",\"errors\":[],\"warnings\":[]"));
+
+    let frag = mux_ec3("verify_frag_src.mp4", &["--output-format", "frag-mp4"]);
+    let (code, report) = verify("verify_frag.mp4", &frag);
+    assert_eq!(code, 0, "{}", report);
+}
+
+#[test]
+fn broken_structure_is_an_error() {
+    let mut data = mux_ec3("verify_stco_src.mp4", &[]);
+
+    // move the first chunk behind the end of the file
+    let pos = find_payload_offsets(&data, b"stco")[0] + 8;
+    data[pos..pos + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+    let (code, report) = verify("verify_stco.mp4", &data);
+    assert_eq!(code, 1);
+    assert!(report.contains("\"status\":\"errors\""));
+    assert!(report.contains(
+        "{\"check\":\"structure\",\"message\":\"track 1: 1 chunks lie outside 'mdat', the first is chunk 1 at offset 4294967295 (18432 bytes)\"}"
+    ));
+}
+
+#[test]
+fn fragment_not_starting_with_sync_sample_is_an_error() {
+    let mut data = mux_ec3("verify_sync_src.mp4", &["--output-format", "frag-mp4"]);
+
+    // first_sample_flags of the first 'trun' with sample_is_non_sync_sample set
+    let pos = find_payload_offsets(&data, b"trun")[0] + 12;
+    data[pos..pos + 4].copy_from_slice(&0x00010000u32.to_be_bytes());
+
+    let (code, report) = verify("verify_sync.mp4", &data);
+    assert_eq!(code, 1);
+    assert!(report.contains(
+        "{\"check\":\"fragments\",\"message\":\"fragment 1: track 1 does not start with a sync sample\"}"
+    ));
+}
+
+#[test]
+fn missing_dolby_brand_is_a_warning() {
+    let data = mux_ec3("verify_brand_src.mp4", &["--mpeg4-comp-brand", "mp42,isom"]);
+
+    let (code, report) = verify("verify_brand.mp4", &data);
+    assert_eq!(code, 3);
+    assert!(report.contains("\"status\":\"warnings\",\"errors\":[],\"warnings\":[{\"check\":\"brands\",\"message\":\"Dolby tracks ('ec-3') without the 'dby1' brand\"}]"));
+}
+
+#[test]
+fn dolby_vision_configuration_matches_sample_entry() {
+    let (code, report) = verify("verify_dv_8.mp4", &dv_file(b"hvc1", b"dvvC", 8, 6, 4));
+    assert_eq!(code, 0, "{}", report);
+
+    let (code, report) = verify("verify_dv_5.mp4", &dv_file(b"hvc1", b"dvcC", 5, 6, 0));
+    assert_eq!(code, 1);
+    assert!(report.contains("track 1: 'hvc1' sample entry with Dolby Vision profile 5, which has no compatible base layer, calls for 'dvh1' or 'dvhe'"));
+
+    let (code, report) = verify("verify_dv_level.mp4", &dv_file(b"dvh1", b"dvcC", 8, 0, 1));
+    assert_eq!(code, 1);
+    assert!(
+        report.contains("track 1: Dolby Vision profile 8 is signalled in 'dvcC' instead of 'dvvC'")
+    );
+    assert!(report.contains("track 1: invalid Dolby Vision level 0"));
+
+    let (code, report) = verify("verify_dv_av1.mp4", &dv_file(b"dvhe", b"dvvC", 10, 6, 0));
+    assert_eq!(code, 1);
+    assert!(report.contains("track 1: 'dvhe' sample entry does not match Dolby Vision profile 10"));
+
+    let (code, report) = verify("verify_dv_compat.mp4", &dv_file(b"dvhe", b"dvvC", 8, 6, 3));
+    assert_eq!(code, 3);
+    assert!(report.contains("\"warnings\":[{\"check\":\"dolby-vision\",\"message\":\"track 1: base layer compatibility ID 3 is not defined for Dolby Vision profile 8\"}]"));
+}
@@ -0,0 +1,191 @@
+mod common;
+use common::{find_boxes, mux_data, u32s, BitWriter};
+
+const NAL_IDR_N_LP: u8 = 8;
+const NAL_CRA: u8 = 9;
+const NAL_TRAIL: u8 = 0;
+const NAL_SPS: u8 = 15;
+const NAL_PPS: u8 = 16;
+const NAL_PREFIX_APS: u8 = 17;
+const NAL_FD: u8 = 25;
+
+/* NAL unit header with nuh_layer_id 0 and TemporalId 0, emulation prevention applied */
+fn nal(nal_type: u8, rbsp: &[u8]) -> Vec<u8> {
+    let mut nal = vec![0, nal_type << 3 | 1];
+    let mut zeros = 0;
+    for &b in rbsp {
+        if zeros == 2 && b <= 3 {
+            nal.push(3);
+            zeros = 0;
+        }
+        nal.push(b);
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+    }
+    nal
+}
+
+/* Main 10 profile, level 4.1: general_profile_idc/tier, general_level_idc,
+ * frame only constraint without general_constraints_info, no sub profiles */
+const PTL: [u8; 4] = [1 << 1, 65, 0x80, 0];
+
+/* 1920x1088 coded, cropped to 1920x1080, 4:2:0 10 bit */
+fn sps() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(4, 0); // sps_seq_parameter_set_id
+    w.put(4, 0); // sps_video_parameter_set_id
+    w.put(3, 0); // sps_max_sublayers_minus1
+    w.put(2, 1); // sps_chroma_format_idc
+    w.put(2, 2); // sps_log2_ctu_size_minus5
+    w.put(1, 1); // sps_ptl_dpb_hrd_params_present_flag
+    w.bytes(&PTL);
+    w.put(1, 0); // sps_gdr_enabled_flag
+    w.put(1, 0); // sps_ref_pic_resampling_enabled_flag
+    w.put_ue(1920); // sps_pic_width_max_in_luma_samples
+    w.put_ue(1088); // sps_pic_height_max_in_luma_samples
+    w.put(1, 1); // sps_conformance_window_flag
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put_ue(0);
+    w.put_ue(4); // sps_conf_win_bottom_offset
+    w.put(1, 0); // sps_subpic_info_present_flag
+    w.put_ue(2); // sps_bitdepth_minus8
+    w.put(2, 0); // sps_entropy_coding_sync_enabled_flag, sps_entry_point_offsets_present_flag
+    w.put(4, 4); // sps_log2_max_pic_order_cnt_lsb_minus4
+    w.put(8, 0x5A); // rest of the SPS, not parsed
+    nal(NAL_SPS, &w.finish())
+}
+
+fn pps() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(6, 0); // pps_pic_parameter_set_id
+    w.put(4, 0); // pps_seq_parameter_set_id
+    w.put(8, 0x5A);
+    nal(NAL_PPS, &w.finish())
+}
+
+fn aps() -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.put(3, 1); // aps_params_type: LMCS
+    w.put(5, 2); // aps_adaptation_parameter_set_id
+    w.put(16, 0x1234);
+    nal(NAL_PREFIX_APS, &w.finish())
+}
+
+/* a slice with the picture header in the slice header */
+fn slice(nal_type: u8, poc_lsb: u64) -> Vec<u8> {
+    let irap = nal_type == NAL_IDR_N_LP || nal_type == NAL_CRA;
+    let mut w = BitWriter::default();
+    w.put(1, 1); // sh_picture_header_in_slice_header_flag
+    w.put(1, irap as u64); // ph_gdr_or_irap_pic_flag
+    w.put(1, 0); // ph_non_ref_pic_flag
+    if irap {
+        w.put(1, 0); // ph_gdr_pic_flag
+    }
+    w.put(1, !irap as u64); // ph_inter_slice_allowed_flag
+    if !irap {
+        w.put(1, 1); // ph_intra_slice_allowed_flag
+    }
+    w.put_ue(0); // ph_pic_parameter_set_id
+    w.put(8, poc_lsb); // ph_pic_order_cnt_lsb
+    w.put(16, 0xA5A5 ^ poc_lsb);
+    nal(nal_type, &w.finish())
+}
+
+/* the NAL units of each access unit and whether they stay in the sample */
+fn access_units() -> Vec<Vec<(Vec<u8>, bool)>> {
+    vec![
+        vec![
+            (sps(), false),
+            (pps(), false),
+            (aps(), true),
+            (slice(NAL_IDR_N_LP, 0), true),
+        ],
+        vec![
+            (slice(NAL_TRAIL, 4), true),
+            (nal(NAL_FD, &[0xFF, 0x80]), false),
+        ],
+        vec![(slice(NAL_TRAIL, 2), true)],
+        vec![(slice(NAL_TRAIL, 1), true)],
+        vec![(slice(NAL_TRAIL, 3), true)],
+        vec![(pps(), false), (slice(NAL_CRA, 8), true)],
+        vec![(slice(NAL_TRAIL, 9), true)],
+    ]
+}
+
+fn annexb(aus: &[Vec<(Vec<u8>, bool)>]) -> Vec<u8> {
+    aus.iter()
+        .flatten()
+        .flat_map(|(nal, _)| [&[0, 0, 0, 1][..], nal].concat())
+        .collect()
+}
+
+#[test]
+fn vvc_annex_b() {
+    let aus = access_units();
+    let (res, mp4) = mux_data("stream.266", &annexb(&aus), &[]);
+    assert!(
+        res.status.success(),
+        "{}",
+        String::from_utf8_lossy(&res.stdout)
+    );
+
+    let stsd = find_boxes(&mp4, b"stsd")[0];
+    assert_eq!(&stsd[12..16], b"vvc1");
+    // width and height of the VisualSampleEntry
+    assert_eq!(&stsd[16 + 24..16 + 28], &[0x07, 0x80, 0x04, 0x38]);
+
+    let mut vvcc = vec![0, 0, 0, 0, 0xFF];
+    // ols_idx 0, 1 sublayer, constant_frame_rate 0, 4:2:0, 10 bit
+    vvcc.extend([0x00, 0x11, 0x5F]);
+    vvcc.push(1); // num_bytes_constraint_info
+    vvcc.extend(PTL);
+    vvcc.extend([0x07, 0x80, 0x04, 0x40, 0, 0]);
+    vvcc.push(3);
+    for (array, nal, complete) in [
+        (NAL_SPS, sps(), true),
+        (NAL_PPS, pps(), true),
+        (NAL_PREFIX_APS, aps(), false),
+    ] {
+        vvcc.push((complete as u8) << 7 | array);
+        vvcc.extend([0, 1]);
+        vvcc.extend((nal.len() as u16).to_be_bytes());
+        vvcc.extend(nal);
+    }
+    assert_eq!(find_boxes(&mp4, b"vvcC"), [&vvcc[..]]);
+
+    // parameter sets in the sample entry only, filler data dropped
+    let sizes: Vec<u32> = aus
+        .iter()
+        .map(|au| {
+            au.iter()
+                .filter(|(_, keep)| *keep)
+                .map(|(nal, _)| 4 + nal.len() as u32)
+                .sum()
+        })
+        .collect();
+    assert_eq!(&u32s(find_boxes(&mp4, b"stsz")[0])[3..], &sizes[..]);
+    assert_eq!(u32s(find_boxes(&mp4, b"stss")[0]), [0, 2, 1, 6]);
+
+    // pocs 0 4 2 1 3 8 9 at 30 frames per second, shifted by the smallest cts - dts
+    let ctts = u32s(find_boxes(&mp4, b"ctts")[0]);
+    let offsets: Vec<u32> = ctts[2..]
+        .chunks(2)
+        .flat_map(|e| vec![e[1]; e[0] as usize])
+        .collect();
+    assert_eq!(offsets, [2000, 5000, 2000, 0, 1000, 5000, 5000]);
+}
+
+#[test]
+fn vvc_without_picture_header_fails() {
+    let mut w = BitWriter::default();
+    w.put(1, 0); // sh_picture_header_in_slice_header_flag
+    w.put(8, 0x5A);
+    let aus = vec![vec![
+        (sps(), false),
+        (pps(), false),
+        (nal(NAL_IDR_N_LP, &w.finish()), true),
+    ]];
+
+    let (res, _) = mux_data("no_ph.vvc", &annexb(&aus), &[]);
+    assert!(!res.status.success());
+}
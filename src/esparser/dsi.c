@@ -25,10 +25,11 @@
  *   @file dsi.c
  *   @brief Implements decoder specific information handling
  *   For different audio/video codec, there're different DSI definition for each of them. In this file, we 
- *   implements the DSI creation and destroy functions for AVC,HEVC,AAC,AC3,EC3 and AC4. For AVC and HEVC, 
- *   the DSI based on the spec: ISO/IEC 14496-15. For AAC, DSI based on the spec: ISO/IEC 14496-1,ISO/IEC 
+ *   implements the DSI creation and destroy functions for AVC,HEVC,VVC,AV1,AAC,AC3,EC3,AC4,Opus,FLAC,PCM,
+ *   MPEG-H and DTS. For AVC, HEVC and VVC, the DSI based on the spec: ISO/IEC 14496-15. For AAC, DSI based on the spec: ISO/IEC 14496-1,ISO/IEC 
  *   14496-3,ISO/IEC 14496-12 and ISO/IEC 14496-14. For AC3 and EC3, DSI based on the spec: ETSI TS 102 366.
- *   For AC4, DSI based on the spec: ETSI TS 103 190-2.
+ *   For AC4, DSI based on the spec: ETSI TS 103 190-2. For AV1, DSI based on the AV1 Codec ISO Media File
+ *   Format Binding. For MPEG-H, DSI based on ISO/IEC 23008-3, for DTS on ETSI TS 102 114 and ETSI TS 103 491.
 */
 
 #include "utils.h"
@@ -360,3 +361,318 @@ dsi_ac4_create(uint32_t dsi_type)
         return NULL;
     }
 }
+
+/** opus specific */
+static void
+mp4_dsi_opus_destroy(dsi_handle_t dsi)
+{
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_opus_create(void)
+{
+    mp4_dsi_opus_handle_t dsi;
+
+    dsi = (mp4_dsi_opus_handle_t)MALLOC_CHK(sizeof(mp4_dsi_opus_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_opus_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_OPUS;
+        dsi->destroy   = mp4_dsi_opus_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_opus_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_opus_create();
+    default:
+        return NULL;
+    }
+}
+
+/** flac specific */
+static void
+mp4_dsi_flac_destroy(dsi_handle_t dsi)
+{
+    mp4_dsi_flac_handle_t dsi_flac = (mp4_dsi_flac_handle_t)dsi;
+
+    if (dsi_flac->metadata_blocks)
+    {
+        FREE_CHK(dsi_flac->metadata_blocks);
+    }
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_flac_create(void)
+{
+    mp4_dsi_flac_handle_t dsi;
+
+    dsi = (mp4_dsi_flac_handle_t)MALLOC_CHK(sizeof(mp4_dsi_flac_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_flac_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_FLAC;
+        dsi->destroy   = mp4_dsi_flac_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_flac_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_flac_create();
+    default:
+        return NULL;
+    }
+}
+
+/** pcm specific */
+static void
+mp4_dsi_pcm_destroy(dsi_handle_t dsi)
+{
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_pcm_create(void)
+{
+    mp4_dsi_pcm_handle_t dsi;
+
+    dsi = (mp4_dsi_pcm_handle_t)MALLOC_CHK(sizeof(mp4_dsi_pcm_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_pcm_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_PCM;
+        dsi->destroy   = mp4_dsi_pcm_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_pcm_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_pcm_create();
+    default:
+        return NULL;
+    }
+}
+
+/** mpeg-h specific */
+static void
+mp4_dsi_mpegh_destroy(dsi_handle_t dsi)
+{
+    mp4_dsi_mpegh_handle_t dsi_mpegh = (mp4_dsi_mpegh_handle_t)dsi;
+
+    if (dsi_mpegh->config)
+    {
+        FREE_CHK(dsi_mpegh->config);
+    }
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_mpegh_create(void)
+{
+    mp4_dsi_mpegh_handle_t dsi;
+
+    dsi = (mp4_dsi_mpegh_handle_t)MALLOC_CHK(sizeof(mp4_dsi_mpegh_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_mpegh_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_MPEGH;
+        dsi->destroy   = mp4_dsi_mpegh_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_mpegh_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_mpegh_create();
+    default:
+        return NULL;
+    }
+}
+
+/** dts specific */
+static void
+mp4_dsi_dts_destroy(dsi_handle_t dsi)
+{
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_dts_create(void)
+{
+    mp4_dsi_dts_handle_t dsi;
+
+    dsi = (mp4_dsi_dts_handle_t)MALLOC_CHK(sizeof(mp4_dsi_dts_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_dts_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_DTS;
+        dsi->destroy   = mp4_dsi_dts_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_dts_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_dts_create();
+    default:
+        return NULL;
+    }
+}
+
+/** av1 specific */
+static void
+mp4_dsi_av1_destroy(dsi_handle_t dsi)
+{
+    mp4_dsi_av1_handle_t dsi_av1 = (mp4_dsi_av1_handle_t)dsi;
+
+    if (dsi_av1->seq_hdr_obu)
+    {
+        FREE_CHK(dsi_av1->seq_hdr_obu);
+    }
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_av1_create(void)
+{
+    mp4_dsi_av1_handle_t dsi;
+
+    dsi = (mp4_dsi_av1_handle_t)MALLOC_CHK(sizeof(mp4_dsi_av1_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_av1_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_AV1;
+        dsi->destroy   = mp4_dsi_av1_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_av1_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_av1_create();
+    default:
+        return NULL;
+    }
+}
+
+/** vvc specific */
+static void
+mp4_dsi_vvc_destroy(dsi_handle_t dsi)
+{
+    mp4_dsi_vvc_handle_t dsi_vvc = (mp4_dsi_vvc_handle_t)dsi;
+    list_handle_t        lsts[4];
+    it_list_handle_t     it;
+    buf_entry_t *        nalu;
+    int                  i;
+
+    lsts[0] = dsi_vvc->vps_lst;
+    lsts[1] = dsi_vvc->sps_lst;
+    lsts[2] = dsi_vvc->pps_lst;
+    lsts[3] = dsi_vvc->aps_lst;
+
+    it = it_create();
+    for (i = 0; i < 4; i++)
+    {
+        if (!lsts[i])
+        {
+            continue;
+        }
+        it_init(it, lsts[i]);
+        while ((nalu = it_get_entry(it)))
+        {
+            FREE_CHK(nalu->data);
+        }
+        list_destroy(lsts[i]);
+    }
+    it_destroy(it);
+
+    if (dsi_vvc->ptl)
+    {
+        FREE_CHK(dsi_vvc->ptl);
+    }
+    FREE_CHK(dsi);
+}
+
+dsi_handle_t
+mp4_dsi_vvc_create(void)
+{
+    mp4_dsi_vvc_handle_t dsi;
+
+    dsi = (mp4_dsi_vvc_handle_t)MALLOC_CHK(sizeof(mp4_dsi_vvc_t));
+    if (dsi)
+    {
+        memset(dsi, 0, sizeof(mp4_dsi_vvc_t));
+
+        dsi->dsi_type  = DSI_TYPE_MP4FF;
+        dsi->stream_id = STREAM_ID_VVC;
+        dsi->destroy   = mp4_dsi_vvc_destroy;
+
+        return (dsi_handle_t)dsi;
+    }
+    return NULL;
+}
+
+dsi_handle_t
+dsi_vvc_create(uint32_t dsi_type)
+{
+    switch (dsi_type)
+    {
+    case DSI_TYPE_MP4FF:
+        return mp4_dsi_vvc_create();
+    default:
+        return NULL;
+    }
+}
@@ -1380,8 +1380,8 @@ ac4_presentation_v1_info(parser_ac4_handle_t parser_ac4, int32_t index)
         {
             if ( !((parser_ac4->n_presentations == 1) && (parser_ac4->presentation_version[index] != 2)))
             {
-                printf("Error: AC4 Multiple presentation or IMS stream MUST have presentation id!\n");
-                return 1;
+                msglog(NULL, MSGLOG_ERR, "ERROR! AC-4 presentation %d has no presentation ID: required for multiple or IMS presentations\n", index);
+                return EMA_MP4_MUXED_ES_ERR;
             }
         }
 
@@ -1545,7 +1545,7 @@ parser_ac4_toc(parser_ac4_handle_t parser_ac4)
 {
     bbio_handle_t       ds         = parser_ac4->ds;
     uint32_t tmp, payload_base, i,j;
-    uint32_t ret = 0;
+    int32_t ret = 0;
 
     src_byte_align(ds);
     parser_ac4->total_n_substream_groups = 0;
@@ -1608,8 +1608,8 @@ parser_ac4_toc(parser_ac4_handle_t parser_ac4)
         }
     }
     if (parser_ac4->bitstream_version <= 1) {
-        printf("Error: AC4 ES with bitstream version 0 or 1 had been deprecated.\n ");
-        return 1;
+        msglog(NULL, MSGLOG_ERR, "ERROR! AC-4 bitstream version %u is deprecated and not supported\n", parser_ac4->bitstream_version);
+        return EMA_MP4_MUXED_NO_SUPPORT;
     }
     else
     {
@@ -1626,7 +1626,7 @@ parser_ac4_toc(parser_ac4_handle_t parser_ac4)
             ret = ac4_presentation_v1_info(parser_ac4, i);
             if(ret)
             {
-                return 1;
+                return ret;
             }
         }
         parser_ac4->total_n_substream_groups = 1 + parser_ac4->max_group_index;
@@ -1751,7 +1751,7 @@ parser_ac4_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
 
     if(ret)
     {
-        return EMA_MP4_MUXED_ES_ERR;
+        return ret;
     }
 
     if (parser_ac4->sample_num)
@@ -2638,10 +2638,10 @@ parser_ac4_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
     sink_write_bits(snk, 1, parser_ac4->fs_index);
     sink_write_bits(snk, 4, parser_ac4->frame_rate_index);
     
-    /** single presentation and it's IMS, we'll add more presentation */
+    /** IMS presentations are followed by a presentation_version 1 copy unless asked otherwise */
     for(i = 0; i < parser_ac4->n_presentations; i++)
     {
-        if(parser_ac4->presentation_version[i] == 2)
+        if(parser_ac4->presentation_version[i] == 2 && parser->ext_timing.ac4_ims_mode == AC4_IMS_DUPLICATE)
             imsPresentationNum++;
     }
 
@@ -2732,7 +2732,7 @@ parser_ac4_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
         }
 
         /** IMS duplicated presentation DSI */
-        if (parser_ac4->presentation_version[i] == 2)
+        if (parser_ac4->presentation_version[i] == 2 && parser->ext_timing.ac4_ims_mode == AC4_IMS_DUPLICATE)
         {
             uint32_t presentation_bytes = 0, presentation_bits = 0;
             sink_write_bits(snk, 8, 1);
@@ -2764,11 +2764,63 @@ parser_ac4_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
     return 0;
 }
 
+/* the language of the first substream group of the presentation that signals one, "" if none does */
+static void
+presentation_language(parser_ac4_handle_t parser_ac4, uint32_t presentation_idx, char *lang, uint32_t lang_size)
+{
+    uint32_t k, sg_idx, len;
+
+    lang[0] = '\0';
+    for (k = 0; k < parser_ac4->n_substream_groups[presentation_idx] && k < SUBSTREAM_GROUP; k++)
+    {
+        sg_idx = parser_ac4->group_index[presentation_idx][k];
+        if (sg_idx < SUBSTREAM_GROUP && parser_ac4->b_content_type_v2[sg_idx] && parser_ac4->b_language_indicator_v2[sg_idx])
+        {
+            len = parser_ac4->n_language_tag_bytes_v2[sg_idx];
+            if (len > lang_size - 1)
+            {
+                len = lang_size - 1;
+            }
+            memcpy(lang, parser_ac4->language_tag_bytes_v2[sg_idx], len);
+            lang[len] = '\0';
+            return;
+        }
+    }
+}
+
 static void
 parser_ac4_show_info(parser_handle_t parser)
 {
-    msglog(NULL, MSGLOG_INFO, "AC-4 Parser\n");
-    (void)parser;  /* avoid compiler warning */
+    parser_ac4_handle_t parser_ac4 = (parser_ac4_handle_t)parser;
+    char     lang[65];
+    uint32_t i;
+
+    msglog(NULL, MSGLOG_INFO, "AC-4 Parser: bitstream version %u, %u presentation(s)%s\n",
+           parser_ac4->bitstream_version, parser_ac4->n_presentations,
+           parser->ext_timing.ac4_ims_mode == AC4_IMS_DUPLICATE ? ", IMS duplicated" : "");
+    msglog(NULL, MSGLOG_INFO, "  presentation  version  ID   Atmos  language  ch_mode\n");
+    for (i = 0; i < parser_ac4->n_presentations && i < PRESENTATION_NUM; i++)
+    {
+        presentation_language(parser_ac4, i, lang, sizeof(lang));
+        if (parser_ac4->b_presentation_id[i])
+        {
+            msglog(NULL, MSGLOG_INFO, "  %12u  %7u  %-3u  %-5s  %-8s  ", i, parser_ac4->presentation_version[i],
+                   parser_ac4->presentation_id[i], parser_ac4->isAtmos[i] ? "yes" : "no", lang[0] ? lang : "-");
+        }
+        else
+        {
+            msglog(NULL, MSGLOG_INFO, "  %12u  %7u  -    %-5s  %-8s  ", i, parser_ac4->presentation_version[i],
+                   parser_ac4->isAtmos[i] ? "yes" : "no", lang[0] ? lang : "-");
+        }
+        if (parser_ac4->pres_ch_mode[i] == 0xff)
+        {
+            msglog(NULL, MSGLOG_INFO, "objects\n");
+        }
+        else
+        {
+            msglog(NULL, MSGLOG_INFO, "%u\n", parser_ac4->pres_ch_mode[i]);
+        }
+    }
 }
 
 static int32_t
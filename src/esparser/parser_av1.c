@@ -0,0 +1,1121 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_av1.c
+    @brief Implements an AV1 parser
+
+    Reads the low overhead bitstream format (.obu), the length delimited format of Annex B and IVF
+    files. Each temporal unit becomes one sample in the format of the AV1 Codec ISO Media File
+    Format Binding: temporal delimiters and padding are dropped, all OBUs carry an obu_size field.
+*/
+
+#include "utils.h"
+#include "list_itr.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+/** OBU types, see AV1 Bitstream & Decoding Process Specification 6.2.2 */
+#define OBU_SEQUENCE_HEADER         1
+#define OBU_TEMPORAL_DELIMITER      2
+#define OBU_FRAME_HEADER            3
+#define OBU_TILE_GROUP              4
+#define OBU_METADATA                5
+#define OBU_FRAME                   6
+#define OBU_REDUNDANT_FRAME_HEADER  7
+#define OBU_TILE_LIST               8
+#define OBU_PADDING                15
+
+/** metadata types, see 6.7.1 */
+#define METADATA_TYPE_HDR_CLL       1
+#define METADATA_TYPE_HDR_MDCV      2
+#define METADATA_TYPE_ITUT_T35      4
+
+#define AV1_KEY_FRAME               0
+
+#define AV1_INPUT_LOW_OVERHEAD      0
+#define AV1_INPUT_ANNEXB            1
+#define AV1_INPUT_IVF               2
+
+#define IVF_FRAME_HEADER_SIZE      12
+#define AV1_MAX_FRAMERATE         300
+
+/** the ITU-T T.35 prefix of a Dolby Vision RPU: country code, provider code and provider oriented code */
+static const uint8_t dv_rpu_t35_prefix[7] = {0xB5, 0x00, 0x3B, 0x00, 0x00, 0x08, 0x00};
+
+typedef struct av1_seq_hdr_t_
+{
+    uint8_t  seq_profile;
+    uint8_t  reduced_still_picture_header;
+    uint8_t  timing_info_present_flag;
+    uint32_t num_units_in_display_tick;
+    uint32_t time_scale;
+    uint8_t  equal_picture_interval;
+    uint32_t num_ticks_per_picture;
+    uint8_t  seq_level_idx_0;
+    uint8_t  seq_tier_0;
+    uint32_t max_frame_width;
+    uint32_t max_frame_height;
+    uint8_t  high_bitdepth;
+    uint8_t  twelve_bit;
+    uint8_t  mono_chrome;
+    uint8_t  color_description_present_flag;
+    uint8_t  color_primaries;
+    uint8_t  transfer_characteristics;
+    uint8_t  matrix_coefficients;
+    uint8_t  color_range;
+    uint8_t  subsampling_x;
+    uint8_t  subsampling_y;
+    uint8_t  chroma_sample_position;
+} av1_seq_hdr_t;
+
+struct parser_av1_t_
+{
+    PARSER_VIDEO_BASE;
+
+    uint32_t input_format;      /** AV1_INPUT_... */
+    uint32_t ivf_rate;          /** IVF time base: rate/scale is the frame rate for consecutive pts */
+    uint32_t ivf_scale;
+
+    uint8_t *tu_buf;            /** the temporal unit as read from the input */
+    size_t   tu_buf_size;
+    size_t   tu_size;
+
+    size_t   sample_buf_size;   /** allocated size of sample->data */
+
+    av1_seq_hdr_t seq_hdr;      /** the latest sequence header */
+    BOOL     seq_hdr_seen;
+    uint32_t sample_num;
+};
+
+typedef struct parser_av1_t_ parser_av1_t;
+typedef parser_av1_t  *parser_av1_handle_t;
+
+/** Reads a leb128() value from buf; returns the number of bytes it takes or 0 if it's invalid */
+static uint32_t
+leb128_read(const uint8_t *buf, size_t buf_size, uint64_t *value)
+{
+    uint32_t i;
+
+    *value = 0;
+    for (i = 0; i < 8 && i < buf_size; i++)
+    {
+        *value |= (uint64_t)(buf[i] & 0x7f) << (7*i);
+        if (!(buf[i] & 0x80))
+        {
+            return i + 1;
+        }
+    }
+    return 0;
+}
+
+/** Reads a leb128() value from the input; returns the number of bytes it takes or 0 if it's invalid */
+static uint32_t
+leb128_read_ds(bbio_handle_t ds, uint64_t *value)
+{
+    uint32_t i;
+    uint8_t  u8;
+
+    *value = 0;
+    for (i = 0; i < 8; i++)
+    {
+        if (ds->read(ds, &u8, 1) != 1)
+        {
+            return 0;
+        }
+        *value |= (uint64_t)(u8 & 0x7f) << (7*i);
+        if (!(u8 & 0x80))
+        {
+            return i + 1;
+        }
+    }
+    return 0;
+}
+
+/** Writes value as leb128() into buf; returns the number of bytes written */
+static uint32_t
+leb128_write(uint8_t *buf, uint64_t value)
+{
+    uint32_t i = 0;
+
+    do
+    {
+        buf[i] = (uint8_t)(value & 0x7f);
+        value >>= 7;
+        if (value)
+        {
+            buf[i] |= 0x80;
+        }
+        i++;
+    } while (value);
+
+    return i;
+}
+
+static uint32_t
+read_uvlc(bbio_handle_t bs)
+{
+    uint32_t leading_zeros = 0;
+
+    while (leading_zeros < 32 && !src_read_bit(bs))
+    {
+        leading_zeros++;
+    }
+    if (leading_zeros >= 32)
+    {
+        return (uint32_t)-1;
+    }
+    return src_read_bits(bs, leading_zeros) + ((1u << leading_zeros) - 1);
+}
+
+/** Parses the color_config() of the sequence header, see 5.5.2 */
+static void
+parse_color_config(bbio_handle_t bs, av1_seq_hdr_t *seq)
+{
+    uint32_t bit_depth = 8;
+
+    seq->high_bitdepth = (uint8_t)src_read_bit(bs);
+    seq->twelve_bit    = 0;
+    if (seq->seq_profile == 2 && seq->high_bitdepth)
+    {
+        seq->twelve_bit = (uint8_t)src_read_bit(bs);
+        bit_depth       = seq->twelve_bit ? 12 : 10;
+    }
+    else if (seq->seq_profile <= 2)
+    {
+        bit_depth = seq->high_bitdepth ? 10 : 8;
+    }
+
+    seq->mono_chrome = (seq->seq_profile == 1) ? 0 : (uint8_t)src_read_bit(bs);
+
+    seq->color_description_present_flag = (uint8_t)src_read_bit(bs);
+    if (seq->color_description_present_flag)
+    {
+        seq->color_primaries          = (uint8_t)src_read_bits(bs, 8);
+        seq->transfer_characteristics = (uint8_t)src_read_bits(bs, 8);
+        seq->matrix_coefficients      = (uint8_t)src_read_bits(bs, 8);
+    }
+    else
+    {
+        /** unspecified */
+        seq->color_primaries          = 2;
+        seq->transfer_characteristics = 2;
+        seq->matrix_coefficients      = 2;
+    }
+
+    seq->chroma_sample_position = 0;
+    if (seq->mono_chrome)
+    {
+        seq->color_range   = (uint8_t)src_read_bit(bs);
+        seq->subsampling_x = 1;
+        seq->subsampling_y = 1;
+        return;
+    }
+    else if (seq->color_primaries == 1 && seq->transfer_characteristics == 13 && seq->matrix_coefficients == 0)
+    {
+        /** sRGB */
+        seq->color_range   = 1;
+        seq->subsampling_x = 0;
+        seq->subsampling_y = 0;
+    }
+    else
+    {
+        seq->color_range = (uint8_t)src_read_bit(bs);
+        if (seq->seq_profile == 0)
+        {
+            seq->subsampling_x = 1;
+            seq->subsampling_y = 1;
+        }
+        else if (seq->seq_profile == 1)
+        {
+            seq->subsampling_x = 0;
+            seq->subsampling_y = 0;
+        }
+        else if (bit_depth == 12)
+        {
+            seq->subsampling_x = (uint8_t)src_read_bit(bs);
+            seq->subsampling_y = seq->subsampling_x ? (uint8_t)src_read_bit(bs) : 0;
+        }
+        else
+        {
+            seq->subsampling_x = 1;
+            seq->subsampling_y = 0;
+        }
+        if (seq->subsampling_x && seq->subsampling_y)
+        {
+            seq->chroma_sample_position = (uint8_t)src_read_bits(bs, 2);
+        }
+    }
+}
+
+/** Parses sequence_header_obu(), see 5.5.1 */
+static int32_t
+parse_seq_hdr(const uint8_t *payload, size_t payload_size, av1_seq_hdr_t *seq)
+{
+    bbio_handle_t bs;
+    uint32_t      decoder_model_info_present_flag    = 0;
+    uint32_t      initial_display_delay_present_flag = 0;
+    uint32_t      buffer_delay_length                = 0;
+    uint32_t      i;
+
+    if (!payload_size)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    memset(seq, 0, sizeof(av1_seq_hdr_t));
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)payload, payload_size, 0);
+
+    seq->seq_profile = (uint8_t)src_read_bits(bs, 3);
+    src_skip_bits(bs, 1);  /** still_picture */
+    seq->reduced_still_picture_header = (uint8_t)src_read_bit(bs);
+
+    if (seq->reduced_still_picture_header)
+    {
+        seq->seq_level_idx_0 = (uint8_t)src_read_bits(bs, 5);
+    }
+    else
+    {
+        uint32_t operating_points_cnt;
+
+        seq->timing_info_present_flag = (uint8_t)src_read_bit(bs);
+        if (seq->timing_info_present_flag)
+        {
+            seq->num_units_in_display_tick = src_read_bits(bs, 32);
+            seq->time_scale                = src_read_bits(bs, 32);
+            seq->equal_picture_interval    = (uint8_t)src_read_bit(bs);
+            if (seq->equal_picture_interval)
+            {
+                seq->num_ticks_per_picture = read_uvlc(bs) + 1;
+            }
+
+            decoder_model_info_present_flag = src_read_bit(bs);
+            if (decoder_model_info_present_flag)
+            {
+                buffer_delay_length = src_read_bits(bs, 5) + 1;
+                src_skip_bits(bs, 32);  /** num_units_in_decoding_tick */
+                src_skip_bits(bs, 5);   /** buffer_removal_time_length_minus_1 */
+                src_skip_bits(bs, 5);   /** frame_presentation_time_length_minus_1 */
+            }
+        }
+        initial_display_delay_present_flag = src_read_bit(bs);
+
+        operating_points_cnt = src_read_bits(bs, 5) + 1;
+        for (i = 0; i < operating_points_cnt; i++)
+        {
+            uint8_t seq_level_idx, seq_tier = 0;
+
+            src_skip_bits(bs, 12);  /** operating_point_idc */
+            seq_level_idx = (uint8_t)src_read_bits(bs, 5);
+            if (seq_level_idx > 7)
+            {
+                seq_tier = (uint8_t)src_read_bit(bs);
+            }
+            if (decoder_model_info_present_flag && src_read_bit(bs))
+            {
+                /** operating_parameters_info(): decoder/encoder_buffer_delay and low_delay_mode_flag */
+                src_skip_bits(bs, 2*buffer_delay_length + 1);
+            }
+            if (initial_display_delay_present_flag && src_read_bit(bs))
+            {
+                src_skip_bits(bs, 4);  /** initial_display_delay_minus_1 */
+            }
+            if (i == 0)
+            {
+                seq->seq_level_idx_0 = seq_level_idx;
+                seq->seq_tier_0      = seq_tier;
+            }
+        }
+    }
+
+    {
+        uint32_t frame_width_bits  = src_read_bits(bs, 4) + 1;
+        uint32_t frame_height_bits = src_read_bits(bs, 4) + 1;
+
+        seq->max_frame_width  = src_read_bits(bs, frame_width_bits) + 1;
+        seq->max_frame_height = src_read_bits(bs, frame_height_bits) + 1;
+    }
+
+    if (!seq->reduced_still_picture_header && src_read_bit(bs))
+    {
+        /** frame_id_numbers_present_flag */
+        src_skip_bits(bs, 4);  /** delta_frame_id_length_minus_2 */
+        src_skip_bits(bs, 3);  /** additional_frame_id_length_minus_1 */
+    }
+    src_skip_bits(bs, 3);  /** use_128x128_superblock, enable_filter_intra, enable_intra_edge_filter */
+
+    if (!seq->reduced_still_picture_header)
+    {
+        uint32_t enable_order_hint;
+        uint32_t seq_force_screen_content_tools = 2;
+
+        src_skip_bits(bs, 4);  /** enable_interintra_compound, enable_masked_compound, enable_warped_motion, enable_dual_filter */
+        enable_order_hint = src_read_bit(bs);
+        if (enable_order_hint)
+        {
+            src_skip_bits(bs, 2);  /** enable_jnt_comp, enable_ref_frame_mvs */
+        }
+        if (!src_read_bit(bs))
+        {
+            /** !seq_choose_screen_content_tools */
+            seq_force_screen_content_tools = src_read_bit(bs);
+        }
+        if (seq_force_screen_content_tools > 0)
+        {
+            if (!src_read_bit(bs))
+            {
+                /** !seq_choose_integer_mv */
+                src_skip_bits(bs, 1);  /** seq_force_integer_mv */
+            }
+        }
+        if (enable_order_hint)
+        {
+            src_skip_bits(bs, 3);  /** order_hint_bits_minus_1 */
+        }
+    }
+    src_skip_bits(bs, 3);  /** enable_superres, enable_cdef, enable_restoration */
+
+    parse_color_config(bs, seq);
+    /** film_grain_params_present and trailing bits follow */
+
+    bs->destroy(bs);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Converts the HDR metadata OBUs into the parser's mdcv and clli info; the first one found is used */
+static void
+parse_metadata(parser_av1_handle_t parser_av1, const uint8_t *payload, size_t payload_size)
+{
+    uint64_t metadata_type;
+    uint32_t len = leb128_read(payload, payload_size, &metadata_type);
+    bbio_handle_t bs;
+    int32_t  i;
+
+    if (!len || len >= payload_size)
+    {
+        return;
+    }
+    payload      += len;
+    payload_size -= len;
+
+    if (metadata_type == METADATA_TYPE_ITUT_T35)
+    {
+        if (payload_size >= sizeof(dv_rpu_t35_prefix) && !memcmp(payload, dv_rpu_t35_prefix, sizeof(dv_rpu_t35_prefix)))
+        {
+            parser_av1->dv_rpu_nal_flag = 1;
+        }
+        return;
+    }
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)payload, payload_size, 0);
+
+    if (metadata_type == METADATA_TYPE_HDR_CLL && !parser_av1->clli_present && payload_size >= 4)
+    {
+        parser_av1->clli_present                = 1;
+        parser_av1->max_content_light_level     = src_read_u16(bs);
+        parser_av1->max_pic_average_light_level = src_read_u16(bs);
+    }
+    else if (metadata_type == METADATA_TYPE_HDR_MDCV && !parser_av1->mdcv_present && payload_size >= 24)
+    {
+        uint16_t x[3], y[3];
+
+        /** R, G, B in 0.16 fixed point => G, B, R in units of 0.00002 */
+        for (i = 0; i < 3; i++)
+        {
+            x[i] = src_read_u16(bs);
+            y[i] = src_read_u16(bs);
+        }
+        for (i = 0; i < 3; i++)
+        {
+            parser_av1->display_primaries_x[i] = (uint16_t)((x[(i + 1)%3]*50000u + 32768) >> 16);
+            parser_av1->display_primaries_y[i] = (uint16_t)((y[(i + 1)%3]*50000u + 32768) >> 16);
+        }
+        parser_av1->white_point_x = (uint16_t)((src_read_u16(bs)*50000u + 32768) >> 16);
+        parser_av1->white_point_y = (uint16_t)((src_read_u16(bs)*50000u + 32768) >> 16);
+
+        /** luminance_max in 24.8 and luminance_min in 18.14 fixed point => units of 0.0001 cd/m2 */
+        parser_av1->max_display_mastering_luminance = (uint32_t)(((uint64_t)src_read_u32(bs)*10000 + 128) >> 8);
+        parser_av1->min_display_mastering_luminance = (uint32_t)(((uint64_t)src_read_u32(bs)*10000 + 8192) >> 14);
+        parser_av1->mdcv_present = 1;
+    }
+
+    bs->destroy(bs);
+}
+
+static void
+timing_info_update(parser_av1_handle_t parser_av1)
+{
+    av1_seq_hdr_t *seq = &parser_av1->seq_hdr;
+    BOOL es_timing = (seq->timing_info_present_flag && seq->equal_picture_interval &&
+                      seq->num_units_in_display_tick && seq->num_ticks_per_picture && seq->time_scale);
+    uint32_t time_scale = 0, num_units_in_tick = 0;
+
+    if (es_timing)
+    {
+        time_scale        = seq->time_scale;
+        num_units_in_tick = seq->num_units_in_display_tick * seq->num_ticks_per_picture;
+    }
+    else if (parser_av1->input_format == AV1_INPUT_IVF && parser_av1->ivf_rate && parser_av1->ivf_scale &&
+             parser_av1->ivf_rate <= (uint64_t)AV1_MAX_FRAMERATE * parser_av1->ivf_scale)
+    {
+        /** plausible frame rate as the IVF time base */
+        time_scale        = parser_av1->ivf_rate;
+        num_units_in_tick = parser_av1->ivf_scale;
+        es_timing         = TRUE;
+    }
+
+    if (parser_av1->ext_timing.usr_timing && es_timing)
+    {
+        if ((uint64_t)time_scale * parser_av1->ext_timing.num_units_in_tick !=
+            (uint64_t)parser_av1->ext_timing.time_scale * num_units_in_tick)
+        {
+            msglog(NULL, MSGLOG_WARNING, "Frame rate %u/%u set by user conflicts with %u/%u in the timing info of the ES, %s\n",
+                   parser_av1->ext_timing.time_scale, parser_av1->ext_timing.num_units_in_tick,
+                   time_scale, num_units_in_tick,
+                   parser_av1->ext_timing.override_timing ? "using the user's" : "ignoring the user's");
+        }
+        parser_av1->ext_timing.usr_timing = 0;  /** report once */
+    }
+
+    if (parser_av1->ext_timing.override_timing || !es_timing)
+    {
+        parser_av1->time_scale        = parser_av1->ext_timing.time_scale;
+        parser_av1->num_units_in_tick = parser_av1->ext_timing.num_units_in_tick;
+    }
+    else
+    {
+        parser_av1->time_scale        = time_scale;
+        parser_av1->num_units_in_tick = num_units_in_tick;
+    }
+    parser_av1->framerate = parser_av1->time_scale / parser_av1->num_units_in_tick;
+}
+
+static void
+dv_level_update(parser_av1_handle_t parser_av1)
+{
+    uint32_t level = parser_av1->width * parser_av1->height * parser_av1->framerate;
+
+    if (level <= 1280*720*24)
+        parser_av1->dv_level = 1;
+    else if (level <= 1280*720*30)
+        parser_av1->dv_level = 2;
+    else if (level <= 1920*1080*24)
+        parser_av1->dv_level = 3;
+    else if (level <= 1920*1080*30)
+        parser_av1->dv_level = 4;
+    else if (level <= 1920*1080*60)
+        parser_av1->dv_level = 5;
+    else if (level <= 3840*2160*24)
+        parser_av1->dv_level = 6;
+    else if (level <= 3840*2160*30)
+        parser_av1->dv_level = 7;
+    else if (level <= 3840*2160*48)
+        parser_av1->dv_level = 8;
+    else if (level <= 3840*2160*60)
+        parser_av1->dv_level = 9;
+    else if (level <= 3840*2160*120)
+        parser_av1->dv_level = (parser_av1->width == 7680) ? 11 : 10;
+    else if (level <= 7680*4320*60)
+        parser_av1->dv_level = 12;
+    else
+        parser_av1->dv_level = 13;
+}
+
+static int32_t
+dsi_take_config(parser_av1_handle_t parser_av1, mp4_dsi_av1_handle_t dsi, const uint8_t *obu, size_t obu_size)
+{
+    av1_seq_hdr_t *seq = &parser_av1->seq_hdr;
+
+    dsi->seq_hdr_obu = (uint8_t *)MALLOC_CHK(obu_size);
+    if (!dsi->seq_hdr_obu)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    memcpy(dsi->seq_hdr_obu, obu, obu_size);
+    dsi->seq_hdr_obu_size = (uint32_t)obu_size;
+
+    dsi->seq_profile            = seq->seq_profile;
+    dsi->seq_level_idx_0        = seq->seq_level_idx_0;
+    dsi->seq_tier_0             = seq->seq_tier_0;
+    dsi->high_bitdepth          = seq->high_bitdepth;
+    dsi->twelve_bit             = seq->twelve_bit;
+    dsi->monochrome             = seq->mono_chrome;
+    dsi->chroma_subsampling_x   = seq->subsampling_x;
+    dsi->chroma_subsampling_y   = seq->subsampling_y;
+    dsi->chroma_sample_position = seq->chroma_sample_position;
+    dsi->width                  = seq->max_frame_width;
+    dsi->height                 = seq->max_frame_height;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Takes a sequence header OBU (with obu_size field) into the current dsi or starts a new one if it changed */
+static int32_t
+av1_update_dsi(parser_av1_handle_t parser_av1, const uint8_t *obu, size_t obu_size, uint32_t *sample_flags)
+{
+    parser_handle_t      parser = (parser_handle_t)parser_av1;
+    mp4_dsi_av1_handle_t dsi    = (mp4_dsi_av1_handle_t)parser->curr_dsi;
+    dsi_handle_t         new_dsi;
+    dsi_handle_t *       p_new_dsi;
+    int32_t              ret;
+
+    if (!dsi->seq_hdr_obu)
+    {
+        *sample_flags |= SAMPLE_NEW_SD; /* the first one should have all the new info */
+        return dsi_take_config(parser_av1, dsi, obu, obu_size);
+    }
+
+    if (dsi->seq_hdr_obu_size == obu_size && !memcmp(dsi->seq_hdr_obu, obu, obu_size))
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    if (parser->sd == 0)
+    {
+        /* single sample description entry */
+        msglog(NULL, MSGLOG_ERR, "Error: Multiple Sample Descriptions necessary but not allowed!\n");
+        parser->sd_collision_flag = 1;
+        return EMA_MP4_MUXED_MULTI_SD_ERR;
+    }
+
+    /* create new sample description for new configuration */
+    new_dsi = parser->dsi_create(parser->dsi_type);
+    if (!new_dsi)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    ret = dsi_take_config(parser_av1, (mp4_dsi_av1_handle_t)new_dsi, obu, obu_size);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        new_dsi->destroy(new_dsi);
+        return ret;
+    }
+    p_new_dsi = (dsi_handle_t*)list_alloc_entry(parser->dsi_lst);
+    if (!p_new_dsi)
+    {
+        new_dsi->destroy(new_dsi);
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    *p_new_dsi = new_dsi;
+
+    /* switch to new entry in dsi list */
+    list_add_entry(parser->dsi_lst, p_new_dsi);
+    parser->curr_dsi = new_dsi;
+
+    /* signal to muxer that new stsd entry has to be written */
+    *sample_flags |= SAMPLE_NEW_SD;
+    msglog(NULL, MSGLOG_INFO, "AV1 sequence header changed at sample %u: new sample description\n",
+           parser_av1->sample_num);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Makes sure sample->data can take size bytes */
+static int32_t
+sample_buf_reserve(parser_av1_handle_t parser_av1, mp4_sample_handle_t sample, size_t size)
+{
+    if (size > parser_av1->sample_buf_size)
+    {
+        uint8_t *data = REALLOC_CHK(sample->data, size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                = data;
+        parser_av1->sample_buf_size = size;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Reads the next temporal unit of the input into tu_buf */
+static int32_t
+read_temporal_unit(parser_av1_handle_t parser_av1)
+{
+    bbio_handle_t ds = parser_av1->ds;
+    uint64_t      size;
+
+    parser_av1->tu_size = 0;
+    if (ds->is_EOD(ds))
+    {
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    if (parser_av1->input_format == AV1_INPUT_IVF)
+    {
+        uint8_t hdr[IVF_FRAME_HEADER_SIZE];
+
+        if (ds->read(ds, hdr, IVF_FRAME_HEADER_SIZE) != IVF_FRAME_HEADER_SIZE)
+        {
+            return EMA_MP4_MUXED_EOES;
+        }
+        size = hdr[0] | (hdr[1] << 8) | (hdr[2] << 16) | ((uint32_t)hdr[3] << 24);
+    }
+    else if (parser_av1->input_format == AV1_INPUT_ANNEXB)
+    {
+        if (!leb128_read_ds(ds, &size))
+        {
+            return EMA_MP4_MUXED_EOES;
+        }
+    }
+    else
+    {
+        /** low overhead format: the OBUs up to the next temporal delimiter */
+        size = 0;
+        while (!ds->is_EOD(ds))
+        {
+            int64_t  pos = ds->position(ds);
+            uint8_t  hdr[2];
+            uint32_t hdr_size;
+            uint64_t obu_size;
+            uint32_t len;
+
+            if (ds->read(ds, hdr, 1) != 1)
+            {
+                break;
+            }
+            if (((hdr[0] >> 3) & 0xf) == OBU_TEMPORAL_DELIMITER && size)
+            {
+                ds->seek(ds, pos, SEEK_SET);
+                break;
+            }
+            if (!(hdr[0] & 0x02))
+            {
+                msglog(NULL, MSGLOG_ERR, "AV1 OBU without obu_size field in low overhead bitstream format\n");
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            hdr_size = (hdr[0] & 0x04) ? 2 : 1;
+            if (hdr_size == 2 && ds->read(ds, hdr + 1, 1) != 1)
+            {
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            len = leb128_read_ds(ds, &obu_size);
+            if (!len)
+            {
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            size += hdr_size + len + obu_size;
+            ds->seek(ds, pos + hdr_size + len + obu_size, SEEK_SET);
+        }
+        ds->seek(ds, -(int64_t)size, SEEK_CUR);
+    }
+
+    if (size > parser_av1->tu_buf_size)
+    {
+        uint8_t *buf = REALLOC_CHK(parser_av1->tu_buf, (size_t)size);
+        if (!buf)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        parser_av1->tu_buf      = buf;
+        parser_av1->tu_buf_size = (size_t)size;
+    }
+    if (ds->read(ds, parser_av1->tu_buf, (size_t)size) != (size_t)size)
+    {
+        msglog(NULL, MSGLOG_ERR, "AV1 temporal unit is truncated\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    parser_av1->tu_size = (size_t)size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_av1_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_av1_handle_t parser_av1  = (parser_av1_handle_t)parser;
+    const uint8_t *     buf;
+    size_t              pos           = 0;
+    size_t              unit_end      = 0;  /** end of the current frame unit in Annex B */
+    BOOL                has_seq_hdr   = FALSE;
+    BOOL                frame_seen    = FALSE;
+    BOOL                key_frame     = FALSE;
+    int32_t             ret;
+
+    sample->flags                 = 0;
+    sample->is_leading            = 0;
+    sample->sample_depends_on     = 0;
+    sample->sample_is_depended_on = 0;
+    sample->sample_has_redundancy = 0;
+    sample->dependency_level      = 0;
+    sample->pic_type              = 0;
+    sample->frame_type            = 0xff;
+    sample->size                  = 0;
+
+    ret = read_temporal_unit(parser_av1);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    buf = parser_av1->tu_buf;
+
+    while (pos < parser_av1->tu_size)
+    {
+        size_t   end = parser_av1->tu_size;
+        uint8_t  hdr, type;
+        uint32_t hdr_size, len;
+        uint64_t obu_size;
+        const uint8_t *payload;
+
+        if (parser_av1->input_format == AV1_INPUT_ANNEXB)
+        {
+            uint64_t unit_size;
+
+            if (pos >= unit_end)
+            {
+                /** frame_unit_size */
+                len = leb128_read(buf + pos, end - pos, &unit_size);
+                if (!len || unit_size > end - pos - len)
+                {
+                    break;
+                }
+                pos     += len;
+                unit_end = pos + (size_t)unit_size;
+                continue;
+            }
+            /** obu_length */
+            len = leb128_read(buf + pos, unit_end - pos, &obu_size);
+            if (!len || obu_size > unit_end - pos - len)
+            {
+                break;
+            }
+            pos += len;
+            end  = pos + (size_t)obu_size;
+        }
+
+        /** an empty OBU has no obu_header() to read */
+        if (pos >= end)
+        {
+            break;
+        }
+        hdr      = buf[pos];
+        type     = (hdr >> 3) & 0xf;
+        hdr_size = (hdr & 0x04) ? 2 : 1;
+        if (hdr & 0x80 || pos + hdr_size > end)
+        {
+            break;
+        }
+        if (hdr & 0x02)
+        {
+            len = leb128_read(buf + pos + hdr_size, end - pos - hdr_size, &obu_size);
+            if (!len || obu_size > end - pos - hdr_size - len)
+            {
+                break;
+            }
+        }
+        else
+        {
+            len      = 0;
+            obu_size = end - pos - hdr_size;
+        }
+        payload = buf + pos + hdr_size + len;
+
+        if (type != OBU_TEMPORAL_DELIMITER && type != OBU_PADDING)
+        {
+            uint8_t *obu;
+
+            ret = sample_buf_reserve(parser_av1, sample, sample->size + hdr_size + 8 + (size_t)obu_size);
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                return ret;
+            }
+            obu     = sample->data + sample->size;
+            obu[0]  = hdr | 0x02;  /** obu_has_size_field */
+            if (hdr_size == 2)
+            {
+                obu[1] = buf[pos + 1];
+            }
+            len = hdr_size + leb128_write(obu + hdr_size, obu_size);
+            memcpy(obu + len, payload, (size_t)obu_size);
+            len += (uint32_t)obu_size;
+            sample->size += len;
+
+            if (type == OBU_SEQUENCE_HEADER)
+            {
+                ret = parse_seq_hdr(payload, (size_t)obu_size, &parser_av1->seq_hdr);
+                if (ret != EMA_MP4_MUXED_OK)
+                {
+                    return ret;
+                }
+                ret = av1_update_dsi(parser_av1, obu, len, &sample->flags);
+                if (ret != EMA_MP4_MUXED_OK)
+                {
+                    return ret;
+                }
+                if (!parser_av1->seq_hdr_seen)
+                {
+                    parser_av1->width  = parser_av1->seq_hdr.max_frame_width;
+                    parser_av1->height = parser_av1->seq_hdr.max_frame_height;
+                    parser_av1->colour_description_present = parser_av1->seq_hdr.color_description_present_flag;
+                    parser_av1->colour_primaries           = parser_av1->seq_hdr.color_primaries;
+                    parser_av1->transfer_characteristics   = parser_av1->seq_hdr.transfer_characteristics;
+                    parser_av1->matrix_coefficients        = parser_av1->seq_hdr.matrix_coefficients;
+                    parser_av1->full_range_flag            = parser_av1->seq_hdr.color_range;
+                    timing_info_update(parser_av1);
+                    parser_av1->seq_hdr_seen = TRUE;
+                }
+                has_seq_hdr = TRUE;
+            }
+            else if ((type == OBU_FRAME_HEADER || type == OBU_FRAME) && !frame_seen && parser_av1->seq_hdr_seen)
+            {
+                frame_seen = TRUE;
+                if (parser_av1->seq_hdr.reduced_still_picture_header)
+                {
+                    key_frame = TRUE;
+                }
+                else if (obu_size && !(payload[0] & 0x80))
+                {
+                    /** !show_existing_frame, frame_type and show_frame */
+                    key_frame = (((payload[0] >> 5) & 0x3) == AV1_KEY_FRAME) && (payload[0] & 0x10);
+                }
+            }
+            else if (type == OBU_METADATA)
+            {
+                parse_metadata(parser_av1, payload, (size_t)obu_size);
+            }
+        }
+
+        pos = (parser_av1->input_format == AV1_INPUT_ANNEXB) ? end : (size_t)(payload - buf) + (size_t)obu_size;
+    }
+
+    if (pos < parser_av1->tu_size)
+    {
+        msglog(NULL, MSGLOG_ERR, "Invalid AV1 OBU in temporal unit %u\n", parser_av1->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if (!parser_av1->seq_hdr_seen)
+    {
+        msglog(NULL, MSGLOG_ERR, "AV1 stream doesn't start with a sequence header\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    if (has_seq_hdr && key_frame)
+    {
+        sample->flags           |= SAMPLE_SYNC;
+        sample->pic_type         = 1;
+        sample->frame_type       = 0;
+        sample->dependency_level = 0x01;
+    }
+    else
+    {
+        sample->frame_type       = 1;
+        sample->dependency_level = 0x02;
+    }
+
+    sample->dts      = (uint64_t)parser_av1->sample_num * parser_av1->num_units_in_tick;
+    sample->cts      = sample->dts;
+    sample->duration = parser_av1->num_units_in_tick;
+
+    parser_av1->sample_num++;
+    parser_av1->num_samples++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int
+parser_av1_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    parser_av1_handle_t  parser_av1 = (parser_av1_handle_t)parser;
+    mp4_dsi_av1_handle_t dsi        = (mp4_dsi_av1_handle_t)parser->curr_dsi;
+    bbio_handle_t        snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 64 + dsi->seq_hdr_obu_size, 1);
+    }
+
+    /** the dimensions of the sample description in use */
+    parser_av1->width  = dsi->width;
+    parser_av1->height = dsi->height;
+
+    /** AV1CodecConfigurationRecord */
+    sink_write_bits(snk, 1, 1);                             /** marker */
+    sink_write_bits(snk, 7, 1);                             /** version */
+    sink_write_bits(snk, 3, dsi->seq_profile);
+    sink_write_bits(snk, 5, dsi->seq_level_idx_0);
+    sink_write_bits(snk, 1, dsi->seq_tier_0);
+    sink_write_bits(snk, 1, dsi->high_bitdepth);
+    sink_write_bits(snk, 1, dsi->twelve_bit);
+    sink_write_bits(snk, 1, dsi->monochrome);
+    sink_write_bits(snk, 1, dsi->chroma_subsampling_x);
+    sink_write_bits(snk, 1, dsi->chroma_subsampling_y);
+    sink_write_bits(snk, 2, dsi->chroma_sample_position);
+    sink_write_u8(snk, 0);                                  /** reserved, initial_presentation_delay_present = 0 */
+    snk->write(snk, dsi->seq_hdr_obu, dsi->seq_hdr_obu_size);  /** configOBUs */
+
+    /** if it's dolby vision, we should add 'dvvC' to 'av1C' */
+    parser->dv_dsi_size = 0;
+    if (parser->dv_rpu_nal_flag)
+    {
+        if (parser->ext_timing.ext_dv_profile == 0xff)
+        {
+            msglog(NULL, MSGLOG_ERR, "Error: For muxing Dolby vision stream, '--dv-profile' must be set by user!\n");
+            snk->destroy(snk);
+            return EMA_MP4_MUXED_BUGGY;
+        }
+        if (parser->ext_timing.ext_dv_profile != 10)
+        {
+            msglog(NULL, MSGLOG_ERR, "Error: For Dolby vision AV1 codec type, only setting profile to 10 makes sense!\n");
+            snk->destroy(snk);
+            return EMA_MP4_MUXED_BUGGY;
+        }
+        dv_level_update(parser_av1);
+
+        parser->dv_dsi_size = 24;
+        memset(parser->dv_dsi_buf, 0, parser->dv_dsi_size);
+
+        parser->dv_dsi_buf[0]  = 1;
+        parser->dv_dsi_buf[2] |= (parser->ext_timing.ext_dv_profile << 1);
+        parser->dv_dsi_buf[2] |= (parser->dv_level >> 5);
+        parser->dv_dsi_buf[3] |= (parser->dv_level << 3);
+        parser->dv_dsi_buf[3] |= 5; /** BL+RPU */
+        parser->dv_dsi_buf[4] |= (parser->ext_timing.ext_dv_bl_compatible_id << 4);
+    }
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_av1_destroy(parser_handle_t parser)
+{
+    parser_av1_handle_t parser_av1 = (parser_av1_handle_t)parser;
+
+    if (parser_av1->tu_buf)
+    {
+        FREE_CHK(parser_av1->tu_buf);
+    }
+    parser_destroy(parser);
+}
+
+static int
+parser_av1_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_av1_handle_t parser_av1 = (parser_av1_handle_t)parser;
+    uint8_t             hdr[32];
+    size_t              hdr_size;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    /** the format is told by the content, not the file extension */
+    hdr_size = ds->read(ds, hdr, sizeof(hdr));
+    if (hdr_size == sizeof(hdr) && !memcmp(hdr, "DKIF", 4))
+    {
+        uint32_t ivf_hdr_size = hdr[6] | (hdr[7] << 8);
+
+        parser_av1->input_format = AV1_INPUT_IVF;
+        parser_av1->ivf_rate     = hdr[16] | (hdr[17] << 8) | (hdr[18] << 16) | ((uint32_t)hdr[19] << 24);
+        parser_av1->ivf_scale    = hdr[20] | (hdr[21] << 8) | (hdr[22] << 16) | ((uint32_t)hdr[23] << 24);
+        ds->seek(ds, MAX2(ivf_hdr_size, sizeof(hdr)), SEEK_SET);
+    }
+    else if (hdr_size >= 2 && hdr[0] == 0x12 && hdr[1] == 0x00)
+    {
+        /** temporal delimiter with obu_size 0 */
+        parser_av1->input_format = AV1_INPUT_LOW_OVERHEAD;
+        ds->seek(ds, 0, SEEK_SET);
+    }
+    else if (hdr_size)
+    {
+        parser_av1->input_format = AV1_INPUT_ANNEXB;
+        ds->seek(ds, 0, SEEK_SET);
+    }
+    else
+    {
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Creates and build interface, base */
+static parser_handle_t
+parser_av1_create(uint32_t dsi_type)
+{
+    parser_av1_handle_t parser;
+
+    parser = (parser_av1_handle_t)MALLOC_CHK(sizeof(parser_av1_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_av1_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type     = STREAM_TYPE_VIDEO;
+    parser->stream_id       = STREAM_ID_AV1;
+    parser->stream_name     = "av1";
+    parser->dsi_FourCC      = "av1C";
+    parser->profile_levelID = 0;
+
+    parser->dsi_type        = dsi_type;
+    parser->dsi_create      = dsi_av1_create;
+
+    parser->init            = parser_av1_init;
+    parser->destroy         = parser_av1_destroy;
+    parser->get_sample      = parser_av1_get_sample;
+
+    OSAL_STRNCPY(parser->codec_name, 12, "\012AV1 Coding", 12);
+
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_av1_get_mp4_cfg;
+    }
+
+    /** use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /***** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_av1_reg(void)
+{
+    /** register all alias to make reg_parser_get easier */
+    reg_parser_set("av1", parser_av1_create);
+    reg_parser_set("obu", parser_av1_create);
+    reg_parser_set("ivf", parser_av1_create);
+}
@@ -1017,6 +1017,33 @@ parse_sei_messages(avc_decode_t *dec, nal_handle_t nal, BOOL keep_all)
             }
             break;
 
+        case SEI_MASTERING_DISPLAY_COLOUR_VOLUME:
+            if (payloadSize >= 24 && !dec->mdcv_present)
+            {
+                int32_t c;
+
+                for (c = 0; c < 3; c++)
+                {
+                    dec->display_primaries_x[c] = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                    dec->display_primaries_y[c] = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                }
+                dec->white_point_x = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                dec->white_point_y = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                dec->max_display_mastering_luminance = src_read_bits(nal->tmp_buf_bbi, 32);
+                dec->min_display_mastering_luminance = src_read_bits(nal->tmp_buf_bbi, 32);
+                dec->mdcv_present = 1;
+            }
+            break;
+
+        case SEI_CONTENT_LIGHT_LEVEL_INFO:
+            if (payloadSize >= 4 && !dec->clli_present)
+            {
+                dec->max_content_light_level     = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                dec->max_pic_average_light_level = (uint16_t)src_read_bits(nal->tmp_buf_bbi, 16);
+                dec->clli_present = 1;
+            }
+            break;
+
         default:
             /* keep */
             break;
@@ -1060,6 +1087,30 @@ get_colr_info(parser_avc_handle_t parser_avc, sps_t *p_sps)
     parser_avc->colour_primaries = p_sps->colour_primaries;
     parser_avc->transfer_characteristics = p_sps->transfer_characteristics;
     parser_avc->matrix_coefficients = p_sps->matrix_coefficients;
+    parser_avc->colour_description_present = p_sps->colour_description_present_flag;
+    parser_avc->full_range_flag = p_sps->video_full_range_flag;
+}
+
+/** Takes over the mastering display and content light level SEI info unless given by the user */
+static void
+get_hdr_info(parser_avc_handle_t parser_avc, avc_decode_t *dec)
+{
+    if (dec->mdcv_present && !parser_avc->mdcv_present)
+    {
+        memcpy(parser_avc->display_primaries_x, dec->display_primaries_x, sizeof(dec->display_primaries_x));
+        memcpy(parser_avc->display_primaries_y, dec->display_primaries_y, sizeof(dec->display_primaries_y));
+        parser_avc->white_point_x = dec->white_point_x;
+        parser_avc->white_point_y = dec->white_point_y;
+        parser_avc->max_display_mastering_luminance = dec->max_display_mastering_luminance;
+        parser_avc->min_display_mastering_luminance = dec->min_display_mastering_luminance;
+        parser_avc->mdcv_present = 1;
+    }
+    if (dec->clli_present && !parser_avc->clli_present)
+    {
+        parser_avc->max_content_light_level = dec->max_content_light_level;
+        parser_avc->max_pic_average_light_level = dec->max_pic_average_light_level;
+        parser_avc->clli_present = 1;
+    }
 }
 
 static void
@@ -1067,6 +1118,20 @@ timing_info_update(parser_avc_handle_t parser_avc, sps_t *p_sps)
 {
     BOOL     frame_only = FALSE;
 
+    /** the frame rate of the user is time_scale/num_units_in_tick, the one of the sps counts in fields */
+    if (p_sps->timing_info_present_flag && parser_avc->ext_timing.usr_timing)
+    {
+        if ((uint64_t)p_sps->time_scale * parser_avc->ext_timing.num_units_in_tick !=
+            2 * (uint64_t)parser_avc->ext_timing.time_scale * p_sps->num_units_in_tick)
+        {
+            msglog(NULL, MSGLOG_WARNING, "Frame rate %u/%u set by user conflicts with %u/%u in the VUI of the SPS, %s\n",
+                   parser_avc->ext_timing.time_scale, parser_avc->ext_timing.num_units_in_tick,
+                   p_sps->time_scale, 2 * p_sps->num_units_in_tick,
+                   parser_avc->ext_timing.override_timing ? "using the user's" : "ignoring the user's");
+        }
+        parser_avc->ext_timing.usr_timing = 0;  /** report once */
+    }
+
     if (!p_sps->timing_info_present_flag || parser_avc->ext_timing.override_timing)
     {
         assert(parser_avc->ext_timing.num_units_in_tick != 0);
@@ -1659,6 +1724,7 @@ parser_avc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
              */
             sei_size2keep = parse_sei_messages(dec, nal, keep_all);
             keep_nal      = (sei_size2keep >= sc_size + 3);
+            get_hdr_info(parser_avc, dec);
             /* have SEI to keep. +3: nal hdr, sei type and size */
             parser_avc->sei_num++;
             break;
@@ -1785,6 +1851,12 @@ parser_avc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
 
         /** DolbyVision RPU NALs */
         case NAL_TYPE_UNSPECIFIED28: 
+            /** Dolby Vision RPU NALs stripped by the user: the BL is muxed as plain AVC */
+            if (parser->ext_timing.ext_dv_mode == DV_MODE_STRIP)
+            {
+                keep_nal = FALSE;
+                break;
+            }
             /** Dolby Vision RPU NALs found, but the user don't want to signal; Just mux it to comment mp4 */
             if (parser->ext_timing.ext_dv_profile == 0xff)
             {
@@ -1801,6 +1873,13 @@ parser_avc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
         case NAL_TYPE_UNSPECIFIED30: 
             {
                 uint8_t nal_unit_type = 0;
+
+                /** EL NALs dropped by the user */
+                if (parser->ext_timing.ext_dv_mode != DV_MODE_KEEP)
+                {
+                    keep_nal = FALSE;
+                    break;
+                }
                 parser->dv_el_nal_flag = 1;
                 keep_nal  = TRUE;
 
@@ -2476,6 +2555,7 @@ parser_avc_get_sample_push(parser_handle_t parser, SEsData_t *asEsd, SSs_t *psNa
         {
             sei_size2keep = parse_sei_messages(dec, nal, dec->keep_all);
             keep_nal      = (sei_size2keep >= sc_size + 3);
+            get_hdr_info(parser_avc, dec);
             /* have SEI to keep. +3: nal hdr, sei type and size */
         }
         else
@@ -786,6 +786,181 @@ get_channel_num(uint16_t channel_flags)
     return channel_num;
 }
 
+/* mp4 channel location of the dependent substreams of an independent substream */
+static uint16_t
+get_dep_subs_chan_loc(parser_dd_handle_t parser_dd, uint32_t streamID)
+{
+    uint16_t chan_loc = 0;
+    uint32_t u;
+
+    for (u = 0; u < get_dep_subs_num(parser_dd, streamID); u++)
+    {
+        chan_loc |= parser_dd->subs[streamID][u].chan_loc;
+    }
+    return chan_loc;
+}
+
+/* copies the substream configuration which goes into 'dac3' / 'dec3' into the dsi */
+static int
+dd_dsi_take_config(parser_dd_handle_t parser_dd, dsi_handle_t dsi)
+{
+    uint32_t i;
+
+    if (dsi->stream_id == STREAM_ID_AC3)
+    {
+        mp4_dsi_ac3_handle_t dsi_ac3 = (mp4_dsi_ac3_handle_t)dsi;
+        dd_substream_t *     sub     = &parser_dd->subs_ind[AC3_SUBSTREAMID];
+
+        dsi_ac3->fscod         = sub->fscod;
+        dsi_ac3->bsid          = sub->bsid;
+        dsi_ac3->bsmod         = sub->bsmod;
+        dsi_ac3->acmod         = sub->acmod;
+        dsi_ac3->lfeon         = sub->lfeon;
+        dsi_ac3->bit_rate_code = sub->bit_rate_code;
+    }
+    else
+    {
+        mp4_dsi_ec3_handle_t dsi_ec3 = (mp4_dsi_ec3_handle_t)dsi;
+
+        if (!dsi_ec3->substreams)
+        {
+            dsi_ec3->substreams = (ec3_substream_t *)MALLOC_CHK(EC3_MAX_STREAMS*sizeof(ec3_substream_t));
+            if (!dsi_ec3->substreams)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+        }
+
+        dsi_ec3->num_ind_sub = get_ind_subs_num(parser_dd);
+        for (i = 0; i < (uint32_t)dsi_ec3->num_ind_sub; i++)
+        {
+            dd_substream_t * ss = &parser_dd->subs_ind[i];
+
+            dsi_ec3->substreams[i].fscod       = ss->fscod;
+            dsi_ec3->substreams[i].bsid        = ss->bsid;
+            dsi_ec3->substreams[i].bsmod       = ss->bsmod;
+            dsi_ec3->substreams[i].acmod       = ss->acmod;
+            dsi_ec3->substreams[i].lfeon       = ss->lfeon;
+            dsi_ec3->substreams[i].num_dep_sub = get_dep_subs_num(parser_dd, i);
+            dsi_ec3->substreams[i].chan_loc    = get_dep_subs_chan_loc(parser_dd, i);
+        }
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/* TRUE if the substream configuration still matches the dsi. The AC-3 bit rate code is
+ * not compared: it may vary from frame to frame without changing the decoder setup */
+static BOOL
+dd_dsi_config_equal(parser_dd_handle_t parser_dd, dsi_handle_t dsi)
+{
+    uint32_t i;
+
+    if (dsi->stream_id == STREAM_ID_AC3)
+    {
+        mp4_dsi_ac3_handle_t dsi_ac3 = (mp4_dsi_ac3_handle_t)dsi;
+        dd_substream_t *     sub     = &parser_dd->subs_ind[AC3_SUBSTREAMID];
+
+        return (dsi_ac3->fscod == sub->fscod && dsi_ac3->bsid == sub->bsid &&
+                dsi_ac3->bsmod == sub->bsmod && dsi_ac3->acmod == sub->acmod &&
+                dsi_ac3->lfeon == sub->lfeon);
+    }
+    else
+    {
+        mp4_dsi_ec3_handle_t dsi_ec3 = (mp4_dsi_ec3_handle_t)dsi;
+
+        if (!dsi_ec3->substreams || (uint32_t)dsi_ec3->num_ind_sub != get_ind_subs_num(parser_dd))
+        {
+            return FALSE;
+        }
+        for (i = 0; i < (uint32_t)dsi_ec3->num_ind_sub; i++)
+        {
+            dd_substream_t *  ss  = &parser_dd->subs_ind[i];
+            ec3_substream_t * sub = &dsi_ec3->substreams[i];
+
+            if (sub->fscod != ss->fscod || sub->bsid != ss->bsid || sub->bsmod != ss->bsmod ||
+                sub->acmod != ss->acmod || sub->lfeon != ss->lfeon ||
+                (uint32_t)sub->num_dep_sub != get_dep_subs_num(parser_dd, i) ||
+                sub->chan_loc != get_dep_subs_chan_loc(parser_dd, i))
+            {
+                return FALSE;
+            }
+        }
+        return TRUE;
+    }
+}
+
+/* keeps the dsi list in sync with the substream configuration of the sample just completed:
+ * a change in configuration requires a new sample description entry */
+static int
+dd_update_dsi(parser_dd_handle_t parser_dd, uint32_t *sample_flags)
+{
+    parser_handle_t parser = (parser_handle_t)parser_dd;
+    dsi_handle_t    new_dsi;
+    dsi_handle_t *  p_new_dsi;
+    int             fscod;
+    int             ret;
+
+    if (!parser_dd->mp4_sample_num)
+    {
+        *sample_flags |= SAMPLE_NEW_SD; /* the first one should have all the new info */
+        return dd_dsi_take_config(parser_dd, parser->curr_dsi);
+    }
+
+    if (dd_dsi_config_equal(parser_dd, parser->curr_dsi))
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    fscod = (parser->curr_dsi->stream_id == STREAM_ID_AC3) ?
+            ((mp4_dsi_ac3_handle_t)parser->curr_dsi)->fscod :
+            ((mp4_dsi_ec3_handle_t)parser->curr_dsi)->substreams[0].fscod;
+    if (fscod != parser_dd->subs_ind[0].fscod)
+    {
+        msglog(NULL, MSGLOG_ERR, "change in DD sampling rate is not allowed / supported\n");
+        return EMA_MP4_MUXED_CONFIG_ERR;
+    }
+
+    if (parser->sd == 0)
+    {
+        /* single sample description entry */
+        msglog(NULL, MSGLOG_ERR, "Error: Multiple Sample Descriptions necessary but not allowed!\n");
+        parser->sd_collision_flag = 1;
+        return EMA_MP4_MUXED_MULTI_SD_ERR;
+    }
+
+    /* create new sample description for new configuration */
+    new_dsi = parser->dsi_create(parser->dsi_type);
+    if (!new_dsi)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    ret = dd_dsi_take_config(parser_dd, new_dsi);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        new_dsi->destroy(new_dsi);
+        return ret;
+    }
+    p_new_dsi = (dsi_handle_t*)list_alloc_entry(parser->dsi_lst);
+    if (!p_new_dsi)
+    {
+        new_dsi->destroy(new_dsi);
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    *p_new_dsi = new_dsi;
+
+    /* switch to new entry in dsi list */
+    list_add_entry(parser->dsi_lst, p_new_dsi);
+    parser->curr_dsi = new_dsi;
+
+    /* signal to muxer that new stsd entry has to be written */
+    *sample_flags |= SAMPLE_NEW_SD;
+    msglog(NULL, MSGLOG_INFO, "DD configuration changed at sample %u: new sample description\n",
+           parser_dd->mp4_sample_num);
+
+    return EMA_MP4_MUXED_OK;
+}
+
 /* get a new ac3 or eac3 frame of multiple program with dependent substreams */
 static int
 parser_dd_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
@@ -962,9 +1137,10 @@ parser_dd_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
         parser_dd->sample_buf_alloc_only = TRUE;
 
         sample->flags = SAMPLE_SYNC;
-        if (!parser_dd->mp4_sample_num)
+        ret = dd_update_dsi(parser_dd, &sample->flags);
+        if (ret != EMA_MP4_MUXED_OK)
         {
-            sample->flags |= SAMPLE_NEW_SD; /* the first one should have all the new info */
+            return ret;
         }
         sample->dts      = parser_dd->dts;
         sample->cts      = sample->dts;
@@ -1014,6 +1190,7 @@ static int
 build_sample(parser_dd_handle_t parser_dd)
 {
     mp4_sample_handle_t sample;
+    int                 ret;
 
     assert(!parser_dd->is1536AudSmplRdy);
 
@@ -1040,9 +1217,10 @@ build_sample(parser_dd_handle_t parser_dd)
     /** setup output sample */
     sample = &(parser_dd->sample_got);
     sample->flags = SAMPLE_SYNC;
-    if (!parser_dd->mp4_sample_num)
+    ret = dd_update_dsi(parser_dd, &sample->flags);
+    if (ret != EMA_MP4_MUXED_OK)
     {
-        sample->flags |= SAMPLE_NEW_SD; /* the first one should have all the new info */
+        return ret;
     }
     sample->dts      = parser_dd->dts;
     sample->cts      = sample->dts;
@@ -1337,9 +1515,8 @@ parser_dd_get_sample_push(parser_handle_t parser, SEsData_t *psEsd, SSs_t *psSf,
 static int
 parser_ac3_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
 {
-    parser_dd_handle_t parser_dd = (parser_dd_handle_t)parser;
-    dd_substream_t *   sub       = &parser_dd->subs_ind[AC3_SUBSTREAMID];
-    bbio_handle_t      snk;
+    mp4_dsi_ac3_handle_t sub = (mp4_dsi_ac3_handle_t)parser->curr_dsi;
+    bbio_handle_t        snk;
 
     DPRINTF(NULL,
             "[AC3] fscod %d, bsid %d, bsmod %d, acmod %d, lfeon %d, bit_rate_code %d\n",
@@ -1375,12 +1552,18 @@ parser_ac3_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
 int
 parser_ec3_get_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len, BOOL dump_joc_flag)
 {
-    parser_dd_handle_t parser_dd   = (parser_dd_handle_t)parser;
-    uint32_t           i, j;
+    parser_dd_handle_t   parser_dd = (parser_dd_handle_t)parser;
+    mp4_dsi_ec3_handle_t dsi       = (mp4_dsi_ec3_handle_t)parser->curr_dsi;
+    uint32_t           i;
     uint32_t           num_indep_sub = 0;
     uint32_t           num_dep_sub = 0;
     bbio_handle_t      snk;
 
+    if (!dsi->substreams && dd_dsi_take_config(parser_dd, parser->curr_dsi) != EMA_MP4_MUXED_OK)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
     snk = reg_bbio_get('b', 'w');
     if (*buf)
     {
@@ -1395,13 +1578,13 @@ parser_ec3_get_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len, BOOL
     sink_write_bits(snk, 5, (parser_dd->maxBitrate / 1000) >> 8);
     sink_write_bits(snk, 8, (parser_dd->maxBitrate / 1000) & 0xff);
 
-    num_indep_sub = parser_dd->num_ind_sub;
+    /** the configuration of the sample description entry in question */
+    num_indep_sub = dsi->num_ind_sub;
     sink_write_bits(snk, 3, num_indep_sub-1);
 
     for (i = 0;i < num_indep_sub; i++)
     {
-        uint32_t           num_dep_sub_tmp = 0;
-        dd_substream_t *ss = &parser_dd->subs_ind[i];
+        ec3_substream_t *ss = &dsi->substreams[i];
         sink_write_bits(snk, 2, ss->fscod);
         sink_write_bits(snk, 5, ss->bsid);
         sink_write_bits(snk, 2, 0);         /* 2 bits reserved */
@@ -1410,22 +1593,11 @@ parser_ec3_get_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len, BOOL
         sink_write_bits(snk, 1, ss->lfeon);
         sink_write_bits(snk, 3, 0);
 
-        num_dep_sub_tmp = get_dep_subs_num(parser_dd, i);
-        sink_write_bits(snk, 4, num_dep_sub_tmp);
-        if (num_dep_sub_tmp)
+        sink_write_bits(snk, 4, ss->num_dep_sub);
+        if (ss->num_dep_sub)
         {
-            uint16_t chan_loc = 0;
-            for (j = 0; j < EC3_MAX_SUBSTREAMS; j++)
-            {
-                dd_substream_t *psub = &(parser_dd->subs[i][j]);
-                if (psub->ddt == DD_TYPE_NONE)
-                {
-                    break;
-                }
-                chan_loc |= psub->chan_loc;
-            }
-            sink_write_bits(snk, 1, chan_loc >> 8 );
-            sink_write_bits(snk, 8, chan_loc & 0xff);
+            sink_write_bits(snk, 1, ss->chan_loc >> 8 );
+            sink_write_bits(snk, 8, ss->chan_loc & 0xff);
         }
         else
         {
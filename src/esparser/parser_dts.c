@@ -0,0 +1,739 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_dts.c
+    @brief Implements a DTS parser
+
+    Reads 16-bit big endian DTS streams. A DTS core frame (ETSI TS 102 114) together with the DTS-HD
+    extension substreams following it becomes a sample of a 'dtsc' or, with substreams, 'dtsh' sample
+    entry described by a 'ddts'. DTS-UHD frames (ETSI TS 103 491) become samples of a 'dtsx' sample entry
+    described by a 'udts', sync frames are sync samples. A DTS-UHD frame ends where the next frame's sync
+    word is. The channel mask and decoder profile of DTS-UHD are carried in metadata chunks which are not
+    parsed: the 'udts' signals profile 2 and no channel mask.
+*/
+
+#include "utils.h"
+#include "io_base.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+#define DTS_SYNCWORD_CORE       0x7FFE8001
+#define DTS_SYNCWORD_SUBSTREAM  0x64582025
+#define DTS_SYNCWORD_UHD_SYNC   0x40411BF2
+#define DTS_SYNCWORD_UHD        0x71C442E8
+
+#define DTS_MAX_HDR_SIZE        16
+#define DTS_MIN_CORE_SIZE       96
+
+#define ES_BUF_SIZE         65536
+
+typedef struct dts_core_hdr_t_
+{
+    BOOL     termination;           /** FTYPE 0: the last frame may be short */
+    uint32_t samples;               /** per channel */
+    uint32_t size;
+    uint32_t amode;
+    uint32_t sample_rate;
+    BOOL     ext_audio;
+    uint32_t lff;
+    uint32_t pcmr;
+} dts_core_hdr_t;
+
+typedef struct dts_uhd_hdr_t_
+{
+    BOOL     sync;
+    uint32_t ftoc_size;
+    /** of sync frames only */
+    uint32_t duration;              /** in clock_rate units */
+    uint32_t duration_ratio;        /** duration/base duration */
+    uint32_t clock_rate;
+    uint32_t sample_rate_mod;
+} dts_uhd_hdr_t;
+
+struct parser_dts_t_
+{
+    PARSER_AUDIO_BASE;
+
+    uint32_t frame_duration;        /** in sample_rate units */
+    dts_uhd_hdr_t uhd_hdr;          /** of the last sync frame */
+
+    uint8_t *es_buf;                /** es_buf[es_pos, es_size) is not parsed yet */
+    size_t   es_buf_size;
+    size_t   es_pos;
+    size_t   es_size;
+    BOOL     es_eof;
+
+    size_t   sample_buf_size;
+    uint64_t sample_num;
+    uint64_t dts;
+
+    /** for the bit rates and maximum payload */
+    uint64_t total_size;
+    uint32_t max_frame_size;
+};
+
+typedef struct parser_dts_t_ parser_dts_t;
+typedef parser_dts_t  *parser_dts_handle_t;
+
+static const uint32_t core_sample_rate_tbl[16] = {
+    0, 8000, 16000, 32000, 0, 0, 11025, 22050, 44100, 0, 0, 12000, 24000, 48000, 0, 0
+};
+
+/** of the AMODE channel arrangements but LFE */
+static const uint8_t core_channel_num_tbl[16] = {1, 2, 2, 2, 2, 3, 3, 4, 4, 5, 6, 6, 6, 7, 8, 8};
+
+/** ChannelLayout bits of 'ddts' of AMODE 0..9: C, L R, L R, L R, Lt Rt, C L R, L R Cs, C L R Cs, L R Ls Rs,
+ *  C L R Ls Rs; 0 for the others */
+static const uint16_t core_channel_layout_tbl[16] = {
+    0x0001, 0x0002, 0x0002, 0x0002, 0x0002, 0x0003, 0x0012, 0x0013, 0x0006, 0x0007, 0, 0, 0, 0, 0, 0
+};
+
+static const uint32_t uhd_base_duration_tbl[4] = {512, 480, 384, 0};
+static const uint32_t uhd_clock_rate_tbl[4]    = {32000, 44100, 48000, 0};
+
+/** Makes at least size bytes available from es_pos on unless the file ends before */
+static int32_t
+es_fill(parser_dts_handle_t parser_dts, size_t size)
+{
+    bbio_handle_t ds = parser_dts->ds;
+
+    while (parser_dts->es_size - parser_dts->es_pos < size && !parser_dts->es_eof)
+    {
+        size_t avail = parser_dts->es_size - parser_dts->es_pos;
+        size_t read;
+
+        memmove(parser_dts->es_buf, parser_dts->es_buf + parser_dts->es_pos, avail);
+        parser_dts->es_pos  = 0;
+        parser_dts->es_size = avail;
+        if (size > parser_dts->es_buf_size)
+        {
+            size_t   buf_size = MAX2(size, 2*parser_dts->es_buf_size);
+            uint8_t *buf      = REALLOC_CHK(parser_dts->es_buf, buf_size);
+            if (!buf)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            parser_dts->es_buf      = buf;
+            parser_dts->es_buf_size = buf_size;
+        }
+        read = ds->read(ds, parser_dts->es_buf + avail, parser_dts->es_buf_size - avail);
+        if (!read)
+        {
+            parser_dts->es_eof = TRUE;
+        }
+        parser_dts->es_size += read;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+static size_t
+es_avail(parser_dts_handle_t parser_dts, size_t offset)
+{
+    size_t avail = parser_dts->es_size - parser_dts->es_pos;
+
+    return (avail > offset) ? avail - offset : 0;
+}
+
+static uint32_t
+es_peek_u32(parser_dts_handle_t parser_dts, size_t offset)
+{
+    return (es_avail(parser_dts, offset) >= 4) ? get_BE_u32(parser_dts->es_buf + parser_dts->es_pos + offset) : 0;
+}
+
+/** Parses the core frame header at p, see ETSI TS 102 114 5.4.1 */
+static BOOL
+parse_core_hdr(const uint8_t *p, size_t avail, dts_core_hdr_t *hdr)
+{
+    bbio_handle_t bs;
+    uint32_t      nblks, cpf;
+
+    if (avail < DTS_MAX_HDR_SIZE || get_BE_u32(p) != DTS_SYNCWORD_CORE)
+    {
+        return FALSE;
+    }
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)p + 4, DTS_MAX_HDR_SIZE - 4, 0);
+    hdr->termination = !src_read_bits(bs, 1);                /** FTYPE */
+    src_skip_bits(bs, 5);                                    /** SHORT */
+    cpf              = src_read_bits(bs, 1);
+    nblks            = src_read_bits(bs, 7);
+    hdr->samples     = (nblks + 1)*32;
+    hdr->size        = src_read_bits(bs, 14) + 1;
+    hdr->amode       = src_read_bits(bs, 6);
+    hdr->sample_rate = core_sample_rate_tbl[src_read_bits(bs, 4)];
+    src_skip_bits(bs, 5 + 1 + 1 + 1 + 1 + 1 + 3);            /** RATE .. EXT_AUDIO_ID */
+    hdr->ext_audio   = src_read_bits(bs, 1);
+    src_skip_bits(bs, 1);                                    /** ASPF */
+    hdr->lff         = src_read_bits(bs, 2);
+    src_skip_bits(bs, 1 + (cpf ? 16 : 0) + 1 + 4 + 2);       /** HFLAG, HCRC, FILTS, VERNUM, CHIST */
+    hdr->pcmr        = src_read_bits(bs, 3);
+    bs->destroy(bs);
+
+    return nblks >= 5 && hdr->size >= DTS_MIN_CORE_SIZE && hdr->amode < 16 && hdr->sample_rate;
+}
+
+/** Returns the size of the extension substream at p, 0 if there is none, see ETSI TS 102 114 7.4 */
+static uint32_t
+parse_substream_size(const uint8_t *p, size_t avail)
+{
+    bbio_handle_t bs;
+    uint32_t      size;
+
+    if (avail < DTS_MAX_HDR_SIZE || get_BE_u32(p) != DTS_SYNCWORD_SUBSTREAM)
+    {
+        return 0;
+    }
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)p + 4, DTS_MAX_HDR_SIZE - 4, 0);
+    src_skip_bits(bs, 8 + 2);                                /** UserDefinedBits, nExtSSIndex */
+    if (!src_read_bits(bs, 1))                               /** bHeaderSizeType */
+    {
+        src_skip_bits(bs, 8);                                /** nuExtSSHeaderSize */
+        size = src_read_bits(bs, 16) + 1;
+    }
+    else
+    {
+        src_skip_bits(bs, 12);
+        size = src_read_bits(bs, 20) + 1;
+    }
+    bs->destroy(bs);
+
+    return size;
+}
+
+/** Parses the start of the frame table of contents at p, see ETSI TS 103 491 6.4 */
+static BOOL
+parse_uhd_hdr(const uint8_t *p, size_t avail, dts_uhd_hdr_t *hdr)
+{
+    static const uint32_t ftoc_size_bits[4] = {5, 8, 10, 12};
+    bbio_handle_t bs;
+    uint32_t      syncword, idx, i, base_duration;
+
+    if (avail < DTS_MAX_HDR_SIZE)
+    {
+        return FALSE;
+    }
+    syncword = get_BE_u32(p);
+    if (syncword != DTS_SYNCWORD_UHD_SYNC && syncword != DTS_SYNCWORD_UHD)
+    {
+        return FALSE;
+    }
+    hdr->sync = (syncword == DTS_SYNCWORD_UHD_SYNC);
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)p + 4, DTS_MAX_HDR_SIZE - 4, 0);
+    /** FTOCPayloadinBytes: prefix code 0, 10, 110, 111 selecting the field size, offset by the smaller ranges */
+    for (idx = 0; idx < 3 && src_read_bits(bs, 1); idx++);
+    hdr->ftoc_size = src_read_bits(bs, ftoc_size_bits[idx]) + 1;
+    for (i = 0; i < idx; i++)
+    {
+        hdr->ftoc_size += 1u << ftoc_size_bits[i];
+    }
+    if (hdr->sync)
+    {
+        src_skip_bits(bs, 1);                                /** bFullChannelBasedMixFlag */
+        base_duration       = uhd_base_duration_tbl[src_read_bits(bs, 2)];
+        hdr->duration_ratio = src_read_bits(bs, 3) + 1;
+        hdr->duration       = base_duration*hdr->duration_ratio;
+        hdr->clock_rate     = uhd_clock_rate_tbl[src_read_bits(bs, 2)];
+        if (src_read_bits(bs, 1))                            /** bTimeStampPresent */
+        {
+            src_skip_bits(bs, 36);
+        }
+        hdr->sample_rate_mod = src_read_bits(bs, 2);
+    }
+    bs->destroy(bs);
+
+    return !hdr->sync || (hdr->duration && hdr->clock_rate);
+}
+
+/** Gets the size of the DTS core frame and extension substreams at es_pos */
+static int32_t
+get_core_frame(parser_dts_handle_t parser_dts, dts_core_hdr_t *hdr, size_t *frame_size)
+{
+    mp4_dsi_dts_handle_t dsi = (mp4_dsi_dts_handle_t)parser_dts->curr_dsi;
+    uint32_t             substream_size;
+    size_t               size;
+    int32_t              ret;
+
+    if (!parse_core_hdr(parser_dts->es_buf + parser_dts->es_pos, es_avail(parser_dts, 0), hdr))
+    {
+        msglog(NULL, MSGLOG_ERR, "No DTS core frame at sample %" PRIu64 "\n", parser_dts->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    for (size = hdr->size; ; size += substream_size)
+    {
+        ret = es_fill(parser_dts, size + DTS_MAX_HDR_SIZE);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        substream_size = parse_substream_size(parser_dts->es_buf + parser_dts->es_pos + size,
+                                              es_avail(parser_dts, size));
+        if (!substream_size)
+        {
+            break;
+        }
+        dsi->substream = TRUE;
+    }
+    if (es_avail(parser_dts, 0) < size)
+    {
+        msglog(NULL, MSGLOG_ERR, "Truncated DTS frame at sample %" PRIu64 "\n", parser_dts->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    *frame_size = size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Gets the size of the DTS-UHD frame at es_pos: up to the next sync word or the end of the stream */
+static int32_t
+get_uhd_frame(parser_dts_handle_t parser_dts, dts_uhd_hdr_t *hdr, size_t *frame_size)
+{
+    size_t  size;
+    int32_t ret;
+
+    if (!parse_uhd_hdr(parser_dts->es_buf + parser_dts->es_pos, es_avail(parser_dts, 0), hdr))
+    {
+        msglog(NULL, MSGLOG_ERR, "No DTS-UHD frame at sample %" PRIu64 "\n", parser_dts->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    for (size = MAX2(hdr->ftoc_size, 4); ; size++)
+    {
+        uint32_t syncword;
+
+        ret = es_fill(parser_dts, size + 4);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        if (es_avail(parser_dts, size) < 4)
+        {
+            size = es_avail(parser_dts, 0);
+            break;
+        }
+        syncword = es_peek_u32(parser_dts, size);
+        if (syncword == DTS_SYNCWORD_UHD_SYNC || syncword == DTS_SYNCWORD_UHD)
+        {
+            break;
+        }
+    }
+    *frame_size = size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_dts_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_dts_handle_t  parser_dts = (parser_dts_handle_t)parser;
+    mp4_dsi_dts_handle_t dsi        = (mp4_dsi_dts_handle_t)parser->curr_dsi;
+    size_t               frame_size;
+    uint32_t             duration   = parser_dts->frame_duration;
+    int32_t              ret;
+
+    sample->flags = 0;
+
+    ret = es_fill(parser_dts, DTS_MAX_HDR_SIZE);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    if (!es_avail(parser_dts, 0))
+    {
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    if (dsi->uhd)
+    {
+        dts_uhd_hdr_t hdr;
+
+        ret = get_uhd_frame(parser_dts, &hdr, &frame_size);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        if (hdr.sync)
+        {
+            if (hdr.duration != parser_dts->uhd_hdr.duration || hdr.clock_rate != parser_dts->uhd_hdr.clock_rate ||
+                hdr.sample_rate_mod != parser_dts->uhd_hdr.sample_rate_mod)
+            {
+                msglog(NULL, MSGLOG_ERR, "DTS-UHD frame duration or sample rate changes at sample %" PRIu64 "\n",
+                       parser_dts->sample_num);
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            sample->flags |= SAMPLE_SYNC;
+        }
+    }
+    else
+    {
+        dts_core_hdr_t hdr;
+
+        ret = get_core_frame(parser_dts, &hdr, &frame_size);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        if (hdr.sample_rate != (uint32_t)parser_dts->sample_rate ||
+            (hdr.samples != parser_dts->frame_duration && !hdr.termination))
+        {
+            msglog(NULL, MSGLOG_ERR, "DTS frame duration or sample rate changes at sample %" PRIu64 "\n",
+                   parser_dts->sample_num);
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        duration       = hdr.samples;
+        sample->flags |= SAMPLE_SYNC;
+    }
+
+    if (!parser_dts->sample_num)
+    {
+        sample->flags |= SAMPLE_NEW_SD;
+    }
+    sample->dts      = parser_dts->dts;
+    sample->cts      = sample->dts;
+    sample->duration = duration;
+
+    if (frame_size > parser_dts->sample_buf_size)
+    {
+        uint8_t *data = REALLOC_CHK(sample->data, frame_size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                = data;
+        parser_dts->sample_buf_size = frame_size;
+    }
+    memcpy(sample->data, parser_dts->es_buf + parser_dts->es_pos, frame_size);
+    sample->size = frame_size;
+
+    parser_dts->es_pos         += frame_size;
+    parser_dts->dts            += duration;
+    parser_dts->total_size     += frame_size;
+    parser_dts->max_frame_size  = MAX2(parser_dts->max_frame_size, (uint32_t)frame_size);
+    parser_dts->sample_num++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** DTSSpecificBox */
+static void
+write_ddts(parser_dts_handle_t parser_dts, bbio_handle_t snk)
+{
+    mp4_dsi_dts_handle_t dsi = (mp4_dsi_dts_handle_t)parser_dts->curr_dsi;
+    uint32_t             max_bitrate, avg_bitrate;
+
+    max_bitrate = (uint32_t)((uint64_t)parser_dts->max_frame_size*8*parser_dts->sample_rate/parser_dts->frame_duration);
+    avg_bitrate = max_bitrate;
+    if (parser_dts->dts)
+    {
+        avg_bitrate = (uint32_t)(parser_dts->total_size*8*parser_dts->sample_rate/parser_dts->dts);
+    }
+
+    sink_write_u32(snk, dsi->sampling_frequency);
+    sink_write_u32(snk, max_bitrate);
+    sink_write_u32(snk, avg_bitrate);
+    sink_write_u8(snk, dsi->pcm_sample_depth);
+    sink_write_bits(snk, 2, dsi->frame_duration_code);
+    sink_write_bits(snk, 5, dsi->stream_construction);
+    sink_write_bits(snk, 1, dsi->core_lfe_present);
+    sink_write_bits(snk, 6, dsi->core_layout);
+    sink_write_bits(snk, 14, dsi->core_size);
+    sink_write_bits(snk, 1, 0);                             /** StereoDownmix */
+    sink_write_bits(snk, 3, 0);                             /** RepresentationType */
+    sink_write_bits(snk, 16, dsi->channel_layout);
+    sink_write_bits(snk, 1, 0);                             /** MultiAssetFlag */
+    sink_write_bits(snk, 1, 0);                             /** LBRDurationMod */
+    sink_write_bits(snk, 1, 0);                             /** ReservedBoxPresent */
+    sink_write_bits(snk, 5, 0);                             /** Reserved */
+}
+
+/** DTSUHDSpecificBox */
+static void
+write_udts(parser_dts_handle_t parser_dts, bbio_handle_t snk)
+{
+    mp4_dsi_dts_handle_t dsi = (mp4_dsi_dts_handle_t)parser_dts->curr_dsi;
+    uint32_t             max_payload_code = 0;
+
+    while (max_payload_code < 7 && (2048u << max_payload_code) < parser_dts->max_frame_size)
+    {
+        max_payload_code++;
+    }
+
+    sink_write_bits(snk, 6, 0);                             /** DecoderProfileCode: profile 2 */
+    sink_write_bits(snk, 2, dsi->frame_duration_code);
+    sink_write_bits(snk, 3, max_payload_code);
+    sink_write_bits(snk, 5, 0);                             /** NumPresentationsCode: 1 presentation */
+    sink_write_bits(snk, 32, 0);                            /** ChannelMask */
+    sink_write_bits(snk, 1, dsi->base_sampling_frequency_code);
+    sink_write_bits(snk, 2, dsi->sample_rate_mod);
+    sink_write_bits(snk, 3, 0);                             /** RepresentationType */
+    sink_write_bits(snk, 3, 0);                             /** StreamIndex */
+    sink_write_bits(snk, 1, 0);                             /** ExpansionBoxPresent */
+    sink_write_bits(snk, 1, 0);                             /** IDTagPresent[0] */
+    sink_flush_bits(snk);
+}
+
+/** get dsi for dts ('ddts' or 'udts') */
+static int32_t
+parser_dts_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    parser_dts_handle_t  parser_dts = (parser_dts_handle_t)parser;
+    mp4_dsi_dts_handle_t dsi        = (mp4_dsi_dts_handle_t)parser->curr_dsi;
+    bbio_handle_t        snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 20, 1);
+    }
+
+    if (dsi->uhd)
+    {
+        write_udts(parser_dts, snk);
+    }
+    else
+    {
+        write_ddts(parser_dts, snk);
+    }
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_dts_show_info(parser_handle_t parser)
+{
+    parser_dts_handle_t  parser_dts = (parser_dts_handle_t)parser;
+    mp4_dsi_dts_handle_t dsi        = (mp4_dsi_dts_handle_t)parser->curr_dsi;
+
+    msglog(NULL, MSGLOG_INFO, "%s: %u channels, %u Hz, %u samples per frame\n",
+           dsi->uhd ? "DTS-UHD" : (dsi->substream ? "DTS-HD" : "DTS"),
+           parser_dts->channelcount, parser_dts->sample_rate, parser_dts->frame_duration);
+}
+
+static int32_t
+init_core(parser_dts_handle_t parser_dts)
+{
+    mp4_dsi_dts_handle_t dsi = (mp4_dsi_dts_handle_t)parser_dts->curr_dsi;
+    dts_core_hdr_t       hdr;
+    size_t               frame_size;
+    int32_t              ret;
+
+    ret = get_core_frame(parser_dts, &hdr, &frame_size);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    if (hdr.samples != 512 && hdr.samples != 1024 && hdr.samples != 2048 && hdr.samples != 4096)
+    {
+        msglog(NULL, MSGLOG_ERR, "Unsupported DTS frame of %u samples\n", hdr.samples);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    parser_dts->sample_rate    = hdr.sample_rate;
+    parser_dts->channelcount   = core_channel_num_tbl[hdr.amode] + (hdr.lff ? 1 : 0);
+    parser_dts->frame_duration = hdr.samples;
+    parser_dts->max_frame_size = (uint32_t)frame_size;
+
+    dsi->sampling_frequency  = hdr.sample_rate;
+    dsi->pcm_sample_depth    = (hdr.pcmr >= 2) ? 24 : 16;
+    dsi->frame_duration_code = (uint8_t)((hdr.samples >= 1024) + (hdr.samples >= 2048) + (hdr.samples >= 4096));
+    /** 1: core only, 0: not specified */
+    dsi->stream_construction = (!hdr.ext_audio && !dsi->substream) ? 1 : 0;
+    dsi->core_lfe_present    = hdr.lff != 0;
+    dsi->core_layout         = (uint8_t)hdr.amode;
+    dsi->core_size           = (uint16_t)(hdr.size - 1);
+    dsi->channel_layout      = core_channel_layout_tbl[hdr.amode] | (hdr.lff ? 0x0008 : 0);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+init_uhd(parser_dts_handle_t parser_dts)
+{
+    mp4_dsi_dts_handle_t dsi = (mp4_dsi_dts_handle_t)parser_dts->curr_dsi;
+    dts_uhd_hdr_t *      hdr = &parser_dts->uhd_hdr;
+    size_t               frame_size;
+    int32_t              ret;
+
+    ret = get_uhd_frame(parser_dts, hdr, &frame_size);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    if (!hdr->sync)
+    {
+        msglog(NULL, MSGLOG_ERR, "The DTS-UHD stream doesn't start with a sync frame\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if (hdr->clock_rate == 32000 || (hdr->duration_ratio & (hdr->duration_ratio - 1)))
+    {
+        msglog(NULL, MSGLOG_ERR, "Unsupported DTS-UHD clock rate %u or frame duration %u\n",
+               hdr->clock_rate, hdr->duration);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    dsi->uhd                          = TRUE;
+    dsi->frame_duration_code          = (uint8_t)((hdr->duration_ratio >= 2) + (hdr->duration_ratio >= 4) +
+                                                  (hdr->duration_ratio >= 8));
+    dsi->base_sampling_frequency_code = (hdr->clock_rate == 48000);
+    dsi->sample_rate_mod              = (uint8_t)hdr->sample_rate_mod;
+
+    parser_dts->sample_rate    = hdr->clock_rate << hdr->sample_rate_mod;
+    parser_dts->frame_duration = hdr->duration << hdr->sample_rate_mod;
+    /** the sample entry channelcount of 'dtsx' is informative */
+    parser_dts->channelcount   = 2;
+    parser_dts->max_frame_size = (uint32_t)frame_size;
+    parser_dts->dsi_FourCC     = "udts";
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_dts_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_dts_handle_t parser_dts = (parser_dts_handle_t)parser;
+    uint32_t            syncword;
+    int32_t             ret;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    parser_dts->es_buf_size = ES_BUF_SIZE;
+    parser_dts->es_buf      = (uint8_t *)MALLOC_CHK(parser_dts->es_buf_size);
+    if (!parser_dts->es_buf)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    ret = es_fill(parser_dts, DTS_MAX_HDR_SIZE);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    syncword = es_peek_u32(parser_dts, 0);
+    if (syncword == DTS_SYNCWORD_CORE)
+    {
+        ret = init_core(parser_dts);
+    }
+    else if (syncword == DTS_SYNCWORD_UHD_SYNC)
+    {
+        ret = init_uhd(parser_dts);
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR, "No 16-bit big endian DTS core or DTS-UHD sync frame at the start\n");
+        ret = EMA_MP4_MUXED_ES_ERR;
+    }
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    parser_dts->time_scale        = parser_dts->sample_rate;
+    parser_dts->num_units_in_tick = parser_dts->frame_duration;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_dts_destroy(parser_handle_t parser)
+{
+    parser_dts_handle_t parser_dts = (parser_dts_handle_t)parser;
+
+    if (parser_dts->es_buf)
+    {
+        FREE_CHK(parser_dts->es_buf);
+    }
+    parser_destroy(parser);
+}
+
+static parser_handle_t
+parser_dts_create(uint32_t dsi_type)
+{
+    parser_dts_handle_t parser;
+
+    assert(dsi_type == DSI_TYPE_MP4FF);
+    parser = (parser_dts_handle_t)MALLOC_CHK(sizeof(parser_dts_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_dts_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type = STREAM_TYPE_AUDIO;
+    parser->stream_id   = STREAM_ID_DTS;
+    parser->stream_name = "dts";
+    parser->dsi_FourCC  = "ddts";
+
+    parser->dsi_type   = dsi_type;
+    parser->dsi_create = dsi_dts_create;
+
+    parser->init       = parser_dts_init;
+    parser->destroy    = parser_dts_destroy;
+    parser->get_sample = parser_dts_get_sample;
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_dts_get_mp4_cfg;
+    }
+
+    parser->show_info = parser_dts_show_info;
+
+    /* use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /**** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_dts_reg(void)
+{
+    reg_parser_set("dts", parser_dts_create);
+    reg_parser_set("dtsx", parser_dts_create);
+}
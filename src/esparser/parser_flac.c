@@ -0,0 +1,550 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_flac.c
+    @brief Implements a FLAC parser
+
+    Reads native FLAC files (RFC 9639). Each frame becomes a sample, the metadata blocks but PADDING and
+    SEEKTABLE go into the 'dfLa' of a 'fLaC' sample entry as in Encapsulation of FLAC in ISO Base Media
+    File Format. A frame ends where the next valid frame header starts and the frame CRC-16 matches.
+*/
+
+#include "utils.h"
+#include "io_base.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+#define FLAC_STREAMINFO         0
+#define FLAC_PADDING            1
+#define FLAC_SEEKTABLE          3
+
+#define FLAC_STREAMINFO_SIZE   34
+#define FLAC_MAX_HDR_SIZE      16
+#define FLAC_MIN_FRAME_SIZE    10   /** frame header, a subframe header, CRC-16 */
+
+#define ES_BUF_SIZE         65536
+
+typedef struct flac_frame_hdr_t_
+{
+    uint32_t size;                  /** of the header */
+    uint32_t block_size;
+    uint32_t sample_rate;           /** 0: the one of STREAMINFO */
+} flac_frame_hdr_t;
+
+struct parser_flac_t_
+{
+    PARSER_AUDIO_BASE;
+
+    /** STREAMINFO */
+    uint32_t min_block_size, max_block_size;
+    uint32_t min_frame_size, max_frame_size;
+    uint64_t total_samples;
+
+    uint8_t *es_buf;                /** es_buf[es_pos, es_size) is not parsed yet */
+    size_t   es_buf_size;
+    size_t   es_pos;
+    size_t   es_size;
+    BOOL     es_eof;
+
+    size_t   sample_buf_size;
+    uint64_t sample_num;
+    uint64_t dts;
+};
+
+typedef struct parser_flac_t_ parser_flac_t;
+typedef parser_flac_t  *parser_flac_handle_t;
+
+static const uint32_t sample_rate_tbl[12] = {
+    0, 88200, 176400, 192000, 8000, 16000, 22050, 24000, 32000, 44100, 48000, 96000
+};
+
+static uint8_t
+crc8(const uint8_t *data, size_t size)
+{
+    uint8_t crc = 0;
+    size_t  i;
+    int     bit;
+
+    for (i = 0; i < size; i++)
+    {
+        crc ^= data[i];
+        for (bit = 0; bit < 8; bit++)
+        {
+            crc = (uint8_t)((crc & 0x80) ? (crc << 1) ^ 0x07 : crc << 1);
+        }
+    }
+    return crc;
+}
+
+static uint16_t
+crc16_update(uint16_t crc, uint8_t byte)
+{
+    int bit;
+
+    crc ^= (uint16_t)(byte << 8);
+    for (bit = 0; bit < 8; bit++)
+    {
+        crc = (uint16_t)((crc & 0x8000) ? (crc << 1) ^ 0x8005 : crc << 1);
+    }
+    return crc;
+}
+
+/** Makes at least size bytes available from es_pos on unless the file ends before */
+static int32_t
+es_fill(parser_flac_handle_t parser_flac, size_t size)
+{
+    bbio_handle_t ds = parser_flac->ds;
+
+    while (parser_flac->es_size - parser_flac->es_pos < size && !parser_flac->es_eof)
+    {
+        size_t avail = parser_flac->es_size - parser_flac->es_pos;
+        size_t read;
+
+        memmove(parser_flac->es_buf, parser_flac->es_buf + parser_flac->es_pos, avail);
+        parser_flac->es_pos  = 0;
+        parser_flac->es_size = avail;
+        if (size > parser_flac->es_buf_size)
+        {
+            size_t   buf_size = MAX2(size, 2*parser_flac->es_buf_size);
+            uint8_t *buf      = REALLOC_CHK(parser_flac->es_buf, buf_size);
+            if (!buf)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            parser_flac->es_buf      = buf;
+            parser_flac->es_buf_size = buf_size;
+        }
+        read = ds->read(ds, parser_flac->es_buf + avail, parser_flac->es_buf_size - avail);
+        if (!read)
+        {
+            parser_flac->es_eof = TRUE;
+        }
+        parser_flac->es_size += read;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Parses the frame header at p, see RFC 9639 9.1; returns FALSE if it isn't a valid one */
+static BOOL
+parse_frame_hdr(const uint8_t *p, size_t avail, flac_frame_hdr_t *hdr)
+{
+    uint32_t block_size_code, sample_rate_code, len, i;
+
+    if (avail < 6 || p[0] != 0xFF || (p[1] & 0xFE) != 0xF8)
+    {
+        return FALSE;
+    }
+    block_size_code  = p[2] >> 4;
+    sample_rate_code = p[2] & 0xF;
+    if (!block_size_code || sample_rate_code == 0xF || (p[3] >> 4) > 10 || ((p[3] >> 1) & 7) == 3 || (p[3] & 1))
+    {
+        return FALSE;
+    }
+
+    /** coded frame or sample number: UTF-8 like, up to 7 bytes */
+    if (!(p[4] & 0x80))
+        len = 1;
+    else if (p[4] == 0xFE)
+        len = 7;
+    else
+    {
+        for (len = 0; len < 7 && (p[4] & (0x80 >> len)); len++);
+        if (len < 2)
+        {
+            return FALSE;
+        }
+    }
+    if (avail < 4 + len + 3)
+    {
+        return FALSE;
+    }
+    for (i = 1; i < len; i++)
+    {
+        if ((p[4 + i] & 0xC0) != 0x80)
+        {
+            return FALSE;
+        }
+    }
+    hdr->size = 4 + len;
+
+    if (block_size_code == 1)
+        hdr->block_size = 192;
+    else if (block_size_code <= 5)
+        hdr->block_size = 576u << (block_size_code - 2);
+    else if (block_size_code == 6)
+        hdr->block_size = p[hdr->size++] + 1u;
+    else if (block_size_code == 7)
+    {
+        hdr->block_size = (p[hdr->size] << 8 | p[hdr->size + 1]) + 1u;
+        hdr->size      += 2;
+    }
+    else
+        hdr->block_size = 256u << (block_size_code - 8);
+
+    if (sample_rate_code < 12)
+        hdr->sample_rate = sample_rate_tbl[sample_rate_code];
+    else if (sample_rate_code == 12)
+        hdr->sample_rate = p[hdr->size++]*1000u;
+    else
+    {
+        hdr->sample_rate = (p[hdr->size] << 8 | p[hdr->size + 1])*((sample_rate_code == 13) ? 1u : 10u);
+        hdr->size       += 2;
+    }
+
+    if (avail < hdr->size + 1 || crc8(p, hdr->size) != p[hdr->size])
+    {
+        return FALSE;
+    }
+    hdr->size++;
+
+    return TRUE;
+}
+
+static int32_t
+parser_flac_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_flac_handle_t parser_flac = (parser_flac_handle_t)parser;
+    flac_frame_hdr_t     hdr, next_hdr;
+    const uint8_t *      frame;
+    size_t               size, frame_size = 0;
+    uint16_t             crc = 0;
+    int32_t              ret;
+
+    sample->flags = 0;
+
+    ret = es_fill(parser_flac, FLAC_MAX_HDR_SIZE);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    if (parser_flac->es_pos == parser_flac->es_size)
+    {
+        return EMA_MP4_MUXED_EOES;
+    }
+    if (!parse_frame_hdr(parser_flac->es_buf + parser_flac->es_pos, parser_flac->es_size - parser_flac->es_pos, &hdr))
+    {
+        msglog(NULL, MSGLOG_ERR, "No FLAC frame header at sample %" PRIu64 "\n", parser_flac->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if (hdr.sample_rate && hdr.sample_rate != (uint32_t)parser_flac->sample_rate)
+    {
+        msglog(NULL, MSGLOG_ERR, "FLAC frame %" PRIu64 " at %u Hz in a %u Hz stream\n",
+               parser_flac->sample_num, hdr.sample_rate, parser_flac->sample_rate);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    /** the frame ends where the CRC-16 of all its bytes is 0 and the next frame header is */
+    for (size = 0; ; size++)
+    {
+        ret = es_fill(parser_flac, size + FLAC_MAX_HDR_SIZE);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        frame = parser_flac->es_buf + parser_flac->es_pos;
+        if (parser_flac->es_pos + size == parser_flac->es_size)
+        {
+            /** the last frame */
+            frame_size = size;
+            break;
+        }
+        if (size >= FLAC_MIN_FRAME_SIZE && !crc &&
+            parse_frame_hdr(frame + size, parser_flac->es_size - parser_flac->es_pos - size, &next_hdr))
+        {
+            frame_size = size;
+            break;
+        }
+        crc = crc16_update(crc, frame[size]);
+    }
+    if (crc)
+    {
+        msglog(NULL, MSGLOG_ERR, "FLAC frame %" PRIu64 " has a wrong CRC\n", parser_flac->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    if (!parser_flac->sample_num)
+    {
+        sample->flags |= SAMPLE_NEW_SD;
+    }
+    sample->flags   |= SAMPLE_SYNC;
+    sample->dts      = parser_flac->dts;
+    sample->cts      = sample->dts;
+    sample->duration = hdr.block_size;
+
+    if (frame_size > parser_flac->sample_buf_size)
+    {
+        uint8_t *data = REALLOC_CHK(sample->data, frame_size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                 = data;
+        parser_flac->sample_buf_size = frame_size;
+    }
+    memcpy(sample->data, frame, frame_size);
+    sample->size = frame_size;
+
+    parser_flac->es_pos += frame_size;
+    parser_flac->dts    += hdr.block_size;
+    parser_flac->sample_num++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** get dsi for flac (FLACSpecificBox) */
+static int32_t
+parser_flac_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    mp4_dsi_flac_handle_t dsi = (mp4_dsi_flac_handle_t)parser->curr_dsi;
+    bbio_handle_t         snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 4 + dsi->metadata_blocks_size, 1);
+    }
+
+    sink_write_u32(snk, 0);                                 /** FullBox version = 0, flags = 0 */
+    snk->write(snk, dsi->metadata_blocks, dsi->metadata_blocks_size);
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_flac_show_info(parser_handle_t parser)
+{
+    parser_flac_handle_t parser_flac = (parser_flac_handle_t)parser;
+
+    msglog(NULL, MSGLOG_INFO, "FLAC: %u channels, %u bit, %u Hz, block size %u..%u\n",
+           parser_flac->channelcount, parser_flac->samplesize, parser_flac->sample_rate,
+           parser_flac->min_block_size, parser_flac->max_block_size);
+}
+
+/** Parses STREAMINFO, see RFC 9639 8.2 */
+static int32_t
+parse_streaminfo(parser_flac_handle_t parser_flac, const uint8_t *p, uint32_t size)
+{
+    if (size < FLAC_STREAMINFO_SIZE)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    parser_flac->min_block_size = p[0] << 8 | p[1];
+    parser_flac->max_block_size = p[2] << 8 | p[3];
+    parser_flac->min_frame_size = p[4] << 16 | p[5] << 8 | p[6];
+    parser_flac->max_frame_size = p[7] << 16 | p[8] << 8 | p[9];
+    parser_flac->sample_rate    = p[10] << 12 | p[11] << 4 | p[12] >> 4;
+    parser_flac->channelcount   = ((p[12] >> 1) & 7) + 1;
+    parser_flac->samplesize     = ((p[12] & 1) << 4 | p[13] >> 4) + 1;
+    parser_flac->total_samples  = (uint64_t)(p[13] & 0xF) << 32 | get_BE_u32(p + 14);
+
+    if (!parser_flac->sample_rate || parser_flac->min_block_size < 16)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Reads the metadata blocks, keeps those for 'dfLa' */
+static int32_t
+parse_metadata(parser_flac_handle_t parser_flac)
+{
+    mp4_dsi_flac_handle_t dsi = (mp4_dsi_flac_handle_t)parser_flac->curr_dsi;
+    uint32_t              last_hdr_pos = 0;
+    BOOL                  last = FALSE;
+    int32_t               ret;
+
+    ret = es_fill(parser_flac, 4);
+    if (ret != EMA_MP4_MUXED_OK || parser_flac->es_size < 4 || memcmp(parser_flac->es_buf, "fLaC", 4))
+    {
+        msglog(NULL, MSGLOG_ERR, "No FLAC stream marker found\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    parser_flac->es_pos = 4;
+
+    while (!last)
+    {
+        const uint8_t *p;
+        uint32_t       type, size;
+
+        ret = es_fill(parser_flac, 4);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        if (parser_flac->es_size - parser_flac->es_pos < 4)
+        {
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        p    = parser_flac->es_buf + parser_flac->es_pos;
+        last = (p[0] & 0x80) != 0;
+        type = p[0] & 0x7F;
+        size = p[1] << 16 | p[2] << 8 | p[3];
+
+        ret = es_fill(parser_flac, 4 + size);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        if (parser_flac->es_size - parser_flac->es_pos < 4 + size || type == 127 ||
+            (type == FLAC_STREAMINFO) != !dsi->metadata_blocks_size)
+        {
+            msglog(NULL, MSGLOG_ERR, "Invalid FLAC metadata block of type %u\n", type);
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        p = parser_flac->es_buf + parser_flac->es_pos;
+
+        if (type == FLAC_STREAMINFO)
+        {
+            ret = parse_streaminfo(parser_flac, p + 4, size);
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                msglog(NULL, MSGLOG_ERR, "Invalid FLAC STREAMINFO\n");
+                return ret;
+            }
+        }
+        if (type != FLAC_PADDING && type != FLAC_SEEKTABLE)
+        {
+            uint8_t *blocks = REALLOC_CHK(dsi->metadata_blocks, dsi->metadata_blocks_size + 4 + size);
+            if (!blocks)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            dsi->metadata_blocks = blocks;
+            last_hdr_pos         = dsi->metadata_blocks_size;
+            memcpy(blocks + dsi->metadata_blocks_size, p, 4 + size);
+            blocks[last_hdr_pos] &= 0x7F;
+            dsi->metadata_blocks_size += 4 + size;
+        }
+        parser_flac->es_pos += 4 + size;
+    }
+    /** the last one kept gets the last-metadata-block flag */
+    dsi->metadata_blocks[last_hdr_pos] |= 0x80;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_flac_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_flac_handle_t parser_flac = (parser_flac_handle_t)parser;
+    int32_t              ret;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    parser_flac->es_buf_size = ES_BUF_SIZE;
+    parser_flac->es_buf      = (uint8_t *)MALLOC_CHK(parser_flac->es_buf_size);
+    if (!parser_flac->es_buf)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    ret = parse_metadata(parser_flac);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    parser_flac->time_scale        = parser_flac->sample_rate;
+    parser_flac->num_units_in_tick = parser_flac->max_block_size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_flac_destroy(parser_handle_t parser)
+{
+    parser_flac_handle_t parser_flac = (parser_flac_handle_t)parser;
+
+    if (parser_flac->es_buf)
+    {
+        FREE_CHK(parser_flac->es_buf);
+    }
+    parser_destroy(parser);
+}
+
+static parser_handle_t
+parser_flac_create(uint32_t dsi_type)
+{
+    parser_flac_handle_t parser;
+
+    assert(dsi_type == DSI_TYPE_MP4FF);
+    parser = (parser_flac_handle_t)MALLOC_CHK(sizeof(parser_flac_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_flac_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type = STREAM_TYPE_AUDIO;
+    parser->stream_id   = STREAM_ID_FLAC;
+    parser->stream_name = "flac";
+    parser->dsi_FourCC  = "dfLa";
+
+    parser->dsi_type   = dsi_type;
+    parser->dsi_create = dsi_flac_create;
+
+    parser->init       = parser_flac_init;
+    parser->destroy    = parser_flac_destroy;
+    parser->get_sample = parser_flac_get_sample;
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_flac_get_mp4_cfg;
+    }
+
+    parser->show_info = parser_flac_show_info;
+
+    /* use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /**** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_flac_reg(void)
+{
+    reg_parser_set("flac", parser_flac_create);
+}
@@ -112,6 +112,10 @@ struct parser_hevc_t_
     uint32_t vps_num, sps_num, pps_num, sps_ext_num;
     uint32_t sei_num;
 
+    /** Dolby Vision NALs in the ES, whatever is signalled or kept of them */
+    uint32_t dv_rpu_found;
+    uint32_t dv_el_found;
+
     /** keep au timing info up to MinCts when SeiPicTiming is available */
     BOOL     bMinCtsKn;
     int32_t  i32PocMin;
@@ -123,7 +127,11 @@ struct parser_hevc_t_
     int64_t delta_dts, dts_pre;
 #endif
 
-    list_handle_t hevc_cts_offset_lst;
+    int64_t  poc_base;         /** cts of poc 0 in the current coded video sequence */
+    int64_t  max_cts;
+    int32_t *cts_offsets;      /** cts - dts of each au */
+    uint32_t cts_offsets_size;
+    int32_t  cts_offset_min;
 
     /** validation */
     uint32_t validation_flags;
@@ -134,23 +142,49 @@ struct parser_hevc_t_
 typedef struct parser_hevc_t_ parser_hevc_t;
 typedef parser_hevc_t  *parser_hevc_handle_t;
 
-typedef struct idx_value_t_
+/** Derives the cts of the picture from its poc, see H.265 8.3.1 */
+static int64_t
+picture_cts(parser_hevc_handle_t parser_hevc, uint64_t dts, BOOL poc_reset)
 {
-    uint32_t idx;
-    uint64_t value;
-} idx_value_t;
+    int64_t ticks = parser_hevc->au_ticks;
+    int32_t poc   = parser_hevc->dec.i_poc;
+    int64_t cts;
+
+    if (parser_hevc->au_num == 0 || poc_reset)
+    {
+        /** after all pictures of the previous coded video sequence */
+        int64_t cts0 = (parser_hevc->au_num == 0) ? (int64_t)dts : MAX2((int64_t)dts, parser_hevc->max_cts + ticks);
+        parser_hevc->poc_base = cts0 - poc*ticks;
+    }
+    cts = parser_hevc->poc_base + poc*ticks;
+    if (parser_hevc->au_num == 0 || cts > parser_hevc->max_cts)
+    {
+        parser_hevc->max_cts = cts;
+    }
 
+    return cts;
+}
 
-static void
-update_idx_value_lst(list_handle_t lst,
-                   uint32_t      idx,
-                   uint64_t      value)
+static int32_t
+cts_offset_add(parser_hevc_handle_t parser_hevc, int32_t cts_offset)
 {
-    idx_value_t *idx_value = (idx_value_t *)list_alloc_entry(lst);
-
-    idx_value->idx = idx;
-    idx_value->value = value;
-    list_add_entry(lst, idx_value);
+    if (parser_hevc->au_num >= parser_hevc->cts_offsets_size)
+    {
+        uint32_t size     = MAX2(1024, 2*parser_hevc->cts_offsets_size);
+        int32_t *offsets  = REALLOC_CHK(parser_hevc->cts_offsets, size*sizeof(int32_t));
+        if (!offsets)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        parser_hevc->cts_offsets      = offsets;
+        parser_hevc->cts_offsets_size = size;
+    }
+    parser_hevc->cts_offsets[parser_hevc->au_num] = cts_offset;
+    if (parser_hevc->au_num == 0 || cts_offset < parser_hevc->cts_offset_min)
+    {
+        parser_hevc->cts_offset_min = cts_offset;
+    }
+    return EMA_MP4_MUXED_OK;
 }
 
 /**  return the offset into buf where the sc is
@@ -529,11 +563,55 @@ get_colr_info(parser_hevc_handle_t parser_hevc, hevc_decode_t * context)
     parser_hevc->colour_primaries = (uint8_t)context->s_vui.i_colour_primaries;
     parser_hevc->transfer_characteristics = (uint8_t)context->s_vui.i_transfer_characteristics;
     parser_hevc->matrix_coefficients = (uint8_t)context->s_vui.i_matrix_coefficients;
+    parser_hevc->colour_description_present = context->s_vui.b_colour_description;
+    parser_hevc->full_range_flag = context->s_vui.b_video_full_range;
+}
+
+/** Takes over the mastering display and content light level SEI info unless given by the user */
+static void
+get_hdr_info(parser_hevc_handle_t parser_hevc, hevc_decode_t * context)
+{
+    if (context->mdcv_present && !parser_hevc->mdcv_present)
+    {
+        memcpy(parser_hevc->display_primaries_x, context->display_primaries_x, sizeof(context->display_primaries_x));
+        memcpy(parser_hevc->display_primaries_y, context->display_primaries_y, sizeof(context->display_primaries_y));
+        parser_hevc->white_point_x = context->white_point_x;
+        parser_hevc->white_point_y = context->white_point_y;
+        parser_hevc->max_display_mastering_luminance = context->max_display_mastering_luminance;
+        parser_hevc->min_display_mastering_luminance = context->min_display_mastering_luminance;
+        parser_hevc->mdcv_present = 1;
+    }
+    if (context->clli_present && !parser_hevc->clli_present)
+    {
+        parser_hevc->max_content_light_level = context->max_content_light_level;
+        parser_hevc->max_pic_average_light_level = context->max_pic_average_light_level;
+        parser_hevc->clli_present = 1;
+    }
 }
 
 static void
 timing_info_update(parser_hevc_handle_t parser_hevc, hevc_decode_t * context)
 {
+    if (parser_hevc->ext_timing.usr_timing &&
+        (context->s_vui.b_timing_info_present_flag || context->s_vps.b_vps_timing_info_present_flag))
+    {
+        /** vps timing takes precedence as below */
+        uint32_t time_scale        = context->s_vps.b_vps_timing_info_present_flag ?
+                                     context->s_vps.ui_vps_time_scale : (uint32_t)context->s_vui.i_time_scale;
+        uint32_t num_units_in_tick = context->s_vps.b_vps_timing_info_present_flag ?
+                                     context->s_vps.ui_vps_num_units_in_tick : (uint32_t)context->s_vui.i_num_units;
+
+        if ((uint64_t)time_scale * parser_hevc->ext_timing.num_units_in_tick !=
+            (uint64_t)parser_hevc->ext_timing.time_scale * num_units_in_tick)
+        {
+            msglog(NULL, MSGLOG_WARNING, "Frame rate %u/%u set by user conflicts with %u/%u in the timing info of the ES, %s\n",
+                   parser_hevc->ext_timing.time_scale, parser_hevc->ext_timing.num_units_in_tick,
+                   time_scale, num_units_in_tick,
+                   parser_hevc->ext_timing.override_timing ? "using the user's" : "ignoring the user's");
+        }
+        parser_hevc->ext_timing.usr_timing = 0;  /** report once */
+    }
+
     if (parser_hevc->ext_timing.override_timing)
     {
         parser_hevc->num_units_in_tick           = parser_hevc->ext_timing.num_units_in_tick;
@@ -1029,6 +1107,8 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
     BOOL       keep_all= FALSE;
     BOOL       found_aud = FALSE;
     BOOL       pic_type_setting_flag = FALSE;
+    hevc_nalu_type_t pic_nal_type = NAL_UNIT_INVALID;
+    int64_t    cts;
 
     uint32_t   sc_size = 0;
     int32_t    nal_in_au = 0;
@@ -1060,6 +1140,7 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
     sample->sample_is_depended_on = 0;
     sample->sample_has_redundancy = 0;
     sample->dependency_level      = 0;
+    sample->sap_type              = 0;
     sample->pic_type              = 0;
     sample->frame_type            = 0xff;
 
@@ -1220,10 +1301,12 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
 
             case NAL_UNIT_PREFIX_SEI:
                 decode_sei_nalu( _context, &nalu );
-                if (_context->rpu_flag)
+                parser_hevc->dv_rpu_found |= _context->rpu_flag;
+                if (_context->rpu_flag && parser->ext_timing.ext_dv_mode != DV_MODE_STRIP)
                 {
                     parser->dv_rpu_nal_flag = 1;
                 }
+                get_hdr_info(parser_hevc, _context);
                 keep_nal  = TRUE;
                 break;
             case NAL_UNIT_SUFFIX_SEI:
@@ -1231,6 +1314,17 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
                 break;
 
             case NAL_UNIT_UNSPECIFIED_62:
+                parser_hevc->dv_rpu_found = 1;
+                if (!_context->rpu_header_present)
+                {
+                    decode_rpu_header(_context, &nalu);
+                }
+                /** Dolby Vision RPU NALs stripped by the user: the BL is muxed as plain HEVC */
+                if (parser->ext_timing.ext_dv_mode == DV_MODE_STRIP)
+                {
+                    keep_nal = FALSE;
+                    break;
+                }
                 /** Dolby Vision RPU NALs found, but the user don't want to signal; Just mux it to comment mp4 */
                 if (parser->ext_timing.ext_dv_profile == 0xff)
                 {
@@ -1244,6 +1338,13 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
                 break;
             /** DolbyVision EL NALs */
             case NAL_UNIT_UNSPECIFIED_63: 
+                parser_hevc->dv_el_found = 1;
+                /** EL NALs dropped by the user: no EL config, the sample sizes cover the kept NALs only */
+                if (parser->ext_timing.ext_dv_mode != DV_MODE_KEEP)
+                {
+                    keep_nal = FALSE;
+                    break;
+                }
                 if (!parser->dv_el_track_flag && parser_hevc->au_num == 0)
                 {
                     uint32_t index = 0;
@@ -1318,6 +1419,7 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
             case NAL_UNIT_EOS:
             case NAL_UNIT_EOB:
                  keep_nal  = TRUE;
+                 _context->b_eos = true;
                 break;
 
             default:
@@ -1380,25 +1482,39 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
         DVB_RAP pictures (including BLA pictures with nal_unit_type equal to BLA_W_LP, CRA 
         pictures with nal_unit_type equal to CRA_NUT and pictures with nal_unit_type equal to 
         TRAIL_R that contain only slices with slice_type equal to 2 (I slice), as specified in 
-        ETSI TS 101 154 [4] clause 5.14.1.8). */
+        ETSI TS 101 154 [4] clause 5.14.1.8). 
+
+        CRA and BLA_W_LP pictures may be followed by RASL pictures referencing pictures before
+        the IRAP picture in decoding order. They are mapped to SAP type 3 as allowed by
+        ISO/IEC 14496-15, the RASL and RADL pictures are signalled as leading samples. */
         if (nal_vcl_flag && !pic_type_setting_flag)
         {
+            pic_nal_type = nalu.e_nalu_type;
             if (   nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_IDR_N_LP
-                || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_CRA
                 || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_N_LP ) 
             {
                 sample->pic_type         = 1;
                 sample->frame_type       = 0;
                 sample->dependency_level = 0x01;
+                sample->sap_type         = 1;
                 sample->flags |= SAMPLE_SYNC;
             }
             else if (   nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_IDR_W_RADL
-                     || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_W_RADL
+                     || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_W_RADL )
+            {
+                sample->pic_type         = 2;
+                sample->frame_type       = 0;
+                sample->dependency_level = 0x01;
+                sample->sap_type         = 2;
+                sample->flags |= SAMPLE_SYNC;
+            }
+            else if (   nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_CRA
                      || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_W_LP )
             {
                 sample->pic_type         = 2;
                 sample->frame_type       = 0;
                 sample->dependency_level = 0x01;
+                sample->sap_type         = 3;
                 sample->flags |= SAMPLE_SYNC;
             }
             else if ( nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_TRAIL_R )
@@ -1413,6 +1529,20 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
                 sample->frame_type       = 1;
                 sample->dependency_level = 0x02;
             }
+
+            /** 'sdtp': IRAP pictures are intra coded, RASL pictures are not decodable without the pictures
+                preceding the associated IRAP picture */
+            sample->sample_depends_on = (sample->flags & SAMPLE_SYNC) ? 2 : 1;
+            if (   nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_RASL_N
+                || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_RASL_R )
+            {
+                sample->is_leading = 1;
+            }
+            else if (   nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_RADL_N
+                     || nalu.e_nalu_type == NAL_UNIT_CODED_SLICE_RADL_R )
+            {
+                sample->is_leading = 3;
+            }
             pic_type_setting_flag = TRUE;
         }
 
@@ -1549,12 +1679,15 @@ parser_hevc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
     sample->dts = parser_hevc->au_num;
     sample->dts *= parser_hevc->au_ticks;
 
-    if(_context->i_prev_poc == 0)
-        _context->poc_offset = sample->dts;
-    sample->cts = _context->poc_offset + _context->i_prev_poc*parser_hevc->au_ticks;
+    cts = picture_cts(parser_hevc, sample->dts,
+                      _context->IDR_pic_flag || (pic_nal_type == NAL_UNIT_CODED_SLICE_CRA && _context->b_no_rasl_output));
+    sample->cts = (uint64_t)cts;
+    err = cts_offset_add(parser_hevc, (int32_t)(cts - (int64_t)sample->dts));
+    if (err != EMA_MP4_MUXED_OK)
+    {
+        return err;
+    }
 
-    update_idx_value_lst(parser_hevc->hevc_cts_offset_lst, parser_hevc->num_samples, sample->cts - sample->dts);
-    
     sample->duration = parser_hevc->au_ticks;
 
     /**** data */
@@ -1710,41 +1843,14 @@ parser_hevc_need_fix_ctts(parser_handle_t parser)
 static int32_t
 parser_hevc_get_cts_offset(parser_handle_t parser, uint32_t sample_idx)
 {
-    idx_value_t *  cv;
-    uint64_t ctts = 0;
-    static int32_t ctts_offset = 0;
-
-    it_list_handle_t   it  = it_create();
     parser_hevc_handle_t parser_hevc = (parser_hevc_handle_t)parser;
-    
-    if(sample_idx == 0)
-    {
 
-        it_init(it,parser_hevc->hevc_cts_offset_lst);
-        while ((cv = (idx_value_t *)it_get_entry(it)))
-        {
-            if((int32_t)cv->value < ctts_offset)
-                ctts_offset = (int32_t)cv->value;
-            
-        }
-        it_destroy(it);
-        return (-ctts_offset);
-    }
-    else
+    if (sample_idx >= parser_hevc->au_num)
     {
-        it_init(it, parser_hevc->hevc_cts_offset_lst);
-        while ((cv = (idx_value_t *)it_get_entry(it)))
-        {
-            if (cv->idx == sample_idx)
-            {
-                ctts = cv->value;
-                break;
-            }
-        }
-        it_destroy(it);
+        return 0;
     }
-
-    return (int32_t)(ctts + (-ctts_offset));
+    /** leading pictures of a CRA starting the stream have a cts below their dts */
+    return parser_hevc->cts_offsets[sample_idx] - parser_hevc->cts_offset_min;
 }
 
 /** get dsi for hevc (HEVCDecoderConfigurationRecord) */
@@ -2069,10 +2175,30 @@ parser_hevc_get_param_ex(parser_handle_t parser, stream_param_id_t param_id, int
 static uint32_t
 parser_hevc_get_param(parser_handle_t parser, stream_param_id_t param_id)
 {
-    /** Currently we have not implement it. */
-    param_id;
-    parser;
-    return 0;
+    parser_hevc_handle_t parser_hevc = (parser_hevc_handle_t)parser;
+    hevc_decode_t *      context     = &parser_hevc->dec;
+
+    switch (param_id)
+    {
+    case STREAM_PARAM_ID_TIME_SCALE:
+        return parser->time_scale;
+    case STREAM_PARAM_ID_NUM_UNITS_IN_TICK:
+        return parser->num_units_in_tick;
+    case STREAM_PARAM_ID_BIT_DEPTH_LUMA:
+        return (uint32_t)context->as_sps[context->i_curr_sps_idx].i_bit_depth_luma;
+    case STREAM_PARAM_ID_PREFERRED_TRANSFER:
+        return context->preferred_transfer_present ? context->preferred_transfer_characteristics : (uint32_t)-1;
+    case STREAM_PARAM_ID_DV_RPU_FOUND:
+        return parser_hevc->dv_rpu_found;
+    case STREAM_PARAM_ID_DV_EL_FOUND:
+        return parser_hevc->dv_el_found;
+    case STREAM_PARAM_ID_DV_VDR_RPU_PROFILE:
+        return context->rpu_header_present ? context->vdr_rpu_profile : (uint32_t)-1;
+    case STREAM_PARAM_ID_DV_VDR_BIT_DEPTH:
+        return context->rpu_header_present ? context->vdr_bit_depth : (uint32_t)-1;
+    default:
+        return (uint32_t)-1;
+    }
 }
 
 
@@ -2115,9 +2241,9 @@ hevc_close(parser_handle_t parser)
 
     FREE_CHK(parser_hevc->nal.tmp_buf);
     FREE_CHK(parser_hevc->nal.buffer);
-    if (parser_hevc->hevc_cts_offset_lst)
+    if (parser_hevc->cts_offsets)
     {
-        list_destroy(parser_hevc->hevc_cts_offset_lst);
+        FREE_CHK(parser_hevc->cts_offsets);
     }
 
     if (parser_hevc->nal.tmp_buf_bbi)
@@ -2212,8 +2338,6 @@ parser_hevc_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t
     parser_hevc->last_idr_pos    = (uint32_t)(-1);
     parser_hevc->post_validation = NULL; 
 
-    parser_hevc->hevc_cts_offset_lst = list_create(sizeof(idx_value_t));
-
     /** reset HEVC sample buffer */
     return EMA_MP4_MUXED_OK;
 
@@ -2273,7 +2397,7 @@ parser_hevc_create(uint32_t dsi_type)
         parser->get_cfg = parser_hevc_get_mp4_cfg;
     }
 
-    parser->get_param    = NULL; 
+    parser->get_param    = parser_hevc_get_param;
     parser->get_param_ex = NULL;
 
     /**** demux related api, currently hevc muxer don't need them */
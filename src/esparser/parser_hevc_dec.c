@@ -74,6 +74,8 @@ hevcdec_create_context(hevc_decode_t *context)
     context->s_vui.i_num_units = 1001;
     context->s_vui.i_time_scale = 60000;
 
+    /** the first picture starts a coded video sequence */
+    context->b_eos = true;
 }
 
 void
@@ -1369,13 +1371,21 @@ bool parse_slice_header( hevc_decode_t *context, hevc_nalu_t *p_nalu, slice_t *p
             p_slice->b_pic_output = true;
     
     
+        /** NoRaslOutputFlag (8.1.3): a CRA starting the bitstream or following an EOS is handled like a BLA */
+        if( p_slice->b_1st_slice &&
+            p_nalu->e_nalu_type >= NAL_UNIT_CODED_SLICE_BLA_W_LP && p_nalu->e_nalu_type <= NAL_UNIT_CODED_SLICE_CRA )
+        {
+            context->b_no_rasl_output = p_nalu->e_nalu_type != NAL_UNIT_CODED_SLICE_CRA || context->b_eos;
+            context->b_eos = false;
+        }
+
         if( p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_IDR_W_RADL || p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_IDR_N_LP )
         {
-            context->i_prev_poc = p_slice->i_poc;
-            if( 0 == p_slice->i_temp_hier && reference_nalu( p_nalu ) && p_nalu->e_nalu_type != NAL_UNIT_CODED_SLICE_RASL_R && p_nalu->e_nalu_type != NAL_UNIT_CODED_SLICE_RADL_R )
-                context->i_prev_tid0_poc = context->i_prev_poc;
-        
             p_slice->i_poc = 0;
+            context->i_poc = 0;
+            context->i_prev_poc = 0;
+            context->i_prev_tid0_poc = 0;
+
             p_slice->s_rps_local.i_num_negativePictures = 0;
             p_slice->s_rps_local.i_num_positivePictures = 0;
             p_slice->s_rps_local.i_num_longtermPictures = 0;
@@ -1410,13 +1420,15 @@ bool parse_slice_header( hevc_decode_t *context, hevc_nalu_t *p_nalu, slice_t *p
 
             if( p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_W_LP   ||
                 p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_W_RADL ||
-                p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_N_LP )
+                p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_BLA_N_LP   ||
+                (p_nalu->e_nalu_type == NAL_UNIT_CODED_SLICE_CRA && context->b_no_rasl_output) )
             {
                 /** For BLA/BLANT, POCmsb is set to 0. Na denn macht mal. */
                 i_poc_msb = 0;
             }
             
             p_slice->i_poc = i_poc_msb + i_poc_lsb;
+            context->i_poc = p_slice->i_poc;
             if( 0 == p_slice->i_temp_hier ) context->i_prev_poc = p_slice->i_poc;
             if( 0 == p_slice->i_temp_hier && reference_nalu( p_nalu ) && p_nalu->e_nalu_type != NAL_UNIT_CODED_SLICE_RASL_R && p_nalu->e_nalu_type != NAL_UNIT_CODED_SLICE_RADL_R )
                 context->i_prev_tid0_poc = context->i_prev_poc;
@@ -1606,25 +1618,34 @@ typedef enum
     SEI_SCALABLE_NESTING                     = 133,
     SEI_REGION_REFRESH_INFO                  = 134,
     SEI_MASTERING_DISPLAY_COLOR_VOLUME       = 137,
-    SEI_LIGHT_LEVEL_INFORMATION              = 144
+    SEI_LIGHT_LEVEL_INFORMATION              = 144,
+    SEI_ALTERNATIVE_TRANSFER_CHARACTERISTICS = 147
 } SEI_PayloadType_t;
 
 void decode_sei_nalu( hevc_decode_t *p_context, hevc_nalu_t *p_nalu )
 {
+    bitstream_t *bs = &p_nalu->bitstream;
+
     do
     {
         SEI_PayloadType_t e_payload_type;
         uint32_t payloadSize;
+        int64_t  i64_payload_end;
         uint8_t byte;
 
         *((int32_t *)&e_payload_type) = 0;
         for( byte = 0xff; 0xff == byte; )
-            *((int32_t *)&e_payload_type) += byte = (uint8_t)bitstream_read( &p_nalu->bitstream, 8 );
+            *((int32_t *)&e_payload_type) += byte = (uint8_t)bitstream_read( bs, 8 );
 
         payloadSize = 0;
         for( byte = 0xff; 0xff == byte; )
-            payloadSize += byte = (uint8_t)bitstream_read( &p_nalu->bitstream, 8 );
-                
+            payloadSize += byte = (uint8_t)bitstream_read( bs, 8 );
+
+        /** a truncated payload ends the sei parsing */
+        i64_payload_end = bs->i64_bits_available - (int64_t)payloadSize * 8;
+        if( i64_payload_end < 0 )
+            return;
+
         switch( e_payload_type )
         {
             case SEI_USER_DATA_REGISTERED_ITU_T_T35:
@@ -1634,10 +1655,13 @@ void decode_sei_nalu( hevc_decode_t *p_context, hevc_nalu_t *p_nalu )
                 uint32_t user_id = 0;
                 uint8_t data_type_code = 0;
 
-                country_code = (uint8_t)bitstream_read( &p_nalu->bitstream, 8 );
-                provider_code = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                user_id = bitstream_read( &p_nalu->bitstream, 32 );
-                data_type_code = (uint8_t)bitstream_read( &p_nalu->bitstream, 8 );
+                if( payloadSize < 8 )
+                    break;
+
+                country_code = (uint8_t)bitstream_read( bs, 8 );
+                provider_code = (uint16_t)bitstream_read( bs, 16 );
+                user_id = bitstream_read( bs, 32 );
+                data_type_code = (uint8_t)bitstream_read( bs, 8 );
 
                 if (country_code  == 0xb5 &&
                     provider_code == 0x31 &&
@@ -1650,35 +1674,88 @@ void decode_sei_nalu( hevc_decode_t *p_context, hevc_nalu_t *p_nalu )
                 break;
             }
             case SEI_MASTERING_DISPLAY_COLOR_VOLUME:
+                /** G, B, R and the white point in units of 0.00002, luminance in units of 0.0001 cd/m2 */
+                if( payloadSize >= 24 && !p_context->mdcv_present )
                 {
-                    uint16_t temp0 = 0;
-                    uint32_t temp1 = 0;
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Green primary  x: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Green primary  y: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Blue primary   x: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Blue primary   y: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Red primary    x: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Red primary    y: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering White primary  x: %f\n", temp0*0.00002);
-                     temp0 = (uint16_t)bitstream_read( &p_nalu->bitstream, 16 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering White primary  y: %f\n", temp0*0.00002);
-
-                     temp1 = bitstream_read( &p_nalu->bitstream, 32 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Luminance Man: %f\n", temp1*0.0001);
-                     temp1 = bitstream_read( &p_nalu->bitstream, 32 );
-                      msglog(NULL, MSGLOG_INFO, "Mastering Luminance Min: %f\n", temp1*0.0001);
+                    int32_t c;
+
+                    for( c = 0; c < 3; c++ )
+                    {
+                        p_context->display_primaries_x[ c ] = (uint16_t)bitstream_read( bs, 16 );
+                        p_context->display_primaries_y[ c ] = (uint16_t)bitstream_read( bs, 16 );
+                    }
+                    p_context->white_point_x = (uint16_t)bitstream_read( bs, 16 );
+                    p_context->white_point_y = (uint16_t)bitstream_read( bs, 16 );
+                    p_context->max_display_mastering_luminance = bitstream_read( bs, 32 );
+                    p_context->min_display_mastering_luminance = bitstream_read( bs, 32 );
+                    p_context->mdcv_present = 1;
+
+                    msglog(NULL, MSGLOG_INFO, "Mastering display: G(%u,%u) B(%u,%u) R(%u,%u) WP(%u,%u) L(%u,%u)\n",
+                           p_context->display_primaries_x[ 0 ], p_context->display_primaries_y[ 0 ],
+                           p_context->display_primaries_x[ 1 ], p_context->display_primaries_y[ 1 ],
+                           p_context->display_primaries_x[ 2 ], p_context->display_primaries_y[ 2 ],
+                           p_context->white_point_x, p_context->white_point_y,
+                           p_context->max_display_mastering_luminance, p_context->min_display_mastering_luminance);
                 }
+                break;
+            case SEI_LIGHT_LEVEL_INFORMATION:
+                if( payloadSize >= 4 && !p_context->clli_present )
+                {
+                    p_context->max_content_light_level     = (uint16_t)bitstream_read( bs, 16 );
+                    p_context->max_pic_average_light_level = (uint16_t)bitstream_read( bs, 16 );
+                    p_context->clli_present = 1;
+
+                    msglog(NULL, MSGLOG_INFO, "Content light level: MaxCLL %u, MaxFALL %u\n",
+                           p_context->max_content_light_level, p_context->max_pic_average_light_level);
+                }
+                break;
+            case SEI_ALTERNATIVE_TRANSFER_CHARACTERISTICS:
+                if( payloadSize >= 1 && !p_context->preferred_transfer_present )
+                {
+                    p_context->preferred_transfer_characteristics = (uint8_t)bitstream_read( bs, 8 );
+                    p_context->preferred_transfer_present = 1;
+                }
+                break;
             default:
                 break;
         }
-    }while( p_nalu->bitstream.i64_bits_available > 2 );
+
+        /** skip what is left of the payload */
+        while( bs->i64_bits_available > i64_payload_end )
+            bitstream_read( bs, (uint32_t)MIN2( bs->i64_bits_available - i64_payload_end, 32 ) );
+    }while( more_rbsp_data( bs ) );
+}
+
+/** Reads rpu_data_header() of a Dolby Vision RPU NAL unit up to vdr_bit_depth_minus8 */
+void decode_rpu_header( hevc_decode_t *p_context, hevc_nalu_t *p_nalu )
+{
+    bitstream_t *bs = &p_nalu->bitstream;
+    uint32_t rpu_format, vdr_rpu_profile;
+
+    /** rpu_nal_prefix */
+    if( bs->i64_bits_available < 8 * 8 || bitstream_read( bs, 8 ) != 25 )
+        return;
+
+    /** rpu_type 2 */
+    if( bitstream_read( bs, 6 ) != 2 )
+        return;
+    rpu_format = bitstream_read( bs, 11 );
+    vdr_rpu_profile = bitstream_read( bs, 4 );
+    bitstream_read( bs, 4 );                /** vdr_rpu_level */
+    if( !bitstream_read( bs, 1 ) )          /** vdr_seq_info_present_flag */
+        return;
+    bitstream_read( bs, 1 );                /** chroma_resampling_explicit_filter_flag */
+    if( bitstream_read( bs, 2 ) == 0 )      /** coefficient_data_type */
+        bitstream_read_uvlc( bs );          /** coefficient_log2_denom */
+    bitstream_read( bs, 3 );                /** vdr_rpu_normalized_idc, bl_video_full_range_flag */
+    if( rpu_format & 0x700 )
+        return;
+    bitstream_read_uvlc( bs );              /** bl_bit_depth_minus8 */
+    bitstream_read_uvlc( bs );              /** el_bit_depth_minus8 */
+
+    p_context->vdr_rpu_profile = (uint8_t)vdr_rpu_profile;
+    p_context->vdr_bit_depth = (uint8_t)(bitstream_read_uvlc( bs ) + 8);
+    p_context->rpu_header_present = 1;
 }
 
 
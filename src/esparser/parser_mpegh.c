@@ -0,0 +1,473 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_mpegh.c
+    @brief Implements an MPEG-H 3D Audio parser
+
+    Reads MPEG-H 3D Audio Stream (MHAS, ISO/IEC 23008-3 clause 14) files. The MHAS packets up to and
+    including an MPEGH3DAFRAME packet make a sample of an 'mhm1' sample entry, SYNC packets are dropped.
+    Samples carrying an MPEGH3DACFG packet are sync samples, the first configuration goes into the 'mhaC'.
+*/
+
+#include "utils.h"
+#include "io_base.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+#define MHAS_PACTYP_MPEGH3DACFG     1
+#define MHAS_PACTYP_MPEGH3DAFRAME   2
+#define MHAS_PACTYP_SYNC            6
+
+#define MHAS_MAX_HDR_SIZE          15   /** escapedValue(3,8,8), (2,8,32), (11,24,24) */
+
+#define ES_BUF_SIZE             65536
+
+typedef struct mhas_packet_hdr_t_
+{
+    uint32_t size;                  /** of the header */
+    uint32_t type;
+    uint64_t label;
+    uint64_t length;                /** of the payload */
+} mhas_packet_hdr_t;
+
+struct parser_mpegh_t_
+{
+    PARSER_AUDIO_BASE;
+
+    uint32_t frame_length;          /** output samples per frame */
+
+    uint8_t *es_buf;                /** es_buf[es_pos, es_size) is not parsed yet */
+    size_t   es_buf_size;
+    size_t   es_pos;
+    size_t   es_size;
+    BOOL     es_eof;
+
+    size_t   sample_buf_size;
+    uint64_t sample_num;
+    uint64_t dts;
+};
+
+typedef struct parser_mpegh_t_ parser_mpegh_t;
+typedef parser_mpegh_t  *parser_mpegh_handle_t;
+
+/** usacSamplingFrequencyIndex, 0: reserved */
+static const uint32_t sampling_frequency_tbl[31] = {
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350, 0, 0,
+    57600, 51200, 40000, 38400, 34150, 28800, 25600, 20000, 19200, 17075, 14400, 12800, 9600, 0, 0, 0
+};
+
+/** channels of the ChannelConfiguration of ISO/IEC 23091-3, 0: reserved */
+static const uint8_t cicp_channel_num_tbl[21] = {
+    0, 1, 2, 3, 4, 5, 6, 8, 2, 3, 4, 7, 8, 24, 8, 12, 10, 12, 14, 12, 14
+};
+
+/** Makes at least size bytes available from es_pos on unless the file ends before */
+static int32_t
+es_fill(parser_mpegh_handle_t parser_mpegh, size_t size)
+{
+    bbio_handle_t ds = parser_mpegh->ds;
+
+    while (parser_mpegh->es_size - parser_mpegh->es_pos < size && !parser_mpegh->es_eof)
+    {
+        size_t avail = parser_mpegh->es_size - parser_mpegh->es_pos;
+        size_t read;
+
+        memmove(parser_mpegh->es_buf, parser_mpegh->es_buf + parser_mpegh->es_pos, avail);
+        parser_mpegh->es_pos  = 0;
+        parser_mpegh->es_size = avail;
+        if (size > parser_mpegh->es_buf_size)
+        {
+            size_t   buf_size = MAX2(size, 2*parser_mpegh->es_buf_size);
+            uint8_t *buf      = REALLOC_CHK(parser_mpegh->es_buf, buf_size);
+            if (!buf)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            parser_mpegh->es_buf      = buf;
+            parser_mpegh->es_buf_size = buf_size;
+        }
+        read = ds->read(ds, parser_mpegh->es_buf + avail, parser_mpegh->es_buf_size - avail);
+        if (!read)
+        {
+            parser_mpegh->es_eof = TRUE;
+        }
+        parser_mpegh->es_size += read;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+/** escapedValue() of ISO/IEC 23008-3 */
+static uint64_t
+escaped_value(bbio_handle_t bs, uint32_t nbits1, uint32_t nbits2, uint32_t nbits3, uint32_t *bit_num)
+{
+    uint64_t value = src_read_bits(bs, nbits1);
+
+    *bit_num += nbits1;
+    if (value == (1u << nbits1) - 1)
+    {
+        uint32_t value_add = src_read_bits(bs, nbits2);
+
+        *bit_num += nbits2;
+        value    += value_add;
+        if (value_add == (1u << nbits2) - 1)
+        {
+            value    += src_read_bits(bs, nbits3);
+            *bit_num += nbits3;
+        }
+    }
+    return value;
+}
+
+/** Reads the header of the MHAS packet at es_pos + offset and makes the whole packet available;
+ *  EMA_MP4_MUXED_EOES if the stream ends there */
+static int32_t
+read_packet_hdr(parser_mpegh_handle_t parser_mpegh, size_t offset, mhas_packet_hdr_t *hdr)
+{
+    bbio_handle_t bs;
+    uint32_t      bit_num = 0;
+    size_t        avail;
+    int32_t       ret;
+
+    ret = es_fill(parser_mpegh, offset + MHAS_MAX_HDR_SIZE);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    avail = parser_mpegh->es_size - parser_mpegh->es_pos - offset;
+    if (!avail)
+    {
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, parser_mpegh->es_buf + parser_mpegh->es_pos + offset, MIN2(avail, MHAS_MAX_HDR_SIZE), 0);
+    hdr->type   = (uint32_t)escaped_value(bs, 3, 8, 8, &bit_num);
+    hdr->label  = escaped_value(bs, 2, 8, 32, &bit_num);
+    hdr->length = escaped_value(bs, 11, 24, 24, &bit_num);
+    bs->destroy(bs);
+    hdr->size = (bit_num + 7)/8;
+
+    ret = es_fill(parser_mpegh, offset + hdr->size + (size_t)hdr->length);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    if (parser_mpegh->es_size - parser_mpegh->es_pos - offset < hdr->size + hdr->length)
+    {
+        msglog(NULL, MSGLOG_ERR, "Truncated MHAS packet of type %u\n", hdr->type);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_mpegh_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_mpegh_handle_t parser_mpegh = (parser_mpegh_handle_t)parser;
+    mhas_packet_hdr_t     hdr;
+    size_t                offset = 0;
+    BOOL                  frame_found = FALSE;
+    int32_t               ret;
+
+    sample->flags = 0;
+    sample->size  = 0;
+
+    while (!frame_found)
+    {
+        size_t packet_size;
+
+        ret = read_packet_hdr(parser_mpegh, offset, &hdr);
+        if (ret == EMA_MP4_MUXED_EOES && offset)
+        {
+            msglog(NULL, MSGLOG_ERR, "MHAS stream ends without an MPEGH3DAFRAME packet\n");
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+        packet_size = hdr.size + (size_t)hdr.length;
+
+        if (hdr.type == MHAS_PACTYP_MPEGH3DACFG)
+        {
+            sample->flags |= SAMPLE_SYNC;
+        }
+        frame_found = (hdr.type == MHAS_PACTYP_MPEGH3DAFRAME);
+
+        /** SYNC packets are for transport streams only */
+        if (hdr.type != MHAS_PACTYP_SYNC)
+        {
+            if (sample->size + packet_size > parser_mpegh->sample_buf_size)
+            {
+                size_t   buf_size = MAX2(sample->size + packet_size, 2*parser_mpegh->sample_buf_size);
+                uint8_t *data     = REALLOC_CHK(sample->data, buf_size);
+                if (!data)
+                {
+                    return EMA_MP4_MUXED_NO_MEM;
+                }
+                sample->data                  = data;
+                parser_mpegh->sample_buf_size = buf_size;
+            }
+            memcpy(sample->data + sample->size, parser_mpegh->es_buf + parser_mpegh->es_pos + offset, packet_size);
+            sample->size += packet_size;
+        }
+        offset += packet_size;
+    }
+
+    if (!parser_mpegh->sample_num)
+    {
+        sample->flags |= SAMPLE_NEW_SD;
+    }
+    sample->dts      = parser_mpegh->dts;
+    sample->cts      = sample->dts;
+    sample->duration = parser_mpegh->frame_length;
+
+    parser_mpegh->es_pos += offset;
+    parser_mpegh->dts    += parser_mpegh->frame_length;
+    parser_mpegh->sample_num++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** get dsi for mpeg-h (MHAConfigurationBox) */
+static int32_t
+parser_mpegh_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    mp4_dsi_mpegh_handle_t dsi = (mp4_dsi_mpegh_handle_t)parser->curr_dsi;
+    bbio_handle_t          snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 5 + dsi->config_size, 1);
+    }
+
+    sink_write_u8(snk, 1);                                  /** configurationVersion */
+    sink_write_u8(snk, dsi->profile_level);
+    sink_write_u8(snk, dsi->reference_channel_layout);
+    sink_write_u16(snk, (uint16_t)dsi->config_size);
+    snk->write(snk, dsi->config, dsi->config_size);
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_mpegh_show_info(parser_handle_t parser)
+{
+    parser_mpegh_handle_t  parser_mpegh = (parser_mpegh_handle_t)parser;
+    mp4_dsi_mpegh_handle_t dsi          = (mp4_dsi_mpegh_handle_t)parser->curr_dsi;
+
+    msglog(NULL, MSGLOG_INFO, "MPEG-H: profile level 0x%02x, %u channels, %u Hz, %u samples per frame\n",
+           dsi->profile_level, parser_mpegh->channelcount, parser_mpegh->sample_rate, parser_mpegh->frame_length);
+}
+
+/** Parses the start of mpegh3daConfig() up to the SpeakerConfig3d() and keeps it for 'mhaC' */
+static int32_t
+parse_config(parser_mpegh_handle_t parser_mpegh, const uint8_t *p, size_t size)
+{
+    mp4_dsi_mpegh_handle_t dsi = (mp4_dsi_mpegh_handle_t)parser_mpegh->curr_dsi;
+    bbio_handle_t          bs;
+    uint32_t               sampling_frequency_idx, frame_length_idx, speaker_layout_type, bit_num = 0;
+
+    if (size < 3 || size > 0xFFFF)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, (uint8_t *)p, size, 0);
+    dsi->profile_level     = (uint8_t)src_read_bits(bs, 8);
+    sampling_frequency_idx = src_read_bits(bs, 5);
+    if (sampling_frequency_idx == 0x1F)
+    {
+        parser_mpegh->sample_rate = src_read_bits(bs, 24);
+    }
+    else
+    {
+        parser_mpegh->sample_rate = sampling_frequency_tbl[sampling_frequency_idx];
+    }
+    frame_length_idx = src_read_bits(bs, 3);        /** coreSbrFrameLengthIndex */
+    src_skip_bits(bs, 2);                           /** cfg_reserved, receiverDelayCompensation */
+    speaker_layout_type = src_read_bits(bs, 2);
+    if (!speaker_layout_type)
+    {
+        dsi->reference_channel_layout = (uint8_t)src_read_bits(bs, 6);
+        parser_mpegh->channelcount    = (dsi->reference_channel_layout < 21) ?
+                                        cicp_channel_num_tbl[dsi->reference_channel_layout] : 0;
+    }
+    else
+    {
+        dsi->reference_channel_layout = 0;
+        parser_mpegh->channelcount    = (int32_t)escaped_value(bs, 5, 8, 16, &bit_num) + 1;
+    }
+    bs->destroy(bs);
+
+    /** no SBR in MPEG-H 3D Audio */
+    if (frame_length_idx > 1)
+    {
+        msglog(NULL, MSGLOG_ERR, "Unsupported MPEG-H coreSbrFrameLengthIndex %u\n", frame_length_idx);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    parser_mpegh->frame_length = frame_length_idx ? 1024 : 768;
+    if (!parser_mpegh->sample_rate || !parser_mpegh->channelcount)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    dsi->config = (uint8_t *)MALLOC_CHK(size);
+    if (!dsi->config)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    memcpy(dsi->config, p, size);
+    dsi->config_size = (uint32_t)size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_mpegh_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_mpegh_handle_t parser_mpegh = (parser_mpegh_handle_t)parser;
+    mhas_packet_hdr_t     hdr;
+    size_t                offset = 0;
+    int32_t               ret;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    parser_mpegh->es_buf_size = ES_BUF_SIZE;
+    parser_mpegh->es_buf      = (uint8_t *)MALLOC_CHK(parser_mpegh->es_buf_size);
+    if (!parser_mpegh->es_buf)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    /** the configuration must come before the first frame, the packets stay for the first sample */
+    for (;;)
+    {
+        ret = read_packet_hdr(parser_mpegh, offset, &hdr);
+        if (ret != EMA_MP4_MUXED_OK || hdr.type == MHAS_PACTYP_MPEGH3DAFRAME)
+        {
+            msglog(NULL, MSGLOG_ERR, "No MPEGH3DACFG packet at the start of the MHAS stream\n");
+            return (ret == EMA_MP4_MUXED_NO_MEM) ? ret : EMA_MP4_MUXED_ES_ERR;
+        }
+        if (hdr.type == MHAS_PACTYP_MPEGH3DACFG)
+        {
+            break;
+        }
+        offset += hdr.size + (size_t)hdr.length;
+    }
+
+    ret = parse_config(parser_mpegh, parser_mpegh->es_buf + parser_mpegh->es_pos + offset + hdr.size,
+                       (size_t)hdr.length);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        msglog(NULL, MSGLOG_ERR, "Invalid MPEG-H configuration\n");
+        return ret;
+    }
+
+    parser_mpegh->time_scale        = parser_mpegh->sample_rate;
+    parser_mpegh->num_units_in_tick = parser_mpegh->frame_length;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_mpegh_destroy(parser_handle_t parser)
+{
+    parser_mpegh_handle_t parser_mpegh = (parser_mpegh_handle_t)parser;
+
+    if (parser_mpegh->es_buf)
+    {
+        FREE_CHK(parser_mpegh->es_buf);
+    }
+    parser_destroy(parser);
+}
+
+static parser_handle_t
+parser_mpegh_create(uint32_t dsi_type)
+{
+    parser_mpegh_handle_t parser;
+
+    assert(dsi_type == DSI_TYPE_MP4FF);
+    parser = (parser_mpegh_handle_t)MALLOC_CHK(sizeof(parser_mpegh_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_mpegh_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type = STREAM_TYPE_AUDIO;
+    parser->stream_id   = STREAM_ID_MPEGH;
+    parser->stream_name = "mpegh";
+    parser->dsi_FourCC  = "mhaC";
+
+    parser->dsi_type   = dsi_type;
+    parser->dsi_create = dsi_mpegh_create;
+
+    parser->init       = parser_mpegh_init;
+    parser->destroy    = parser_mpegh_destroy;
+    parser->get_sample = parser_mpegh_get_sample;
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_mpegh_get_mp4_cfg;
+    }
+
+    parser->show_info = parser_mpegh_show_info;
+
+    /* use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /**** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_mpegh_reg(void)
+{
+    reg_parser_set("mhas", parser_mpegh_create);
+    reg_parser_set("mpegh", parser_mpegh_create);
+}
@@ -0,0 +1,492 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_opus.c
+    @brief Implements an Opus parser
+
+    Reads the first Opus stream of an Ogg file (RFC 7845). Each Opus packet becomes a sample, the
+    identification header becomes the 'dOps' of an 'Opus' sample entry as in Encapsulation of Opus in ISO
+    Base Media File Format. The pre-skip and the end trimming of the last page's granule position are
+    handed to the muxer for the edit list, the 80 ms pre-roll for a 'roll' sample group.
+*/
+
+#include "utils.h"
+#include "io_base.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+#define OPUS_SAMPLE_RATE      48000
+#define OPUS_PRE_ROLL         3840   /** 80 ms at 48 kHz */
+
+#define OGG_PAGE_HDR_SIZE     27
+#define OGG_HDR_CONTINUED     0x01
+#define OGG_HDR_BOS           0x02
+
+struct parser_opus_t_
+{
+    PARSER_AUDIO_BASE;
+
+    /** the Ogg page being read */
+    uint8_t  lacing[255];
+    uint32_t segment_num;
+    uint32_t segment_idx;
+    uint32_t serial;
+    BOOL     serial_known;
+    uint64_t granule_pos;           /** of the last page of the stream read so far */
+    BOOL     granule_valid;
+
+    uint8_t *packet;
+    size_t   packet_size;
+    size_t   packet_buf_size;
+
+    size_t   sample_buf_size;
+    uint64_t sample_num;
+    uint64_t total_samples;         /** sum of the packet durations */
+};
+
+typedef struct parser_opus_t_ parser_opus_t;
+typedef parser_opus_t  *parser_opus_handle_t;
+
+static uint16_t
+get_LE_u16(const uint8_t *bytes)
+{
+    return (uint16_t)(bytes[0] | bytes[1] << 8);
+}
+
+static uint32_t
+get_LE_u32(const uint8_t *bytes)
+{
+    return (uint32_t)get_LE_u16(bytes) | (uint32_t)get_LE_u16(bytes + 2) << 16;
+}
+
+/** Reads the header of the next page of the Opus stream; returns FALSE at the end of the file */
+static BOOL
+read_page_hdr(parser_opus_handle_t parser_opus)
+{
+    bbio_handle_t ds = parser_opus->ds;
+    uint8_t       hdr[OGG_PAGE_HDR_SIZE];
+
+    for (;;)
+    {
+        uint32_t serial, i, page_size = 0;
+
+        if (ds->read(ds, hdr, OGG_PAGE_HDR_SIZE) != OGG_PAGE_HDR_SIZE)
+        {
+            return FALSE;
+        }
+        if (!IS_FOURCC_EQUAL(hdr, "OggS") || hdr[4] != 0)
+        {
+            msglog(NULL, MSGLOG_ERR, "Ogg page expected at offset %" PRIi64 "\n", ds->position(ds) - OGG_PAGE_HDR_SIZE);
+            return FALSE;
+        }
+        parser_opus->segment_num = hdr[26];
+        if (ds->read(ds, parser_opus->lacing, parser_opus->segment_num) != parser_opus->segment_num)
+        {
+            return FALSE;
+        }
+
+        serial = get_LE_u32(hdr + 14);
+        if (!parser_opus->serial_known && (hdr[5] & OGG_HDR_BOS))
+        {
+            parser_opus->serial       = serial;
+            parser_opus->serial_known = TRUE;
+        }
+        if (parser_opus->serial_known && serial == parser_opus->serial)
+        {
+            /** granule_position -1: no packet ends on this page */
+            if (get_LE_u32(hdr + 6) != 0xFFFFFFFF || get_LE_u32(hdr + 10) != 0xFFFFFFFF)
+            {
+                parser_opus->granule_pos   = (uint64_t)get_LE_u32(hdr + 10) << 32 | get_LE_u32(hdr + 6);
+                parser_opus->granule_valid = TRUE;
+            }
+            parser_opus->segment_idx = 0;
+            return TRUE;
+        }
+
+        /** a page of another logical stream */
+        for (i = 0; i < parser_opus->segment_num; i++)
+        {
+            page_size += parser_opus->lacing[i];
+        }
+        ds->skip_bytes(ds, page_size);
+    }
+}
+
+/** Reads the next packet of the Opus stream into packet and packet_size */
+static int32_t
+read_packet(parser_opus_handle_t parser_opus)
+{
+    bbio_handle_t ds = parser_opus->ds;
+
+    parser_opus->packet_size = 0;
+    for (;;)
+    {
+        uint32_t lacing;
+
+        if (parser_opus->segment_idx >= parser_opus->segment_num)
+        {
+            if (!read_page_hdr(parser_opus))
+            {
+                return EMA_MP4_MUXED_EOES;
+            }
+            continue;
+        }
+
+        lacing = parser_opus->lacing[parser_opus->segment_idx++];
+        if (parser_opus->packet_size + lacing > parser_opus->packet_buf_size)
+        {
+            size_t   buf_size = MAX2(parser_opus->packet_size + lacing, 2*parser_opus->packet_buf_size);
+            uint8_t *buf      = REALLOC_CHK(parser_opus->packet, buf_size);
+            if (!buf)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            parser_opus->packet          = buf;
+            parser_opus->packet_buf_size = buf_size;
+        }
+        if (ds->read(ds, parser_opus->packet + parser_opus->packet_size, lacing) != lacing)
+        {
+            return EMA_MP4_MUXED_EOES;
+        }
+        parser_opus->packet_size += lacing;
+
+        if (lacing < 255)
+        {
+            return EMA_MP4_MUXED_OK;
+        }
+    }
+}
+
+/** Gets the number of 48 kHz samples of an Opus packet, see RFC 6716 3.1; 0 for an invalid one */
+static uint32_t
+packet_duration(const uint8_t *packet, size_t size)
+{
+    static const uint32_t silk_frame_sizes[4] = {480, 960, 1920, 2880};
+    uint32_t config, frame_size, frame_num;
+
+    if (!size)
+    {
+        return 0;
+    }
+    config = packet[0] >> 3;
+    if (config < 12)
+    {
+        frame_size = silk_frame_sizes[config & 3];          /** SILK-only */
+    }
+    else if (config < 16)
+    {
+        frame_size = 480 << (config & 1);                   /** Hybrid */
+    }
+    else
+    {
+        frame_size = 120 << (config & 3);                   /** CELT-only */
+    }
+
+    switch (packet[0] & 3)
+    {
+    case 0:
+        frame_num = 1;
+        break;
+    case 3:
+        if (size < 2)
+        {
+            return 0;
+        }
+        frame_num = packet[1] & 0x3F;
+        break;
+    default:
+        frame_num = 2;
+        break;
+    }
+
+    /** at most 120 ms per packet */
+    if (!frame_num || frame_num*frame_size > 5760)
+    {
+        return 0;
+    }
+    return frame_num*frame_size;
+}
+
+/** Parses the identification header, see RFC 7845 5.1 */
+static int32_t
+parse_opus_head(parser_opus_handle_t parser_opus)
+{
+    mp4_dsi_opus_handle_t dsi    = (mp4_dsi_opus_handle_t)parser_opus->curr_dsi;
+    const uint8_t *       packet = parser_opus->packet;
+    size_t                size   = parser_opus->packet_size;
+
+    if (size < 19 || memcmp(packet, "OpusHead", 8) || (packet[8] & 0xF0))
+    {
+        msglog(NULL, MSGLOG_ERR, "No Opus identification header found\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    dsi->output_channel_count   = packet[9];
+    dsi->pre_skip               = get_LE_u16(packet + 10);
+    dsi->input_sample_rate      = get_LE_u32(packet + 12);
+    dsi->output_gain            = (int16_t)get_LE_u16(packet + 16);
+    dsi->channel_mapping_family = packet[18];
+    if (dsi->channel_mapping_family)
+    {
+        if (size < 21u + dsi->output_channel_count)
+        {
+            msglog(NULL, MSGLOG_ERR, "Opus identification header too short for its channel mapping\n");
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        dsi->stream_count  = packet[19];
+        dsi->coupled_count = packet[20];
+        memcpy(dsi->channel_mapping, packet + 21, dsi->output_channel_count);
+    }
+    else if (dsi->output_channel_count > 2)
+    {
+        msglog(NULL, MSGLOG_ERR, "Opus channel mapping family 0 with %u channels\n", dsi->output_channel_count);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if (!dsi->output_channel_count)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    parser_opus->channelcount    = dsi->output_channel_count;
+    parser_opus->priming_samples = dsi->pre_skip;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_opus_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_opus_handle_t parser_opus = (parser_opus_handle_t)parser;
+    uint32_t             duration;
+    int32_t              ret;
+
+    sample->flags = 0;
+
+    ret = read_packet(parser_opus);
+    if (ret == EMA_MP4_MUXED_EOES)
+    {
+        /** the granule position of the last page tells the end trimming */
+        if (parser_opus->granule_valid && parser_opus->granule_pos > parser_opus->priming_samples &&
+            parser_opus->granule_pos < parser_opus->total_samples)
+        {
+            parser_opus->valid_samples = parser_opus->granule_pos - parser_opus->priming_samples;
+        }
+        return EMA_MP4_MUXED_EOES;
+    }
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    duration = packet_duration(parser_opus->packet, parser_opus->packet_size);
+    if (!duration)
+    {
+        msglog(NULL, MSGLOG_ERR, "Invalid Opus packet %" PRIu64 "\n", parser_opus->sample_num);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    if (!parser_opus->sample_num)
+    {
+        sample->flags |= SAMPLE_NEW_SD;
+        /** the pre-roll in packets of the duration of the first one */
+        parser_opus->roll_distance = (int16_t)(-(int32_t)((OPUS_PRE_ROLL + duration - 1) / duration));
+    }
+    sample->flags   |= SAMPLE_SYNC;
+    sample->dts      = parser_opus->total_samples;
+    sample->cts      = sample->dts;
+    sample->duration = duration;
+
+    if (parser_opus->packet_size > parser_opus->sample_buf_size)
+    {
+        uint8_t *data = REALLOC_CHK(sample->data, parser_opus->packet_size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                 = data;
+        parser_opus->sample_buf_size = parser_opus->packet_size;
+    }
+    memcpy(sample->data, parser_opus->packet, parser_opus->packet_size);
+    sample->size = parser_opus->packet_size;
+
+    parser_opus->total_samples += duration;
+    parser_opus->sample_num++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** get dsi for opus (OpusSpecificBox) */
+static int32_t
+parser_opus_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    mp4_dsi_opus_handle_t dsi = (mp4_dsi_opus_handle_t)parser->curr_dsi;
+    bbio_handle_t         snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 64, 1);
+    }
+
+    sink_write_u8(snk, 0);                                  /** Version */
+    sink_write_u8(snk, dsi->output_channel_count);
+    sink_write_u16(snk, dsi->pre_skip);
+    sink_write_u32(snk, dsi->input_sample_rate);
+    sink_write_u16(snk, (uint16_t)dsi->output_gain);
+    sink_write_u8(snk, dsi->channel_mapping_family);
+    if (dsi->channel_mapping_family)
+    {
+        sink_write_u8(snk, dsi->stream_count);
+        sink_write_u8(snk, dsi->coupled_count);
+        snk->write(snk, dsi->channel_mapping, dsi->output_channel_count);
+    }
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_opus_show_info(parser_handle_t parser)
+{
+    parser_opus_handle_t  parser_opus = (parser_opus_handle_t)parser;
+    mp4_dsi_opus_handle_t dsi         = (mp4_dsi_opus_handle_t)parser->curr_dsi;
+
+    msglog(NULL, MSGLOG_INFO, "Opus: %u channels, mapping family %u, pre-skip %u, input sample rate %u\n",
+           parser_opus->channelcount, dsi->channel_mapping_family, dsi->pre_skip, dsi->input_sample_rate);
+}
+
+static int32_t
+parser_opus_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_opus_handle_t parser_opus = (parser_opus_handle_t)parser;
+    int32_t              ret;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    /** the identification header is alone on the first page of the stream */
+    ret = read_packet(parser_opus);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        msglog(NULL, MSGLOG_ERR, "No Ogg stream found\n");
+        return EMA_MP4_MUXED_EOES;
+    }
+    ret = parse_opus_head(parser_opus);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    /** the comment header, not used */
+    ret = read_packet(parser_opus);
+    if (ret != EMA_MP4_MUXED_OK || parser_opus->packet_size < 8 || memcmp(parser_opus->packet, "OpusTags", 8))
+    {
+        msglog(NULL, MSGLOG_ERR, "No Opus comment header found\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    parser_opus->granule_valid = FALSE;
+
+    parser_opus->sample_rate       = OPUS_SAMPLE_RATE;
+    parser_opus->time_scale        = OPUS_SAMPLE_RATE;
+    parser_opus->num_units_in_tick = 960;
+    parser_opus->samplesize        = 16;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_opus_destroy(parser_handle_t parser)
+{
+    parser_opus_handle_t parser_opus = (parser_opus_handle_t)parser;
+
+    if (parser_opus->packet)
+    {
+        FREE_CHK(parser_opus->packet);
+    }
+    parser_destroy(parser);
+}
+
+static parser_handle_t
+parser_opus_create(uint32_t dsi_type)
+{
+    parser_opus_handle_t parser;
+
+    assert(dsi_type == DSI_TYPE_MP4FF);
+    parser = (parser_opus_handle_t)MALLOC_CHK(sizeof(parser_opus_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_opus_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type = STREAM_TYPE_AUDIO;
+    parser->stream_id   = STREAM_ID_OPUS;
+    parser->stream_name = "opus";
+    parser->dsi_FourCC  = "dOps";
+
+    parser->dsi_type   = dsi_type;
+    parser->dsi_create = dsi_opus_create;
+
+    parser->init       = parser_opus_init;
+    parser->destroy    = parser_opus_destroy;
+    parser->get_sample = parser_opus_get_sample;
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_opus_get_mp4_cfg;
+    }
+
+    parser->show_info = parser_opus_show_info;
+
+    /* use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /**** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_opus_reg(void)
+{
+    reg_parser_set("opus", parser_opus_create);
+}
@@ -0,0 +1,544 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_pcm.c
+    @brief Implements a PCM parser for WAV, RF64/BW64 and Sony Wave64 files
+
+    The 'fmt ' chunk gives an ISO/IEC 23003-5 'ipcm' or 'fpcm' sample entry with its 'pcmC', the channel
+    mask of WAVE_FORMAT_EXTENSIBLE its 'chnl'. As many frames go into a sample as the chunk span allows.
+*/
+
+#include "utils.h"
+#include "io_base.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+
+#define WAVE_FORMAT_PCM         0x0001
+#define WAVE_FORMAT_IEEE_FLOAT  0x0003
+#define WAVE_FORMAT_EXTENSIBLE  0xFFFE
+
+#define PCM_DEFAULT_SPAN_MS     250     /** sample duration without interleaving */
+#define WAVE_FMT_MAX_SIZE       40
+
+#define SPEAKER_BACK_LEFT       0x10
+#define SPEAKER_BACK_RIGHT      0x20
+#define SPEAKER_SIDE_LEFT       0x200
+#define SPEAKER_SIDE_RIGHT      0x400
+
+typedef enum pcm_container_t_
+{
+    PCM_CONTAINER_RIFF,
+    PCM_CONTAINER_RF64,             /** RF64 and BW64: 64 bit sizes in 'ds64' */
+    PCM_CONTAINER_W64
+} pcm_container_t;
+
+struct parser_pcm_t_
+{
+    PARSER_AUDIO_BASE;
+
+    pcm_container_t container;
+    uint64_t data_size;             /** (uint64_t)-1: up to the end of the file */
+    uint64_t data_read;
+    uint32_t frames_per_sample;
+
+    size_t   sample_buf_size;
+    uint64_t dts;
+};
+
+typedef struct parser_pcm_t_ parser_pcm_t;
+typedef parser_pcm_t  *parser_pcm_handle_t;
+
+/** The GUIDs of Sony Wave64 chunks; the ones of 'fmt ', 'data' and 'ds64' end as the one of 'wave' */
+static const uint8_t w64_riff_guid[16] = {
+    'r', 'i', 'f', 'f', 0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00
+};
+static const uint8_t w64_wave_guid[16] = {
+    'w', 'a', 'v', 'e', 0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A
+};
+
+/** KSDATAFORMAT_SUBTYPE_PCM and _IEEE_FLOAT but their first two bytes */
+static const uint8_t ksdataformat_subtype_tail[14] = {
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71
+};
+
+/** ISO/IEC 23091-3 OutputChannelPosition of the WAVE_FORMAT_EXTENSIBLE speaker mask bits */
+static const uint8_t wave_speaker_position[18] = {
+    0,  /** FRONT_LEFT: L */
+    1,  /** FRONT_RIGHT: R */
+    2,  /** FRONT_CENTER: C */
+    3,  /** LOW_FREQUENCY: LFE */
+    4,  /** BACK_LEFT: Ls, Lsr if there are side speakers */
+    5,  /** BACK_RIGHT: Rs, Rsr if there are side speakers */
+    6,  /** FRONT_LEFT_OF_CENTER: Lc */
+    7,  /** FRONT_RIGHT_OF_CENTER: Rc */
+    10, /** BACK_CENTER: Cs */
+    4,  /** SIDE_LEFT: Ls */
+    5,  /** SIDE_RIGHT: Rs */
+    25, /** TOP_CENTER: Ts */
+    17, /** TOP_FRONT_LEFT: Lv */
+    19, /** TOP_FRONT_CENTER: Cv */
+    18, /** TOP_FRONT_RIGHT: Rv */
+    20, /** TOP_BACK_LEFT: Lvr */
+    22, /** TOP_BACK_CENTER: Cvr */
+    21  /** TOP_BACK_RIGHT: Rvr */
+};
+
+static uint16_t
+get_LE_u16(const uint8_t *bytes)
+{
+    return (uint16_t)(bytes[0] | bytes[1] << 8);
+}
+
+static uint32_t
+get_LE_u32(const uint8_t *bytes)
+{
+    return (uint32_t)get_LE_u16(bytes) | (uint32_t)get_LE_u16(bytes + 2) << 16;
+}
+
+static uint64_t
+get_LE_u64(const uint8_t *bytes)
+{
+    return (uint64_t)get_LE_u32(bytes) | (uint64_t)get_LE_u32(bytes + 4) << 32;
+}
+
+/** Sets up the 'chnl' of the channel mask, none if a channel has no position */
+static void
+set_channel_layout(parser_pcm_handle_t parser_pcm, uint32_t channel_mask)
+{
+    mp4_dsi_pcm_handle_t dsi = (mp4_dsi_pcm_handle_t)parser_pcm->curr_dsi;
+    uint32_t             channelcount = (uint32_t)parser_pcm->channelcount;
+    uint32_t             bit, ch = 0;
+
+    if (!channel_mask)
+    {
+        /** without a mask mono is C and stereo L R, more channels are unknown */
+        if (channelcount <= 2)
+        {
+            dsi->layout_present = TRUE;
+            dsi->defined_layout = (uint8_t)channelcount;
+        }
+        return;
+    }
+
+    for (bit = 0; bit < 18 && ch < channelcount; bit++)
+    {
+        if (channel_mask & (1u << bit))
+        {
+            uint8_t position = wave_speaker_position[bit];
+
+            if ((1u << bit) & (SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT) &&
+                channel_mask & (SPEAKER_SIDE_LEFT | SPEAKER_SIDE_RIGHT))
+            {
+                position += 4;
+            }
+            dsi->speaker_position[ch++] = position;
+        }
+    }
+    if (ch < channelcount)
+    {
+        msglog(NULL, MSGLOG_WARNING, "Channel mask 0x%x has no position for %u of the %u channels, no 'chnl' written\n",
+               channel_mask, channelcount - ch, channelcount);
+        return;
+    }
+
+    dsi->layout_present = TRUE;
+    /** ChannelConfiguration 1 and 2 of ISO/IEC 23091-3 */
+    if (ch == 1 && dsi->speaker_position[0] == 2)
+    {
+        dsi->defined_layout = 1;
+    }
+    else if (ch == 2 && dsi->speaker_position[0] == 0 && dsi->speaker_position[1] == 1)
+    {
+        dsi->defined_layout = 2;
+    }
+}
+
+/** Parses the 'fmt ' chunk: WAVEFORMATEX, WAVEFORMATEXTENSIBLE */
+static int32_t
+parse_fmt(parser_pcm_handle_t parser_pcm, const uint8_t *p, uint32_t size)
+{
+    mp4_dsi_pcm_handle_t dsi = (mp4_dsi_pcm_handle_t)parser_pcm->curr_dsi;
+    uint32_t             format, container_bits, channel_mask = 0;
+
+    if (size < 16)
+    {
+        msglog(NULL, MSGLOG_ERR, "'fmt ' chunk too short\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    format                   = get_LE_u16(p);
+    parser_pcm->channelcount = get_LE_u16(p + 2);
+    parser_pcm->sample_rate  = get_LE_u32(p + 4);
+    dsi->block_align         = get_LE_u16(p + 12);
+    dsi->valid_bits          = (uint8_t)get_LE_u16(p + 14);
+
+    if (format == WAVE_FORMAT_EXTENSIBLE)
+    {
+        if (size < 40 || get_LE_u16(p + 16) < 22 || memcmp(p + 26, ksdataformat_subtype_tail, 14))
+        {
+            msglog(NULL, MSGLOG_ERR, "Unknown WAVE_FORMAT_EXTENSIBLE sub format\n");
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        if (get_LE_u16(p + 18))
+        {
+            dsi->valid_bits = (uint8_t)get_LE_u16(p + 18);
+        }
+        channel_mask = get_LE_u32(p + 20);
+        format       = get_LE_u16(p + 24);
+    }
+    if (format != WAVE_FORMAT_PCM && format != WAVE_FORMAT_IEEE_FLOAT)
+    {
+        msglog(NULL, MSGLOG_ERR, "WAVE format 0x%x is not PCM\n", format);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if (!parser_pcm->channelcount || !parser_pcm->sample_rate || dsi->block_align % parser_pcm->channelcount)
+    {
+        msglog(NULL, MSGLOG_ERR, "Invalid 'fmt ' chunk\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    /** ISO/IEC 23003-5: 16, 24 or 32 bit integers, 32 or 64 bit floats */
+    container_bits  = dsi->block_align / parser_pcm->channelcount * 8;
+    dsi->float_flag = (format == WAVE_FORMAT_IEEE_FLOAT);
+    if (dsi->float_flag ? (container_bits != 32 && container_bits != 64) || dsi->valid_bits != container_bits
+                        : container_bits < 16 || container_bits > 32 || dsi->valid_bits > container_bits)
+    {
+        msglog(NULL, MSGLOG_ERR, "%u bit %s PCM in %u bit containers not supported\n",
+               dsi->valid_bits, dsi->float_flag ? "floating point" : "integer", container_bits);
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    dsi->format_flags        = 1;  /** little endian */
+    dsi->pcm_sample_size     = (uint8_t)container_bits;
+    parser_pcm->samplesize   = container_bits;
+    parser_pcm->wave_format  = format;
+
+    set_channel_layout(parser_pcm, channel_mask);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Reads the chunks up to 'data' */
+static int32_t
+parse_chunks(parser_pcm_handle_t parser_pcm)
+{
+    bbio_handle_t ds = parser_pcm->ds;
+    uint8_t       hdr[40];
+    uint64_t      ds64_data_size = (uint64_t)-1;
+    BOOL          fmt_found      = FALSE;
+
+    if (ds->read(ds, hdr, 12) != 12)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    if ((IS_FOURCC_EQUAL(hdr, "RIFF") || IS_FOURCC_EQUAL(hdr, "RF64") || IS_FOURCC_EQUAL(hdr, "BW64")) &&
+        IS_FOURCC_EQUAL(hdr + 8, "WAVE"))
+    {
+        parser_pcm->container = IS_FOURCC_EQUAL(hdr, "RIFF") ? PCM_CONTAINER_RIFF : PCM_CONTAINER_RF64;
+    }
+    else if (!memcmp(hdr, w64_riff_guid, 12) && ds->read(ds, hdr + 12, 28) == 28 &&
+             !memcmp(hdr + 12, w64_riff_guid + 12, 4) && !memcmp(hdr + 24, w64_wave_guid, 16))
+    {
+        parser_pcm->container = PCM_CONTAINER_W64;
+    }
+    else
+    {
+        msglog(NULL, MSGLOG_ERR, "No RIFF/WAVE, RF64 or Wave64 header found\n");
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+
+    for (;;)
+    {
+        uint8_t  fmt[WAVE_FMT_MAX_SIZE];
+        uint32_t hdr_size = (parser_pcm->container == PCM_CONTAINER_W64) ? 24 : 8;
+        uint64_t size, padded_size;
+
+        if (ds->read(ds, hdr, hdr_size) != hdr_size)
+        {
+            msglog(NULL, MSGLOG_ERR, "No 'data' chunk found\n");
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        if (parser_pcm->container == PCM_CONTAINER_W64)
+        {
+            /** the size includes the chunk header, chunks are 8 byte aligned */
+            if (get_LE_u64(hdr + 16) < hdr_size)
+            {
+                msglog(NULL, MSGLOG_ERR, "Invalid Wave64 chunk size\n");
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            if (memcmp(hdr + 4, w64_wave_guid + 4, 12))
+            {
+                hdr[0] = 0;     /** not one of ours */
+            }
+            size        = get_LE_u64(hdr + 16) - hdr_size;
+            padded_size = (size + 7) & ~(uint64_t)7;
+        }
+        else
+        {
+            size        = get_LE_u32(hdr + 4);
+            padded_size = size + (size & 1);
+        }
+
+        if (IS_FOURCC_EQUAL(hdr, "data"))
+        {
+            if (!fmt_found)
+            {
+                msglog(NULL, MSGLOG_ERR, "'data' chunk before 'fmt ' chunk\n");
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            parser_pcm->data_size = size;
+            if (parser_pcm->container == PCM_CONTAINER_RF64 && size == 0xFFFFFFFF)
+            {
+                parser_pcm->data_size = ds64_data_size;
+            }
+            else if (parser_pcm->container == PCM_CONTAINER_RIFF && !size)
+            {
+                /** written while recording, the size never got updated */
+                parser_pcm->data_size = (uint64_t)-1;
+            }
+            return EMA_MP4_MUXED_OK;
+        }
+
+        if (IS_FOURCC_EQUAL(hdr, "fmt "))
+        {
+            uint32_t fmt_size = (uint32_t)MIN2(size, WAVE_FMT_MAX_SIZE);
+            int32_t  ret;
+
+            if (ds->read(ds, fmt, fmt_size) != fmt_size)
+            {
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            ret = parse_fmt(parser_pcm, fmt, fmt_size);
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                return ret;
+            }
+            fmt_found    = TRUE;
+            padded_size -= fmt_size;
+        }
+        else if (IS_FOURCC_EQUAL(hdr, "ds64") && parser_pcm->container == PCM_CONTAINER_RF64)
+        {
+            /** riffSize, dataSize, sampleCount, table */
+            if (size < 24 || ds->read(ds, fmt, 24) != 24)
+            {
+                return EMA_MP4_MUXED_ES_ERR;
+            }
+            ds64_data_size = get_LE_u64(fmt + 8);
+            padded_size   -= 24;
+        }
+        ds->skip_bytes(ds, padded_size);
+    }
+}
+
+static int32_t
+parser_pcm_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_pcm_handle_t  parser_pcm = (parser_pcm_handle_t)parser;
+    mp4_dsi_pcm_handle_t dsi        = (mp4_dsi_pcm_handle_t)parser->curr_dsi;
+    bbio_handle_t        ds         = parser->ds;
+    uint64_t             size       = (uint64_t)parser_pcm->frames_per_sample*dsi->block_align;
+    size_t               read;
+
+    sample->flags = 0;
+
+    if (parser_pcm->data_size - parser_pcm->data_read < size)
+    {
+        size = parser_pcm->data_size - parser_pcm->data_read;
+    }
+    if (size > parser_pcm->sample_buf_size)
+    {
+        uint8_t *data = REALLOC_CHK(sample->data, (size_t)size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                = data;
+        parser_pcm->sample_buf_size = (size_t)size;
+    }
+
+    read = ds->read(ds, sample->data, (size_t)size);
+    parser_pcm->data_read += read;
+    if (read % dsi->block_align)
+    {
+        msglog(NULL, MSGLOG_WARNING, "Incomplete last PCM frame of %u bytes dropped\n",
+               (uint32_t)(read % dsi->block_align));
+        read -= read % dsi->block_align;
+    }
+    if (!read)
+    {
+        if (parser_pcm->data_size != (uint64_t)-1 && parser_pcm->data_read < parser_pcm->data_size)
+        {
+            msglog(NULL, MSGLOG_WARNING, "'data' chunk truncated: %" PRIu64 " of %" PRIu64 " bytes\n",
+                   parser_pcm->data_read, parser_pcm->data_size);
+        }
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    if (!parser_pcm->dts)
+    {
+        sample->flags |= SAMPLE_NEW_SD;
+    }
+    sample->flags   |= SAMPLE_SYNC;
+    sample->dts      = parser_pcm->dts;
+    sample->cts      = sample->dts;
+    sample->duration = (uint32_t)(read / dsi->block_align);
+    sample->size     = read;
+
+    parser_pcm->dts += sample->duration;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** get dsi for pcm (PCMConfig) */
+static int32_t
+parser_pcm_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    mp4_dsi_pcm_handle_t dsi = (mp4_dsi_pcm_handle_t)parser->curr_dsi;
+    bbio_handle_t        snk;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 8, 1);
+    }
+
+    sink_write_u32(snk, 0);                     /** FullBox version = 0, flags = 0 */
+    sink_write_u8(snk, dsi->format_flags);
+    sink_write_u8(snk, dsi->pcm_sample_size);
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_pcm_show_info(parser_handle_t parser)
+{
+    parser_pcm_handle_t  parser_pcm = (parser_pcm_handle_t)parser;
+    mp4_dsi_pcm_handle_t dsi        = (mp4_dsi_pcm_handle_t)parser->curr_dsi;
+
+    msglog(NULL, MSGLOG_INFO, "PCM: %u channels, %u of %u bit %s, %u Hz, %u frames per sample\n",
+           parser_pcm->channelcount, dsi->valid_bits, dsi->pcm_sample_size, dsi->float_flag ? "float" : "integer",
+           parser_pcm->sample_rate, parser_pcm->frames_per_sample);
+}
+
+static int32_t
+parser_pcm_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_pcm_handle_t  parser_pcm = (parser_pcm_handle_t)parser;
+    mp4_dsi_pcm_handle_t dsi        = (mp4_dsi_pcm_handle_t)parser->curr_dsi;
+    uint32_t             span_ms;
+    int32_t              ret;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    ret = parse_chunks(parser_pcm);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    /** a sample spans a chunk and fits in its size limit */
+    span_ms = ext_timing->chunk_span_time ? ext_timing->chunk_span_time : PCM_DEFAULT_SPAN_MS;
+    parser_pcm->frames_per_sample = (uint32_t)((uint64_t)parser_pcm->sample_rate*span_ms/1000);
+    if (ext_timing->chunk_span_size &&
+        (uint64_t)parser_pcm->frames_per_sample*dsi->block_align > ext_timing->chunk_span_size)
+    {
+        parser_pcm->frames_per_sample = ext_timing->chunk_span_size / dsi->block_align;
+    }
+    if (!parser_pcm->frames_per_sample)
+    {
+        parser_pcm->frames_per_sample = 1;
+    }
+
+    parser_pcm->time_scale        = parser_pcm->sample_rate;
+    parser_pcm->num_units_in_tick = parser_pcm->frames_per_sample;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static parser_handle_t
+parser_pcm_create(uint32_t dsi_type)
+{
+    parser_pcm_handle_t parser;
+
+    assert(dsi_type == DSI_TYPE_MP4FF);
+    parser = (parser_pcm_handle_t)MALLOC_CHK(sizeof(parser_pcm_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_pcm_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type = STREAM_TYPE_AUDIO;
+    parser->stream_id   = STREAM_ID_PCM;
+    parser->stream_name = "pcm";
+    parser->dsi_FourCC  = "pcmC";
+
+    parser->dsi_type   = dsi_type;
+    parser->dsi_create = dsi_pcm_create;
+
+    parser->init       = parser_pcm_init;
+    parser->destroy    = parser_destroy;
+    parser->get_sample = parser_pcm_get_sample;
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_pcm_get_mp4_cfg;
+    }
+
+    parser->show_info = parser_pcm_show_info;
+
+    /* use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /**** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_pcm_reg(void)
+{
+    reg_parser_set("wav", parser_pcm_create);
+    reg_parser_set("wave", parser_pcm_create);
+    reg_parser_set("rf64", parser_pcm_create);
+    reg_parser_set("w64", parser_pcm_create);
+}
@@ -0,0 +1,1361 @@
+/************************************************************************************************************
+ * Copyright (c) 2017, Dolby Laboratories Inc.
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without modification, are permitted
+ * provided that the following conditions are met:
+
+ * 1. Redistributions of source code must retain the above copyright notice, this list of conditions
+ *    and the following disclaimer.
+ * 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions
+ *    and the following disclaimer in the documentation and/or other materials provided with the distribution.
+ * 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or
+ *    promote products derived from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED
+ * WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A
+ * PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR
+ * ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+ * HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED
+ * OF THE POSSIBILITY OF SUCH DAMAGE.
+ ************************************************************************************************************/
+/*<
+    @file parser_vvc.c
+    @brief Implements a VVC parser
+
+    Based on ISO/IEC 14496-15:2022 clause 11. Reads the byte stream format of ITU-T H.266 Annex B; each
+    access unit becomes one sample of NAL units with a 4 byte length field. VPS, SPS and PPS go into the
+    'vvcC' of a 'vvc1' sample entry only, a changed one starts a new sample description. APS stay in the
+    samples, the ones ahead of the first picture are copied into 'vvcC' as well.
+*/
+
+#include "utils.h"
+#include "list_itr.h"
+#include "registry.h"
+#include "dsi.h"
+#include "parser.h"
+#include "parser_avc_dec.h"  /** src_read_ue(), parser_avc_remove_0x03() */
+
+/** nal_unit_type, see H.266 Table 5 */
+#define VVC_NAL_TRAIL           0
+#define VVC_NAL_STSA            1
+#define VVC_NAL_RADL            2
+#define VVC_NAL_RASL            3
+#define VVC_NAL_IDR_W_RADL      7
+#define VVC_NAL_IDR_N_LP        8
+#define VVC_NAL_CRA             9
+#define VVC_NAL_GDR            10
+#define VVC_NAL_RSV_IRAP_11    11
+#define VVC_NAL_OPI            12
+#define VVC_NAL_DCI            13
+#define VVC_NAL_VPS            14
+#define VVC_NAL_SPS            15
+#define VVC_NAL_PPS            16
+#define VVC_NAL_PREFIX_APS     17
+#define VVC_NAL_SUFFIX_APS     18
+#define VVC_NAL_PH             19
+#define VVC_NAL_AUD            20
+#define VVC_NAL_EOS            21
+#define VVC_NAL_EOB            22
+#define VVC_NAL_PREFIX_SEI     23
+#define VVC_NAL_SUFFIX_SEI     24
+#define VVC_NAL_FD             25
+#define VVC_NAL_RSV_NVCL_26    26
+#define VVC_NAL_UNSPEC_28      28
+#define VVC_NAL_UNSPEC_29      29
+
+#define VVC_IS_VCL(type)       ((type) <= VVC_NAL_RSV_IRAP_11)
+#define VVC_IS_IDR(type)       ((type) == VVC_NAL_IDR_W_RADL || (type) == VVC_NAL_IDR_N_LP)
+
+#define VVC_MAX_SPS_NUM        16
+#define VVC_MAX_PPS_NUM        64
+#define VVC_GCI_FLAGS_BITS     71  /** the fixed part of general_constraints_info() */
+
+#define NAL_LENGTH_SIZE         4
+#define ES_BUF_SIZE         65536
+
+typedef struct vvc_sps_t_
+{
+    BOOL     valid;
+    uint8_t  sps_id;
+    uint8_t  max_sublayers;
+    uint8_t  chroma_format_idc;
+    uint8_t  bit_depth_minus8;
+    uint8_t  log2_max_poc_lsb;
+    uint8_t  general_profile_idc;
+    uint8_t  general_level_idc;
+    uint32_t max_width, max_height;
+    uint32_t width, height;           /** after the conformance window */
+
+    /** profile_tier_level() in the rbsp of the SPS */
+    uint32_t ptl_off;
+    uint32_t ptl_size;
+    uint8_t  num_bytes_constraint_info;
+} vvc_sps_t;
+
+/** what the access unit being parsed tells about its picture */
+typedef struct vvc_pic_t_
+{
+    BOOL     vcl_seen;
+    BOOL     ph_seen;
+    uint8_t  nal_type;                /** of the first VCL NAL unit */
+    uint8_t  temporal_id;
+    uint8_t  non_ref_pic;
+    uint32_t poc_lsb;
+    uint32_t max_poc_lsb;
+} vvc_pic_t;
+
+struct parser_vvc_t_
+{
+    PARSER_VIDEO_BASE;
+
+    uint8_t *es_buf;                  /** input buffer; es_buf[es_pos, es_size) is not parsed yet */
+    size_t   es_buf_size;
+    size_t   es_pos;
+    size_t   es_size;
+    BOOL     es_eof;
+
+    const uint8_t *nal;               /** the NAL unit read ahead, without start code */
+    size_t   nal_size;
+    BOOL     nal_pending;
+
+    uint8_t *rbsp;                    /** emulation prevention bytes removed */
+    size_t   rbsp_buf_size;
+
+    size_t   sample_buf_size;         /** allocated size of sample->data */
+
+    vvc_sps_t sps[VVC_MAX_SPS_NUM];
+    uint8_t   pps_sps_id[VVC_MAX_PPS_NUM];
+    BOOL      pps_valid[VVC_MAX_PPS_NUM];
+
+    vvc_pic_t pic;
+    BOOL     dsi_used;                /** a sample refers to the current dsi */
+    BOOL     eos_seen;
+    int32_t  prev_tid0_poc;
+    int64_t  poc_base;                /** cts of poc 0 in the current coded video sequence */
+    int64_t  max_cts;
+
+    int32_t *cts_offsets;
+    uint32_t cts_offsets_size;
+    int32_t  cts_offset_min;
+
+    uint32_t au_num;
+};
+
+typedef struct parser_vvc_t_ parser_vvc_t;
+typedef parser_vvc_t  *parser_vvc_handle_t;
+
+/** Returns the offset of the next 0x000001 in buf or -1 */
+static int64_t
+find_sc(const uint8_t *buf, size_t size)
+{
+    size_t i;
+
+    for (i = 0; i + 2 < size; i++)
+    {
+        if (buf[i + 2] > 1)
+        {
+            i += 2;
+        }
+        else if (!buf[i] && !buf[i + 1] && buf[i + 2] == 1)
+        {
+            return (int64_t)i;
+        }
+    }
+    return -1;
+}
+
+/** Reads the next NAL unit of the byte stream into nal and nal_size */
+static int32_t
+read_nal(parser_vvc_handle_t parser_vvc)
+{
+    bbio_handle_t ds = parser_vvc->ds;
+
+    for (;;)
+    {
+        size_t  avail = parser_vvc->es_size - parser_vvc->es_pos;
+        int64_t sc    = find_sc(parser_vvc->es_buf + parser_vvc->es_pos, avail);
+
+        if (sc >= 0)
+        {
+            size_t  nal_start = parser_vvc->es_pos + (size_t)sc + 3;
+            int64_t sc_next   = find_sc(parser_vvc->es_buf + nal_start, parser_vvc->es_size - nal_start);
+            size_t  nal_end;
+
+            if (sc_next >= 0 || parser_vvc->es_eof)
+            {
+                nal_end = (sc_next >= 0) ? nal_start + (size_t)sc_next : parser_vvc->es_size;
+                parser_vvc->es_pos = nal_end;
+
+                /** the zero_byte of the next start code and trailing_zero_8bits */
+                while (nal_end > nal_start && !parser_vvc->es_buf[nal_end - 1])
+                {
+                    nal_end--;
+                }
+                if (nal_end - nal_start < 2)
+                {
+                    continue;
+                }
+                parser_vvc->nal      = parser_vvc->es_buf + nal_start;
+                parser_vvc->nal_size = nal_end - nal_start;
+                return EMA_MP4_MUXED_OK;
+            }
+            /** keep the start code */
+            parser_vvc->es_pos += (size_t)sc;
+        }
+        else if (parser_vvc->es_eof)
+        {
+            return EMA_MP4_MUXED_EOES;
+        }
+        else if (avail > 2)
+        {
+            /** no start code: the last 2 bytes may start one */
+            parser_vvc->es_pos = parser_vvc->es_size - 2;
+        }
+
+        /** load more data */
+        avail = parser_vvc->es_size - parser_vvc->es_pos;
+        memmove(parser_vvc->es_buf, parser_vvc->es_buf + parser_vvc->es_pos, avail);
+        parser_vvc->es_pos  = 0;
+        parser_vvc->es_size = avail;
+        if (avail == parser_vvc->es_buf_size)
+        {
+            uint8_t *buf = REALLOC_CHK(parser_vvc->es_buf, 2*parser_vvc->es_buf_size);
+            if (!buf)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            parser_vvc->es_buf       = buf;
+            parser_vvc->es_buf_size *= 2;
+        }
+        avail = ds->read(ds, parser_vvc->es_buf + parser_vvc->es_size, parser_vvc->es_buf_size - parser_vvc->es_size);
+        if (!avail)
+        {
+            parser_vvc->es_eof = TRUE;
+        }
+        parser_vvc->es_size += avail;
+    }
+}
+
+/** Gets a reader of the rbsp of the nal; at most max_size bytes of the nal are converted */
+static bbio_handle_t
+rbsp_reader(parser_vvc_handle_t parser_vvc, size_t max_size, size_t *rbsp_size)
+{
+    size_t        size = MIN2(parser_vvc->nal_size, max_size) - 2;
+    bbio_handle_t bs;
+
+    if (size > parser_vvc->rbsp_buf_size)
+    {
+        uint8_t *buf = REALLOC_CHK(parser_vvc->rbsp, size);
+        if (!buf)
+        {
+            return NULL;
+        }
+        parser_vvc->rbsp          = buf;
+        parser_vvc->rbsp_buf_size = size;
+    }
+    parser_avc_remove_0x03(parser_vvc->rbsp, rbsp_size, parser_vvc->nal + 2, size);
+
+    bs = reg_bbio_get('b', 'r');
+    bs->set_buffer(bs, parser_vvc->rbsp, *rbsp_size, 0);
+    return bs;
+}
+
+static uint32_t
+ceil_log2(uint32_t value)
+{
+    uint32_t n = 0;
+
+    while (n < 32 && (1u << n) < value)
+    {
+        n++;
+    }
+    return n;
+}
+
+static uint32_t
+bit_pos(bbio_handle_t bs, size_t size)
+{
+    return (uint32_t)((int64_t)size*8 - src_following_bit_num(bs));
+}
+
+/** Parses profile_tier_level(1, max_sublayers - 1), see H.266 7.3.3.1 */
+static void
+parse_ptl(bbio_handle_t bs, size_t size, vvc_sps_t *sps)
+{
+    uint32_t sublayer_level_present = 0;
+    uint32_t i;
+
+    sps->ptl_off             = bit_pos(bs, size) >> 3;
+    sps->general_profile_idc = (uint8_t)src_read_bits(bs, 7);
+    src_skip_bits(bs, 1);                               /** general_tier_flag */
+    sps->general_level_idc   = (uint8_t)src_read_bits(bs, 8);
+    src_skip_bits(bs, 2);                               /** ptl_frame_only_constraint_flag, ptl_multilayer_enabled_flag */
+    if (src_read_bit(bs))
+    {
+        /** gci_present_flag */
+        src_skip_bits(bs, VVC_GCI_FLAGS_BITS);
+        src_skip_bits(bs, src_read_bits(bs, 8));        /** gci_num_additional_bits */
+    }
+    src_byte_align(bs);
+    sps->num_bytes_constraint_info = (uint8_t)((bit_pos(bs, size) >> 3) - sps->ptl_off - 2);
+
+    for (i = 1; i < sps->max_sublayers; i++)
+    {
+        sublayer_level_present = (sublayer_level_present << 1) | src_read_bit(bs);
+    }
+    src_byte_align(bs);
+    for (i = 1; i < sps->max_sublayers; i++)
+    {
+        if (sublayer_level_present & (1u << (i - 1)))
+        {
+            src_skip_bits(bs, 8);                       /** sublayer_level_idc */
+        }
+    }
+    src_skip_bits(bs, 32*src_read_bits(bs, 8));         /** ptl_num_sub_profiles, general_sub_profile_idc */
+
+    sps->ptl_size = (bit_pos(bs, size) >> 3) - sps->ptl_off;
+}
+
+/** Parses the SPS up to sps_log2_max_pic_order_cnt_lsb_minus4, see H.266 7.3.2.4 */
+static int32_t
+parse_sps(bbio_handle_t bs, size_t size, vvc_sps_t *sps)
+{
+    uint32_t ctb_size, ctb_width, ctb_height;
+    uint32_t sub_width_c, sub_height_c;
+    uint32_t i;
+
+    memset(sps, 0, sizeof(vvc_sps_t));
+
+    sps->sps_id            = (uint8_t)src_read_bits(bs, 4);
+    src_skip_bits(bs, 4);                               /** sps_video_parameter_set_id */
+    sps->max_sublayers     = (uint8_t)src_read_bits(bs, 3) + 1;
+    sps->chroma_format_idc = (uint8_t)src_read_bits(bs, 2);
+    ctb_size               = 1u << (src_read_bits(bs, 2) + 5);
+    if (src_read_bit(bs))
+    {
+        /** sps_ptl_dpb_hrd_params_present_flag */
+        parse_ptl(bs, size, sps);
+    }
+    src_skip_bits(bs, 1);                               /** sps_gdr_enabled_flag */
+    if (src_read_bit(bs))
+    {
+        src_skip_bits(bs, 1);                           /** sps_res_change_in_clvs_allowed_flag */
+    }
+    sps->max_width  = src_read_ue(bs);
+    sps->max_height = src_read_ue(bs);
+    sps->width      = sps->max_width;
+    sps->height     = sps->max_height;
+    if (src_read_bit(bs))
+    {
+        /** sps_conformance_window_flag */
+        uint32_t left   = src_read_ue(bs);
+        uint32_t right  = src_read_ue(bs);
+        uint32_t top    = src_read_ue(bs);
+        uint32_t bottom = src_read_ue(bs);
+
+        sub_width_c  = (sps->chroma_format_idc == 1 || sps->chroma_format_idc == 2) ? 2 : 1;
+        sub_height_c = (sps->chroma_format_idc == 1) ? 2 : 1;
+        if (sub_width_c*(left + right) >= sps->max_width || sub_height_c*(top + bottom) >= sps->max_height)
+        {
+            return EMA_MP4_MUXED_ES_ERR;
+        }
+        sps->width  -= sub_width_c*(left + right);
+        sps->height -= sub_height_c*(top + bottom);
+    }
+
+    if (src_read_bit(bs))
+    {
+        /** sps_subpic_info_present_flag */
+        uint32_t num_subpics_minus1 = src_read_ue(bs);
+        uint32_t independent_subpics = 1, subpic_same_size = 0;
+
+        ctb_width  = (sps->max_width + ctb_size - 1)/ctb_size;
+        ctb_height = (sps->max_height + ctb_size - 1)/ctb_size;
+        if (num_subpics_minus1 > 0)
+        {
+            independent_subpics = src_read_bit(bs);
+            subpic_same_size    = src_read_bit(bs);
+        }
+        for (i = 0; num_subpics_minus1 > 0 && i <= num_subpics_minus1; i++)
+        {
+            if (!subpic_same_size || i == 0)
+            {
+                if (i > 0 && sps->max_width > ctb_size)
+                    src_skip_bits(bs, ceil_log2(ctb_width));    /** sps_subpic_ctu_top_left_x */
+                if (i > 0 && sps->max_height > ctb_size)
+                    src_skip_bits(bs, ceil_log2(ctb_height));   /** sps_subpic_ctu_top_left_y */
+                if (i < num_subpics_minus1 && sps->max_width > ctb_size)
+                    src_skip_bits(bs, ceil_log2(ctb_width));    /** sps_subpic_width_minus1 */
+                if (i < num_subpics_minus1 && sps->max_height > ctb_size)
+                    src_skip_bits(bs, ceil_log2(ctb_height));   /** sps_subpic_height_minus1 */
+            }
+            if (!independent_subpics)
+            {
+                src_skip_bits(bs, 2);   /** sps_subpic_treated_as_pic_flag, sps_loop_filter_across_subpic_enabled_flag */
+            }
+        }
+        i = src_read_ue(bs) + 1;                        /** sps_subpic_id_len_minus1 */
+        if (src_read_bit(bs) && src_read_bit(bs))
+        {
+            /** sps_subpic_id_mapping_explicitly_signalled_flag, sps_subpic_id_mapping_present_flag */
+            src_skip_bits(bs, i*(num_subpics_minus1 + 1));
+        }
+    }
+
+    sps->bit_depth_minus8 = (uint8_t)src_read_ue(bs);
+    src_skip_bits(bs, 2);   /** sps_entropy_coding_sync_enabled_flag, sps_entry_point_offsets_present_flag */
+    sps->log2_max_poc_lsb = (uint8_t)src_read_bits(bs, 4) + 4;
+
+    if (src_following_bit_num(bs) <= 0 || sps->bit_depth_minus8 > 8 || !sps->max_width || !sps->max_height)
+    {
+        return EMA_MP4_MUXED_ES_ERR;
+    }
+    sps->valid = TRUE;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Parses picture_header_structure() up to ph_pic_order_cnt_lsb, see H.266 7.3.2.8 */
+static int32_t
+parse_ph(parser_vvc_handle_t parser_vvc, bbio_handle_t bs)
+{
+    vvc_pic_t *pic = &parser_vvc->pic;
+    uint32_t   pps_id;
+    vvc_sps_t *sps;
+
+    if (src_read_bit(bs))
+    {
+        /** ph_gdr_or_irap_pic_flag */
+        pic->non_ref_pic = (uint8_t)src_read_bit(bs);
+        src_skip_bits(bs, 1);                           /** ph_gdr_pic_flag */
+    }
+    else
+    {
+        pic->non_ref_pic = (uint8_t)src_read_bit(bs);
+    }
+    if (src_read_bit(bs))
+    {
+        src_skip_bits(bs, 1);                           /** ph_intra_slice_allowed_flag */
+    }
+    pps_id = src_read_ue(bs);
+    if (pps_id >= VVC_MAX_PPS_NUM || !parser_vvc->pps_valid[pps_id] ||
+        !parser_vvc->sps[parser_vvc->pps_sps_id[pps_id]].valid)
+    {
+        msglog(NULL, MSGLOG_ERR, "VVC picture header refers to a missing PPS or SPS\n");
+        return EMA_MP4_MUXED_NO_CONFIG_ERR;
+    }
+    sps = &parser_vvc->sps[parser_vvc->pps_sps_id[pps_id]];
+
+    pic->poc_lsb     = src_read_bits(bs, sps->log2_max_poc_lsb);
+    pic->max_poc_lsb = 1u << sps->log2_max_poc_lsb;
+    pic->ph_seen     = TRUE;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static list_handle_t *
+ps_lst_get(mp4_dsi_vvc_handle_t dsi, uint32_t nal_type)
+{
+    switch (nal_type)
+    {
+    case VVC_NAL_VPS: return &dsi->vps_lst;
+    case VVC_NAL_SPS: return &dsi->sps_lst;
+    case VVC_NAL_PPS: return &dsi->pps_lst;
+    default:          return &dsi->aps_lst;
+    }
+}
+
+static int32_t
+ps_entry_set(buf_entry_t *entry, const uint8_t *nal, size_t nal_size)
+{
+    if (entry->data)
+    {
+        FREE_CHK(entry->data);
+    }
+    entry->data = (uint8_t *)MALLOC_CHK(nal_size);
+    if (!entry->data)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    memcpy(entry->data, nal, nal_size);
+    entry->size = nal_size;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+ps_lst_copy(list_handle_t *dst, list_handle_t src)
+{
+    it_list_handle_t it;
+    buf_entry_t *    entry, *new_entry;
+    int32_t          ret = EMA_MP4_MUXED_OK;
+
+    *dst = list_create(sizeof(buf_entry_t));
+    if (!*dst)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    if (!src)
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    it = it_create();
+    it_init(it, src);
+    while (ret == EMA_MP4_MUXED_OK && (entry = it_get_entry(it)))
+    {
+        new_entry = (buf_entry_t *)list_alloc_entry(*dst);
+        if (!new_entry)
+        {
+            ret = EMA_MP4_MUXED_NO_MEM;
+            break;
+        }
+        new_entry->id   = entry->id;
+        new_entry->data = NULL;
+        ret = ps_entry_set(new_entry, entry->data, entry->size);
+        list_add_entry(*dst, new_entry);
+    }
+    it_destroy(it);
+
+    return ret;
+}
+
+/** Starts a new sample description with the parameter sets of the current one but the APS */
+static int32_t
+vvc_clone_dsi(parser_handle_t parser)
+{
+    mp4_dsi_vvc_handle_t dsi     = (mp4_dsi_vvc_handle_t)parser->curr_dsi;
+    dsi_handle_t         new_dsi = parser->dsi_create(parser->dsi_type);
+    mp4_dsi_vvc_handle_t new_vvc = (mp4_dsi_vvc_handle_t)new_dsi;
+    dsi_handle_t *       p_new_dsi;
+    int32_t              ret;
+
+    if (!new_dsi)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    new_vvc->ptl_sps_id                = dsi->ptl_sps_id;
+    new_vvc->num_sublayers             = dsi->num_sublayers;
+    new_vvc->chroma_format_idc         = dsi->chroma_format_idc;
+    new_vvc->bit_depth_minus8          = dsi->bit_depth_minus8;
+    new_vvc->max_picture_width         = dsi->max_picture_width;
+    new_vvc->max_picture_height        = dsi->max_picture_height;
+    new_vvc->num_bytes_constraint_info = dsi->num_bytes_constraint_info;
+    new_vvc->width                     = dsi->width;
+    new_vvc->height                    = dsi->height;
+    if (dsi->ptl_size)
+    {
+        new_vvc->ptl = (uint8_t *)MALLOC_CHK(dsi->ptl_size);
+        if (!new_vvc->ptl)
+        {
+            new_dsi->destroy(new_dsi);
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        memcpy(new_vvc->ptl, dsi->ptl, dsi->ptl_size);
+        new_vvc->ptl_size = dsi->ptl_size;
+    }
+
+    ret = ps_lst_copy(&new_vvc->vps_lst, dsi->vps_lst);
+    if (ret == EMA_MP4_MUXED_OK)
+    {
+        ret = ps_lst_copy(&new_vvc->sps_lst, dsi->sps_lst);
+    }
+    if (ret == EMA_MP4_MUXED_OK)
+    {
+        ret = ps_lst_copy(&new_vvc->pps_lst, dsi->pps_lst);
+    }
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        new_dsi->destroy(new_dsi);
+        return ret;
+    }
+
+    p_new_dsi = (dsi_handle_t*)list_alloc_entry(parser->dsi_lst);
+    if (!p_new_dsi)
+    {
+        new_dsi->destroy(new_dsi);
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    *p_new_dsi = new_dsi;
+
+    /* switch to new entry in dsi list */
+    list_add_entry(parser->dsi_lst, p_new_dsi);
+    parser->curr_dsi = new_dsi;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Takes the 'vvcC' fields of the dsi from sps */
+static int32_t
+dsi_take_sps(parser_vvc_handle_t parser_vvc, mp4_dsi_vvc_handle_t dsi, const vvc_sps_t *sps)
+{
+    if (dsi->ptl)
+    {
+        FREE_CHK(dsi->ptl);
+        dsi->ptl      = NULL;
+        dsi->ptl_size = 0;
+    }
+    if (sps->ptl_size)
+    {
+        dsi->ptl = (uint8_t *)MALLOC_CHK(sps->ptl_size);
+        if (!dsi->ptl)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        memcpy(dsi->ptl, parser_vvc->rbsp + sps->ptl_off, sps->ptl_size);
+        dsi->ptl_size = sps->ptl_size;
+    }
+
+    dsi->ptl_sps_id                = sps->sps_id;
+    dsi->num_sublayers             = sps->max_sublayers;
+    dsi->chroma_format_idc         = sps->chroma_format_idc;
+    dsi->bit_depth_minus8          = sps->bit_depth_minus8;
+    dsi->max_picture_width         = (uint16_t)sps->max_width;
+    dsi->max_picture_height        = (uint16_t)sps->max_height;
+    dsi->num_bytes_constraint_info = sps->num_bytes_constraint_info;
+    dsi->width                     = sps->width;
+    dsi->height                    = sps->height;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Puts the parameter set in nal into the current dsi. VPS, SPS and PPS with an id already there but a
+ *  different content start a new sample description once a sample refers to the current one.
+ */
+static int32_t
+ps_update(parser_vvc_handle_t parser_vvc, uint32_t nal_type, uint32_t id, uint32_t *sample_flags)
+{
+    parser_handle_t      parser = (parser_handle_t)parser_vvc;
+    mp4_dsi_vvc_handle_t dsi    = (mp4_dsi_vvc_handle_t)parser->curr_dsi;
+    list_handle_t *      plst   = ps_lst_get(dsi, nal_type);
+    it_list_handle_t     it;
+    buf_entry_t *        entry;
+    int32_t              ret;
+
+    if (!*plst)
+    {
+        *plst = list_create(sizeof(buf_entry_t));
+        if (!*plst)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+    }
+
+    it = it_create();
+    it_init(it, *plst);
+    while ((entry = it_get_entry(it)) && entry->id != id)
+    {
+        continue;
+    }
+    it_destroy(it);
+
+    if (!entry)
+    {
+        if (nal_type == VVC_NAL_PREFIX_APS && parser_vvc->dsi_used)
+        {
+            return EMA_MP4_MUXED_OK;
+        }
+        entry = (buf_entry_t *)list_alloc_entry(*plst);
+        if (!entry)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        entry->id   = id;
+        entry->data = NULL;
+        ret = ps_entry_set(entry, parser_vvc->nal, parser_vvc->nal_size);
+        list_add_entry(*plst, entry);
+        return ret;
+    }
+
+    if (entry->size == parser_vvc->nal_size && !memcmp(entry->data, parser_vvc->nal, entry->size))
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    if (!parser_vvc->dsi_used)
+    {
+        /** no sample refers to it yet */
+        return ps_entry_set(entry, parser_vvc->nal, parser_vvc->nal_size);
+    }
+    if (nal_type == VVC_NAL_PREFIX_APS)
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    if (parser->sd == 0)
+    {
+        /* single sample description entry */
+        msglog(NULL, MSGLOG_ERR, "Error: Multiple Sample Descriptions necessary but not allowed!\n");
+        parser->sd_collision_flag = 1;
+        return EMA_MP4_MUXED_MULTI_SD_ERR;
+    }
+
+    ret = vvc_clone_dsi(parser);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+    parser_vvc->dsi_used = FALSE;
+    *sample_flags       |= SAMPLE_NEW_SD;
+    msglog(NULL, MSGLOG_INFO, "VVC parameter set changed at sample %u: new sample description\n", parser_vvc->au_num);
+
+    return ps_update(parser_vvc, nal_type, id, sample_flags);
+}
+
+/** Parses the parameter set in nal and updates the current dsi with it */
+static int32_t
+parse_ps(parser_vvc_handle_t parser_vvc, uint32_t nal_type, uint32_t *sample_flags)
+{
+    mp4_dsi_vvc_handle_t dsi;
+    bbio_handle_t        bs;
+    size_t               rbsp_size;
+    vvc_sps_t            sps;
+    uint32_t             id;
+    int32_t              ret = EMA_MP4_MUXED_OK;
+
+    bs = rbsp_reader(parser_vvc, parser_vvc->nal_size, &rbsp_size);
+    if (!bs)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    switch (nal_type)
+    {
+    case VVC_NAL_VPS:
+        id = src_read_bits(bs, 4);                      /** vps_video_parameter_set_id */
+        break;
+    case VVC_NAL_SPS:
+        ret = parse_sps(bs, rbsp_size, &sps);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            msglog(NULL, MSGLOG_ERR, "Invalid VVC SPS\n");
+        }
+        id = sps.sps_id;
+        break;
+    case VVC_NAL_PPS:
+        id = src_read_bits(bs, 6);                      /** pps_pic_parameter_set_id */
+        parser_vvc->pps_sps_id[id] = (uint8_t)src_read_bits(bs, 4);
+        parser_vvc->pps_valid[id]  = TRUE;
+        break;
+    default:
+        id = src_read_bits(bs, 8);                      /** aps_params_type, aps_adaptation_parameter_set_id */
+        break;
+    }
+    bs->destroy(bs);
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    ret = ps_update(parser_vvc, nal_type, id, sample_flags);
+    if (ret != EMA_MP4_MUXED_OK || nal_type != VVC_NAL_SPS)
+    {
+        return ret;
+    }
+
+    parser_vvc->sps[id] = sps;
+    dsi = (mp4_dsi_vvc_handle_t)parser_vvc->curr_dsi;
+    if (!dsi->num_sublayers || dsi->ptl_sps_id == id)
+    {
+        ret = dsi_take_sps(parser_vvc, dsi, &sps);
+    }
+    return ret;
+}
+
+/** Tells if the nal starts a new access unit once the current one has got a VCL NAL unit, see H.266 7.4.2.4.3 */
+static BOOL
+nal_starts_au(const uint8_t *nal, size_t nal_size)
+{
+    uint32_t nal_type = nal[1] >> 3;
+
+    switch (nal_type)
+    {
+    case VVC_NAL_AUD:
+    case VVC_NAL_OPI:
+    case VVC_NAL_DCI:
+    case VVC_NAL_VPS:
+    case VVC_NAL_SPS:
+    case VVC_NAL_PPS:
+    case VVC_NAL_PREFIX_APS:
+    case VVC_NAL_PH:
+    case VVC_NAL_PREFIX_SEI:
+    case VVC_NAL_RSV_NVCL_26:
+    case VVC_NAL_UNSPEC_28:
+    case VVC_NAL_UNSPEC_29:
+        return TRUE;
+    default:
+        break;
+    }
+
+    /** a slice carrying the picture header is the only one of its picture */
+    return VVC_IS_VCL(nal_type) && nal_size > 2 && (nal[2] & 0x80);
+}
+
+/** Derives the poc and the cts of the picture, see H.266 8.3.1 */
+static int64_t
+picture_cts(parser_vvc_handle_t parser_vvc, uint64_t dts)
+{
+    vvc_pic_t *pic   = &parser_vvc->pic;
+    int64_t    ticks = parser_vvc->num_units_in_tick;
+    BOOL       poc_reset;
+    int32_t    poc_msb, poc;
+    int64_t    cts;
+
+    poc_reset = (parser_vvc->au_num == 0 || VVC_IS_IDR(pic->nal_type) ||
+                 (parser_vvc->eos_seen && (pic->nal_type == VVC_NAL_CRA || pic->nal_type == VVC_NAL_GDR)));
+    if (poc_reset)
+    {
+        poc_msb = 0;
+    }
+    else
+    {
+        int32_t max_lsb  = (int32_t)pic->max_poc_lsb;
+        int32_t prev_lsb = parser_vvc->prev_tid0_poc & (max_lsb - 1);
+        int32_t prev_msb = parser_vvc->prev_tid0_poc - prev_lsb;
+        int32_t lsb      = (int32_t)pic->poc_lsb;
+
+        if (lsb < prev_lsb && prev_lsb - lsb >= max_lsb/2)
+            poc_msb = prev_msb + max_lsb;
+        else if (lsb > prev_lsb && lsb - prev_lsb > max_lsb/2)
+            poc_msb = prev_msb - max_lsb;
+        else
+            poc_msb = prev_msb;
+    }
+    poc = poc_msb + (int32_t)pic->poc_lsb;
+
+    if (pic->temporal_id == 0 && !pic->non_ref_pic &&
+        pic->nal_type != VVC_NAL_RASL && pic->nal_type != VVC_NAL_RADL)
+    {
+        parser_vvc->prev_tid0_poc = poc;
+    }
+    parser_vvc->eos_seen = FALSE;
+
+    if (poc_reset)
+    {
+        /** after all pictures of the previous coded video sequence */
+        int64_t cts0 = (parser_vvc->au_num == 0) ? (int64_t)dts : MAX2((int64_t)dts, parser_vvc->max_cts + ticks);
+        parser_vvc->poc_base = cts0 - poc*ticks;
+    }
+    cts = parser_vvc->poc_base + poc*ticks;
+    if (parser_vvc->au_num == 0 || cts > parser_vvc->max_cts)
+    {
+        parser_vvc->max_cts = cts;
+    }
+
+    return cts;
+}
+
+static void
+timing_info_update(parser_vvc_handle_t parser_vvc)
+{
+    /** the timing of the ES is at the end of the SPS or in the VPS, not parsed */
+    parser_vvc->time_scale        = parser_vvc->ext_timing.time_scale;
+    parser_vvc->num_units_in_tick = parser_vvc->ext_timing.num_units_in_tick;
+    parser_vvc->framerate         = parser_vvc->time_scale / parser_vvc->num_units_in_tick;
+    parser_vvc->ext_timing.usr_timing = 0;
+
+    msglog(NULL, MSGLOG_NOTICE, "VVC frame rate %u/%u as set by user or default\n",
+           parser_vvc->time_scale, parser_vvc->num_units_in_tick);
+}
+
+/** Makes sure sample->data can take size bytes */
+static int32_t
+sample_buf_reserve(parser_vvc_handle_t parser_vvc, mp4_sample_handle_t sample, size_t size)
+{
+    if (size > parser_vvc->sample_buf_size)
+    {
+        size_t   buf_size = MAX2(size, 2*parser_vvc->sample_buf_size);
+        uint8_t *data     = REALLOC_CHK(sample->data, buf_size);
+        if (!data)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        sample->data                = data;
+        parser_vvc->sample_buf_size = buf_size;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+cts_offset_add(parser_vvc_handle_t parser_vvc, int32_t cts_offset)
+{
+    if (parser_vvc->au_num >= parser_vvc->cts_offsets_size)
+    {
+        uint32_t size     = MAX2(1024, 2*parser_vvc->cts_offsets_size);
+        int32_t *offsets  = REALLOC_CHK(parser_vvc->cts_offsets, size*sizeof(int32_t));
+        if (!offsets)
+        {
+            return EMA_MP4_MUXED_NO_MEM;
+        }
+        parser_vvc->cts_offsets      = offsets;
+        parser_vvc->cts_offsets_size = size;
+    }
+    parser_vvc->cts_offsets[parser_vvc->au_num] = cts_offset;
+    if (parser_vvc->au_num == 0 || cts_offset < parser_vvc->cts_offset_min)
+    {
+        parser_vvc->cts_offset_min = cts_offset;
+    }
+    return EMA_MP4_MUXED_OK;
+}
+
+static int32_t
+parser_vvc_get_sample(parser_handle_t parser, mp4_sample_handle_t sample)
+{
+    parser_vvc_handle_t parser_vvc = (parser_vvc_handle_t)parser;
+    vvc_pic_t *         pic        = &parser_vvc->pic;
+    int64_t             cts;
+    int32_t             ret;
+
+    sample->flags                 = 0;
+    sample->is_leading            = 0;
+    sample->sample_depends_on     = 0;
+    sample->sample_is_depended_on = 0;
+    sample->sample_has_redundancy = 0;
+    sample->dependency_level      = 0;
+    sample->pic_type              = 0;
+    sample->frame_type            = 0xff;
+    sample->size                  = 0;
+
+    memset(pic, 0, sizeof(vvc_pic_t));
+
+    for (;;)
+    {
+        uint32_t nal_type;
+        BOOL     keep = TRUE;
+
+        if (!parser_vvc->nal_pending)
+        {
+            ret = read_nal(parser_vvc);
+            if (ret == EMA_MP4_MUXED_EOES)
+            {
+                break;
+            }
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                return ret;
+            }
+            parser_vvc->nal_pending = TRUE;
+        }
+        if (pic->vcl_seen && nal_starts_au(parser_vvc->nal, parser_vvc->nal_size))
+        {
+            break;
+        }
+        parser_vvc->nal_pending = FALSE;
+
+        nal_type = parser_vvc->nal[1] >> 3;
+        switch (nal_type)
+        {
+        case VVC_NAL_VPS:
+        case VVC_NAL_SPS:
+        case VVC_NAL_PPS:
+            if (parser->dsi_type == DSI_TYPE_MP4FF)
+            {
+                ret = parse_ps(parser_vvc, nal_type, &sample->flags);
+                if (ret != EMA_MP4_MUXED_OK)
+                {
+                    return ret;
+                }
+                keep = FALSE;
+            }
+            break;
+
+        case VVC_NAL_PREFIX_APS:
+            if (parser->dsi_type == DSI_TYPE_MP4FF)
+            {
+                ret = parse_ps(parser_vvc, nal_type, &sample->flags);
+                if (ret != EMA_MP4_MUXED_OK)
+                {
+                    return ret;
+                }
+            }
+            break;
+
+        case VVC_NAL_PH:
+        {
+            size_t        rbsp_size;
+            bbio_handle_t bs = rbsp_reader(parser_vvc, 64, &rbsp_size);
+
+            if (!bs)
+            {
+                return EMA_MP4_MUXED_NO_MEM;
+            }
+            ret = parse_ph(parser_vvc, bs);
+            bs->destroy(bs);
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                return ret;
+            }
+            break;
+        }
+
+        case VVC_NAL_EOS:
+            parser_vvc->eos_seen = TRUE;
+            break;
+
+        case VVC_NAL_FD:
+            /** filler data doesn't get added to sample buffer */
+            keep = FALSE;
+            break;
+
+        default:
+            if (VVC_IS_VCL(nal_type) && !pic->vcl_seen)
+            {
+                pic->vcl_seen    = TRUE;
+                pic->nal_type    = (uint8_t)nal_type;
+                pic->temporal_id = (parser_vvc->nal[1] & 0x7) - 1;
+                if (!pic->ph_seen)
+                {
+                    size_t        rbsp_size;
+                    bbio_handle_t bs = rbsp_reader(parser_vvc, 64, &rbsp_size);
+
+                    if (!bs)
+                    {
+                        return EMA_MP4_MUXED_NO_MEM;
+                    }
+                    ret = EMA_MP4_MUXED_ES_ERR;
+                    if (src_read_bit(bs))
+                    {
+                        /** sh_picture_header_in_slice_header_flag */
+                        ret = parse_ph(parser_vvc, bs);
+                    }
+                    else
+                    {
+                        msglog(NULL, MSGLOG_ERR, "VVC picture without picture header\n");
+                    }
+                    bs->destroy(bs);
+                    if (ret != EMA_MP4_MUXED_OK)
+                    {
+                        return ret;
+                    }
+                }
+            }
+            break;
+        }
+
+        if (keep)
+        {
+            uint8_t *dst;
+
+            ret = sample_buf_reserve(parser_vvc, sample, sample->size + NAL_LENGTH_SIZE + parser_vvc->nal_size);
+            if (ret != EMA_MP4_MUXED_OK)
+            {
+                return ret;
+            }
+            dst = sample->data + sample->size;
+            dst[0] = (uint8_t)(parser_vvc->nal_size >> 24);
+            dst[1] = (uint8_t)(parser_vvc->nal_size >> 16);
+            dst[2] = (uint8_t)(parser_vvc->nal_size >> 8);
+            dst[3] = (uint8_t)(parser_vvc->nal_size);
+            memcpy(dst + NAL_LENGTH_SIZE, parser_vvc->nal, parser_vvc->nal_size);
+            sample->size += NAL_LENGTH_SIZE + (uint32_t)parser_vvc->nal_size;
+        }
+    }
+
+    if (!pic->vcl_seen)
+    {
+        if (parser_vvc->au_num == 0)
+        {
+            msglog(NULL, MSGLOG_ERR, "No VVC picture found\n");
+            return EMA_MP4_MUXED_NO_CONFIG_ERR;
+        }
+        return EMA_MP4_MUXED_EOES;
+    }
+
+    if (parser_vvc->au_num == 0)
+    {
+        mp4_dsi_vvc_handle_t dsi = (mp4_dsi_vvc_handle_t)parser->curr_dsi;
+
+        if (parser->dsi_type == DSI_TYPE_MP4FF && !dsi->num_sublayers)
+        {
+            return EMA_MP4_MUXED_NO_CONFIG_ERR;
+        }
+        parser_vvc->width    = dsi->width;
+        parser_vvc->height   = dsi->height;
+        parser_vvc->hSpacing = 1;
+        parser_vvc->vSpacing = 1;
+        timing_info_update(parser_vvc);
+
+        sample->flags |= SAMPLE_NEW_SD;
+    }
+
+    switch (pic->nal_type)
+    {
+    case VVC_NAL_IDR_N_LP:
+    case VVC_NAL_IDR_W_RADL:
+        sample->flags   |= SAMPLE_SYNC;
+        sample->pic_type = 1;
+        break;
+    case VVC_NAL_CRA:
+    case VVC_NAL_GDR:
+    case VVC_NAL_RSV_IRAP_11:
+        sample->flags   |= SAMPLE_SYNC;
+        sample->pic_type = 2;
+        break;
+    case VVC_NAL_RASL:
+        sample->is_leading = 1;
+        break;
+    case VVC_NAL_RADL:
+        sample->is_leading = 3;
+        break;
+    default:
+        break;
+    }
+    if (sample->flags & SAMPLE_SYNC)
+    {
+        sample->frame_type       = 0;
+        sample->dependency_level = 0x01;
+    }
+    else
+    {
+        sample->frame_type       = 1;
+        sample->dependency_level = 0x02;
+    }
+
+    /**** timing */
+    sample->dts      = (uint64_t)parser_vvc->au_num * parser_vvc->num_units_in_tick;
+    cts              = picture_cts(parser_vvc, sample->dts);
+    sample->cts      = (uint64_t)cts;
+    sample->duration = parser_vvc->num_units_in_tick;
+    ret = cts_offset_add(parser_vvc, (int32_t)(cts - (int64_t)sample->dts));
+    if (ret != EMA_MP4_MUXED_OK)
+    {
+        return ret;
+    }
+
+    parser_vvc->dsi_used = TRUE;
+    parser_vvc->au_num++;
+    parser_vvc->num_samples++;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static BOOL
+parser_vvc_need_fix_ctts(parser_handle_t parser)
+{
+    parser;
+    return TRUE;
+}
+
+static int32_t
+parser_vvc_get_cts_offset(parser_handle_t parser, uint32_t sample_idx)
+{
+    parser_vvc_handle_t parser_vvc = (parser_vvc_handle_t)parser;
+
+    if (sample_idx >= parser_vvc->au_num)
+    {
+        return 0;
+    }
+    return parser_vvc->cts_offsets[sample_idx] - parser_vvc->cts_offset_min;
+}
+
+static void
+write_ps_array(bbio_handle_t snk, list_handle_t lst, uint32_t nal_type, uint32_t complete)
+{
+    it_list_handle_t it = it_create();
+    buf_entry_t *    entry;
+
+    sink_write_bits(snk, 1, complete);                      /** array_completeness */
+    sink_write_bits(snk, 2, 0);                             /** reserved */
+    sink_write_bits(snk, 5, nal_type);                      /** NAL_unit_type */
+    sink_write_u16(snk, (uint16_t)list_get_entry_num(lst)); /** num_nalus */
+    it_init(it, lst);
+    while ((entry = it_get_entry(it)))
+    {
+        sink_write_u16(snk, (uint16_t)entry->size);         /** nal_unit_length */
+        snk->write(snk, entry->data, entry->size);          /** nal_unit */
+    }
+    it_destroy(it);
+}
+
+/** get dsi for vvc (VvcConfigurationBox) */
+static int
+parser_vvc_get_mp4_cfg(parser_handle_t parser, uint8_t **buf, size_t *buf_len)
+{
+    parser_vvc_handle_t  parser_vvc = (parser_vvc_handle_t)parser;
+    mp4_dsi_vvc_handle_t dsi        = (mp4_dsi_vvc_handle_t)parser->curr_dsi;
+    list_handle_t        lsts[4];
+    uint32_t             nal_types[4] = {VVC_NAL_VPS, VVC_NAL_SPS, VVC_NAL_PPS, VVC_NAL_PREFIX_APS};
+    uint32_t             num_of_arrays = 0;
+    bbio_handle_t        snk;
+    int                  i;
+
+    snk = reg_bbio_get('b', 'w');
+    if (*buf)
+    {
+        snk->set_buffer(snk, *buf, *buf_len, 1);
+    }
+    else
+    {
+        snk->set_buffer(snk, NULL, 1024, 1);
+    }
+
+    /** the dimensions of the sample description in use */
+    parser_vvc->width  = dsi->width;
+    parser_vvc->height = dsi->height;
+
+    lsts[0] = dsi->vps_lst;
+    lsts[1] = dsi->sps_lst;
+    lsts[2] = dsi->pps_lst;
+    lsts[3] = dsi->aps_lst;
+    for (i = 0; i < 4; i++)
+    {
+        if (lsts[i] && list_get_entry_num(lsts[i]))
+        {
+            num_of_arrays++;
+        }
+    }
+
+    sink_write_u32(snk, 0);                                 /** FullBox version = 0, flags = 0 */
+
+    /** VvcDecoderConfigurationRecord */
+    sink_write_bits(snk, 5, 0x1f);                          /** reserved */
+    sink_write_bits(snk, 2, NAL_LENGTH_SIZE - 1);           /** LengthSizeMinusOne */
+    sink_write_bits(snk, 1, dsi->ptl_size ? 1 : 0);         /** ptl_present_flag */
+    if (dsi->ptl_size)
+    {
+        sink_write_bits(snk, 9, 0);                         /** ols_idx */
+        sink_write_bits(snk, 3, dsi->num_sublayers);
+        sink_write_bits(snk, 2, 0);                         /** constant_frame_rate */
+        sink_write_bits(snk, 2, dsi->chroma_format_idc);
+        sink_write_bits(snk, 3, dsi->bit_depth_minus8);
+        sink_write_bits(snk, 5, 0x1f);                      /** reserved */
+
+        /** VvcPTLRecord: the rest is laid out as profile_tier_level() */
+        sink_write_bits(snk, 2, 0);                         /** reserved */
+        sink_write_bits(snk, 6, dsi->num_bytes_constraint_info);
+        snk->write(snk, dsi->ptl, dsi->ptl_size);
+
+        sink_write_u16(snk, dsi->max_picture_width);
+        sink_write_u16(snk, dsi->max_picture_height);
+        sink_write_u16(snk, 0);                             /** avg_frame_rate */
+    }
+
+    sink_write_u8(snk, (uint8_t)num_of_arrays);
+    for (i = 0; i < 4; i++)
+    {
+        if (lsts[i] && list_get_entry_num(lsts[i]))
+        {
+            /** parameter sets other than APS are in the sample entry only */
+            write_ps_array(snk, lsts[i], nal_types[i], nal_types[i] != VVC_NAL_PREFIX_APS);
+        }
+    }
+
+    *buf = snk->get_buffer(snk, buf_len, 0);  /** here buf_len is set to data_size */
+    snk->destroy(snk);
+
+    return EMA_MP4_MUXED_OK;
+}
+
+static void
+parser_vvc_destroy(parser_handle_t parser)
+{
+    parser_vvc_handle_t parser_vvc = (parser_vvc_handle_t)parser;
+
+    if (parser_vvc->es_buf)
+    {
+        FREE_CHK(parser_vvc->es_buf);
+    }
+    if (parser_vvc->rbsp)
+    {
+        FREE_CHK(parser_vvc->rbsp);
+    }
+    if (parser_vvc->cts_offsets)
+    {
+        FREE_CHK(parser_vvc->cts_offsets);
+    }
+    parser_destroy(parser);
+}
+
+static int
+parser_vvc_init(parser_handle_t parser, ext_timing_info_t *ext_timing, uint32_t es_idx, bbio_handle_t ds)
+{
+    parser_vvc_handle_t parser_vvc = (parser_vvc_handle_t)parser;
+
+    parser->ext_timing = *ext_timing;
+    parser->es_idx     = es_idx;
+    parser->ds         = ds;
+
+    parser_vvc->es_buf_size = ES_BUF_SIZE;
+    parser_vvc->es_buf      = (uint8_t *)MALLOC_CHK(parser_vvc->es_buf_size);
+    if (!parser_vvc->es_buf)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+
+    if (read_nal(parser_vvc) != EMA_MP4_MUXED_OK)
+    {
+        /** no nal at all */
+        return EMA_MP4_MUXED_EOES;
+    }
+    parser_vvc->nal_pending = TRUE;
+
+    return EMA_MP4_MUXED_OK;
+}
+
+/** Creates and build interface, base */
+static parser_handle_t
+parser_vvc_create(uint32_t dsi_type)
+{
+    parser_vvc_handle_t parser;
+
+    parser = (parser_vvc_handle_t)MALLOC_CHK(sizeof(parser_vvc_t));
+    if (!parser)
+    {
+        return 0;
+    }
+    memset(parser, 0, sizeof(parser_vvc_t));
+
+    /**** build the interface, base for the instance */
+    parser->stream_type     = STREAM_TYPE_VIDEO;
+    parser->stream_id       = STREAM_ID_VVC;
+    parser->stream_name     = "vvc";
+    parser->dsi_FourCC      = "vvcC";
+    parser->profile_levelID = 0;
+
+    parser->dsi_type        = dsi_type;
+    parser->dsi_create      = dsi_vvc_create;
+
+    parser->init            = parser_vvc_init;
+    parser->destroy         = parser_vvc_destroy;
+    parser->get_sample      = parser_vvc_get_sample;
+
+    OSAL_STRNCPY(parser->codec_name, 12, "\012VVC Coding", 12);
+
+    if (dsi_type == DSI_TYPE_MP4FF)
+    {
+        parser->get_cfg = parser_vvc_get_mp4_cfg;
+    }
+
+    parser->need_fix_cts   = parser_vvc_need_fix_ctts;
+    parser->get_cts_offset = parser_vvc_get_cts_offset;
+
+    /** use dsi list for the sake of multiple entries of stsd */
+    if (dsi_list_create((parser_handle_t)parser, dsi_type))
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+    parser->codec_config_lst  = list_create(sizeof(codec_config_t));
+    parser->curr_codec_config = NULL;
+    if (!parser->codec_config_lst)
+    {
+        parser->destroy((parser_handle_t)parser);
+        return 0;
+    }
+
+    /***** cast to base */
+    return (parser_handle_t)parser;
+}
+
+void
+parser_vvc_reg(void)
+{
+    /** register all alias to make reg_parser_get easier */
+    reg_parser_set("vvc", parser_vvc_create);
+    reg_parser_set("266", parser_vvc_create);
+    reg_parser_set("h266", parser_vvc_create);
+}
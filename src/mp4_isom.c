@@ -50,6 +50,8 @@ const stream_id_codingname_map_t stream_id_video_codingname_tbl[] = {
     {STREAM_ID_H264,    "h264"},     /** AVC-1/H.264 */
     {STREAM_ID_H264,    "264"},      /** AVC-1/H.264 */
     {STREAM_ID_H264,    "H264"},     /** AVC-1/H.264 */
+    {STREAM_ID_AV1,     "av01"},     /** AV1 */
+    {STREAM_ID_VVC,     "vvc1"},     /** VVC/H.266 */
     {STREAM_ID_VC1,     "vc-1"},     /** don't know yet vc1 */
     {STREAM_ID_VC1,     "VC-1"},     /** don't know yet vc1 */
     {STREAM_ID_H263,    "s263"},     /** s263/H.263 */
@@ -131,6 +133,13 @@ const stream_id_codingname_map_t stream_id_audio_codingname_tbl[] = {
     {STREAM_ID_DTS,     "dtsh"},  /** DTS */
     {STREAM_ID_DTS,     "dtse"},  /** DTS */
     {STREAM_ID_DTS,     "dtsl"},  /** DTS */
+    {STREAM_ID_DTS,     "dtsx"},  /** DTS-UHD */
+    {STREAM_ID_OPUS,    "Opus"},  /** Opus */
+    {STREAM_ID_FLAC,    "fLaC"},  /** FLAC */
+    {STREAM_ID_PCM,     "ipcm"},  /** integer PCM */
+    {STREAM_ID_PCM,     "fpcm"},  /** floating point PCM */
+    {STREAM_ID_MPEGH,   "mhm1"},  /** MPEG-H 3D Audio in MHAS */
+    {STREAM_ID_MPEGH,   "mha1"},  /** MPEG-H 3D Audio */
     {STREAM_ID_QTAUDIO, "lpcm"},
     {STREAM_ID_QTAUDIO, "raw "},
     {STREAM_ID_QTAUDIO, "twos"},
@@ -140,12 +140,26 @@ get_codingname(parser_handle_t parser)
     {
     case STREAM_ID_HEVC: codingname = "hvc1"; break; 
     case STREAM_ID_H264: codingname = "avc1"; break;
+    case STREAM_ID_AV1:  codingname = "av01"; break;
+    case STREAM_ID_VVC:  codingname = "vvc1"; break;
     case STREAM_ID_H263: codingname = "s263"; break;
     case STREAM_ID_MP4V: codingname = "mp4v"; break;
     case STREAM_ID_VC1:  codingname = "vc-1"; break;
     case STREAM_ID_AC3:  codingname = "ac-3"; break;
     case STREAM_ID_EC3:  codingname = "ec-3"; break;
     case STREAM_ID_AC4:  codingname = "ac-4"; break;
+    case STREAM_ID_OPUS: codingname = "Opus"; break;
+    case STREAM_ID_FLAC: codingname = "fLaC"; break;
+    case STREAM_ID_PCM:
+        codingname = ((mp4_dsi_pcm_handle_t)parser->curr_dsi)->float_flag ? "fpcm" : "ipcm";
+        break;
+    case STREAM_ID_MPEGH: codingname = "mhm1"; break;
+    case STREAM_ID_DTS:
+        if (((mp4_dsi_dts_handle_t)parser->curr_dsi)->uhd)
+            codingname = "dtsx";
+        else
+            codingname = ((mp4_dsi_dts_handle_t)parser->curr_dsi)->substream ? "dtsh" : "dtsc";
+        break;
     case STREAM_ID_MLP:  codingname = "mlpa"; break;
     case STREAM_ID_METX: codingname = "metx"; break;
     case STREAM_ID_METT: codingname = "mett"; break;
@@ -196,6 +210,20 @@ write_private_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer, int8_t* parent_box
     }
 }
 
+/** Appends brand to the compatible brands of size *size if it isn't one of them yet */
+static void
+add_compatible_brand(uint8_t *compatible_brands, uint32_t buf_size, uint32_t *size, const int8_t *brand)
+{
+    uint32_t i;
+
+    for (i = 0; i + 4 <= *size && !IS_FOURCC_EQUAL(compatible_brands + i, brand); i += 4);
+    if (i + 4 > *size && *size + 4 <= buf_size)
+    {
+        memcpy(compatible_brands + *size, brand, 4);
+        *size += 4;
+    }
+}
+
 static int32_t
 write_ftyp_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
 {
@@ -213,6 +241,18 @@ write_ftyp_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
         k = j;
     }
 
+    /** AV1 Codec ISO Media File Format Binding: files with 'av01' tracks carry the 'av01' brand */
+    if (muxer->has_av1)
+    {
+        add_compatible_brand(compatible_brands, sizeof(compatible_brands), &j, "av01");
+    }
+    /** Encapsulation of Opus in ISO Base Media File Format 4.1: the 'Opus' brand */
+    if (muxer->has_opus)
+    {
+        add_compatible_brand(compatible_brands, sizeof(compatible_brands), &j, "Opus");
+    }
+    k = j;
+
     sink_write_u32(snk, 16 + k);   /** size */
     sink_write_4CC(snk, "ftyp");
     sink_write_4CC(snk, brand);
@@ -361,7 +401,7 @@ write_tfdt_box(bbio_handle_t snk, track_handle_t track)
 
     dts_id  = list_it_peek_entry(track->dts_lst);
     dts_u64 = dts_id->dts;
-    if (dts_u64 < 0xffffffff)
+    if (dts_u64 < 0xffffffff && !(track->mp4_ctrl->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_FORCE_TFDT_V1))
     {
         version = 0;
         dts_u32 = (uint32_t)dts_u64;
@@ -399,7 +439,7 @@ write_trik_box(bbio_handle_t snk, track_handle_t track)
     uint32_t i;
 
     /** sample count is taken from 'trun' when part of 'traf' */
-    uint32_t sample_count = track->trun.sample_count;
+    uint32_t sample_count = track->trun.sample_count != 0 ? track->trun.sample_count : track->sample_num;
     uint32_t size         = 12 + sample_count;
 
     /** FullBox header */
@@ -499,7 +539,7 @@ write_sdtp_box(bbio_handle_t snk, track_handle_t track)
     uint32_t i;
 
     /** sample count is taken from 'trun' when part of 'traf' */
-    uint32_t sample_count = track->trun.sample_count;
+    uint32_t sample_count = track->trun.sample_count != 0 ? track->trun.sample_count : track->sample_num;
     uint32_t size         = 12 + sample_count;
 
     /** FullBox header */
@@ -1245,6 +1285,8 @@ write_frma_box(bbio_handle_t snk, track_handle_t track)
             codingname = "dvav";
         else if (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "hvcC"))
             codingname = "dvhe";    
+        else if (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "av1C") && !track->parser->ext_timing.ext_dv_bl_compatible_id)
+            codingname = "dav1";
     }
 
     sink_write_4CC(snk, codingname);
@@ -1343,7 +1385,8 @@ write_dsi_box(bbio_handle_t snk, track_handle_t track)
     snk->write(snk, (uint8_t *)track->dsi_buf, track->dsi_size);
     
     /** add dolby vision dsi */
-    if (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "avcC") || (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "hvcC")))
+    if (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "avcC") || (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "hvcC"))
+        || IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "av1C"))
     {
         if (track->parser->dv_dsi_size)
         {
@@ -1449,11 +1492,51 @@ write_colr_box(bbio_handle_t snk, track_handle_t track)
     sink_write_4CC(snk, "colr");
 
     /**  color type */
-    sink_write_4CC(snk, "nclc");
+    sink_write_4CC(snk, "nclx");
 
     sink_write_u16(snk, parser_video->colour_primaries);
     sink_write_u16(snk, parser_video->transfer_characteristics);
     sink_write_u16(snk, parser_video->matrix_coefficients);
+    sink_write_bits(snk, 1, parser_video->full_range_flag);
+    sink_write_bits(snk, 7, 0);  /** reserved */
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
+static offset_t
+write_mdcv_box(bbio_handle_t snk, track_handle_t track)
+{
+    parser_video_handle_t parser_video = (parser_video_handle_t)track->parser;
+    int32_t i;
+
+    /** mastering display colour volume box hdr */
+    SKIP_SIZE_FIELD(snk);
+    sink_write_4CC(snk, "mdcv");
+
+    for (i = 0; i < 3; i++)
+    {
+        sink_write_u16(snk, parser_video->display_primaries_x[i]);
+        sink_write_u16(snk, parser_video->display_primaries_y[i]);
+    }
+    sink_write_u16(snk, parser_video->white_point_x);
+    sink_write_u16(snk, parser_video->white_point_y);
+    sink_write_u32(snk, parser_video->max_display_mastering_luminance);
+    sink_write_u32(snk, parser_video->min_display_mastering_luminance);
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
+static offset_t
+write_clli_box(bbio_handle_t snk, track_handle_t track)
+{
+    parser_video_handle_t parser_video = (parser_video_handle_t)track->parser;
+
+    /** content light level box hdr */
+    SKIP_SIZE_FIELD(snk);
+    sink_write_4CC(snk, "clli");
+
+    sink_write_u16(snk, parser_video->max_content_light_level);
+    sink_write_u16(snk, parser_video->max_pic_average_light_level);
 
     WRITE_SIZE_FIELD_RETURN(snk);
 }
@@ -1527,6 +1610,11 @@ write_video_box(bbio_handle_t snk, track_handle_t track)
     {
         dolby_vision_flag = 1;
     }
+    else if ((track->parser->ext_timing.ext_dv_profile == 10) && track->parser->dv_rpu_nal_flag
+             && !track->parser->ext_timing.ext_dv_bl_compatible_id)
+    {
+        dolby_vision_flag = 1;  /** non backward compatible AV1 */
+    }
 
     /** sample entry name */
     if(dolby_vision_flag && !track->encryptor)
@@ -1553,6 +1641,10 @@ write_video_box(bbio_handle_t snk, track_handle_t track)
                 codingname = "dvh1";
             }
         }
+        else if (IS_FOURCC_EQUAL(track->parser->dsi_FourCC, "av1C"))
+        {
+            codingname = "dav1";
+        }
         
         memcpy(track->codingname, codingname, 4);
     }
@@ -1612,6 +1704,19 @@ write_video_box(bbio_handle_t snk, track_handle_t track)
         write_dsi_box(snk, track);
     }
 
+    if (parser_video->colour_description_present)
+    {
+        write_colr_box(snk, track);
+    }
+    if (parser_video->mdcv_present)
+    {
+        write_mdcv_box(snk, track);
+    }
+    if (parser_video->clli_present)
+    {
+        write_clli_box(snk, track);
+    }
+
 #ifdef ENABLE_MP4_ENCRYPTION
     if (track->encryptor)
     {
@@ -1643,19 +1748,87 @@ update_audio_dsi(track_handle_t track)
             track->audio_channel_count = aac_dsi->channel_count;
         }
     }
-    else if (track->parser->stream_id == STREAM_ID_AC4)
+    else if (track->parser->stream_id == STREAM_ID_AC4 || track->parser->stream_id == STREAM_ID_OPUS ||
+             track->parser->stream_id == STREAM_ID_FLAC || track->parser->stream_id == STREAM_ID_PCM ||
+             track->parser->stream_id == STREAM_ID_MPEGH || track->parser->stream_id == STREAM_ID_DTS)
     {
         parser_audio_handle_t parser  = (parser_audio_handle_t)track->parser;
         track->audio_channel_count = parser->channelcount;
     }
 }
 
+/** QuickTime sound sample description version 2 of 'lpcm' */
+static offset_t
+write_lpcm_box(bbio_handle_t snk, track_handle_t track)
+{
+    parser_audio_handle_t parser_audio = (parser_audio_handle_t)track->parser;
+    mp4_dsi_pcm_handle_t  dsi          = (mp4_dsi_pcm_handle_t)track->parser->curr_dsi;
+    double                sample_rate  = parser_audio->sample_rate;
+    uint64_t              sample_rate_bits;
+    uint32_t              flags;
+
+    SKIP_SIZE_FIELD(snk);
+    MOV_WRITE_SAMPLE_ENTRY(snk, (uint8_t *)"lpcm", track->data_ref_index);
+
+    sink_write_u16(snk, 2);             /** version */
+    sink_write_u16(snk, 0);             /** revision level */
+    sink_write_u32(snk, 0);             /** vendor */
+    sink_write_u16(snk, 3);             /** always 3 */
+    sink_write_u16(snk, 16);            /** always 16 */
+    sink_write_u16(snk, 0xFFFE);        /** always -2 */
+    sink_write_u16(snk, 0);             /** always 0 */
+    sink_write_u32(snk, 0x00010000);    /** always 65536 */
+    sink_write_u32(snk, 72);            /** sizeOfStructOnly */
+    memcpy(&sample_rate_bits, &sample_rate, sizeof(sample_rate_bits));
+    sink_write_u64(snk, sample_rate_bits);
+    sink_write_u32(snk, parser_audio->channelcount);
+    sink_write_u32(snk, 0x7F000000);    /** always 0x7F000000 */
+    sink_write_u32(snk, dsi->valid_bits);
+
+    /** kAudioFormatFlagIsFloat, IsSignedInteger, IsPacked or IsAlignedHigh; little endian */
+    flags  = dsi->float_flag ? 0x1 : 0x4;
+    flags |= (dsi->valid_bits == dsi->pcm_sample_size) ? 0x8 : 0x10;
+    sink_write_u32(snk, flags);
+    sink_write_u32(snk, dsi->block_align);  /** constBytesPerAudioPacket */
+    sink_write_u32(snk, 1);                 /** constLPCMFramesPerAudioPacket */
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
+/** ChannelLayout of ISO/IEC 23003-5 PCM */
+static offset_t
+write_chnl_box(bbio_handle_t snk, track_handle_t track)
+{
+    mp4_dsi_pcm_handle_t dsi = (mp4_dsi_pcm_handle_t)track->parser->curr_dsi;
+
+    SKIP_SIZE_FIELD(snk);
+    sink_write_4CC(snk, "chnl");
+    sink_write_u32(snk, 0);             /** version & flags */
+    sink_write_u8(snk, 1);              /** stream_structure: channel structured */
+    sink_write_u8(snk, dsi->defined_layout);
+    if (dsi->defined_layout)
+    {
+        sink_write_u64(snk, 0);         /** omittedChannelsMap */
+    }
+    else
+    {
+        snk->write(snk, dsi->speaker_position, ((parser_audio_handle_t)track->parser)->channelcount);
+    }
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
 static offset_t
 write_audio_box(bbio_handle_t snk, track_handle_t track)
 {
     int8_t*    codingname = (track->encryptor) ? "enca" : track->codingname;
     uint32_t sample_rate;
 
+    if (IS_FOURCC_EQUAL(track->codingname, "lpcm"))
+    {
+        return write_lpcm_box(snk, track);
+    }
+
     /** Sample Entry */
     SKIP_SIZE_FIELD(snk);
     MOV_WRITE_SAMPLE_ENTRY(snk, (uint8_t *)codingname, track->data_ref_index);
@@ -1667,7 +1840,14 @@ write_audio_box(bbio_handle_t snk, track_handle_t track)
     sink_write_u32(snk, 0);                                     /** reserved */
 
     sink_write_u16(snk, (uint16_t)track->audio_channel_count);  /** channel count */
-    sink_write_u16(snk, 16);                                    /** sample size   */
+    if (track->parser->stream_id == STREAM_ID_FLAC || track->parser->stream_id == STREAM_ID_PCM)
+    {
+        sink_write_u16(snk, (uint16_t)((parser_audio_handle_t)(track->parser))->samplesize);
+    }
+    else
+    {
+        sink_write_u16(snk, 16);                                /** sample size   */
+    }
     sink_write_u16(snk, 0);                                     /** predefined    */
 
     sink_write_u16(snk, 0);                                     /** reserved */
@@ -1692,6 +1872,10 @@ write_audio_box(bbio_handle_t snk, track_handle_t track)
     {
         write_dsi_box(snk, track);
     }
+    if (track->parser->stream_id == STREAM_ID_PCM && ((mp4_dsi_pcm_handle_t)track->parser->curr_dsi)->layout_present)
+    {
+        write_chnl_box(snk, track);
+    }
 
 #ifdef ENABLE_MP4_ENCRYPTION
     if (track->encryptor)
@@ -1904,7 +2088,19 @@ write_stts_box(bbio_handle_t snk, track_handle_t track)
     dts1 = track->media_duration;
     dts0 = 0;
 
-    if (!(track->output_mode & EMA_MP4_FRAG))
+    if (track->lpcm_frame_size && !(track->output_mode & EMA_MP4_FRAG))
+    {
+        /** all frames of duration 1 */
+        entry_count = 1;
+        sink_write_u32(snk, (uint32_t)(track->mdat_size / track->lpcm_frame_size));
+        sink_write_u32(snk, 1);
+
+        cur_pos = snk->position(snk);
+        snk->seek(snk, CURRENT_BOX_OFFSET()+12, SEEK_SET);
+        sink_write_u32(snk, entry_count);
+        snk->seek(snk, cur_pos, SEEK_SET);
+    }
+    else if (!(track->output_mode & EMA_MP4_FRAG))
     {
         it_list_handle_t it = it_create();
 
@@ -2111,7 +2307,7 @@ static offset_t
 write_stsc_box(bbio_handle_t snk, track_handle_t track)
 {
     uint32_t num, i;
-    uint32_t entry_count = 0, sample_num = 0, sample_description_index = 0;
+    uint32_t entry_count = 0, sample_num = 0, sample_description_index = 0, chunk_sample_num;
     offset_t cur_pos;
 
     SKIP_SIZE_FIELD(snk);
@@ -2137,11 +2333,13 @@ write_stsc_box(bbio_handle_t snk, track_handle_t track)
                 continue;
             }
 
-            if (sample_num != chunk->sample_num ||
+            /** 'lpcm' samples are frames */
+            chunk_sample_num = track->lpcm_frame_size ? (uint32_t)(chunk->size / track->lpcm_frame_size) : chunk->sample_num;
+            if (sample_num != chunk_sample_num ||
                 sample_description_index != chunk->sample_description_index)
             {
                 entry_count++;
-                sample_num = chunk->sample_num;
+                sample_num = chunk_sample_num;
                 sample_description_index = chunk->sample_description_index;
 
                 sink_write_u32(snk, i + 1);  /** first chunk start from 1 */
@@ -2172,7 +2370,12 @@ write_stsz_box(bbio_handle_t snk, track_handle_t track)
 
     if (!(track->output_mode & EMA_MP4_FRAG))
     {
-        if (track->all_same_size_samples)
+        if (track->lpcm_frame_size)
+        {
+            sink_write_u32(snk, track->lpcm_frame_size);                               /** sample_size */
+            sink_write_u32(snk, (uint32_t)(track->mdat_size / track->lpcm_frame_size)); /** sample_count */
+        }
+        else if (track->all_same_size_samples)
         {
             /** same size case */
             cv = list_peek_first_entry(track->size_lst);
@@ -2261,21 +2464,88 @@ write_sbgp_box(bbio_handle_t snk, track_handle_t track)
     sink_write_4CC(snk, tag);               /** grouping_type */
     sink_write_u32(snk, 1 );                /** entry_count */
     sink_write_u32(snk, track->sample_num); /** sample_count */
-    sink_write_u32(snk, 0 );                /** group_description_index */
+    sink_write_u32(snk, 1 );                /** group_description_index */
     WRITE_SIZE_FIELD_RETURN(snk);
 }
 
 static offset_t
-write_sgpd_box(bbio_handle_t snk)
+write_sgpd_box(bbio_handle_t snk, track_handle_t track)
 {
     int8_t *tag = "roll";
 
     SKIP_SIZE_FIELD(snk);
     sink_write_4CC(snk, "sgpd");
-    sink_write_u32(snk, 0);         /** version & flags */
+    sink_write_u32(snk, 1<<24);     /** version = 1 & flags */
     sink_write_4CC(snk, tag);       /** grouping_type */
+    sink_write_u32(snk, 2);         /** default_length */
     sink_write_u32(snk, 1);         /** entry count */
-    sink_write_u16(snk, 0xffff);    /** roll_distance */
+    sink_write_u16(snk, (uint16_t)track->roll_distance);  /** roll_distance */
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
+/** Maps the samples first .. first+sample_count-1 to the 'sap ' group of their SAP type */
+static offset_t
+write_sap_sbgp_box(bbio_handle_t snk, track_handle_t track, uint32_t first, uint32_t sample_count, uint32_t index_base)
+{
+    it_list_handle_t it          = it_create();
+    uint32_t         end         = first + sample_count;
+    uint32_t         entry_count = 0;
+    count_value_t   *cv;
+    int32_t          pass;
+
+    SKIP_SIZE_FIELD(snk);
+    sink_write_4CC(snk, "sbgp");
+    sink_write_u32(snk, 0);                 /** version & flags */
+    sink_write_4CC(snk, "sap ");            /** grouping_type */
+
+    /** count the runs of equal SAP type first */
+    for (pass = 0; pass < 2; pass++)
+    {
+        if (pass)
+        {
+            sink_write_u32(snk, entry_count);
+        }
+        it_init(it, track->sap_lst);
+        while ((cv = (count_value_t *)it_get_entry(it)))
+        {
+            uint32_t run_first = MAX2(cv->idx - 1, first);  /** the run idx is 1 based */
+            uint32_t run_end   = MIN2(cv->idx - 1 + cv->count, end);
+
+            if (run_first >= run_end)
+            {
+                continue;
+            }
+            if (!pass)
+            {
+                entry_count++;
+                continue;
+            }
+            sink_write_u32(snk, run_end - run_first);                               /** sample_count */
+            sink_write_u32(snk, cv->value ? index_base + (uint32_t)cv->value : 0);  /** group_description_index */
+        }
+    }
+    it_destroy(it);
+
+    WRITE_SIZE_FIELD_RETURN(snk);
+}
+
+/** One 'sap ' group per SAP type 1..3, the group_description_index equals the SAP type */
+static offset_t
+write_sap_sgpd_box(bbio_handle_t snk)
+{
+    uint8_t sap_type;
+
+    SKIP_SIZE_FIELD(snk);
+    sink_write_4CC(snk, "sgpd");
+    sink_write_u32(snk, 1<<24);     /** version = 1 & flags */
+    sink_write_4CC(snk, "sap ");    /** grouping_type */
+    sink_write_u32(snk, 1);         /** default_length */
+    sink_write_u32(snk, 3);         /** entry count */
+    for (sap_type = 1; sap_type <= 3; sap_type++)
+    {
+        sink_write_u8(snk, sap_type);   /** dependent_flag = 0, reserved = 0, SAP_type */
+    }
 
     WRITE_SIZE_FIELD_RETURN(snk);
 }
@@ -2311,7 +2581,19 @@ write_stbl_box(bbio_handle_t snk, track_handle_t track)
         if (track->write_pre_roll)
         {
             write_sbgp_box(snk, track);
-            write_sgpd_box(snk);
+            write_sgpd_box(snk, track);
+        }
+
+        /** leading samples of open GOPs */
+        if (track->leading_present && (track->output_mode & EMA_MP4_FRAG) == 0)
+        {
+            list_it_init(track->sdtp_lst);
+            write_sdtp_box(snk, track);
+        }
+        if (track->open_gop && (track->output_mode & EMA_MP4_FRAG) == 0)
+        {
+            write_sap_sbgp_box(snk, track, 0, track->sample_num, 0);
+            write_sap_sgpd_box(snk);
         }
     }
     WRITE_SIZE_FIELD_RETURN(snk);
@@ -2630,8 +2912,8 @@ write_vdep_box(bbio_handle_t snk, track_handle_t track)
 {
     SKIP_SIZE_FIELD(snk);
     sink_write_4CC(snk, "vdep"); /** reference type */
-    /** For DoVi, we assume BL track id = EL track id - 1  */
-    sink_write_u32(snk, track->track_ID - 1); 
+    /** For DoVi, the EL track references the BL track, by default BL track id = EL track id - 1 */
+    sink_write_u32(snk, track->BL_track ? ((track_handle_t)track->BL_track)->track_ID : track->track_ID - 1);
     WRITE_SIZE_FIELD_RETURN(snk);
 }
 
@@ -2938,6 +3220,28 @@ get_dts_from_idx(track_handle_t track, uint32_t idx)
     return (uint64_t)-1;
 }
 
+/** Returns the SAP type of the sample, type 1 is assumed when the track has no SAP information */
+static uint8_t
+get_sap_type_from_idx(track_handle_t track, uint32_t idx)
+{
+    it_list_handle_t it = it_create();
+    count_value_t *  cv;
+    uint8_t          sap_type = 1;
+
+    it_init(it, track->sap_lst);
+    while ((cv = it_get_entry(it)))
+    {
+        if (idx < cv->idx - 1 + cv->count)
+        {
+            sap_type = (uint8_t)cv->value;
+            break;
+        }
+    }
+    it_destroy(it);
+
+    return sap_type;
+}
+
 /**
  * @brief gets dts_max limit imposed by new sample description
  * a new sample description within the fragment range shall start a new fragment
@@ -3340,8 +3644,59 @@ update_frag_index_lst(list_handle_t lst,
     }
 }
 
+/** gets the dts of the first sample at or after the next fragment boundary after frag_dts.
+ *  returns FALSE if there is no such boundary or sample */
+static BOOL
+get_frag_boundary_dts(track_handle_t track, uint64_t frag_dts, uint64_t *boundary_dts)
+{
+    usr_cfg_mux_t *usr_cfg_mux = track->mp4_ctrl->usr_cfg_mux_ref;
+    idx_dts_t     *dts_id;
+    uint64_t       dts = 0;
+    uint32_t       i;
+
+    for (i = 0; i < usr_cfg_mux->frag_boundary_num; i++)
+    {
+        dts = rescale_u64(usr_cfg_mux->frag_boundaries[i], track->media_timescale, 1000);
+        if (dts > frag_dts)
+        {
+            break;
+        }
+    }
+    if (i == usr_cfg_mux->frag_boundary_num)
+    {
+        return FALSE;
+    }
+
+    list_it_save_mark(track->dts_lst);
+    while ((dts_id = list_it_get_entry(track->dts_lst)) && dts_id->dts < dts);
+    list_it_goto_mark(track->dts_lst);
+
+    if (!dts_id)
+    {
+        return FALSE;
+    }
+    *boundary_dts = dts_id->dts;
+
+    return TRUE;
+}
+
+/** checks if the sample at dts is a sync sample */
+static BOOL
+is_sync_dts(track_handle_t track, uint64_t dts)
+{
+    idx_dts_t *dts_id;
+
+    list_it_save_mark(track->sync_lst);
+    while ((dts_id = list_it_get_entry(track->sync_lst)) && dts_id->dts < dts);
+    list_it_goto_mark(track->sync_lst);
+
+    return (dts_id && dts_id->dts == dts);
+}
+
 /** Creates fragment list based on: sync list and multiple stsd box info.
  *  Fragment duration will not exceed  'usr_cfg_mux_ref->frag_range_max'
+ *  Fragments end at the boundaries in 'usr_cfg_mux_ref->frag_boundaries' and, with
+ *  'usr_cfg_mux_ref->frag_on_every_sync', at the first sync sample after 'frag_range_min'.
  *  For the input ES, we have the following assumptions:
  *  1) The first sample in a fragment must be sync sample
  *  2) The first sample referenced by stsd must be sync sample
@@ -3367,17 +3722,23 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
     uint32_t          stop_sample_is_sync_flag = 0;
     uint64_t          dts_max, dts_min;
     uint64_t          frag_dts, frag_duration;
-    /** if the frist sample of the seg must be sync sample, we reset the min duration to a very little value (10 ms). */
-    if (first_sample_is_sync)
+    uint64_t          boundary_dts = 0;
+    BOOL              has_boundary;
+    uint32_t          frag_range_min = muxer->usr_cfg_mux_ref->frag_range_min;
+    uint32_t          on_every_sync  = muxer->usr_cfg_mux_ref->frag_on_every_sync;
+    /** with a min duration or a fragment per GOP set, a sync sample up to the min duration still ends the fragment */
+    BOOL              stop_at_early_sync = first_sample_is_sync && (frag_range_min != 0 || on_every_sync);
+    /** if the frist sample of the seg must be sync sample, the default min duration is a very little value (10 ms). */
+    if (frag_range_min == 0)
     {
-        muxer->usr_cfg_mux_ref->frag_range_min = 10;
+        frag_range_min = (first_sample_is_sync) ? 10 : 1000;
     }
 
     for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
     {
         track = muxer->tracks[track_idx];    
         frag_range_max_s    = rescale_u64(muxer->usr_cfg_mux_ref->frag_range_max, track->media_timescale, 1000);
-        frag_range_min_s    = rescale_u64(muxer->usr_cfg_mux_ref->frag_range_min, track->media_timescale, 1000);
+        frag_range_min_s    = rescale_u64(frag_range_min, track->media_timescale, 1000);
         one_sample_per_frag = (IS_FOURCC_EQUAL(track->codingname, "stpp")) ? 1 : 0;
         frag_dts            = track->frag_dts;
         frag_duration       = track->frag_duration;
@@ -3385,7 +3746,7 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
         if ((frag_range_max_s <= frag_range_min_s) || (frag_range_max_s <= 0))
         {
             msglog(NULL, MSGLOG_ERR, "\nError: max/min fragment duration setting error! \n");
-            return -1;
+            return EMA_MP4_MUXED_FRAG_ERR;
         }
         
         while(frag_dts < track->media_duration)
@@ -3404,6 +3765,9 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
                 break; 
             }
 
+            /** the next boundary, looked up before the dts list mark is taken */
+            has_boundary = get_frag_boundary_dts(track, frag_dts, &boundary_dts);
+
             list_it_save_mark(track->dts_lst);
             idx_start = dts_id_1st->idx;
             idx_stop  = idx_start + 1;
@@ -3432,6 +3796,18 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
                     dts_max = dts_max_sd;
                 }
 
+                /** end the fragment at the next boundary */
+                if (has_boundary && boundary_dts <= dts_max)
+                {
+                    if (first_sample_is_sync && !track->all_rap_samples && !is_sync_dts(track, boundary_dts))
+                    {
+                        msglog(NULL, MSGLOG_ERR, "\nError: no sync sample at fragment boundary %" PRIu64 " ms of track %u\n",
+                               rescale_u64(boundary_dts, 1000, track->media_timescale), track->track_ID);
+                        return EMA_MP4_MUXED_FRAG_ERR;
+                    }
+                    dts_max = boundary_dts;
+                }
+
                 /** potentially add more samples to fill the fragment */
                 if (!track->all_rap_samples)
                 {
@@ -3444,7 +3820,7 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
                         if (first_sample_is_sync)
                         {
                             msglog(NULL, MSGLOG_ERR, "\nError: rap distance larger than max fragment duration \n");
-                            return -1;
+                            return EMA_MP4_MUXED_FRAG_ERR;
                         }
                     }
                     /** try to start fragments on sync samples */
@@ -3456,6 +3832,11 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
                             idx_stop = dts_id->idx;
                             dts      = dts_id->dts;
                             stop_sample_is_sync_flag = 1;
+                            /** a fragment per GOP */
+                            if (on_every_sync && dts >= dts_min)
+                            {
+                                break;
+                            }
                         }
                     }
                     list_it_goto_mark(track->sync_lst);
@@ -3470,7 +3851,7 @@ create_fragment_lst(mp4_ctrl_handle_t muxer, uint32_t first_sample_is_sync)
                     }
                 }
 
-                if ((dts <= dts_min) || (!stop_sample_is_sync_flag))
+                if (((dts <= dts_min) && !stop_at_early_sync) || (!stop_sample_is_sync_flag))
                 {
                     /** if all samples are sync samples or if there are no sync samples in range,
                        fill up with normal samples */
@@ -3548,7 +3929,7 @@ get_min_sample_idx_nlt_dts(list_handle_t dts_lst, uint64_t dts)
 }
 
 /** fills in 'tfhd' for now only one traf per trak
- *  since no change in sample_description_index */
+ *  since fragments are split at a change in sample_description_index */
 static BOOL
 get_tfhd(track_handle_t track)
 {
@@ -3662,6 +4043,13 @@ get_tfhd(track_handle_t track)
         }
     }
 
+    /** fragments don't span sample description changes: a traf using another than
+     *  the default sample description must signal it */
+    if (ptfhd->sample_description_index != ptrex->default_sample_description_index)
+    {
+        ptfhd->tf_flags |= TF_FLAGS_SAMPLE_DESCRIPTION_INDEX;
+    }
+
     /** base-data-offset may be forbidden in application standards, e.g. DECE */
     if (track->mp4_ctrl->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_NO_BDO_IN_TFHD)
     {
@@ -4202,7 +4590,10 @@ write_traf_box(bbio_handle_t snk, track_handle_t track)
     msglog(NULL, MSGLOG_DEBUG, "  traf\n");
     while (get_tfhd(track))
     {
-        track->trun_idx = 1; /** reset within each traf */
+        uint32_t first_idx = track->sample_num_to_fraged - 1;
+
+        track->trun_idx      = 1; /** reset within each traf */
+        track->frag_sap_type = get_sap_type_from_idx(track, first_idx);
 
         write_tfhd_box(snk, track);
 
@@ -4249,7 +4640,9 @@ write_traf_box(bbio_handle_t snk, track_handle_t track)
 
         }
 
-        if ((track->mp4_ctrl->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_WRITE_SDTP) && list_get_entry_num(track->sdtp_lst))
+        /** the leading samples of open GOPs are signalled in 'sdtp' only */
+        if (((track->mp4_ctrl->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_WRITE_SDTP) || track->leading_present) &&
+            list_get_entry_num(track->sdtp_lst))
         {
             write_sdtp_box(snk, track);
         }
@@ -4270,6 +4663,13 @@ write_traf_box(bbio_handle_t snk, track_handle_t track)
             write_subs_box(snk, track);
         }
 
+        /** group descriptions local to the 'traf' are indexed from 0x10001 on */
+        if (track->open_gop)
+        {
+            write_sap_sbgp_box(snk, track, first_idx, track->sample_num_to_fraged - 1 - first_idx, 0x10000);
+            write_sap_sgpd_box(snk);
+        }
+
         track->mp4_ctrl->traf_idx++;
     }
 
@@ -4472,6 +4872,165 @@ modify_base_data_offset(bbio_handle_t snk, mp4_ctrl_handle_t muxer, uint32_t tra
     return TRUE;
 }
 
+/** [DASH] Section 5.10.3.3: Event Message Box. Version 1 carries the presentation time on the media timeline,
+ *  version 0 the delta to frag_time, the earliest presentation time of the fragment in the timescale of the event */
+static uint32_t
+write_emsg_box(bbio_handle_t snk, const emsg_t *emsg, uint64_t frag_time)
+{
+    const uint32_t scheme_size = (uint32_t)strlen(emsg->scheme_id_uri) + 1;
+    const uint32_t value_size  = (uint32_t)strlen(emsg->value) + 1;
+    const uint32_t size        = ((emsg->version == 0) ? 28 : 32) + scheme_size + value_size + emsg->message_size;
+
+    sink_write_u32(snk, size);
+    sink_write_4CC(snk, "emsg");
+    if (emsg->version == 0)
+    {
+        sink_write_u32(snk, 0);  /** version 0, flags */
+        snk->write(snk, (const uint8_t *)emsg->scheme_id_uri, scheme_size);
+        snk->write(snk, (const uint8_t *)emsg->value, value_size);
+        sink_write_u32(snk, emsg->timescale);
+        sink_write_u32(snk, (uint32_t)(emsg->presentation_time - frag_time));
+        sink_write_u32(snk, emsg->event_duration);
+        sink_write_u32(snk, emsg->id);
+    }
+    else
+    {
+        sink_write_u32(snk, 0x01000000);  /** version 1, flags */
+        sink_write_u32(snk, emsg->timescale);
+        sink_write_u64(snk, emsg->presentation_time);
+        sink_write_u32(snk, emsg->event_duration);
+        sink_write_u32(snk, emsg->id);
+        snk->write(snk, (const uint8_t *)emsg->scheme_id_uri, scheme_size);
+        snk->write(snk, (const uint8_t *)emsg->value, value_size);
+    }
+    if (emsg->message_size)
+    {
+        snk->write(snk, emsg->message_data, emsg->message_size);
+    }
+
+    msglog(NULL, MSGLOG_INFO, "emsg %u @ %" PRIu64 "/%u\n", emsg->id, emsg->presentation_time, emsg->timescale);
+    return size;
+}
+
+/** Returns the earliest presentation time of the samples idx_start to idx_end - 1 as 'tfdt' and 'trun' give it,
+ *  the value 'sidx' carries for the fragment. (uint64_t)-1 if there is no such sample. */
+static uint64_t
+get_earliest_pts_from_idx(track_handle_t track, uint32_t idx_start, uint32_t idx_end)
+{
+    BOOL             is_ctts_v1  = (track->mp4_ctrl->usr_cfg_mux_ref->mux_cfg_flags & ISOM_MUXCFG_WRITE_CTTS_V1) != 0;
+    BOOL             force_v0    = (track->mp4_ctrl->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_FORCE_TRUN_V0) != 0;
+    it_list_handle_t it_dts      = it_create_on(track->dts_lst);
+    it_list_handle_t it_cts      = it_create_on(track->cts_offset_lst);
+    idx_dts_t *      idx_dts;
+    count_value_t *  cv          = NULL;
+    uint32_t         cts_idx_end = 0;  /** the samples up to here take the cts offset of cv */
+    uint64_t         earliest    = (uint64_t)-1;
+
+    while ((idx_dts = it_get_entry(it_dts)) && idx_dts->idx < idx_end)
+    {
+        uint64_t pts = idx_dts->dts;
+
+        while (idx_dts->idx >= cts_idx_end && (cv = it_get_entry(it_cts)))
+        {
+            cts_idx_end += cv->count;
+        }
+        if (idx_dts->idx < idx_start)
+        {
+            continue;
+        }
+
+        if (cv && !track->no_cts_offset)
+        {
+            /** version 1 'trun' boxes carry signed offsets */
+            if (is_ctts_v1 && !force_v0)
+            {
+                pts += (int64_t)(int32_t)(uint32_t)cv->value;
+            }
+            else
+            {
+                pts += (uint32_t)cv->value;
+            }
+        }
+        if (pts < earliest)
+        {
+            earliest = pts;
+        }
+    }
+    it_destroy(it_cts);
+    it_destroy(it_dts);
+
+    return earliest;
+}
+
+/** Writes the event messages starting before the next fragment of track, the last fragment takes the rest.
+ *  [DASH] Section 5.10.3.3: fragments start at their earliest presentation time, as in 'sidx'.
+ *  Fails on a version 0 event the 32 bit delta can't place relative to its fragment. */
+static int32_t
+write_emsg_boxes(bbio_handle_t snk,        /**< mp4 sink */
+                 mp4_ctrl_handle_t muxer,  /**< muxer */
+                 track_handle_t track,     /**< track of the fragment */
+                 BOOL last_frag,           /**< the fragment is the last of track */
+                 uint32_t *size            /**< bytes written */
+                 )
+{
+    it_list_handle_t it;
+    emsg_t *         emsg;
+    idx_dts_t *      dts_id;
+    frag_index_t *   next_frag_index;
+    uint32_t         idx_start     = 0;
+    uint32_t         idx_end       = list_get_entry_num(track->dts_lst);
+    uint64_t         frag_pts;
+    uint64_t         next_frag_pts = (uint64_t)-1;
+
+    *size = 0;
+    if (!muxer->emsg_lst || !(muxer->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_WRITE_EMSG))
+    {
+        return EMA_MP4_MUXED_OK;
+    }
+
+    /** the fragment starts with the next sample to write, the entry of the fragment is taken already */
+    dts_id = list_it_peek_entry(track->dts_lst);
+    if (dts_id)
+    {
+        idx_start = dts_id->idx;
+    }
+    next_frag_index = list_it_peek_entry(track->segment_lst);
+    if (next_frag_index)
+    {
+        idx_end       = next_frag_index->frag_start_idx;
+        next_frag_pts = get_earliest_pts_from_idx(track, next_frag_index->frag_start_idx, next_frag_index->frag_end_idx);
+    }
+    frag_pts = get_earliest_pts_from_idx(track, idx_start, idx_end);
+    if (frag_pts == (uint64_t)-1)
+    {
+        frag_pts = dts_id ? dts_id->dts : 0;
+    }
+
+    it = it_create_on(muxer->emsg_lst);
+    while ((emsg = it_get_entry(it)))
+    {
+        if (!emsg->written &&
+            (last_frag || rescale_u64(emsg->presentation_time, track->media_timescale, emsg->timescale) < next_frag_pts))
+        {
+            uint64_t frag_time = rescale_u64(frag_pts, emsg->timescale, track->media_timescale);
+
+            if (emsg->version == 0 &&
+                (emsg->presentation_time < frag_time || emsg->presentation_time - frag_time > 0xFFFFFFFF))
+            {
+                msglog(NULL, MSGLOG_ERR, "\nError: version 0 emsg %u @ %" PRIu64 "/%u out of the 32 bit range after its fragment at %" PRIu64 "\n",
+                       emsg->id, emsg->presentation_time, emsg->timescale, frag_time);
+                it_destroy(it);
+                return EMA_MP4_MUXED_PARAM_ERR;
+            }
+            *size += write_emsg_box(snk, emsg, frag_time);
+            emsg->written = TRUE;
+        }
+    }
+    it_destroy(it);
+
+    return EMA_MP4_MUXED_OK;
+}
+
 static int32_t
 write_mfra_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
 {
@@ -4613,17 +5172,6 @@ write_moov_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
             muxer->moov_size_est += write_iods_box(snk, muxer);
         }
 
-        if (!(muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG))
-        {
-            msglog(NULL, MSGLOG_INFO, "\nworst case moov size %u\n", muxer->moov_size_est);
-
-            if (!muxer->co64_mode && muxer->moov_size_est + (16 + muxer->mdat_size) > (uint32_t)(-1))
-            {
-                muxer->co64_mode = TRUE;
-            }
-            /** else cfg to co64_mode, always co64_mode */
-        }
-
         /** [ISO] Section 8.11.1: Meta Box; [CFF]: DECE Required Metadata */
         if (muxer->moov_meta_xml_data)
         {
@@ -4913,6 +5461,11 @@ chunk_update(track_handle_t track, mp4_sample_handle_t sample)
 
         list_add_entry(track->stsd_lst, ip);
         track->sample_descr_index++;
+        if (track->sample_descr_index > 1)
+        {
+            msglog(NULL, MSGLOG_INFO, "track %u: sample description %u starts at sample %u\n",
+                   track->track_ID, track->sample_descr_index, track->sample_num + 1);
+        }
     }
     else
     {
@@ -5217,6 +5770,16 @@ mp4_muxer_input_sample (track_handle_t      htrack
 
         update_frame_type_lst(htrack->frame_type_lst,
                         hsample->frame_type);
+
+        count_value_lst_update(htrack->sap_lst, (hsample->flags & SAMPLE_SYNC) ? MAX2(hsample->sap_type, 1) : 0);
+        if ((hsample->flags & SAMPLE_SYNC) && hsample->sap_type == 3)
+        {
+            htrack->open_gop = TRUE;
+        }
+        if (hsample->is_leading)
+        {
+            htrack->leading_present = TRUE;
+        }
     }
 
     if (htrack->parser->stream_type == STREAM_TYPE_SUBTITLE)
@@ -5715,8 +6278,38 @@ setup_muxer(mp4_ctrl_handle_t muxer)
                                         ((count_value_t*)list_peek_first_entry(track->cts_offset_lst))->value == 0 );
 
 
+        /** audio priming and end trimming, such as the Opus pre-skip, and audio pre-roll */
+        if (track->parser->stream_type == STREAM_TYPE_AUDIO)
+        {
+            parser_audio_handle_t parser_audio = (parser_audio_handle_t)track->parser;
+            uint64_t              media_time   = parser_audio->priming_samples;
+            uint64_t              duration     = parser_audio->valid_samples;
+
+            if (track->warp_media_timestamps)
+            {
+                media_time = rescale_u64(media_time, track->media_timescale, track->warp_parser_timescale);
+                duration   = rescale_u64(duration, track->media_timescale, track->warp_parser_timescale);
+            }
+            if (!duration || media_time + duration > track->media_duration)
+            {
+                duration = (track->media_duration > media_time) ? track->media_duration - media_time : 0;
+            }
+            if ((media_time || duration < track->media_duration) && duration && !list_get_entry_num(track->edt_lst))
+            {
+                mp4_muxer_add_to_track_edit_list(track, duration, (int64_t)media_time);
+                msglog(NULL, MSGLOG_INFO, "adding edit list to skip %" PRIu64 " priming samples, %" PRIu64 " samples played\n",
+                       media_time, duration);
+            }
+            if (parser_audio->roll_distance)
+            {
+                track->write_pre_roll = TRUE;
+                track->roll_distance  = parser_audio->roll_distance;
+            }
+        }
+
         /** build edit list, if necessary */
-        if (!track->no_cts_offset && !list_get_entry_num(track->edt_lst) && list_get_entry_num(track->cts_offset_lst))
+        if (!track->no_cts_offset && !list_get_entry_num(track->edt_lst) && list_get_entry_num(track->cts_offset_lst) &&
+            !(muxer->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_AUDIO_EDTS_ONLY))
         {
             uint32_t cts_offset = (uint32_t)((count_value_t*)list_peek_first_entry(track->cts_offset_lst))->value;
             if (cts_offset)
@@ -6053,12 +6646,60 @@ setup_muxer_short(mp4_ctrl_handle_t muxer, uint16_t *p_video_width, uint16_t *p_
     return EMA_MP4_MUXED_OK;
 }
 
+/** Returns the track whose next chunk goes into 'mdat' in interleave mode, NULL if all are out */
+static track_handle_t
+next_mdat_chunk_track(mp4_ctrl_handle_t muxer, uint64_t *dts_out)
+{
+    uint32_t       track_idx;
+    track_handle_t track;
+    chunk_handle_t chunk;
+
+    /** To find the chunk with dts no larger than current output one.
+     *  This process should speed up the search since all chunk have the similar
+     *  size.
+     */
+    for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
+    {
+        track = muxer->tracks[track_idx];
+        if (track->chunk_to_out == track->chunk_num)
+        {
+            continue;
+        }
+        chunk = list_it_peek_entry(track->chunk_lst);
+        if (chunk->dts <= *dts_out)
+        {
+            return track;
+        }
+    }
+
+    /** find the track smallest dts if not yet */
+    {
+        track_handle_t track_out = NULL;
+
+        *dts_out = (uint64_t)(-1);
+        for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
+        {
+            track = muxer->tracks[track_idx];
+            if (track->chunk_to_out == track->chunk_num)
+            {
+                continue;
+            }
+            chunk = list_it_peek_entry(track->chunk_lst);
+            if (chunk->dts < *dts_out)
+            {
+                *dts_out = chunk->dts;
+                track_out = track;
+            }
+        }
+        return track_out;
+    }
+}
+
 static int32_t
 write_mdat_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
 {
     int32_t               ret = EMA_MP4_MUXED_OK;
     uint32_t          track_idx;
-    track_handle_t    track;
     uint32_t          chunk_idx;
     uint64_t          dts_out;
     progress_handle_t prgh;
@@ -6091,46 +6732,7 @@ write_mdat_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
     for (chunk_idx = 0; chunk_idx < muxer->chunk_num; chunk_idx++)
     {
         chunk_handle_t chunk;
-        track_handle_t track_out = NULL;
-
-        /** To find the chunk with dts no larger than current output one.
-         *  This process should speed up the search since all chunk have the similar
-         *  size.
-         */
-        for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
-        {
-            track = muxer->tracks[track_idx];
-            if (track->chunk_to_out == track->chunk_num)
-            {
-                continue;
-            }
-            chunk = list_it_peek_entry(track->chunk_lst);
-            if (chunk->dts <= dts_out)
-            {
-                track_out = track;
-                break;
-            }
-        }
-
-        /** find the track smallest dts if not yet */
-        if (!track_out)
-        {
-            dts_out = (uint64_t)(-1);
-            for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
-            {
-                track = muxer->tracks[track_idx];
-                if (track->chunk_to_out == track->chunk_num)
-                {
-                    continue;
-                }
-                chunk = list_it_peek_entry(track->chunk_lst);
-                if (chunk->dts < dts_out)
-                {
-                    dts_out = chunk->dts;
-                    track_out = track;
-                }
-            }
-        }
+        track_handle_t track_out = next_mdat_chunk_track(muxer, &dts_out);
 
         if (track_out)
         {
@@ -6168,6 +6770,76 @@ write_mdat_box(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
     return ret;
 }
 
+BOOL
+mp4_muxer_chunk_offsets_exceed_32bit(offset_t mdat_pos, uint64_t mdat_size, uint64_t last_chunk_size)
+{
+    uint64_t hdr_size = (mdat_size + 8 <= (uint32_t)(-1)) ? 8 : 16;  /** 'largesize' header */
+
+    return (uint64_t)mdat_pos + hdr_size + mdat_size - last_chunk_size > (uint32_t)(-1);
+}
+
+/** Checks if chunk offsets need 64 bit when the 'mdat' box starts at mdat_pos */
+static BOOL
+chunk_offsets_exceed_32bit(mp4_ctrl_handle_t muxer, offset_t mdat_pos)
+{
+    uint32_t       track_idx;
+    uint32_t       chunk_idx;
+    uint64_t       dts_out         = 0;
+    uint64_t       last_chunk_size = 0;
+    chunk_handle_t chunk;
+
+    /** the chunk written last is the one write_mdat_box() takes last in its interleave order */
+    for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
+    {
+        list_it_init(muxer->tracks[track_idx]->chunk_lst);
+    }
+    for (chunk_idx = 0; chunk_idx < muxer->chunk_num; chunk_idx++)
+    {
+        track_handle_t track_out = next_mdat_chunk_track(muxer, &dts_out);
+
+        if (!track_out)
+        {
+            break;
+        }
+        chunk = list_it_get_entry(track_out->chunk_lst);
+        track_out->chunk_to_out++;
+        last_chunk_size = chunk->size;
+    }
+    for (track_idx = 0; track_idx < muxer->stream_num; track_idx++)
+    {
+        muxer->tracks[track_idx]->chunk_to_out = 0;
+    }
+
+    return mp4_muxer_chunk_offsets_exceed_32bit(mdat_pos, muxer->mdat_size, last_chunk_size);
+}
+
+/** Selects 'stco' or 'co64' according to the user config once the position of 'mdat' is known */
+static int32_t
+select_chunk_offset_box(mp4_ctrl_handle_t muxer, offset_t mdat_pos)
+{
+    BOOL co64_needed = chunk_offsets_exceed_32bit(muxer, mdat_pos);
+
+    if (muxer->usr_cfg_mux_ref->withopt & ISOM_MUXCFG_WITHOPT_CO64)
+    {
+        muxer->co64_mode = TRUE;
+    }
+    else if (muxer->usr_cfg_mux_ref->withopt & ISOM_MUXCFG_WITHOPT_NO_CO64)
+    {
+        if (co64_needed)
+        {
+            msglog(NULL, MSGLOG_ERR, "ERROR: chunk offsets exceed 32 bit, 64 bit chunk offsets ('co64') are required\n");
+            return EMA_MP4_MUXED_PARAM_ERR;
+        }
+        muxer->co64_mode = FALSE;
+    }
+    else
+    {
+        muxer->co64_mode = co64_needed;
+    }
+
+    return EMA_MP4_MUXED_OK;
+}
+
 static void
 modify_stco_boxes(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
 {
@@ -6202,7 +6874,7 @@ modify_stco_boxes(bbio_handle_t snk, mp4_ctrl_handle_t muxer)
  *  assumptions:
  *  - each fragment forms one (sub)segment
  *  - only one track will be muxed when writing of sidx boxes is enabled
- *  - each fragment starts with a sync sample
+ *  - each fragment starts with a sync sample, the SAP type is taken from its first sample
  */
 static int32_t
 write_sidx_box(bbio_handle_t snk,     /**< mp4 sink */
@@ -6240,7 +6912,7 @@ write_sidx_box(bbio_handle_t snk,     /**< mp4 sink */
     {
         sink_write_u32(snk, 0);                       /** reference_type + referenced_size - will be updated */
         sink_write_u32(snk, 0);                       /** subsegment_duration - will be updated */
-        sink_write_u32(snk, 0x90000000);              /** starts_with_SAP = 1, SAP_type = 1, SAP_delta_time = 0 */
+        sink_write_u32(snk, 0x90000000);              /** starts_with_SAP, SAP_type - will be updated, SAP_delta_time = 0 */
     }
 
     msglog(NULL, MSGLOG_INFO, "sidx: timescale:                  %u\n", timescale);
@@ -6252,7 +6924,7 @@ write_sidx_box(bbio_handle_t snk,     /**< mp4 sink */
     return EMA_MP4_MUXED_OK;
 }
 
-/** Updates referenced_size and subsegment_duration in sidx box */
+/** Updates referenced_size, subsegment_duration and the SAP of the fragment start in sidx box */
 static void
 update_sidx_box(bbio_handle_t     snk,              /**< mp4 sink */
                 track_handle_t    track,            /**< track */
@@ -6275,6 +6947,7 @@ update_sidx_box(bbio_handle_t     snk,              /**< mp4 sink */
         snk->seek(snk, sidx_pos + 32 + frag_num * 12, SEEK_SET);
         sink_write_u32(snk, referenced_size);
         sink_write_u32(snk, subsegment_duration);
+        sink_write_u32(snk, track->frag_sap_type ? (0x80000000 | (uint32_t)track->frag_sap_type << 28) : 0);
         snk->seek(snk, cur_pos, SEEK_SET);
     }
     else
@@ -6433,6 +7106,7 @@ mp4_muxer_output_tracks(mp4_ctrl_handle_t muxer)
     offset_t sidx_pos[MAX_STREAMS];
     offset_t sidx_size[MAX_STREAMS];
     int32_t  sidx_first_offset_written[MAX_STREAMS];
+    offset_t moov_pos = 0;
 
     uint64_t data_written = 0ULL;  /** 'mdat' data written */
 
@@ -6483,9 +7157,24 @@ mp4_muxer_output_tracks(mp4_ctrl_handle_t muxer)
         list_it_init(muxer->tracks[0]->stsd_lst);
     }
 
-    /** write 'moov' */
-    write_moov_box(snk, muxer);
-    msglog(NULL, MSGLOG_INFO, "moov end @ offset %" PRIi64 "\n", snk->position(snk)-1);
+    /** write 'moov', unless it goes after 'mdat' */
+    if ((muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG) || !muxer->usr_cfg_mux_ref->moov_at_end)
+    {
+        if (!(muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG))
+        {
+            msglog(NULL, MSGLOG_INFO, "\nworst case moov size %u\n", muxer->moov_size_est);
+
+            /** first guess based on the estimated 'moov' size, verified once 'moov' is written */
+            if (!(muxer->usr_cfg_mux_ref->withopt & (ISOM_MUXCFG_WITHOPT_CO64 | ISOM_MUXCFG_WITHOPT_NO_CO64)))
+            {
+                muxer->co64_mode = chunk_offsets_exceed_32bit(muxer, muxer->moov_size_est);
+            }
+        }
+
+        moov_pos = snk->position(snk);
+        write_moov_box(snk, muxer);
+        msglog(NULL, MSGLOG_INFO, "moov end @ offset %" PRIi64 "\n", snk->position(snk)-1);
+    }
 
     /** [ISO] Section 8.16.3: Segment Index Box */
     if (muxer->usr_cfg_mux_ref->frag_cfg_flags & ISOM_FRAGCFG_WRITE_SIDX)
@@ -6513,16 +7202,50 @@ mp4_muxer_output_tracks(mp4_ctrl_handle_t muxer)
         }
     }
 
-    if (!(muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG))
+    if (!(muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG) && muxer->usr_cfg_mux_ref->moov_at_end)
+    {
+        /** chunk offsets are final once 'mdat' is written, so 'moov' follows without rewriting 'stco' */
+        ret = select_chunk_offset_box(muxer, snk->position(snk));
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+
+        /** write 'mdat' */
+        ret = write_mdat_box(snk, muxer);
+        if (ret != EMA_MP4_MUXED_OK)
+        {
+            return ret;
+        }
+
+        write_moov_box(snk, muxer);
+        msglog(NULL, MSGLOG_INFO, "moov end @ offset %" PRIi64 "\n", snk->position(snk)-1);
+    }
+    else if (!(muxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG))
     {
         /**to check if the 'stco'/'co64' selection is good or not */
         if (muxer->moov_size_est < snk->position(snk))
         {
             msglog(NULL, MSGLOG_WARNING, "\nWARNING: estimated moov size is too small\n");
-            if (!muxer->co64_mode && snk->position(snk) + (16 + muxer->mdat_size) > (uint32_t)(-1))
+        }
+
+        {
+            BOOL co64_mode = muxer->co64_mode;
+
+            ret = select_chunk_offset_box(muxer, snk->position(snk));
+            if (ret != EMA_MP4_MUXED_OK)
             {
-                msglog(NULL, MSGLOG_ERR, "ERROR: must use co64: use option -with 64 please\n");
-                return EMA_MP4_MUXED_PARAM_ERR;
+                return ret;
+            }
+
+            if (co64_mode != muxer->co64_mode)
+            {
+                /** the guess was wrong: rewrite 'moov' in place with the other chunk offset box.
+                    'co64' only grows 'moov' and therefore the offsets, 'stco' only shrinks them,
+                    so the selection holds for the rewritten 'moov' as well. */
+                msglog(NULL, MSGLOG_INFO, "Rewriting moov with %s\n", muxer->co64_mode ? "co64" : "stco");
+                snk->seek(snk, moov_pos, SEEK_SET);
+                write_moov_box(snk, muxer);
             }
         }
 
@@ -6651,6 +7374,7 @@ mp4_muxer_output_tracks(mp4_ctrl_handle_t muxer)
                     offset_t moof_offset;
                     int32_t      referenced_size;
                     int32_t      bytes_written;
+                    uint32_t     emsg_size;
                     uint32_t  trackID;
 
                     trackID = muxer->tracks[track_index]->track_ID;
@@ -6660,7 +7384,13 @@ mp4_muxer_output_tracks(mp4_ctrl_handle_t muxer)
                     }
 
                     moof_offset     = snk->position(snk);
-                    referenced_size = write_moof_box(snk, muxer, trackID);
+                    ret = write_emsg_boxes(snk, muxer, muxer->tracks[track_index], fragment_number == 1, &emsg_size);
+                    if (ret != EMA_MP4_MUXED_OK)
+                    {
+                        goto cleanup;
+                    }
+                    referenced_size  = (int32_t)emsg_size;
+                    referenced_size += write_moof_box(snk, muxer, trackID);
 
                     ret = write_mdat_box_frag(snk, muxer, trackID, &bytes_written);
                     if (ret != EMA_MP4_MUXED_OK)
@@ -6810,10 +7540,23 @@ mp4_muxer_destroy (mp4_ctrl_handle_t hmuxer
     {
         FREE_CHK((void *)atom->data);
     }
-    it_destroy(it);
     list_destroy(hmuxer->moov_child_atom_lst);
     list_destroy(hmuxer->udta_child_atom_lst);
     list_destroy(hmuxer->next_track_lst);
+    if (hmuxer->emsg_lst)
+    {
+        emsg_t *emsg;
+
+        it_init(it, hmuxer->emsg_lst);
+        while ((emsg = it_get_entry(it)))
+        {
+            FREE_CHK(emsg->scheme_id_uri);
+            FREE_CHK(emsg->value);
+            FREE_CHK(emsg->message_data);
+        }
+        list_destroy(hmuxer->emsg_lst);
+    }
+    it_destroy(it);
     if (hmuxer->scratchbuf)
     {
         FREE_CHK(hmuxer->scratchbuf);
@@ -6874,7 +7617,7 @@ mp4_muxer_create (usr_cfg_mux_t *p_usr_cfg_mux
     muxer->timescale     = p_usr_cfg_mux->timescale;
     muxer->next_track_ID = 1;
 
-    if (p_usr_cfg_mux->fix_cm_time)
+    if (p_usr_cfg_mux->fix_cm_time_set)
     {
         muxer->creation_time = p_usr_cfg_mux->fix_cm_time;
     }
@@ -6891,7 +7634,7 @@ mp4_muxer_create (usr_cfg_mux_t *p_usr_cfg_mux
     muxer->audio_profile_level    = p_usr_cfg_mux->audio_profile_level;
     muxer->graphics_profile_level = p_usr_cfg_mux->graphics_profile_level;
 
-    muxer->co64_mode = ((p_usr_cfg_mux->withopt & 0x1) == 0x1);
+    muxer->co64_mode = ((p_usr_cfg_mux->withopt & ISOM_MUXCFG_WITHOPT_CO64) == ISOM_MUXCFG_WITHOPT_CO64);
     muxer->usr_cfg_mux_ref = p_usr_cfg_mux;
     muxer->usr_cfg_ess_ref = p_usr_cfg_ess;
 
@@ -6943,6 +7686,22 @@ mp4_muxer_get_sink (mp4_ctrl_handle_t hmuxer
     return hmuxer->mp4_sink;
 }
 
+/** TRUE if a stream requested the track ID by user setting */
+static BOOL
+track_ID_is_requested(mp4_ctrl_handle_t hmuxer, uint32_t track_ID)
+{
+    uint32_t u;
+
+    for (u = 0; hmuxer->usr_cfg_ess_ref && u < (uint32_t)hmuxer->usr_cfg_mux_ref->es_num; u++)
+    {
+        if (hmuxer->usr_cfg_ess_ref[u].track_ID == track_ID)
+        {
+            return TRUE;
+        }
+    }
+    return FALSE;
+}
+
 /** return track_ID */
 uint32_t
 mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
@@ -6974,6 +7733,19 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
         msglog(NULL, MSGLOG_ERR, "stream %d: could not find codingname for parser\n", p_usr_cfg_es->es_idx);
         return EMA_MP4_MUXED_UNKNOW_ES;
     }
+    /** QuickTime files take PCM as 'lpcm' */
+    if (hparser->stream_id == STREAM_ID_PCM && !strcmp(hmuxer->usr_cfg_mux_ref->major_brand, "qt  "))
+    {
+        if (hmuxer->usr_cfg_mux_ref->output_mode & EMA_MP4_FRAG)
+        {
+            msglog(NULL, MSGLOG_WARNING, "stream %d: no 'lpcm' in fragmented files, '%s' used\n",
+                   p_usr_cfg_es->es_idx, codingname);
+        }
+        else
+        {
+            codingname = "lpcm";
+        }
+    }
 
     track = (track_handle_t)MALLOC_CHK(sizeof(track_t));
     if (!track)
@@ -6990,12 +7762,21 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
     else
     {
         track->track_ID = hmuxer->next_track_ID;
+        /** skip the track IDs requested for streams still to be added */
+        while (track_ID_is_requested(hmuxer, track->track_ID))
+        {
+            track->track_ID++;
+        }
     }
     /** check if track ID conflicting, track ID can be used by mp4 source */
     if (mp4_muxer_get_track(hmuxer, track->track_ID))
     {
         /** find unused track ID */
         uint32_t i;
+        if (p_usr_cfg_es->track_ID)
+        {
+            msglog(NULL, MSGLOG_WARNING, "Warning: track ID %u already in use\n", p_usr_cfg_es->track_ID);
+        }
         for (i = 1; i <= MAX_STREAMS; i++)
         {
             if (!mp4_muxer_get_track(hmuxer, i))
@@ -7077,7 +7858,16 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
     hparser->sd                = hmuxer->usr_cfg_mux_ref->sd; /** set sd from user config */
     hparser->sd_collision_flag = 0;                           /** reset sd collision flag */
 
-    if (p_usr_cfg_es->warp_media_timescale != 0)
+    if (IS_FOURCC_EQUAL(codingname, "lpcm"))
+    {
+        /** a frame is a sample of duration 1: the media timescale has to be the sample rate */
+        track->lpcm_frame_size = ((mp4_dsi_pcm_handle_t)hparser->curr_dsi)->block_align;
+        if (p_usr_cfg_es->warp_media_timescale)
+        {
+            msglog(NULL, MSGLOG_WARNING, "stream %d: timescale ignored for 'lpcm'\n", p_usr_cfg_es->es_idx);
+        }
+    }
+    else if (p_usr_cfg_es->warp_media_timescale != 0)
     {
         track->warp_media_timescale  = p_usr_cfg_es->warp_media_timescale;
         track->warp_media_timestamps = 1;
@@ -7106,6 +7896,14 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
     {
         hmuxer->has_avc = TRUE;
     }
+    else if (hparser->stream_id == STREAM_ID_AV1)
+    {
+        hmuxer->has_av1 = TRUE;
+    }
+    else if (hparser->stream_id == STREAM_ID_OPUS)
+    {
+        hmuxer->has_opus = TRUE;
+    }
     else if (hparser->stream_id == STREAM_ID_MP4V)
     {
         hmuxer->has_mp4v = TRUE;
@@ -7139,6 +7937,7 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
     track->sdtp_lst = list_create(sizeof(sample_sdtp_t));
     track->trik_lst = list_create(sizeof(sample_trik_t));
     track->frame_type_lst = list_create(sizeof(sample_frame_type_t));
+    track->sap_lst  = list_create(sizeof(count_value_t));
     track->subs_lst = list_create(sizeof(sample_subs_t));
     track->segment_lst = list_create(sizeof(frag_index_t));
 
@@ -7176,7 +7975,6 @@ mp4_muxer_add_track (mp4_ctrl_handle_t  hmuxer
 
     hmuxer->tracks[hmuxer->stream_num] = track;
     hmuxer->stream_num++;
-    hmuxer->next_track_ID++;
     if (track->track_ID + 1 > hmuxer->next_track_ID)
     {
         hmuxer->next_track_ID = track->track_ID + 1;
@@ -7223,6 +8021,49 @@ mp4_muxer_add_moov_child_atom (mp4_muxer_handle_t  hmuxer
     return EMA_MP4_MUXED_OK;
 }
 
+int32_t
+mp4_muxer_add_emsg (mp4_muxer_handle_t  hmuxer
+                   ,const emsg_t       *p_emsg
+                   )
+{
+    emsg_t *emsg;
+
+    if (hmuxer->emsg_lst == NULL)
+    {
+        hmuxer->emsg_lst = list_create(sizeof(emsg_t));
+    }
+
+    emsg = (emsg_t *)list_alloc_entry(hmuxer->emsg_lst);
+    if (!emsg)
+    {
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    *emsg = *p_emsg;
+    emsg->scheme_id_uri = STRDUP_CHK(p_emsg->scheme_id_uri);
+    emsg->value         = STRDUP_CHK(p_emsg->value ? p_emsg->value : "");
+    emsg->message_data  = NULL;
+    emsg->written       = FALSE;
+    if (p_emsg->message_size)
+    {
+        emsg->message_data = (uint8_t *)MALLOC_CHK(p_emsg->message_size);
+        if (emsg->message_data)
+        {
+            memcpy(emsg->message_data, p_emsg->message_data, p_emsg->message_size);
+        }
+    }
+    if (!emsg->scheme_id_uri || !emsg->value || (p_emsg->message_size && !emsg->message_data))
+    {
+        FREE_CHK(emsg->scheme_id_uri);
+        FREE_CHK(emsg->value);
+        FREE_CHK(emsg->message_data);
+        list_free_entry(emsg);
+        return EMA_MP4_MUXED_NO_MEM;
+    }
+    list_add_entry(hmuxer->emsg_lst, emsg);
+
+    return EMA_MP4_MUXED_OK;
+}
+
 void
 mp4_muxer_add_moov_ainf_atom (mp4_ctrl_handle_t  hmuxer
                              ,const int8_t        *p_data
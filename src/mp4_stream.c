@@ -647,6 +647,7 @@ stream_destroy (stream_handle_t stream)
         list_destroy(stream->sdtp_lst);
         list_destroy(stream->trik_lst);
         list_destroy(stream->frame_type_lst);
+        list_destroy(stream->sap_lst);
         list_destroy(stream->subs_lst);
         list_destroy(stream->segment_lst);
 #ifdef ENABLE_MP4_ENCRYPTION